@@ -0,0 +1,105 @@
+use cafe_tab::{
+    domain::tab::{
+        command::{OrderItem, TabCommand},
+        order_priority::OrderPriority,
+        services::TabServices,
+        tab_id::TabId,
+        waiter_id::WaiterId,
+    },
+    infrasctructure::{
+        persistence::context::{
+            connection_parameters::ConnectionBuilder,
+            postgres::{create_db, postgres_pool},
+        },
+        respository::{
+            postgresql::cqrs::{cqrs_tab, TabCqrsFramework},
+            query_errors::QueryErrorPolicy,
+        },
+    },
+    shared_kernel::{
+        money::{Currency, Money},
+        BarTabViewRepository, KitchenTabViewRepository, OpenTabsViewRepository,
+        WaiterTabViewRepository,
+    },
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rust_decimal::Decimal;
+use secrecy::Secret;
+use tokio::runtime::Runtime;
+use uuid::Uuid;
+
+const TAB_EVENTS: usize = 300;
+
+async fn busy_tab(snapshot_size: Option<usize>) -> (TabCqrsFramework, TabId) {
+    let params = ConnectionBuilder::new()
+        .with_credentials("postgres", Secret::new(String::from("password")))
+        .with_database(&Uuid::new_v4().to_string())
+        .build();
+    create_db(&params).await;
+    let pool = postgres_pool(&params).await;
+    let cqrs = cqrs_tab(
+        pool.clone(),
+        TabServices::default(),
+        WaiterTabViewRepository::new(pool.clone()),
+        KitchenTabViewRepository::new(pool.clone()),
+        BarTabViewRepository::new(pool.clone()),
+        OpenTabsViewRepository::new(pool),
+        None,
+        QueryErrorPolicy::default(),
+        snapshot_size,
+    );
+    let tab_id = TabId::new();
+    cqrs.execute(
+        &tab_id.to_string(),
+        TabCommand::OpenTab {
+            id: tab_id,
+            waiter_id: WaiterId::new(),
+            table: 1,
+        },
+    )
+    .await
+    .expect("failed to open tab");
+    for _ in 0..TAB_EVENTS {
+        cqrs.execute(&tab_id.to_string(), order_drink())
+            .await
+            .expect("failed to place order");
+    }
+
+    (cqrs, tab_id)
+}
+
+fn order_drink() -> TabCommand {
+    TabCommand::PlaceOrder {
+        order_items: vec![OrderItem {
+            menu_number: 2,
+            description: "Coca-Cola".into(),
+            is_drink: true,
+            price: Money::new(Decimal::from(3), Currency::USD).unwrap(),
+            notes: None,
+        }],
+        priority: OrderPriority::Normal,
+    }
+}
+
+fn command_latency(c: &mut Criterion) {
+    let rt = Runtime::new().expect("failed to start runtime");
+    let mut group = c.benchmark_group("place_order_on_busy_tab");
+    for snapshot_size in [None, Some(50)] {
+        let (cqrs, tab_id) = rt.block_on(busy_tab(snapshot_size));
+        let label = match snapshot_size {
+            Some(size) => format!("snapshot_every_{size}"),
+            None => "no_snapshots".to_string(),
+        };
+        group.bench_with_input(BenchmarkId::from_parameter(label), &tab_id, |b, tab_id| {
+            b.to_async(&rt).iter(|| async {
+                cqrs.execute(&tab_id.to_string(), order_drink())
+                    .await
+                    .expect("failed to place order")
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, command_latency);
+criterion_main!(benches);
@@ -0,0 +1,41 @@
+## Tab errors, one message per TabError code
+
+tab-item-already-served = የቀረበን ዕቃ መሰረዝ አይቻልም
+tab-has-unserved-items = ሂሳቡ ያልቀረቡ ዕቃዎች አሉት
+tab-payment-too-low = የተከፈለው መጠን በቂ አይደለም
+tab-not-open = ሂሳቡ አልተከፈተም
+tab-drink-not-outstanding = መጠጡ በመጠባበቅ ላይ አይደለም፦ የምናሌ ቁጥር { $menu_number }
+tab-already-open = አስቀድሞ ተከፍቷል፦ { $id }
+tab-food-not-outstanding = ምግቡ በመጠባበቅ ላይ አይደለም፦ የምናሌ ቁጥር { $menu_number }
+tab-food-not-prepared = ምግቡ ገና አልተዘጋጀም፦ የምናሌ ቁጥር { $menu_number }
+tab-food-already-rushed = ምግቡ አስቀድሞ እንዲፋጠን ተጠይቋል፦ የምናሌ ቁጥር { $menu_number }
+tab-id-mismatch = ለሂሳብ { $actual } የተላከ ትዕዛዝ ወደ ሂሳብ { $expected } ደርሷል
+tab-currency-mismatch = የቀረበው መጠን በ{ $actual } ነው፤ የሚጠበቀው { $expected } ነበር
+tab-exchange-rate-unavailable = ከ{ $from } ወደ { $to } የምንዛሪ ተመን የለም
+tab-gift-card-exceeds-balance = የስጦታ ካርድ ክፍያው ከቀሪው { $outstanding } ይበልጣል
+tab-idempotency-key-reused = የድግግሞሽ መከላከያ ቁልፉ ለሌላ ትዕዛዝ ጥቅም ላይ ውሏል
+tab-exchange-rate-lookup-failed = ከ{ $from } ወደ { $to } የምንዛሪ ተመኑን መጫን አልተቻለም
+tab-gift-card-not-redeemed = የስጦታ ካርድ ክፍያው ከካርዱ አልተቀነሰም
+tab-command-in-flight = በዚህ የድግግሞሽ መከላከያ ቁልፍ የተላከ ትዕዛዝ ገና በሂደት ላይ ነው
+tab-negative-amount = መጠኑ አሉታዊ መሆን የለበትም
+tab-invalid-currency = ልክ ያልሆነ የገንዘብ ኮድ፦ { $code }
+tab-invalid-exchange-rate = የምንዛሬ ተመኑ አዎንታዊ መሆን አለበት፦ { $rate }
+tab-amount-too-large = መጠኑ በጣም ትልቅ ነው
+
+## Receipts
+
+receipt-table = ጠረጴዛ { $table }
+receipt-subtotal = ንዑስ ድምር
+receipt-tax = ታክስን ጨምሮ { $rate }%
+receipt-total = ጠቅላላ
+receipt-paid = የተከፈለ
+receipt-paid-foreign = የተከፈለ { $tendered } በ{ $rate }
+receipt-gift-card = የስጦታ ካርድ { $code }
+receipt-tip = ጉርሻ
+
+## Kitchen tickets
+
+ticket-table = ጠረጴዛ { $table }
+ticket-waiter = አስተናጋጅ፦ { $waiter }
+ticket-priority-high = ቅድሚያ
+ticket-priority-rush = አስቸኳይ
@@ -0,0 +1,41 @@
+## Tab errors, one message per TabError code
+
+tab-item-already-served = cannot cancel served item
+tab-has-unserved-items = tab has unserved items
+tab-payment-too-low = payment amount is not enough
+tab-not-open = tab is not open
+tab-drink-not-outstanding = drink is not outstanding: menu number { $menu_number }
+tab-already-open = already open: { $id }
+tab-food-not-outstanding = food is not outstanding: menu number { $menu_number }
+tab-food-not-prepared = food has not been prepared: menu number { $menu_number }
+tab-food-already-rushed = food has already been rushed: menu number { $menu_number }
+tab-id-mismatch = command for tab { $actual } sent to tab { $expected }
+tab-currency-mismatch = amount in { $actual } where { $expected } was expected
+tab-exchange-rate-unavailable = no exchange rate from { $from } to { $to }
+tab-gift-card-exceeds-balance = gift card payment exceeds the outstanding { $outstanding }
+tab-idempotency-key-reused = idempotency key was already used for another command
+tab-exchange-rate-lookup-failed = could not load the exchange rate from { $from } to { $to }
+tab-gift-card-not-redeemed = gift card payment was not redeemed from the card
+tab-command-in-flight = a command with this idempotency key is still running
+tab-negative-amount = amount must not be negative
+tab-invalid-currency = invalid currency code: { $code }
+tab-invalid-exchange-rate = exchange rate must be positive: { $rate }
+tab-amount-too-large = amount is too large
+
+## Receipts
+
+receipt-table = Table { $table }
+receipt-subtotal = Subtotal
+receipt-tax = Incl. tax { $rate }%
+receipt-total = TOTAL
+receipt-paid = Paid
+receipt-paid-foreign = Paid { $tendered } @ { $rate }
+receipt-gift-card = Gift card { $code }
+receipt-tip = Tip
+
+## Kitchen tickets
+
+ticket-table = TABLE { $table }
+ticket-waiter = Waiter: { $waiter }
+ticket-priority-high = PRIORITY
+ticket-priority-rush = RUSH
@@ -0,0 +1,41 @@
+## Tab errors, one message per TabError code
+
+tab-item-already-served = impossible d'annuler un article déjà servi
+tab-has-unserved-items = l'addition contient des articles non servis
+tab-payment-too-low = le montant payé est insuffisant
+tab-not-open = l'addition n'est pas ouverte
+tab-drink-not-outstanding = boisson non en attente : numéro { $menu_number }
+tab-already-open = déjà ouverte : { $id }
+tab-food-not-outstanding = plat non en attente : numéro { $menu_number }
+tab-food-not-prepared = plat pas encore préparé : numéro { $menu_number }
+tab-food-already-rushed = plat déjà signalé urgent : numéro { $menu_number }
+tab-id-mismatch = commande pour l'addition { $actual } envoyée à l'addition { $expected }
+tab-currency-mismatch = montant en { $actual } alors que { $expected } était attendu
+tab-exchange-rate-unavailable = aucun taux de change de { $from } vers { $to }
+tab-gift-card-exceeds-balance = le paiement par carte cadeau dépasse le solde dû de { $outstanding }
+tab-idempotency-key-reused = clé d'idempotence déjà utilisée pour une autre commande
+tab-exchange-rate-lookup-failed = impossible de charger le taux de change de { $from } vers { $to }
+tab-gift-card-not-redeemed = le paiement par carte cadeau n'a pas été débité de la carte
+tab-command-in-flight = une commande avec cette clé d'idempotence est encore en cours
+tab-negative-amount = le montant ne peut pas être négatif
+tab-invalid-currency = code de devise invalide : { $code }
+tab-invalid-exchange-rate = le taux de change doit être positif : { $rate }
+tab-amount-too-large = montant trop élevé
+
+## Receipts
+
+receipt-table = Table { $table }
+receipt-subtotal = Sous-total
+receipt-tax = Dont TVA { $rate } %
+receipt-total = TOTAL
+receipt-paid = Payé
+receipt-paid-foreign = Payé { $tendered } à { $rate }
+receipt-gift-card = Carte cadeau { $code }
+receipt-tip = Pourboire
+
+## Kitchen tickets
+
+ticket-table = TABLE { $table }
+ticket-waiter = Serveur : { $waiter }
+ticket-priority-high = PRIORITAIRE
+ticket-priority-rush = URGENT
@@ -0,0 +1,2 @@
+-- Add down migration script here
+DROP TABLE bar_tab_query;
\ No newline at end of file
@@ -0,0 +1,9 @@
+-- Add up migration script here
+CREATE TABLE projection_checkpoints
+(
+    projection    text                              NOT NULL,
+    aggregate_id  text                              NOT NULL,
+    last_sequence bigint CHECK (last_sequence >= 0) NOT NULL,
+    updated_at    timestamptz DEFAULT now()         NOT NULL,
+    PRIMARY KEY (projection, aggregate_id)
+);
@@ -0,0 +1,7 @@
+-- Add down migration script here
+DROP TRIGGER events_notify_appended ON events;
+DROP FUNCTION events_notify_appended();
+DROP TRIGGER events_assign_position ON events;
+DROP FUNCTION events_assign_position();
+DROP INDEX events_position_idx;
+ALTER TABLE events DROP COLUMN position;
@@ -0,0 +1,30 @@
+-- Add up migration script here
+ALTER TABLE events ADD COLUMN position bigserial NOT NULL;
+CREATE UNIQUE INDEX events_position_idx ON events (position);
+
+-- Appends take a transaction-scoped lock before drawing a position, so
+-- positions become visible in commit order and readers never skip a gap.
+CREATE FUNCTION events_assign_position() RETURNS trigger AS
+$$
+BEGIN
+    PERFORM pg_advisory_xact_lock(hashtext('events_position'));
+    NEW.position := nextval(pg_get_serial_sequence('events', 'position'));
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER events_assign_position
+    BEFORE INSERT ON events
+    FOR EACH ROW EXECUTE FUNCTION events_assign_position();
+
+CREATE FUNCTION events_notify_appended() RETURNS trigger AS
+$$
+BEGIN
+    PERFORM pg_notify('tab_events', NEW.position::text);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER events_notify_appended
+    AFTER INSERT ON events
+    FOR EACH ROW EXECUTE FUNCTION events_notify_appended();
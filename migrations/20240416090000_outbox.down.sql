@@ -0,0 +1,6 @@
+-- Add down migration script here
+DROP TRIGGER events_write_outbox ON events;
+DROP FUNCTION events_write_outbox();
+DROP TABLE outbox_dead_letters;
+DROP TABLE outbox_deliveries;
+DROP TABLE outbox;
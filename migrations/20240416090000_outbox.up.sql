@@ -0,0 +1,50 @@
+-- Add up migration script here
+CREATE TABLE outbox
+(
+    id             bigserial PRIMARY KEY,
+    aggregate_type text                      NOT NULL,
+    aggregate_id   text                      NOT NULL,
+    sequence       bigint                    NOT NULL,
+    event_type     text                      NOT NULL,
+    payload        json                      NOT NULL,
+    metadata       json                      NOT NULL,
+    created_at     timestamptz DEFAULT now() NOT NULL
+);
+
+CREATE TABLE outbox_deliveries
+(
+    webhook         text                          NOT NULL,
+    outbox_id       bigint REFERENCES outbox (id) NOT NULL,
+    attempts        int CHECK (attempts >= 0)     NOT NULL,
+    next_attempt_at timestamptz                   NOT NULL,
+    delivered_at    timestamptz,
+    last_error      text,
+    PRIMARY KEY (webhook, outbox_id)
+);
+
+CREATE TABLE outbox_dead_letters
+(
+    webhook    text                          NOT NULL,
+    outbox_id  bigint REFERENCES outbox (id) NOT NULL,
+    attempts   int                           NOT NULL,
+    last_error text                          NOT NULL,
+    failed_at  timestamptz DEFAULT now()     NOT NULL,
+    PRIMARY KEY (webhook, outbox_id)
+);
+
+-- Runs inside the transaction that appends the event, so an entry exists if
+-- and only if the event was committed.
+CREATE FUNCTION events_write_outbox() RETURNS trigger AS
+$$
+BEGIN
+    INSERT INTO outbox (aggregate_type, aggregate_id, sequence, event_type, payload, metadata)
+    VALUES (NEW.aggregate_type, NEW.aggregate_id, NEW.sequence, NEW.event_type, NEW.payload, NEW.metadata);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER events_write_outbox
+    AFTER INSERT ON events
+    FOR EACH ROW
+    WHEN (NEW.event_type IN ('TabClosed'))
+    EXECUTE FUNCTION events_write_outbox();
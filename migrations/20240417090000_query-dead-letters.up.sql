@@ -0,0 +1,12 @@
+-- Add up migration script here
+CREATE TABLE query_dead_letters
+(
+    id        bigserial PRIMARY KEY,
+    query     text                      NOT NULL,
+    view_id   text                      NOT NULL,
+    events    json                      NOT NULL,
+    error     text                      NOT NULL,
+    attempts  int                       NOT NULL,
+    failed_at timestamptz DEFAULT now() NOT NULL
+);
+CREATE INDEX query_dead_letters_query_idx ON query_dead_letters (query);
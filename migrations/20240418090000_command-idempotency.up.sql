@@ -0,0 +1,9 @@
+-- Add up migration script here
+CREATE TABLE command_idempotency
+(
+    idempotency_key text                      NOT NULL PRIMARY KEY,
+    aggregate_id    text                      NOT NULL,
+    outcome         json,
+    created_at      timestamptz DEFAULT now() NOT NULL,
+    completed_at    timestamptz
+);
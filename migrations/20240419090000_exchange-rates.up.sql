@@ -0,0 +1,9 @@
+-- Add up migration script here
+CREATE TABLE exchange_rates
+(
+    from_currency text                      NOT NULL,
+    to_currency   text                      NOT NULL,
+    rate          numeric                   NOT NULL CHECK (rate > 0),
+    effective_at  timestamptz DEFAULT now() NOT NULL,
+    PRIMARY KEY (from_currency, to_currency, effective_at)
+);
@@ -0,0 +1,16 @@
+-- Add down migration script here
+DROP TRIGGER events_write_outbox ON events;
+CREATE OR REPLACE FUNCTION events_write_outbox() RETURNS trigger AS
+$$
+BEGIN
+    INSERT INTO outbox (aggregate_type, aggregate_id, sequence, event_type, payload, metadata)
+    VALUES (NEW.aggregate_type, NEW.aggregate_id, NEW.sequence, NEW.event_type, NEW.payload, NEW.metadata);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER events_write_outbox
+    AFTER INSERT ON events
+    FOR EACH ROW
+    WHEN (NEW.event_type IN ('TabClosed'))
+    EXECUTE FUNCTION events_write_outbox();
+DROP TABLE outbox_published_event_types;
@@ -0,0 +1,23 @@
+-- Add up migration script here
+CREATE TABLE outbox_published_event_types
+(
+    event_type text PRIMARY KEY
+);
+INSERT INTO outbox_published_event_types (event_type) VALUES ('TabClosed');
+
+CREATE OR REPLACE FUNCTION events_write_outbox() RETURNS trigger AS
+$$
+BEGIN
+    IF EXISTS (SELECT 1 FROM outbox_published_event_types WHERE event_type = NEW.event_type) THEN
+        INSERT INTO outbox (aggregate_type, aggregate_id, sequence, event_type, payload, metadata)
+        VALUES (NEW.aggregate_type, NEW.aggregate_id, NEW.sequence, NEW.event_type, NEW.payload, NEW.metadata);
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER events_write_outbox ON events;
+CREATE TRIGGER events_write_outbox
+    AFTER INSERT ON events
+    FOR EACH ROW
+    EXECUTE FUNCTION events_write_outbox();
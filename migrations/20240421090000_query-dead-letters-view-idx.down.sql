@@ -0,0 +1,2 @@
+-- Add down migration script here
+DROP INDEX query_dead_letters_view_idx;
@@ -0,0 +1,2 @@
+-- Add up migration script here
+CREATE INDEX query_dead_letters_view_idx ON query_dead_letters (query, view_id);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE command_idempotency
+    DROP COLUMN claimed_at;
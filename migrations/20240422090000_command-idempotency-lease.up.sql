@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE command_idempotency
+    ADD COLUMN claimed_at timestamptz DEFAULT now() NOT NULL;
@@ -0,0 +1,10 @@
+-- Add up migration script here
+CREATE TABLE gift_card_redemptions
+(
+    redemption text                      NOT NULL PRIMARY KEY,
+    tab_id     text                      NOT NULL,
+    saga       json                      NOT NULL,
+    updated_at timestamptz DEFAULT now() NOT NULL
+);
+
+CREATE INDEX gift_card_redemptions_updated_at_idx ON gift_card_redemptions (updated_at);
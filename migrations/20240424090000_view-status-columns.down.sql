@@ -0,0 +1,9 @@
+-- Add down migration script here
+DROP INDEX kitchen_tab_query_outstanding_idx;
+ALTER TABLE kitchen_tab_query DROP COLUMN outstanding;
+
+DROP INDEX bar_tab_query_outstanding_idx;
+ALTER TABLE bar_tab_query DROP COLUMN outstanding;
+
+DROP INDEX tab_query_open_idx;
+ALTER TABLE tab_query DROP COLUMN open;
@@ -0,0 +1,8 @@
+-- Add up migration script here
+CREATE TABLE projection_positions
+(
+    projection text                         NOT NULL,
+    position   bigint CHECK (position >= 0) NOT NULL,
+    updated_at timestamptz DEFAULT now()    NOT NULL,
+    PRIMARY KEY (projection)
+);
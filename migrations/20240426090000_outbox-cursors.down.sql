@@ -0,0 +1,15 @@
+-- Add down migration script here
+CREATE OR REPLACE FUNCTION events_write_outbox() RETURNS trigger AS
+$$
+BEGIN
+    IF EXISTS (SELECT 1 FROM outbox_published_event_types WHERE event_type = NEW.event_type) THEN
+        INSERT INTO outbox (aggregate_type, aggregate_id, sequence, event_type, payload, metadata)
+        VALUES (NEW.aggregate_type, NEW.aggregate_id, NEW.sequence, NEW.event_type, NEW.payload, NEW.metadata);
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+ALTER TABLE outbox DROP COLUMN event_version;
+
+DROP TABLE outbox_cursors;
@@ -0,0 +1,27 @@
+-- Add up migration script here
+CREATE TABLE outbox_cursors
+(
+    webhook    text                         NOT NULL,
+    position   bigint CHECK (position >= 0) NOT NULL,
+    updated_at timestamptz DEFAULT now()    NOT NULL,
+    PRIMARY KEY (webhook)
+);
+
+ALTER TABLE outbox ADD COLUMN event_version text;
+UPDATE outbox o
+  SET event_version = e.event_version
+  FROM events e
+  WHERE e.aggregate_type = o.aggregate_type AND e.aggregate_id = o.aggregate_id AND e.sequence = o.sequence;
+UPDATE outbox SET event_version = '1.0.0' WHERE event_version IS NULL;
+ALTER TABLE outbox ALTER COLUMN event_version SET NOT NULL;
+
+CREATE OR REPLACE FUNCTION events_write_outbox() RETURNS trigger AS
+$$
+BEGIN
+    IF EXISTS (SELECT 1 FROM outbox_published_event_types WHERE event_type = NEW.event_type) THEN
+        INSERT INTO outbox (aggregate_type, aggregate_id, sequence, event_type, event_version, payload, metadata)
+        VALUES (NEW.aggregate_type, NEW.aggregate_id, NEW.sequence, NEW.event_type, NEW.event_version, NEW.payload, NEW.metadata);
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,7 @@
+-- Add down migration script here
+DROP TRIGGER events_complete_idempotency ON events;
+
+DROP FUNCTION events_complete_idempotency();
+
+ALTER TABLE command_idempotency
+    DROP COLUMN command_hash;
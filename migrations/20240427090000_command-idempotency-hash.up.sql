@@ -0,0 +1,24 @@
+-- Add up migration script here
+ALTER TABLE command_idempotency
+    ADD COLUMN command_hash text;
+
+-- Runs inside the transaction that appends the events, so a key whose
+-- command committed is recorded as succeeded even if the dispatcher never
+-- gets to complete it.
+CREATE FUNCTION events_complete_idempotency() RETURNS trigger AS
+$$
+BEGIN
+    UPDATE command_idempotency
+    SET outcome = '"Succeeded"', completed_at = now()
+    WHERE idempotency_key = NEW.metadata ->> 'idempotency_key'
+      AND aggregate_id = NEW.aggregate_id
+      AND outcome IS NULL;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER events_complete_idempotency
+    AFTER INSERT ON events
+    FOR EACH ROW
+    WHEN (NEW.metadata ->> 'idempotency_key' IS NOT NULL)
+    EXECUTE FUNCTION events_complete_idempotency();
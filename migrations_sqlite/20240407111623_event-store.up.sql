@@ -0,0 +1,12 @@
+-- Add up migration script here
+CREATE TABLE events
+(
+    aggregate_type text                          NOT NULL,
+    aggregate_id   text                          NOT NULL,
+    sequence       integer CHECK (sequence >= 0) NOT NULL,
+    event_type     text                          NOT NULL,
+    event_version  text                          NOT NULL,
+    payload        text                          NOT NULL,
+    metadata       text                          NOT NULL,
+    PRIMARY KEY (aggregate_type, aggregate_id, sequence)
+);
@@ -0,0 +1,8 @@
+-- Add up migration script here
+CREATE TABLE bar_tab_query
+(
+    view_id text                         NOT NULL,
+    version integer CHECK (version >= 0) NOT NULL,
+    payload text                         NOT NULL,
+    PRIMARY KEY (view_id)
+);
@@ -0,0 +1,10 @@
+-- Add up migration script here
+CREATE TABLE snapshots
+(
+    aggregate_type   text                                  NOT NULL,
+    aggregate_id     text                                  NOT NULL,
+    last_sequence    integer CHECK (last_sequence >= 0)    NOT NULL,
+    current_snapshot integer CHECK (current_snapshot >= 0) NOT NULL,
+    payload          text                                  NOT NULL,
+    PRIMARY KEY (aggregate_type, aggregate_id, last_sequence)
+);
@@ -0,0 +1,2 @@
+-- Add down migration script here
+DROP TABLE query_dead_letters;
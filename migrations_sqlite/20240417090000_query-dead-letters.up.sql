@@ -0,0 +1,12 @@
+-- Add up migration script here
+CREATE TABLE query_dead_letters
+(
+    id        integer PRIMARY KEY AUTOINCREMENT,
+    query     text                              NOT NULL,
+    view_id   text                              NOT NULL,
+    events    text                              NOT NULL,
+    error     text                              NOT NULL,
+    attempts  integer                           NOT NULL,
+    failed_at text DEFAULT CURRENT_TIMESTAMP    NOT NULL
+);
+CREATE INDEX query_dead_letters_view_idx ON query_dead_letters (query, view_id);
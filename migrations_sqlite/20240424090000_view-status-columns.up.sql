@@ -0,0 +1,14 @@
+-- Add up migration script here
+ALTER TABLE tab_query
+    ADD COLUMN open integer GENERATED ALWAYS AS (json_extract(payload, '$.open')) VIRTUAL;
+CREATE INDEX tab_query_open_idx ON tab_query (view_id) WHERE open;
+
+ALTER TABLE bar_tab_query
+    ADD COLUMN outstanding integer GENERATED ALWAYS AS (
+        json_extract(payload, '$.open') AND json_array_length(payload, '$.drinks') > 0
+    ) VIRTUAL;
+CREATE INDEX bar_tab_query_outstanding_idx ON bar_tab_query (view_id) WHERE outstanding;
+
+ALTER TABLE kitchen_tab_query
+    ADD COLUMN outstanding integer GENERATED ALWAYS AS (json_array_length(payload, '$.inner') > 0) VIRTUAL;
+CREATE INDEX kitchen_tab_query_outstanding_idx ON kitchen_tab_query (view_id) WHERE outstanding;
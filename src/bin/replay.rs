@@ -0,0 +1,77 @@
+use std::env;
+
+use cafe_tab::{
+    domain::tab::{
+        aggregate::Tab,
+        queries::{
+            bar::BarTodoList,
+            kitchen::KitchenTodoList,
+            open_tabs::{TabStatus, WaiterTodoList},
+        },
+    },
+    infrasctructure::respository::postgresql::replay::{
+        ProgressReporter, ProjectionReplay, ReplayProgress,
+    },
+    shared_kernel::money::Currency,
+};
+use cqrs_es::{persist::PersistenceError, View};
+use postgres_es::default_postgress_pool;
+use sqlx::{Pool, Postgres};
+
+const USAGE: &str = "usage: replay <kitchen|waiter|bar|tab> [aggregate_id]
+  rebuilds the view table from the events table; DATABASE_URL selects the database
+  and MENU_CURRENCY the currency of amounts recorded before currencies were";
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (view, aggregate_id) = match args.as_slice() {
+        [view] => (view.as_str(), None),
+        [view, aggregate_id] => (view.as_str(), Some(aggregate_id.as_str())),
+        _ => exit_with_usage(),
+    };
+    let url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let currency = env::var("MENU_CURRENCY").expect("MENU_CURRENCY must be set");
+    let currency = Currency::new(&currency).expect("MENU_CURRENCY must be a currency code");
+    let pool = default_postgress_pool(&url).await;
+
+    let result = match view {
+        "kitchen" => {
+            replay::<KitchenTodoList>(pool, "kitchen_tab_query", currency, aggregate_id).await
+        }
+        "waiter" => {
+            replay::<WaiterTodoList>(pool, "waiter_tab_query", currency, aggregate_id).await
+        }
+        "bar" => replay::<BarTodoList>(pool, "bar_tab_query", currency, aggregate_id).await,
+        "tab" => replay::<TabStatus>(pool, "tab_query", currency, aggregate_id).await,
+        _ => exit_with_usage(),
+    };
+    if let Err(e) = result {
+        eprintln!("replay failed: {e}");
+        std::process::exit(1);
+    }
+}
+
+async fn replay<V: View<Tab>>(
+    pool: Pool<Postgres>,
+    view_name: &str,
+    currency: Currency,
+    aggregate_id: Option<&str>,
+) -> Result<ReplayProgress, PersistenceError> {
+    let reporter: ProgressReporter = Box::new(|p| {
+        let state = if p.finished { "done" } else { "replaying" };
+        println!("{state}: {} events, {} aggregates", p.events, p.aggregates);
+    });
+    let replay =
+        ProjectionReplay::<V>::new(pool, view_name, currency).with_progress(1000, reporter);
+
+    match aggregate_id {
+        Some(aggregate_id) => replay.rebuild(aggregate_id).await,
+        None => replay.rebuild_all().await,
+    }
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("{USAGE}");
+    std::process::exit(2)
+}
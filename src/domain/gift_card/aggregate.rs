@@ -0,0 +1,572 @@
+use std::collections::{HashMap, HashSet};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use cqrs_es::Aggregate;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    domain::tab::tab_id::TabId,
+    shared_kernel::money::{Money, MoneyError},
+};
+
+use super::{
+    command::GiftCardCommand, error::GiftCardError, event::GiftCardEvent,
+    redemption_id::RedemptionId, services::GiftCardServices,
+};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GiftCard {
+    code: String,
+    issued: bool,
+    expired: bool,
+    balance: Money,
+    expires_at: Option<DateTime<Utc>>,
+    redeemed_by_tab: HashMap<TabId, Money>,
+    open_redemptions: HashSet<RedemptionId>,
+}
+
+#[async_trait]
+impl Aggregate for GiftCard {
+    type Command = GiftCardCommand;
+    type Event = GiftCardEvent;
+    type Error = GiftCardError;
+    type Services = GiftCardServices;
+
+    fn aggregate_type() -> String {
+        "GiftCard".into()
+    }
+
+    async fn handle(
+        &self,
+        command: Self::Command,
+        services: &Self::Services,
+    ) -> Result<Vec<Self::Event>, Self::Error> {
+        match command {
+            GiftCardCommand::Issue {
+                code,
+                amount,
+                expires_at,
+            } => {
+                if self.issued {
+                    return Err(GiftCardError::AlreadyIssued {
+                        code: self.code.clone(),
+                    });
+                }
+                positive_or_error(amount)?;
+                if expires_at.is_some_and(|at| at <= services.now()) {
+                    return Err(GiftCardError::ExpiryInPast);
+                }
+                Ok(vec![GiftCardEvent::GiftCardIssued {
+                    code,
+                    amount,
+                    expires_at,
+                }])
+            }
+            GiftCardCommand::TopUp { code, amount } => {
+                self.usable_or_error(&code, services.now())?;
+                positive_or_error(amount)?;
+                self.balance.checked_add(amount).map_err(balance_error)?;
+                Ok(vec![GiftCardEvent::GiftCardToppedUp { code, amount }])
+            }
+            GiftCardCommand::Redeem {
+                code,
+                tab_id,
+                amount,
+                redemption,
+            } => {
+                self.usable_or_error(&code, services.now())?;
+                positive_or_error(amount)?;
+                self.balance.checked_sub(amount).map_err(|e| match e {
+                    MoneyError::Negative => GiftCardError::InsufficientBalance {
+                        balance: self.balance,
+                    },
+                    e => balance_error(e),
+                })?;
+                self.redeemed_by(tab_id)
+                    .checked_add(amount)
+                    .map_err(balance_error)?;
+                Ok(vec![GiftCardEvent::GiftCardRedeemed {
+                    code,
+                    tab_id,
+                    amount,
+                    redemption,
+                }])
+            }
+            GiftCardCommand::ReverseRedemption {
+                code,
+                tab_id,
+                amount,
+                redemption,
+            } => {
+                self.issued_or_error(&code)?;
+                // Only redemptions the saga recorded can be reversed.
+                if redemption.is_nil() {
+                    return Err(GiftCardError::RedemptionNotFound { tab_id });
+                }
+                // Reversing a redemption that never happened, or was already
+                // reversed, changes nothing, so the saga can safely retry.
+                if !self.open_redemptions.contains(&redemption) {
+                    return Ok(vec![]);
+                }
+                self.redeemed_by(tab_id)
+                    .checked_sub(amount)
+                    .map_err(|e| match e {
+                        MoneyError::Negative => GiftCardError::RedemptionNotFound { tab_id },
+                        e => balance_error(e),
+                    })?;
+                self.balance.checked_add(amount).map_err(balance_error)?;
+                Ok(vec![GiftCardEvent::GiftCardRedemptionReversed {
+                    code,
+                    tab_id,
+                    amount,
+                    redemption,
+                }])
+            }
+            GiftCardCommand::Expire { code } => {
+                self.issued_or_error(&code)?;
+                if self.expired {
+                    return Err(GiftCardError::Expired { code });
+                }
+                Ok(vec![GiftCardEvent::GiftCardExpired {
+                    code,
+                    forfeited: self.balance,
+                }])
+            }
+        }
+    }
+
+    fn apply(&mut self, event: Self::Event) {
+        match event {
+            GiftCardEvent::GiftCardIssued {
+                code,
+                amount,
+                expires_at,
+            } => {
+                self.code = code;
+                self.issued = true;
+                self.balance = amount;
+                self.expires_at = expires_at;
+            }
+            GiftCardEvent::GiftCardToppedUp { amount, .. } => self.credit(amount),
+            GiftCardEvent::GiftCardRedeemed {
+                tab_id,
+                amount,
+                redemption,
+                ..
+            } => {
+                self.balance = self
+                    .balance
+                    .checked_sub(amount)
+                    .unwrap_or(Money::zero(amount.currency()));
+                let redeemed = self.redeemed_by(tab_id).checked_add(amount);
+                self.redeemed_by_tab
+                    .insert(tab_id, redeemed.unwrap_or(self.redeemed_by(tab_id)));
+                if !redemption.is_nil() {
+                    self.open_redemptions.insert(redemption);
+                }
+            }
+            GiftCardEvent::GiftCardRedemptionReversed {
+                tab_id,
+                amount,
+                redemption,
+                ..
+            } => {
+                self.open_redemptions.remove(&redemption);
+                self.credit(amount);
+                let redeemed = self.redeemed_by(tab_id).checked_sub(amount);
+                self.redeemed_by_tab
+                    .insert(tab_id, redeemed.unwrap_or(Money::zero(amount.currency())));
+            }
+            GiftCardEvent::GiftCardExpired { .. } => {
+                self.expired = true;
+                self.balance = Money::zero(self.balance.currency());
+            }
+        }
+    }
+}
+
+impl GiftCard {
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    pub fn balance(&self) -> Money {
+        self.balance
+    }
+
+    // The command handlers checked the sum, so an amount that cannot be added
+    // only comes from events recorded before they did and is left out.
+    fn credit(&mut self, amount: Money) {
+        self.balance = self.balance.checked_add(amount).unwrap_or(self.balance);
+    }
+
+    fn redeemed_by(&self, tab_id: TabId) -> Money {
+        self.redeemed_by_tab
+            .get(&tab_id)
+            .copied()
+            .unwrap_or(Money::zero(self.balance.currency()))
+    }
+
+    fn issued_or_error(&self, code: &str) -> Result<(), GiftCardError> {
+        if !self.issued {
+            return Err(GiftCardError::NotIssued);
+        }
+        if code != self.code {
+            return Err(GiftCardError::CodeMismatch {
+                expected: self.code.clone(),
+                actual: code.to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
+    // Expired cards can still have redemptions reversed, but nothing else.
+    fn usable_or_error(&self, code: &str, now: DateTime<Utc>) -> Result<(), GiftCardError> {
+        self.issued_or_error(code)?;
+        if self.expired || self.expires_at.is_some_and(|at| at <= now) {
+            return Err(GiftCardError::Expired {
+                code: self.code.clone(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+fn positive_or_error(amount: Money) -> Result<(), GiftCardError> {
+    if amount.is_zero() {
+        return Err(GiftCardError::InvalidAmount);
+    }
+
+    Ok(())
+}
+
+fn balance_error(error: MoneyError) -> GiftCardError {
+    match error {
+        MoneyError::CurrencyMismatch { expected, actual } => {
+            GiftCardError::CurrencyMismatch { expected, actual }
+        }
+        MoneyError::Overflow => GiftCardError::BalanceTooLarge,
+        MoneyError::Negative | MoneyError::InvalidCurrency(_) | MoneyError::InvalidRate(_) => {
+            GiftCardError::InvalidAmount
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::{Duration, TimeZone, Utc};
+    use cqrs_es::test::TestFramework;
+    use rust_decimal::Decimal;
+
+    use crate::{
+        domain::{
+            gift_card::{
+                command::GiftCardCommand, error::GiftCardError, event::GiftCardEvent,
+                redemption_id::RedemptionId, services::GiftCardServices,
+            },
+            tab::tab_id::TabId,
+        },
+        shared_kernel::{clock::FixedClock, fixtures::usd},
+    };
+
+    use super::GiftCard;
+
+    const CODE: &str = "GC-1001";
+
+    fn issued(amount: i64) -> GiftCardEvent {
+        GiftCardEvent::GiftCardIssued {
+            code: CODE.into(),
+            amount: usd(amount),
+            expires_at: None,
+        }
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_no_card_when_Issue_then_GiftCardIssued_event() {
+        let expires_at = Some(Utc::now() + Duration::days(365));
+
+        let result = TestFramework::<GiftCard>::with(GiftCardServices::default())
+            .given_no_previous_events()
+            .when(GiftCardCommand::Issue {
+                code: CODE.into(),
+                amount: usd(50),
+                expires_at,
+            });
+
+        result.then_expect_events(vec![GiftCardEvent::GiftCardIssued {
+            code: CODE.into(),
+            amount: usd(50),
+            expires_at,
+        }]);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_issued_card_when_Issue_again_then_AlreadyIssued_error() {
+        let result = TestFramework::<GiftCard>::with(GiftCardServices::default())
+            .given(vec![issued(50)])
+            .when(GiftCardCommand::Issue {
+                code: CODE.into(),
+                amount: usd(10),
+                expires_at: None,
+            });
+
+        result.then_expect_error(GiftCardError::AlreadyIssued { code: CODE.into() });
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_full_card_when_TopUp_overflows_then_BalanceTooLarge_error() {
+        let result = TestFramework::<GiftCard>::with(GiftCardServices::default())
+            .given(vec![GiftCardEvent::GiftCardIssued {
+                code: CODE.into(),
+                amount: usd(Decimal::MAX),
+                expires_at: None,
+            }])
+            .when(GiftCardCommand::TopUp {
+                code: CODE.into(),
+                amount: usd(1),
+            });
+
+        result.then_expect_error(GiftCardError::BalanceTooLarge);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_topped_up_card_when_Redeem_within_balance_then_GiftCardRedeemed_event() {
+        let tab_id = TabId::new();
+
+        let result = TestFramework::<GiftCard>::with(GiftCardServices::default())
+            .given(vec![
+                issued(20),
+                GiftCardEvent::GiftCardToppedUp {
+                    code: CODE.into(),
+                    amount: usd(30),
+                },
+            ])
+            .when(GiftCardCommand::Redeem {
+                code: CODE.into(),
+                tab_id,
+                amount: usd(50),
+                redemption: RedemptionId::default(),
+            });
+
+        result.then_expect_events(vec![GiftCardEvent::GiftCardRedeemed {
+            code: CODE.into(),
+            tab_id,
+            amount: usd(50),
+            redemption: RedemptionId::default(),
+        }]);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_partly_redeemed_card_when_Redeem_more_than_balance_then_InsufficientBalance_error() {
+        let result = TestFramework::<GiftCard>::with(GiftCardServices::default())
+            .given(vec![
+                issued(50),
+                GiftCardEvent::GiftCardRedeemed {
+                    code: CODE.into(),
+                    tab_id: TabId::new(),
+                    amount: usd(35),
+                    redemption: RedemptionId::default(),
+                },
+            ])
+            .when(GiftCardCommand::Redeem {
+                code: CODE.into(),
+                tab_id: TabId::new(),
+                amount: usd(20),
+                redemption: RedemptionId::default(),
+            });
+
+        result.then_expect_error(GiftCardError::InsufficientBalance { balance: usd(15) });
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_card_past_its_expiry_when_Redeem_or_TopUp_then_Expired_error() {
+        let expires_at = Utc.with_ymd_and_hms(2024, 4, 19, 12, 0, 0).unwrap();
+        let given = || {
+            vec![GiftCardEvent::GiftCardIssued {
+                code: CODE.into(),
+                amount: usd(50),
+                expires_at: Some(expires_at),
+            }]
+        };
+        let at = |now| GiftCardServices::default().with_clock(Arc::new(FixedClock(now)));
+        let top_up = || GiftCardCommand::TopUp {
+            code: CODE.into(),
+            amount: usd(10),
+        };
+
+        TestFramework::<GiftCard>::with(at(expires_at - Duration::seconds(1)))
+            .given(given())
+            .when(top_up())
+            .then_expect_events(vec![GiftCardEvent::GiftCardToppedUp {
+                code: CODE.into(),
+                amount: usd(10),
+            }]);
+        TestFramework::<GiftCard>::with(at(expires_at))
+            .given(given())
+            .when(GiftCardCommand::Redeem {
+                code: CODE.into(),
+                tab_id: TabId::new(),
+                amount: usd(10),
+                redemption: RedemptionId::default(),
+            })
+            .then_expect_error(GiftCardError::Expired { code: CODE.into() });
+        TestFramework::<GiftCard>::with(at(expires_at + Duration::days(1)))
+            .given(given())
+            .when(top_up())
+            .then_expect_error(GiftCardError::Expired { code: CODE.into() });
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_expiry_not_in_the_future_when_Issue_then_ExpiryInPast_error() {
+        let now = Utc.with_ymd_and_hms(2024, 4, 19, 12, 0, 0).unwrap();
+        let services = || GiftCardServices::default().with_clock(Arc::new(FixedClock(now)));
+
+        for expires_at in [now, now - Duration::days(1)] {
+            TestFramework::<GiftCard>::with(services())
+                .given_no_previous_events()
+                .when(GiftCardCommand::Issue {
+                    code: CODE.into(),
+                    amount: usd(50),
+                    expires_at: Some(expires_at),
+                })
+                .then_expect_error(GiftCardError::ExpiryInPast);
+        }
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_issued_card_when_Expire_then_remaining_balance_is_forfeited() {
+        let result = TestFramework::<GiftCard>::with(GiftCardServices::default())
+            .given(vec![issued(50)])
+            .when(GiftCardCommand::Expire { code: CODE.into() });
+
+        result.then_expect_events(vec![GiftCardEvent::GiftCardExpired {
+            code: CODE.into(),
+            forfeited: usd(50),
+        }]);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_redemption_for_tab_when_ReverseRedemption_then_only_that_amount_can_be_reversed() {
+        let tab_id = TabId::new();
+        let other_tab_id = TabId::new();
+        let redemption = RedemptionId::new();
+        let given = || {
+            vec![
+                issued(50),
+                GiftCardEvent::GiftCardRedeemed {
+                    code: CODE.into(),
+                    tab_id,
+                    amount: usd(30),
+                    redemption,
+                },
+            ]
+        };
+
+        TestFramework::<GiftCard>::with(GiftCardServices::default())
+            .given(given())
+            .when(GiftCardCommand::ReverseRedemption {
+                code: CODE.into(),
+                tab_id,
+                amount: usd(30),
+                redemption,
+            })
+            .then_expect_events(vec![GiftCardEvent::GiftCardRedemptionReversed {
+                code: CODE.into(),
+                tab_id,
+                amount: usd(30),
+                redemption,
+            }]);
+        TestFramework::<GiftCard>::with(GiftCardServices::default())
+            .given(given())
+            .when(GiftCardCommand::ReverseRedemption {
+                code: CODE.into(),
+                tab_id: other_tab_id,
+                amount: usd(30),
+                redemption,
+            })
+            .then_expect_error(GiftCardError::RedemptionNotFound {
+                tab_id: other_tab_id,
+            });
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_issued_card_when_command_for_another_code_or_zero_amount_then_error() {
+        TestFramework::<GiftCard>::with(GiftCardServices::default())
+            .given(vec![issued(50)])
+            .when(GiftCardCommand::TopUp {
+                code: "GC-2002".into(),
+                amount: usd(10),
+            })
+            .then_expect_error(GiftCardError::CodeMismatch {
+                expected: CODE.into(),
+                actual: "GC-2002".into(),
+            });
+        TestFramework::<GiftCard>::with(GiftCardServices::default())
+            .given(vec![issued(50)])
+            .when(GiftCardCommand::Redeem {
+                code: CODE.into(),
+                tab_id: TabId::new(),
+                amount: usd(0),
+                redemption: RedemptionId::default(),
+            })
+            .then_expect_error(GiftCardError::InvalidAmount);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_reversed_or_unknown_redemption_when_ReverseRedemption_then_nothing_changes() {
+        let tab_id = TabId::new();
+        let redemption = RedemptionId::new();
+        let reverse = |redemption| GiftCardCommand::ReverseRedemption {
+            code: CODE.into(),
+            tab_id,
+            amount: usd(30),
+            redemption,
+        };
+        let redeemed = GiftCardEvent::GiftCardRedeemed {
+            code: CODE.into(),
+            tab_id,
+            amount: usd(30),
+            redemption,
+        };
+        let reversed = GiftCardEvent::GiftCardRedemptionReversed {
+            code: CODE.into(),
+            tab_id,
+            amount: usd(30),
+            redemption,
+        };
+
+        TestFramework::<GiftCard>::with(GiftCardServices::default())
+            .given(vec![issued(50), redeemed.clone()])
+            .when(reverse(redemption))
+            .then_expect_events(vec![reversed.clone()]);
+        TestFramework::<GiftCard>::with(GiftCardServices::default())
+            .given(vec![issued(50), redeemed.clone(), reversed])
+            .when(reverse(redemption))
+            .then_expect_events(vec![]);
+        TestFramework::<GiftCard>::with(GiftCardServices::default())
+            .given(vec![issued(50), redeemed.clone()])
+            .when(reverse(RedemptionId::new()))
+            .then_expect_events(vec![]);
+        TestFramework::<GiftCard>::with(GiftCardServices::default())
+            .given(vec![issued(50), redeemed])
+            .when(reverse(RedemptionId::default()))
+            .then_expect_error(GiftCardError::RedemptionNotFound { tab_id });
+    }
+}
@@ -0,0 +1,68 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::{domain::tab::tab_id::TabId, shared_kernel::money::Money};
+
+use super::redemption_id::RedemptionId;
+
+#[derive(Clone, Debug, Deserialize)]
+pub enum GiftCardCommand {
+    Issue {
+        code: String,
+        amount: Money,
+        #[serde(default)]
+        expires_at: Option<DateTime<Utc>>,
+    },
+    TopUp {
+        code: String,
+        amount: Money,
+    },
+    Redeem {
+        code: String,
+        tab_id: TabId,
+        amount: Money,
+        // Issued by the gift card saga, never taken from clients.
+        #[serde(skip_deserializing)]
+        redemption: RedemptionId,
+    },
+    ReverseRedemption {
+        code: String,
+        tab_id: TabId,
+        amount: Money,
+        // Issued by the gift card saga, never taken from clients.
+        #[serde(skip_deserializing)]
+        redemption: RedemptionId,
+    },
+    Expire {
+        code: String,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn redemptions_sent_by_clients_are_dropped() {
+        let fields = json!({
+            "code": "GC-1001",
+            "tab_id": "00000000-0000-0000-0000-000000000001",
+            "amount": {"amount": "10", "currency": "USD"},
+            "redemption": "7f1c2a9e-5a4b-4c3d-9e8f-0123456789ab"
+        });
+
+        let redeem: GiftCardCommand = serde_json::from_value(json!({ "Redeem": fields })).unwrap();
+        let reverse: GiftCardCommand =
+            serde_json::from_value(json!({ "ReverseRedemption": fields })).unwrap();
+
+        assert!(
+            matches!(redeem, GiftCardCommand::Redeem { redemption, .. } if redemption.is_nil())
+        );
+        assert!(matches!(
+            reverse,
+            GiftCardCommand::ReverseRedemption { redemption, .. } if redemption.is_nil()
+        ));
+    }
+}
@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    domain::tab::{
+        error::{ErrorBody, ErrorCategory},
+        tab_id::TabId,
+    },
+    shared_kernel::money::{Currency, Money},
+};
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum GiftCardError {
+    NotIssued,
+    AlreadyIssued {
+        code: String,
+    },
+    CodeMismatch {
+        expected: String,
+        actual: String,
+    },
+    Expired {
+        code: String,
+    },
+    InvalidAmount,
+    InsufficientBalance {
+        balance: Money,
+    },
+    CurrencyMismatch {
+        expected: Currency,
+        actual: Currency,
+    },
+    RedemptionNotFound {
+        tab_id: TabId,
+    },
+    BalanceTooLarge,
+    ExpiryInPast,
+}
+
+impl GiftCardError {
+    // Codes are part of the public API: never change or reuse one.
+    pub fn code(&self) -> &'static str {
+        match self {
+            GiftCardError::NotIssued => "GIFT_CARD_NOT_ISSUED",
+            GiftCardError::AlreadyIssued { .. } => "GIFT_CARD_ALREADY_ISSUED",
+            GiftCardError::CodeMismatch { .. } => "GIFT_CARD_CODE_MISMATCH",
+            GiftCardError::Expired { .. } => "GIFT_CARD_EXPIRED",
+            GiftCardError::InvalidAmount => "GIFT_CARD_INVALID_AMOUNT",
+            GiftCardError::InsufficientBalance { .. } => "GIFT_CARD_INSUFFICIENT_BALANCE",
+            GiftCardError::CurrencyMismatch { .. } => "GIFT_CARD_CURRENCY_MISMATCH",
+            GiftCardError::RedemptionNotFound { .. } => "GIFT_CARD_REDEMPTION_NOT_FOUND",
+            GiftCardError::BalanceTooLarge => "GIFT_CARD_BALANCE_TOO_LARGE",
+            GiftCardError::ExpiryInPast => "GIFT_CARD_EXPIRY_IN_PAST",
+        }
+    }
+
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            GiftCardError::CodeMismatch { .. }
+            | GiftCardError::InvalidAmount
+            | GiftCardError::CurrencyMismatch { .. }
+            | GiftCardError::BalanceTooLarge
+            | GiftCardError::ExpiryInPast => ErrorCategory::Validation,
+            GiftCardError::NotIssued | GiftCardError::RedemptionNotFound { .. } => {
+                ErrorCategory::NotFound
+            }
+            GiftCardError::AlreadyIssued { .. } => ErrorCategory::Conflict,
+            GiftCardError::Expired { .. } | GiftCardError::InsufficientBalance { .. } => {
+                ErrorCategory::State
+            }
+        }
+    }
+
+    pub fn http_status(&self) -> u16 {
+        self.category().http_status()
+    }
+}
+
+impl From<&GiftCardError> for ErrorBody {
+    fn from(error: &GiftCardError) -> Self {
+        let category = error.category();
+        Self {
+            code: error.code().to_string(),
+            category,
+            status: category.http_status(),
+            message: error.to_string(),
+            details: None,
+        }
+    }
+}
+
+impl std::error::Error for GiftCardError {}
+
+impl std::fmt::Display for GiftCardError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            GiftCardError::NotIssued => String::from("gift card has not been issued"),
+            GiftCardError::AlreadyIssued { code } => format!("already issued: {code}"),
+            GiftCardError::CodeMismatch { expected, actual } => {
+                format!("command for gift card {actual} sent to gift card {expected}")
+            }
+            GiftCardError::Expired { code } => format!("gift card {code} has expired"),
+            GiftCardError::InvalidAmount => String::from("amount must be greater than zero"),
+            GiftCardError::InsufficientBalance { balance } => {
+                format!("insufficient balance: {balance}")
+            }
+            GiftCardError::CurrencyMismatch { expected, actual } => {
+                format!("amount in {actual} where {expected} was expected")
+            }
+            GiftCardError::RedemptionNotFound { tab_id } => {
+                format!("no redemption to reverse for tab {tab_id}")
+            }
+            GiftCardError::BalanceTooLarge => String::from("balance would be too large"),
+            GiftCardError::ExpiryInPast => String::from("expiry must be in the future"),
+        };
+
+        write!(f, "gift card error: {msg}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{
+        domain::tab::{error::ErrorBody, tab_id::TabId},
+        shared_kernel::money::{Currency, Money},
+    };
+
+    use super::GiftCardError;
+
+    #[test]
+    fn every_error_has_a_unique_gift_card_code() {
+        let errors = [
+            GiftCardError::NotIssued,
+            GiftCardError::AlreadyIssued { code: "A1".into() },
+            GiftCardError::CodeMismatch {
+                expected: "A1".into(),
+                actual: "B2".into(),
+            },
+            GiftCardError::Expired { code: "A1".into() },
+            GiftCardError::InvalidAmount,
+            GiftCardError::InsufficientBalance {
+                balance: Money::zero(Currency::USD),
+            },
+            GiftCardError::CurrencyMismatch {
+                expected: Currency::USD,
+                actual: Currency::EUR,
+            },
+            GiftCardError::RedemptionNotFound {
+                tab_id: TabId::default(),
+            },
+            GiftCardError::BalanceTooLarge,
+            GiftCardError::ExpiryInPast,
+        ];
+
+        let codes: HashSet<_> = errors.iter().map(|e| e.code()).collect();
+
+        assert_eq!(codes.len(), errors.len());
+        assert!(codes.iter().all(|c| c.starts_with("GIFT_CARD_")));
+    }
+
+    #[test]
+    fn given_error_then_body_carries_code_status_and_message() {
+        let error = GiftCardError::Expired { code: "A1".into() };
+
+        let body = ErrorBody::from(&error);
+
+        assert_eq!(body.code, "GIFT_CARD_EXPIRED");
+        assert_eq!(body.status, 422);
+        assert_eq!(body.message, "gift card error: gift card A1 has expired");
+    }
+}
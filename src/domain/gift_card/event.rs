@@ -0,0 +1,59 @@
+use chrono::{DateTime, Utc};
+use cqrs_es::DomainEvent;
+use serde::{Deserialize, Serialize};
+
+use crate::{domain::tab::tab_id::TabId, shared_kernel::money::Money};
+
+use super::redemption_id::RedemptionId;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum GiftCardEvent {
+    GiftCardIssued {
+        code: String,
+        amount: Money,
+        expires_at: Option<DateTime<Utc>>,
+    },
+    GiftCardToppedUp {
+        code: String,
+        amount: Money,
+    },
+    GiftCardRedeemed {
+        code: String,
+        tab_id: TabId,
+        amount: Money,
+        #[serde(default)]
+        redemption: RedemptionId,
+    },
+    GiftCardRedemptionReversed {
+        code: String,
+        tab_id: TabId,
+        amount: Money,
+        #[serde(default)]
+        redemption: RedemptionId,
+    },
+    GiftCardExpired {
+        code: String,
+        forfeited: Money,
+    },
+}
+
+impl DomainEvent for GiftCardEvent {
+    fn event_type(&self) -> String {
+        match self {
+            GiftCardEvent::GiftCardIssued { .. } => "GiftCardIssued".into(),
+            GiftCardEvent::GiftCardToppedUp { .. } => "GiftCardToppedUp".into(),
+            GiftCardEvent::GiftCardRedeemed { .. } => "GiftCardRedeemed".into(),
+            GiftCardEvent::GiftCardRedemptionReversed { .. } => "GiftCardRedemptionReversed".into(),
+            GiftCardEvent::GiftCardExpired { .. } => "GiftCardExpired".into(),
+        }
+    }
+
+    // 1.1 recorded the redemption id; earlier events read as the nil id.
+    fn event_version(&self) -> String {
+        match self {
+            GiftCardEvent::GiftCardRedeemed { .. }
+            | GiftCardEvent::GiftCardRedemptionReversed { .. } => "1.1.0".into(),
+            _ => "1.0.0".into(),
+        }
+    }
+}
@@ -0,0 +1,6 @@
+pub mod aggregate;
+pub mod command;
+pub mod error;
+pub mod event;
+pub mod redemption_id;
+pub mod services;
@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+// Issued by the gift card saga for each redemption. The default, nil id
+// stands for redemptions recorded before ids existed.
+#[derive(
+    Copy, Clone, Debug, Default, Hash, Eq, PartialEq, PartialOrd, Ord, Serialize, Deserialize,
+)]
+pub struct RedemptionId(uuid::Uuid);
+
+impl RedemptionId {
+    pub fn new() -> RedemptionId {
+        Self(uuid::Uuid::new_v4())
+    }
+
+    pub fn is_nil(&self) -> bool {
+        self.0.is_nil()
+    }
+}
+
+impl From<uuid::Uuid> for RedemptionId {
+    fn from(value: uuid::Uuid) -> Self {
+        Self(value)
+    }
+}
+
+impl std::fmt::Display for RedemptionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
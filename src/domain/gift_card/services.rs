@@ -0,0 +1,32 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+
+use crate::shared_kernel::clock::{Clock, SystemClock};
+
+#[derive(Clone, Debug)]
+pub struct GiftCardServices {
+    clock: Arc<dyn Clock>,
+}
+
+impl GiftCardServices {
+    pub fn new() -> Self {
+        Self {
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> Self {
+        Self { clock }
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+}
+
+impl Default for GiftCardServices {
+    fn default() -> Self {
+        Self::new()
+    }
+}
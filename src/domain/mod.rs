@@ -1 +1,2 @@
+pub mod gift_card;
 pub mod tab;
@@ -1,21 +1,32 @@
 use std::collections::HashMap;
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use cqrs_es::Aggregate;
-use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::{
+    domain::gift_card::redemption_id::RedemptionId,
+    shared_kernel::{
+        exchange_rates::ExchangeRate,
+        money::{Money, MoneyError},
+    },
+};
+
 use super::{
     command::{OrderItem, TabCommand},
     error::TabError,
-    event::{MenuItem, TabEvent},
+    event::{AppliedPromotion, GiftCardPayment, MenuItem, TabEvent},
+    order_priority::OrderPriority,
+    promotions::Unit,
     services::TabServices,
     tab_id::TabId,
     waiter_id::WaiterId,
 };
 
 #[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Tab {
     id: TabId,
     table: usize,
@@ -24,8 +35,13 @@ pub struct Tab {
     food_items: Vec<MenuItem>,
     foods_prepared: HashMap<usize, usize>,
     foods_served: HashMap<usize, usize>,
+    // Per menu number, the units rushed, counted in order from the first ordered.
+    foods_rushed: HashMap<usize, Vec<usize>>,
     drink_items: Vec<MenuItem>,
     drinks_served: HashMap<usize, usize>,
+    gift_card_payments: Vec<Money>,
+    gift_card_redemptions: Vec<RedemptionId>,
+    promotions: Vec<AppliedPromotion>,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
@@ -48,7 +64,7 @@ impl Aggregate for Tab {
     async fn handle(
         &self,
         command: Self::Command,
-        _service: &Self::Services,
+        services: &Self::Services,
     ) -> Result<Vec<Self::Event>, Self::Error> {
         match command {
             TabCommand::OpenTab {
@@ -61,25 +77,59 @@ impl Aggregate for Tab {
                 }
                 self.handle_open_tab_command(&id, &waiter_id, table)
             }
-            TabCommand::CloseTab { id, amount_paid } => {
+            TabCommand::PayWithGiftCard { id, payment } => {
+                redeemed_or_error(&payment)?;
+                // Already paid in, e.g. by the saga resuming after a crash.
+                if self.gift_card_redemptions.contains(&payment.redemption) {
+                    return Ok(vec![]);
+                }
+                self.tab_is_open_or_error()?;
+                self.tab_id_matches_or_error(id)?;
+                self.handle_pay_with_gift_card_command(payment, services)
+            }
+            TabCommand::CloseTab {
+                id,
+                amount_paid,
+                gift_card,
+            } => {
+                // Closed with this payment already, e.g. by the saga resuming.
+                if let Some(payment) = &gift_card {
+                    redeemed_or_error(payment)?;
+                    if self.gift_card_redemptions.contains(&payment.redemption) {
+                        return Ok(vec![]);
+                    }
+                }
+                self.tab_is_open_or_error()?;
+                self.tab_id_matches_or_error(id)?;
+                self.handle_close_tab_command(amount_paid, gift_card, services)
+                    .await
+            }
+            TabCommand::PlaceOrder {
+                order_items,
+                priority,
+            } => {
                 self.tab_is_open_or_error()?;
-                self.handle_close_tab_command(id, amount_paid)
+                self.handle_place_order_command(&order_items, priority, services)
             }
-            TabCommand::PlaceOrder { order_items } => {
+            TabCommand::RushOrder { id, menu_numbers } => {
                 self.tab_is_open_or_error()?;
-                self.handle_place_order_command(&order_items)
+                self.tab_id_matches_or_error(id)?;
+                self.handle_rush_order_command(&menu_numbers)
             }
-            TabCommand::MarkDrinksServed { id, menu_numbers } => {
+            TabCommand::MarkDrinksServed { id, items } => {
                 self.tab_is_open_or_error()?;
-                self.handle_mark_drink_served_command(id, menu_numbers)
+                self.tab_id_matches_or_error(id)?;
+                self.handle_mark_drink_served_command(&items)
             }
-            TabCommand::MarkFoodPrepared { id, menu_numbers } => {
+            TabCommand::MarkFoodPrepared { id, items } => {
                 self.tab_is_open_or_error()?;
-                self.handle_mark_food_prepared_command(id, &menu_numbers)
+                self.tab_id_matches_or_error(id)?;
+                self.handle_mark_food_prepared_command(&items)
             }
-            TabCommand::MarkFoodServed { id, menu_numbers } => {
+            TabCommand::MarkFoodServed { id, items } => {
                 self.tab_is_open_or_error()?;
-                self.handle_mark_food_served_command(id, &menu_numbers)
+                self.tab_id_matches_or_error(id)?;
+                self.handle_mark_food_served_command(&items)
             }
         }
     }
@@ -96,14 +146,17 @@ impl Aggregate for Tab {
             TabEvent::FoodOrderPlaced { id, menu_item } => self.apply_order_food(id, menu_item),
             TabEvent::DrinkOrderPlaced { id, menu_item } => self.apply_order_drink(id, menu_item),
             TabEvent::DrinkServed { id, menu_number } => self.apply_drinks_served(id, menu_number),
+            TabEvent::FoodOrderRushed { menu_number, .. } => {
+                self.apply_food_order_rushed(menu_number)
+            }
             TabEvent::FoodPrepared { id, menu_number } => self.apply_food_prepared(id, menu_number),
             TabEvent::FoodServed { id, menu_number } => self.apply_food_served(id, menu_number),
-            TabEvent::TabClosed {
-                id: _,
-                amount_paid: _,
-                order_value: _,
-                tip_value: _,
-            } => todo!(),
+            TabEvent::GiftCardPaymentReceived { payment, .. } => {
+                self.gift_card_payments.push(payment.amount);
+                self.gift_card_redemptions.push(payment.redemption);
+            }
+            TabEvent::PromotionApplied { promotion, .. } => self.promotions.push(promotion),
+            TabEvent::TabClosed { .. } => self.opened = false,
         }
     }
 }
@@ -133,12 +186,21 @@ impl Tab {
         }
     }
 
+    fn apply_food_order_rushed(&mut self, menu_number: usize) {
+        if let Some(unit) = self.next_unrushed_unit(menu_number, 0) {
+            self.foods_rushed.entry(menu_number).or_default().push(unit);
+        }
+    }
+
     fn apply_open_tab(&mut self, id: TabId, waiter_id: WaiterId, table: usize) {
         self.id = id;
         self.waiter_id = waiter_id;
         self.table = table;
         self.drink_items = Vec::new();
         self.food_items = Vec::new();
+        self.foods_rushed = HashMap::new();
+        self.gift_card_payments = Vec::new();
+        self.promotions = Vec::new();
         self.opened = true;
     }
 
@@ -147,29 +209,19 @@ impl Tab {
     }
 
     fn apply_order_food(&mut self, _id: TabId, menu_item: MenuItem) {
-        let mut found = false;
-        for food_item in self.food_items.iter_mut() {
-            if food_item.menu_number == menu_item.menu_number {
-                food_item.quantity += 1;
-                found = true;
-                break;
-            }
-        }
-        if !found {
-            self.food_items.push(menu_item);
-        }
+        self.food_items.push(menu_item);
     }
 
-    fn drink_fully_served(&self, menu_number: &usize) -> bool {
+    fn food_fully_prepared(&self, menu_number: &usize) -> bool {
         let mut ordered_qty = 0;
-        for order in self.drink_items.iter() {
+        for order in self.food_items.iter() {
             if order.menu_number == *menu_number {
                 ordered_qty += order.quantity;
             }
         }
         if ordered_qty > 0 {
-            if let Some(served_qty) = self.drinks_served.get(menu_number) {
-                if *served_qty == ordered_qty {
+            if let Some(prepared_qty) = self.foods_prepared.get(menu_number) {
+                if *prepared_qty == ordered_qty {
                     return true;
                 }
             }
@@ -178,105 +230,211 @@ impl Tab {
         false
     }
 
-    fn food_fully_prepared(&self, menu_number: &usize) -> bool {
-        let mut ordered_qty = 0;
-        for order in self.food_items.iter() {
-            if order.menu_number == *menu_number {
-                ordered_qty += order.quantity;
-            }
+    fn drinks_outstanding(&self, menu_number: usize) -> usize {
+        ordered_quantity(&self.drink_items, menu_number)
+            .saturating_sub(counted(&self.drinks_served, menu_number))
+    }
+
+    fn food_not_prepared(&self, menu_number: usize) -> usize {
+        ordered_quantity(&self.food_items, menu_number)
+            .saturating_sub(counted(&self.foods_prepared, menu_number))
+    }
+
+    // Food is prepared oldest first, so a rush flags the oldest unit still
+    // being cooked that isn't already rushed, skipping `skip` such units.
+    fn next_unrushed_unit(&self, menu_number: usize, skip: usize) -> Option<usize> {
+        let rushed = self.foods_rushed.get(&menu_number);
+        (counted(&self.foods_prepared, menu_number)
+            ..ordered_quantity(&self.food_items, menu_number))
+            .filter(|unit| rushed.is_none_or(|r| !r.contains(unit)))
+            .nth(skip)
+    }
+
+    fn food_not_served(&self, menu_number: usize) -> usize {
+        ordered_quantity(&self.food_items, menu_number)
+            .saturating_sub(counted(&self.foods_served, menu_number))
+    }
+
+    fn food_prepared_not_served(&self, menu_number: usize) -> usize {
+        counted(&self.foods_prepared, menu_number)
+            .saturating_sub(counted(&self.foods_served, menu_number))
+    }
+
+    // The order less every promotion applied so far and those still `pending`.
+    fn order_value(
+        &self,
+        pending: &[AppliedPromotion],
+        services: &TabServices,
+    ) -> Result<Money, TabError> {
+        let mut subtotal = Money::zero(services.currency());
+        for item in self.food_items.iter().chain(self.drink_items.iter()) {
+            subtotal = item
+                .price
+                .times(item.quantity)
+                .and_then(|total| subtotal.checked_add(total))
+                .map_err(currency_error)?;
         }
-        if ordered_qty > 0 {
-            if let Some(prepared_qty) = self.foods_prepared.get(menu_number) {
-                if *prepared_qty == ordered_qty {
-                    return true;
-                }
-            }
+        for promotion in self.promotions.iter().chain(pending.iter()) {
+            subtotal = match subtotal.checked_sub(promotion.discount) {
+                Err(MoneyError::Negative) => Money::zero(services.currency()),
+                result => result.map_err(currency_error)?,
+            };
         }
 
-        false
+        Ok(subtotal.round(services.rounding()))
     }
 
-    fn food_fully_served(&self, menu_number: &usize) -> bool {
-        let mut ordered_qty = 0;
-        for order in self.food_items.iter() {
-            if order.menu_number == *menu_number {
-                ordered_qty += order.quantity;
+    // Units already discounted are left out so promotions never stack. Units
+    // ordered before order times were recorded count as ordered `now`, and
+    // promotions recorded before their units were are matched by menu number.
+    fn undiscounted_units(&self, services: &TabServices) -> Vec<Unit> {
+        let mut discounted: HashMap<(usize, DateTime<Utc>), usize> = HashMap::new();
+        let mut discounted_before_units: HashMap<usize, usize> = HashMap::new();
+        for promotion in self.promotions.iter() {
+            if promotion.units.is_empty() {
+                for &(menu_number, quantity) in promotion.items.iter() {
+                    *discounted_before_units.entry(menu_number).or_default() += quantity;
+                }
+            }
+            for &unit in promotion.units.iter() {
+                *discounted.entry(unit).or_default() += 1;
             }
         }
-        if ordered_qty > 0 {
-            if let Some(served_qty) = self.foods_served.get(menu_number) {
-                if *served_qty == ordered_qty {
-                    return true;
+        let mut units = Vec::new();
+        for item in self.food_items.iter().chain(self.drink_items.iter()) {
+            for _ in 0..item.quantity {
+                let unit = item.ordered_at.map(|at| (item.menu_number, at));
+                if let Some(quantity) = unit
+                    .and_then(|unit| discounted.get_mut(&unit))
+                    .filter(|quantity| **quantity > 0)
+                {
+                    *quantity -= 1;
+                    continue;
+                }
+                match discounted_before_units.get_mut(&item.menu_number) {
+                    Some(quantity) if *quantity > 0 => *quantity -= 1,
+                    _ => units.push((
+                        item.menu_number,
+                        item.price,
+                        item.ordered_at.unwrap_or_else(|| services.now()),
+                    )),
                 }
             }
         }
 
-        false
+        units
     }
 
-    fn food_prepared_not_served(&self, menu_number: &usize) -> usize {
-        let mut prepared_qty = 0;
-        for (prepared_menu_number, qty) in self.foods_prepared.iter() {
-            if *prepared_menu_number == *menu_number {
-                prepared_qty += qty;
-            }
-        }
-        let mut served_qty = 0;
-        for (served_menu_number, qty) in self.foods_served.iter() {
-            if *served_menu_number == *menu_number {
-                served_qty += qty;
-            }
+    fn close_promotions(&self, services: &TabServices) -> Vec<AppliedPromotion> {
+        services
+            .promotions()
+            .on_close(&self.undiscounted_units(services), services.rounding())
+    }
+
+    fn promotions_applied(&self, promotions: Vec<AppliedPromotion>) -> Vec<TabEvent> {
+        promotions
+            .into_iter()
+            .map(|promotion| TabEvent::PromotionApplied {
+                id: self.id,
+                promotion,
+            })
+            .collect()
+    }
+
+    fn paid_by_gift_card(&self, services: &TabServices) -> Result<Money, TabError> {
+        let mut paid = Money::zero(services.currency());
+        for amount in self.gift_card_payments.iter() {
+            paid = paid.checked_add(*amount).map_err(currency_error)?;
         }
-        let result = prepared_qty - served_qty;
 
-        result.max(0)
+        Ok(paid)
     }
 
-    fn handle_close_tab_command(
+    // The close promotions the payment is measured against are applied with it,
+    // so the outstanding amount it was checked against is the one recorded.
+    fn handle_pay_with_gift_card_command(
         &self,
-        _id: TabId,
-        amount_paid: Decimal,
+        payment: GiftCardPayment,
+        services: &TabServices,
     ) -> Result<Vec<TabEvent>, TabError> {
-        let mut subtotal = Decimal::ZERO;
-        for food in self.food_items.iter() {
-            subtotal += food.price * Decimal::from(food.quantity)
-        }
-        for drink in self.drink_items.iter() {
-            subtotal += drink.price * Decimal::from(drink.quantity)
+        let promotions = self.close_promotions(services);
+        let order_value = self.order_value(&promotions, services)?;
+        let payment = self.handle_gift_card_payment(payment, order_value, services)?;
+        let mut events = self.promotions_applied(promotions);
+        events.push(payment);
+
+        Ok(events)
+    }
+
+    // Gift cards pay towards the order only; any tip is paid when closing.
+    fn handle_gift_card_payment(
+        &self,
+        payment: GiftCardPayment,
+        order_value: Money,
+        services: &TabServices,
+    ) -> Result<TabEvent, TabError> {
+        redeemed_or_error(&payment)?;
+        currency_matches_or_error(payment.amount, services)?;
+        let outstanding = order_value
+            .checked_sub(self.paid_by_gift_card(services)?)
+            .unwrap_or(Money::zero(services.currency()));
+        if outstanding.checked_sub(payment.amount).is_err() {
+            return Err(TabError::GiftCardExceedsBalance { outstanding });
         }
-        let difference = amount_paid - subtotal;
-        if difference < Decimal::ZERO {
-            return Err(TabError::MustPayEnough);
+
+        Ok(TabEvent::GiftCardPaymentReceived {
+            id: self.id,
+            payment,
+        })
+    }
+
+    async fn handle_close_tab_command(
+        &self,
+        amount_tendered: Money,
+        gift_card: Option<GiftCardPayment>,
+        services: &TabServices,
+    ) -> Result<Vec<TabEvent>, TabError> {
+        let (amount_paid, exchange_rate) = converted_payment(amount_tendered, services).await?;
+        let promotions = self.close_promotions(services);
+        let order_value = self.order_value(&promotions, services)?;
+        let mut paid = self.paid_by_gift_card(services)?;
+        let mut events = self.promotions_applied(promotions);
+        if let Some(payment) = gift_card {
+            paid = paid.checked_add(payment.amount).map_err(currency_error)?;
+            events.push(self.handle_gift_card_payment(payment, order_value, services)?);
         }
-        let event = TabEvent::TabClosed {
+        let tip_value = amount_paid
+            .checked_add(paid)
+            .and_then(|total| total.checked_sub(order_value))
+            .map_err(|e| match e {
+                MoneyError::Negative => TabError::MustPayEnough,
+                e => currency_error(e),
+            })?;
+        events.push(TabEvent::TabClosed {
             id: self.id,
             amount_paid,
-            order_value: subtotal,
-            tip_value: difference,
-        };
+            order_value,
+            tip_value,
+            amount_tendered,
+            exchange_rate,
+        });
 
-        Ok(vec![event])
+        Ok(events)
     }
 
     fn handle_mark_food_prepared_command(
         &self,
-        _id: TabId,
-        menu_numbers: &[usize],
+        items: &[(usize, usize)],
     ) -> Result<Vec<TabEvent>, TabError> {
         let mut result = Vec::new();
-        for menu_number in menu_numbers.iter() {
-            let menu_numbers_ordered: Vec<usize> =
-                self.food_items.iter().map(|i| i.menu_number).collect();
-            if !menu_numbers_ordered.contains(menu_number) || self.food_fully_prepared(menu_number)
-            {
-                return Err(TabError::FoodNotOutstanding {
-                    menu_number: *menu_number,
-                });
+        for (menu_number, quantity) in requested_quantities(items) {
+            if quantity > self.food_not_prepared(menu_number) {
+                return Err(TabError::FoodNotOutstanding { menu_number });
             }
-            result.push(TabEvent::FoodPrepared {
+            result.extend((0..quantity).map(|_| TabEvent::FoodPrepared {
                 id: self.id,
-                menu_number: *menu_number,
-            });
+                menu_number,
+            }));
         }
 
         Ok(result)
@@ -284,26 +442,19 @@ impl Tab {
 
     fn handle_mark_food_served_command(
         &self,
-        _id: TabId,
-        menu_numbers: &[usize],
+        items: &[(usize, usize)],
     ) -> Result<Vec<TabEvent>, TabError> {
         let mut result = Vec::new();
-        for menu_number in menu_numbers.iter() {
-            let menu_numbers_ordered: Vec<usize> =
-                self.food_items.iter().map(|i| i.menu_number).collect();
-            if !menu_numbers_ordered.contains(menu_number) || self.food_fully_served(menu_number) {
-                return Err(TabError::FoodNotOutstanding {
-                    menu_number: *menu_number,
-                });
-            } else if self.food_prepared_not_served(menu_number) == 0 {
-                return Err(TabError::FoodNotPrepared {
-                    menu_number: *menu_number,
-                });
+        for (menu_number, quantity) in requested_quantities(items) {
+            if quantity > self.food_not_served(menu_number) {
+                return Err(TabError::FoodNotOutstanding { menu_number });
+            } else if quantity > self.food_prepared_not_served(menu_number) {
+                return Err(TabError::FoodNotPrepared { menu_number });
             }
-            result.push(TabEvent::FoodServed {
+            result.extend((0..quantity).map(|_| TabEvent::FoodServed {
                 id: self.id,
-                menu_number: *menu_number,
-            });
+                menu_number,
+            }));
         }
 
         Ok(result)
@@ -311,21 +462,17 @@ impl Tab {
 
     fn handle_mark_drink_served_command(
         &self,
-        _id: TabId,
-        menu_numbers: Vec<usize>,
+        items: &[(usize, usize)],
     ) -> Result<Vec<TabEvent>, TabError> {
         let mut result = Vec::new();
-        for menu_number in menu_numbers {
-            let menu_numbers_ordered: Vec<usize> =
-                self.drink_items.iter().map(|i| i.menu_number).collect();
-            if !menu_numbers_ordered.contains(&menu_number) || self.drink_fully_served(&menu_number)
-            {
+        for (menu_number, quantity) in requested_quantities(items) {
+            if quantity > self.drinks_outstanding(menu_number) {
                 return Err(TabError::DrinkNotOutstanding { menu_number });
             }
-            result.push(TabEvent::DrinkServed {
+            result.extend((0..quantity).map(|_| TabEvent::DrinkServed {
                 id: self.id,
                 menu_number,
-            });
+            }));
         }
 
         Ok(result)
@@ -334,15 +481,24 @@ impl Tab {
     fn handle_place_order_command(
         &self,
         order_items: &[OrderItem],
+        priority: OrderPriority,
+        services: &TabServices,
     ) -> Result<Vec<TabEvent>, TabError> {
+        let ordered_at = services.now();
         let mut orders = Vec::new();
+        let mut units = Vec::new();
         for order_item in order_items.iter() {
+            currency_matches_or_error(order_item.price, services)?;
             let menu_item = MenuItem {
                 menu_number: order_item.menu_number,
                 description: order_item.description.to_owned(),
                 price: order_item.price,
                 quantity: 1,
+                priority,
+                notes: order_item.notes.clone(),
+                ordered_at: Some(ordered_at),
             };
+            units.push((menu_item.menu_number, menu_item.price, ordered_at));
             if order_item.is_drink {
                 orders.push(TabEvent::DrinkOrderPlaced {
                     id: self.id,
@@ -355,10 +511,44 @@ impl Tab {
                 });
             }
         }
+        let promotions = services.promotions().on_order(&units, services.rounding());
+        orders.extend(self.promotions_applied(promotions));
 
         Ok(orders)
     }
 
+    fn handle_rush_order_command(&self, menu_numbers: &[usize]) -> Result<Vec<TabEvent>, TabError> {
+        let mut result = Vec::new();
+        for (index, menu_number) in menu_numbers.iter().enumerate() {
+            let menu_numbers_ordered: Vec<usize> =
+                self.food_items.iter().map(|i| i.menu_number).collect();
+            if !menu_numbers_ordered.contains(menu_number) || self.food_fully_prepared(menu_number)
+            {
+                return Err(TabError::FoodNotOutstanding {
+                    menu_number: *menu_number,
+                });
+            }
+            let rushed_earlier = menu_numbers[..index]
+                .iter()
+                .filter(|m| *m == menu_number)
+                .count();
+            if self
+                .next_unrushed_unit(*menu_number, rushed_earlier)
+                .is_none()
+            {
+                return Err(TabError::FoodAlreadyRushed {
+                    menu_number: *menu_number,
+                });
+            }
+            result.push(TabEvent::FoodOrderRushed {
+                id: self.id,
+                menu_number: *menu_number,
+            });
+        }
+
+        Ok(result)
+    }
+
     fn handle_open_tab_command(
         &self,
         id: &TabId,
@@ -383,36 +573,161 @@ impl Tab {
 
         Ok(())
     }
+
+    fn tab_id_matches_or_error(&self, id: TabId) -> Result<(), TabError> {
+        if id != self.id {
+            return Err(TabError::TabIdMismatch {
+                expected: self.id,
+                actual: id,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+fn ordered_quantity(items: &[MenuItem], menu_number: usize) -> usize {
+    items
+        .iter()
+        .filter(|item| item.menu_number == menu_number)
+        .map(|item| item.quantity)
+        .sum()
+}
+
+// Gift cards are only paid in through the saga, which redeems the card first.
+fn redeemed_or_error(payment: &GiftCardPayment) -> Result<(), TabError> {
+    if payment.redemption.is_nil() {
+        return Err(TabError::GiftCardNotRedeemed);
+    }
+
+    Ok(())
+}
+
+fn currency_matches_or_error(amount: Money, services: &TabServices) -> Result<(), TabError> {
+    if amount.currency() != services.currency() {
+        return Err(TabError::CurrencyMismatch {
+            expected: services.currency(),
+            actual: amount.currency(),
+        });
+    }
+
+    Ok(())
+}
+
+// Payments in a foreign currency are converted into the menu currency at the
+// current rate, which is kept with the payment. A rate that could not be
+// loaded is an internal error, so the command may be retried.
+async fn converted_payment(
+    amount_tendered: Money,
+    services: &TabServices,
+) -> Result<(Money, Option<ExchangeRate>), TabError> {
+    let (from, to) = (amount_tendered.currency(), services.currency());
+    if from == to {
+        return Ok((amount_tendered, None));
+    }
+    let rate = match services.exchange_rates().rate(from, to).await {
+        Ok(Some(rate)) => rate,
+        Ok(None) => return Err(TabError::ExchangeRateUnavailable { from, to }),
+        Err(_) => return Err(TabError::ExchangeRateLookupFailed { from, to }),
+    };
+    let converted = rate.convert(amount_tendered).map_err(currency_error)?;
+
+    Ok((converted.round(services.rounding()), Some(rate)))
+}
+
+fn currency_error(error: MoneyError) -> TabError {
+    match error {
+        MoneyError::Negative => TabError::NegativeAmount,
+        MoneyError::InvalidCurrency(code) => TabError::InvalidCurrency { code },
+        MoneyError::InvalidRate(rate) => TabError::InvalidExchangeRate { rate },
+        MoneyError::CurrencyMismatch { expected, actual } => {
+            TabError::CurrencyMismatch { expected, actual }
+        }
+        MoneyError::Overflow => TabError::AmountTooLarge,
+    }
+}
+
+fn counted(quantities: &HashMap<usize, usize>, menu_number: usize) -> usize {
+    quantities.get(&menu_number).copied().unwrap_or_default()
+}
+
+// Merges repeated menu numbers so a command is validated against its
+// cumulative effect rather than item by item.
+fn requested_quantities(items: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut requested: Vec<(usize, usize)> = Vec::new();
+    for &(menu_number, quantity) in items {
+        match requested.iter_mut().find(|(m, _)| *m == menu_number) {
+            Some((_, total)) => *total += quantity,
+            None => requested.push((menu_number, quantity)),
+        }
+    }
+
+    requested
 }
 
 #[cfg(test)]
 pub mod tests {
-    use std::str::FromStr;
+    use std::{str::FromStr, sync::Arc};
 
-    use cqrs_es::test::{AggregateResultValidator, AggregateTestExecutor, TestFramework};
+    use async_trait::async_trait;
+    use chrono::{DateTime, TimeZone, Utc};
+    use cqrs_es::{
+        persist::PersistenceError,
+        test::{AggregateResultValidator, AggregateTestExecutor, TestFramework},
+    };
     use rust_decimal::Decimal;
 
-    use crate::domain::tab::{
-        aggregate::Tab,
-        command::{OrderItem, TabCommand},
-        error::TabError,
-        event::{MenuItem, TabEvent},
-        services::TabServices,
-        tab_id::TabId,
-        waiter_id::WaiterId,
+    use crate::{
+        domain::{
+            gift_card::redemption_id::RedemptionId,
+            tab::{
+                aggregate::Tab,
+                command::{OrderItem, TabCommand},
+                error::TabError,
+                event::{AppliedPromotion, GiftCardPayment, MenuItem, TabEvent},
+                order_priority::OrderPriority,
+                promotions::Promotions,
+                services::TabServices,
+                tab_id::TabId,
+                waiter_id::WaiterId,
+            },
+        },
+        shared_kernel::{
+            clock::FixedClock,
+            exchange_rates::{ExchangeRate, ExchangeRateTable, ExchangeRates},
+            fixtures::usd,
+            money::{Currency, Money, MoneyError, Rounding},
+        },
     };
 
+    use super::currency_error;
+
+    #[derive(Debug)]
+    struct UnreachableRates;
+
+    #[async_trait]
+    impl ExchangeRates for UnreachableRates {
+        async fn rate(
+            &self,
+            _: Currency,
+            _: Currency,
+        ) -> Result<Option<ExchangeRate>, PersistenceError> {
+            Err(PersistenceError::ConnectionError("rates offline".into()))
+        }
+    }
+
     #[test]
     #[allow(non_snake_case)]
     fn given_unopened_tab_when_PlaceOrder_command_then_TabNotOpened_error() {
         // Arrange
-        let tab_services = TabServices {};
+        let tab_services = services();
         let executor = TestFramework::<Tab>::with(tab_services).given_no_previous_events();
 
         // Act
         let result = executor
             .when(TabCommand::PlaceOrder {
                 order_items: vec![OrderItem::default()],
+                priority: OrderPriority::Normal,
             })
             .inspect_result();
 
@@ -424,14 +739,14 @@ pub mod tests {
     #[allow(non_snake_case)]
     fn given_unopened_tab_when_MarkDrinksServed_command_then_TabNotOpened_error() {
         // Arrange
-        let tab_services = TabServices {};
+        let tab_services = services();
         let executor = TestFramework::<Tab>::with(tab_services).given_no_previous_events();
 
         // Act
         let result = executor
             .when(TabCommand::MarkDrinksServed {
                 id: TabId::new(),
-                menu_numbers: vec![2],
+                items: vec![(2, 1)],
             })
             .inspect_result();
 
@@ -470,7 +785,7 @@ pub mod tests {
             assert_eq!(wid, expected_waiter_id);
             assert_eq!(table_num, 1);
         } else {
-            assert!(false, "TabOpened event")
+            panic!("expected TabOpened event")
         }
     }
 
@@ -482,13 +797,17 @@ pub mod tests {
             menu_number: 1,
             description: "Steak".into(),
             is_drink: false,
-            price: Decimal::from(10),
+            price: usd(Decimal::from(10)),
+            notes: None,
         }];
 
         let mut event = arrange_and_act(
             tab_id,
             Some(Vec::new()),
-            TabCommand::PlaceOrder { order_items },
+            TabCommand::PlaceOrder {
+                order_items,
+                priority: OrderPriority::Normal,
+            },
         )
         .inspect_result()
         .expect("failed to execute command: OrderItem");
@@ -502,8 +821,11 @@ pub mod tests {
                 menu_item: MenuItem {
                     menu_number: 1,
                     description: "Steak".into(),
-                    price: Decimal::from(10),
+                    price: usd(Decimal::from(10)),
                     quantity: 1,
+                    priority: OrderPriority::Normal,
+                    notes: None,
+                    ordered_at: Some(now()),
                 }
             },
             "ItemOrdered"
@@ -518,13 +840,17 @@ pub mod tests {
             menu_number: 2,
             description: "Coca-Cola".into(),
             is_drink: true,
-            price: Decimal::from(3),
+            price: usd(Decimal::from(3)),
+            notes: None,
         }];
 
         let mut event = arrange_and_act(
             tab_id,
             Some(Vec::new()),
-            TabCommand::PlaceOrder { order_items },
+            TabCommand::PlaceOrder {
+                order_items,
+                priority: OrderPriority::Normal,
+            },
         )
         .inspect_result()
         .expect("failed to execute command: OrderItem");
@@ -538,8 +864,11 @@ pub mod tests {
                 menu_item: MenuItem {
                     menu_number: 2,
                     description: "Coca-Cola".into(),
-                    price: Decimal::from(3),
+                    price: usd(Decimal::from(3)),
                     quantity: 1,
+                    priority: OrderPriority::Normal,
+                    notes: None,
+                    ordered_at: Some(now()),
                 }
             },
             "DrinkOrderPlaced"
@@ -555,20 +884,25 @@ pub mod tests {
                 menu_number: 1,
                 description: "Steak".into(),
                 is_drink: false,
-                price: Decimal::from(10),
+                price: usd(Decimal::from(10)),
+                notes: None,
             },
             OrderItem {
                 menu_number: 2,
                 description: "Coca-Cola".into(),
                 is_drink: true,
-                price: Decimal::from(3),
+                price: usd(Decimal::from(3)),
+                notes: None,
             },
         ];
 
         let event = arrange_and_act(
             tab_id,
             Some(Vec::new()),
-            TabCommand::PlaceOrder { order_items },
+            TabCommand::PlaceOrder {
+                order_items,
+                priority: OrderPriority::Normal,
+            },
         )
         .inspect_result()
         .expect("failed to execute command: OrderItem");
@@ -581,8 +915,11 @@ pub mod tests {
                 menu_item: MenuItem {
                     menu_number: 1,
                     description: "Steak".into(),
-                    price: Decimal::from(10),
+                    price: usd(Decimal::from(10)),
                     quantity: 1,
+                    priority: OrderPriority::Normal,
+                    notes: None,
+                    ordered_at: Some(now()),
                 }
             },
             "FoodOrderPlaced"
@@ -594,8 +931,11 @@ pub mod tests {
                 menu_item: MenuItem {
                     menu_number: 2,
                     description: "Coca-Cola".into(),
-                    price: Decimal::from(3),
+                    price: usd(Decimal::from(3)),
                     quantity: 1,
+                    priority: OrderPriority::Normal,
+                    notes: None,
+                    ordered_at: Some(now()),
                 }
             },
             "DrinkOrderPlaced"
@@ -614,13 +954,16 @@ pub mod tests {
                 menu_item: MenuItem {
                     menu_number: 2,
                     description: "Coca-Cola".into(),
-                    price: Decimal::from(3),
+                    price: usd(Decimal::from(3)),
                     quantity: 1,
+                    priority: OrderPriority::Normal,
+                    notes: None,
+                    ordered_at: Some(now()),
                 },
             }]),
             TabCommand::MarkDrinksServed {
                 id: tab_id,
-                menu_numbers: vec![2],
+                items: vec![(2, 1)],
             },
         )
         .inspect_result()
@@ -649,13 +992,16 @@ pub mod tests {
                 menu_item: MenuItem {
                     menu_number: 2,
                     description: "Coca-Cola".into(),
-                    price: Decimal::from(3),
+                    price: usd(Decimal::from(3)),
                     quantity: 1,
+                    priority: OrderPriority::Normal,
+                    notes: None,
+                    ordered_at: Some(now()),
                 },
             }]),
             TabCommand::MarkDrinksServed {
                 id: tab_id,
-                menu_numbers: vec![12],
+                items: vec![(12, 1)],
             },
         )
         .inspect_result();
@@ -679,8 +1025,11 @@ pub mod tests {
                     menu_item: MenuItem {
                         menu_number: 2,
                         description: "Coca-Cola".into(),
-                        price: Decimal::from(3),
+                        price: usd(Decimal::from(3)),
                         quantity: 1,
+                        priority: OrderPriority::Normal,
+                        notes: None,
+                        ordered_at: Some(now()),
                     },
                 },
                 TabEvent::DrinkServed {
@@ -690,7 +1039,7 @@ pub mod tests {
             ]),
             TabCommand::MarkDrinksServed {
                 id: tab_id,
-                menu_numbers: vec![2],
+                items: vec![(2, 1)],
             },
         )
         .inspect_result();
@@ -715,8 +1064,11 @@ pub mod tests {
                     menu_item: MenuItem {
                         menu_number: 2,
                         description: "Coca-Cola".into(),
-                        price: Decimal::from(3),
+                        price: usd(Decimal::from(3)),
                         quantity: 1,
+                        priority: OrderPriority::Normal,
+                        notes: None,
+                        ordered_at: Some(now()),
                     },
                 },
                 TabEvent::DrinkOrderPlaced {
@@ -724,8 +1076,11 @@ pub mod tests {
                     menu_item: MenuItem {
                         menu_number: 2,
                         description: "Coca-Cola".into(),
-                        price: Decimal::from(3),
+                        price: usd(Decimal::from(3)),
                         quantity: 1,
+                        priority: OrderPriority::Normal,
+                        notes: None,
+                        ordered_at: Some(now()),
                     },
                 },
                 TabEvent::DrinkServed {
@@ -735,7 +1090,7 @@ pub mod tests {
             ]),
             TabCommand::MarkDrinksServed {
                 id: tab_id,
-                menu_numbers: vec![2],
+                items: vec![(2, 1)],
             },
         )
         .inspect_result()
@@ -751,6 +1106,135 @@ pub mod tests {
         );
     }
 
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_open_tab_and_one_drink_ordered_when_MarkDrinksServed_repeats_it_then_DrinkNotOutstanding_error(
+    ) {
+        let tab_id = TabId::new();
+        let drink_ordered = TabEvent::DrinkOrderPlaced {
+            id: tab_id,
+            menu_item: MenuItem {
+                menu_number: 2,
+                description: "Coca-Cola".into(),
+                price: usd(Decimal::from(3)),
+                quantity: 1,
+                priority: OrderPriority::Normal,
+                notes: None,
+                ordered_at: Some(now()),
+            },
+        };
+
+        for items in [vec![(2, 2)], vec![(2, 1), (2, 1)]] {
+            let result = arrange_and_act(
+                tab_id,
+                Some(vec![drink_ordered.clone()]),
+                TabCommand::MarkDrinksServed { id: tab_id, items },
+            );
+
+            result.then_expect_error(TabError::DrinkNotOutstanding { menu_number: 2 });
+        }
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_open_tab_and_food_prepared_once_when_MarkFoodServed_quantity_2_then_FoodNotPrepared_error(
+    ) {
+        let tab_id = TabId::new();
+
+        let result = arrange_and_act(
+            tab_id,
+            Some(vec![
+                TabEvent::FoodOrderPlaced {
+                    id: tab_id,
+                    menu_item: MenuItem {
+                        menu_number: 1,
+                        description: "Steak".into(),
+                        price: usd(Decimal::from(20)),
+                        quantity: 2,
+                        priority: OrderPriority::Normal,
+                        notes: None,
+                        ordered_at: Some(now()),
+                    },
+                },
+                TabEvent::FoodPrepared {
+                    id: tab_id,
+                    menu_number: 1,
+                },
+            ]),
+            TabCommand::MarkFoodServed {
+                id: tab_id,
+                items: vec![(1, 2)],
+            },
+        );
+
+        result.then_expect_error(TabError::FoodNotPrepared { menu_number: 1 });
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_open_tab_and_food_ordered_when_MarkFoodPrepared_with_one_item_too_many_then_no_events()
+    {
+        let tab_id = TabId::new();
+
+        let result = arrange_and_act(
+            tab_id,
+            Some(vec![TabEvent::FoodOrderPlaced {
+                id: tab_id,
+                menu_item: MenuItem {
+                    menu_number: 1,
+                    description: "Steak".into(),
+                    price: usd(Decimal::from(20)),
+                    quantity: 1,
+                    priority: OrderPriority::Normal,
+                    notes: None,
+                    ordered_at: Some(now()),
+                },
+            }]),
+            TabCommand::MarkFoodPrepared {
+                id: tab_id,
+                items: vec![(1, 1), (3, 1)],
+            },
+        );
+
+        result.then_expect_error(TabError::FoodNotOutstanding { menu_number: 3 });
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_open_tab_and_two_drinks_ordered_when_MarkDrinksServed_quantity_2_then_DrinkServed_event_twice(
+    ) {
+        let tab_id = TabId::new();
+        let drink_ordered = TabEvent::DrinkOrderPlaced {
+            id: tab_id,
+            menu_item: MenuItem {
+                menu_number: 2,
+                description: "Coca-Cola".into(),
+                price: usd(Decimal::from(3)),
+                quantity: 1,
+                priority: OrderPriority::Normal,
+                notes: None,
+                ordered_at: Some(now()),
+            },
+        };
+
+        let events = arrange_and_act(
+            tab_id,
+            Some(vec![drink_ordered.clone(), drink_ordered]),
+            TabCommand::MarkDrinksServed {
+                id: tab_id,
+                items: vec![(2, 2)],
+            },
+        )
+        .inspect_result()
+        .expect("command MarkDrinksServed failed");
+
+        let served = TabEvent::DrinkServed {
+            id: tab_id,
+            menu_number: 2,
+        };
+        assert_eq!(events, vec![served.clone(), served]);
+    }
+
     #[test]
     #[allow(non_snake_case)]
     fn given_open_tab_when_OpenTab_command_then_error() {
@@ -779,7 +1263,7 @@ pub mod tests {
             Some(Vec::new()),
             TabCommand::MarkFoodPrepared {
                 id: tab_id,
-                menu_numbers: vec![1],
+                items: vec![(1, 1)],
             },
         );
 
@@ -796,7 +1280,7 @@ pub mod tests {
             None,
             TabCommand::MarkFoodPrepared {
                 id: tab_id,
-                menu_numbers: vec![1],
+                items: vec![(1, 1)],
             },
         );
 
@@ -815,13 +1299,16 @@ pub mod tests {
                 menu_item: MenuItem {
                     menu_number: 1,
                     description: "Steak".into(),
-                    price: Decimal::from(10),
+                    price: usd(Decimal::from(10)),
                     quantity: 1,
+                    priority: OrderPriority::Normal,
+                    notes: None,
+                    ordered_at: Some(now()),
                 },
             }]),
             TabCommand::MarkFoodPrepared {
                 id: tab_id,
-                menu_numbers: vec![1],
+                items: vec![(1, 1)],
             },
         )
         .inspect_result()
@@ -850,8 +1337,11 @@ pub mod tests {
                     menu_item: MenuItem {
                         menu_number: 1,
                         description: "Steak".into(),
-                        price: Decimal::from(10),
+                        price: usd(Decimal::from(10)),
                         quantity: 1,
+                        priority: OrderPriority::Normal,
+                        notes: None,
+                        ordered_at: Some(now()),
                     },
                 },
                 TabEvent::FoodPrepared {
@@ -861,7 +1351,7 @@ pub mod tests {
             ]),
             TabCommand::MarkFoodPrepared {
                 id: tab_id,
-                menu_numbers: vec![1],
+                items: vec![(1, 1)],
             },
         );
 
@@ -882,8 +1372,11 @@ pub mod tests {
                     menu_item: MenuItem {
                         menu_number: 1,
                         description: "Steak".into(),
-                        price: Decimal::from(10),
+                        price: usd(Decimal::from(10)),
                         quantity: 1,
+                        priority: OrderPriority::Normal,
+                        notes: None,
+                        ordered_at: Some(now()),
                     },
                 },
                 TabEvent::FoodOrderPlaced {
@@ -891,8 +1384,11 @@ pub mod tests {
                     menu_item: MenuItem {
                         menu_number: 1,
                         description: "Steak".into(),
-                        price: Decimal::from(10),
+                        price: usd(Decimal::from(10)),
                         quantity: 1,
+                        priority: OrderPriority::Normal,
+                        notes: None,
+                        ordered_at: Some(now()),
                     },
                 },
                 TabEvent::FoodPrepared {
@@ -902,7 +1398,7 @@ pub mod tests {
             ]),
             TabCommand::MarkFoodPrepared {
                 id: tab_id,
-                menu_numbers: vec![1],
+                items: vec![(1, 1)],
             },
         )
         .inspect_result()
@@ -928,7 +1424,7 @@ pub mod tests {
             None,
             TabCommand::MarkFoodServed {
                 id: tab_id,
-                menu_numbers: vec![1],
+                items: vec![(1, 1)],
             },
         );
 
@@ -945,7 +1441,7 @@ pub mod tests {
             Some(Vec::new()),
             TabCommand::MarkFoodServed {
                 id: tab_id,
-                menu_numbers: vec![1],
+                items: vec![(1, 1)],
             },
         );
 
@@ -965,13 +1461,16 @@ pub mod tests {
                 menu_item: MenuItem {
                     menu_number: 1,
                     description: "Steak".into(),
-                    price: Decimal::from(10),
+                    price: usd(Decimal::from(10)),
                     quantity: 1,
+                    priority: OrderPriority::Normal,
+                    notes: None,
+                    ordered_at: Some(now()),
                 },
             }]),
             TabCommand::MarkFoodServed {
                 id: tab_id,
-                menu_numbers: vec![1],
+                items: vec![(1, 1)],
             },
         );
 
@@ -991,8 +1490,11 @@ pub mod tests {
                     menu_item: MenuItem {
                         menu_number: 1,
                         description: "Steak".into(),
-                        price: Decimal::from(10),
+                        price: usd(Decimal::from(10)),
                         quantity: 1,
+                        priority: OrderPriority::Normal,
+                        notes: None,
+                        ordered_at: Some(now()),
                     },
                 },
                 TabEvent::FoodPrepared {
@@ -1002,7 +1504,7 @@ pub mod tests {
             ]),
             TabCommand::MarkFoodServed {
                 id: tab_id,
-                menu_numbers: vec![1],
+                items: vec![(1, 1)],
             },
         )
         .inspect_result()
@@ -1031,8 +1533,11 @@ pub mod tests {
                     menu_item: MenuItem {
                         menu_number: 1,
                         description: "Steak".into(),
-                        price: Decimal::from(10),
+                        price: usd(Decimal::from(10)),
                         quantity: 1,
+                        priority: OrderPriority::Normal,
+                        notes: None,
+                        ordered_at: Some(now()),
                     },
                 },
                 TabEvent::FoodPrepared {
@@ -1046,7 +1551,7 @@ pub mod tests {
             ]),
             TabCommand::MarkFoodServed {
                 id: tab_id,
-                menu_numbers: vec![1],
+                items: vec![(1, 1)],
             },
         );
 
@@ -1067,8 +1572,11 @@ pub mod tests {
                     menu_item: MenuItem {
                         menu_number: 1,
                         description: "Steak".into(),
-                        price: Decimal::from(10),
+                        price: usd(Decimal::from(10)),
                         quantity: 1,
+                        priority: OrderPriority::Normal,
+                        notes: None,
+                        ordered_at: Some(now()),
                     },
                 },
                 TabEvent::FoodOrderPlaced {
@@ -1076,8 +1584,11 @@ pub mod tests {
                     menu_item: MenuItem {
                         menu_number: 1,
                         description: "Steak".into(),
-                        price: Decimal::from(10),
+                        price: usd(Decimal::from(10)),
                         quantity: 1,
+                        priority: OrderPriority::Normal,
+                        notes: None,
+                        ordered_at: Some(now()),
                     },
                 },
                 TabEvent::FoodPrepared {
@@ -1095,7 +1606,7 @@ pub mod tests {
             ]),
             TabCommand::MarkFoodServed {
                 id: tab_id,
-                menu_numbers: vec![1],
+                items: vec![(1, 1)],
             },
         )
         .inspect_result()
@@ -1120,7 +1631,8 @@ pub mod tests {
             None,
             TabCommand::CloseTab {
                 id: tab_id,
-                amount_paid: Decimal::from(16),
+                amount_paid: usd(Decimal::from(16)),
+                gift_card: None,
             },
         );
 
@@ -1140,8 +1652,11 @@ pub mod tests {
                     menu_item: MenuItem {
                         menu_number: 1,
                         description: "Steak".into(),
-                        price: Decimal::from(10),
+                        price: usd(Decimal::from(10)),
                         quantity: 1,
+                        priority: OrderPriority::Normal,
+                        notes: None,
+                        ordered_at: Some(now()),
                     },
                 },
                 TabEvent::DrinkOrderPlaced {
@@ -1149,8 +1664,11 @@ pub mod tests {
                     menu_item: MenuItem {
                         menu_number: 2,
                         description: "Coca-Cola".into(),
-                        price: Decimal::from(5),
+                        price: usd(Decimal::from(5)),
                         quantity: 1,
+                        priority: OrderPriority::Normal,
+                        notes: None,
+                        ordered_at: Some(now()),
                     },
                 },
                 TabEvent::FoodPrepared {
@@ -1168,7 +1686,8 @@ pub mod tests {
             ]),
             TabCommand::CloseTab {
                 id: tab_id,
-                amount_paid: Decimal::from(16),
+                amount_paid: usd(Decimal::from(16)),
+                gift_card: None,
             },
         )
         .inspect_result()
@@ -1179,9 +1698,11 @@ pub mod tests {
             event[0],
             TabEvent::TabClosed {
                 id: tab_id,
-                amount_paid: Decimal::from(16),
-                order_value: Decimal::from(15),
-                tip_value: Decimal::from(1),
+                amount_paid: usd(Decimal::from(16)),
+                order_value: usd(Decimal::from(15)),
+                tip_value: usd(Decimal::from(1)),
+                amount_tendered: usd(Decimal::from(16)),
+                exchange_rate: None,
             }
         );
     }
@@ -1198,20 +1719,1117 @@ pub mod tests {
                 menu_item: MenuItem {
                     menu_number: 2,
                     description: "Coca-Cola".into(),
-                    price: Decimal::from(5),
+                    price: usd(Decimal::from(5)),
                     quantity: 1,
+                    priority: OrderPriority::Normal,
+                    notes: None,
+                    ordered_at: Some(now()),
                 },
             }]),
             TabCommand::CloseTab {
                 id: tab_id,
-                amount_paid: Decimal::from_str("4.99").unwrap(),
+                amount_paid: usd(Decimal::from_str("4.99").unwrap()),
+                gift_card: None,
+            },
+        );
+
+        // Assert
+        result.then_expect_error(TabError::MustPayEnough);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_open_tab_when_PayWithGiftCard_then_GiftCardPaymentReceived_event() {
+        let tab_id = TabId::new();
+
+        let result = arrange_and_act(
+            tab_id,
+            Some(vec![wine_ordered(tab_id)]),
+            TabCommand::PayWithGiftCard {
+                id: tab_id,
+                payment: gift_card(15),
+            },
+        );
+
+        result.then_expect_events(vec![TabEvent::GiftCardPaymentReceived {
+            id: tab_id,
+            payment: gift_card(15),
+        }]);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_payment_without_redemption_when_PayWithGiftCard_then_GiftCardNotRedeemed_error() {
+        let tab_id = TabId::new();
+
+        let result = arrange_and_act(
+            tab_id,
+            Some(vec![wine_ordered(tab_id)]),
+            TabCommand::PayWithGiftCard {
+                id: tab_id,
+                payment: GiftCardPayment {
+                    redemption: RedemptionId::default(),
+                    ..gift_card(15)
+                },
+            },
+        );
+
+        result.then_expect_error(TabError::GiftCardNotRedeemed);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_redemption_already_paid_in_when_PayWithGiftCard_again_then_no_events() {
+        let tab_id = TabId::new();
+
+        let result = arrange_and_act(
+            tab_id,
+            Some(vec![
+                wine_ordered(tab_id),
+                TabEvent::GiftCardPaymentReceived {
+                    id: tab_id,
+                    payment: gift_card(15),
+                },
+                TabEvent::TabClosed {
+                    id: tab_id,
+                    amount_paid: usd(Decimal::from(5)),
+                    order_value: usd(Decimal::from(20)),
+                    tip_value: usd(Decimal::ZERO),
+                    amount_tendered: usd(Decimal::from(5)),
+                    exchange_rate: None,
+                },
+            ]),
+            TabCommand::PayWithGiftCard {
+                id: tab_id,
+                payment: gift_card(15),
+            },
+        );
+
+        result.then_expect_events(vec![]);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_partly_paid_tab_when_PayWithGiftCard_for_more_than_outstanding_then_GiftCardExceedsBalance_error(
+    ) {
+        let tab_id = TabId::new();
+
+        let result = arrange_and_act(
+            tab_id,
+            Some(vec![
+                wine_ordered(tab_id),
+                TabEvent::GiftCardPaymentReceived {
+                    id: tab_id,
+                    payment: gift_card(15),
+                },
+            ]),
+            TabCommand::PayWithGiftCard {
+                id: tab_id,
+                payment: gift_card(10),
+            },
+        );
+
+        result.then_expect_error(TabError::GiftCardExceedsBalance {
+            outstanding: usd(Decimal::from(5)),
+        });
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_gift_card_payment_when_CloseTab_then_only_the_rest_is_due_and_the_excess_is_tip() {
+        let tab_id = TabId::new();
+
+        let result = arrange_and_act(
+            tab_id,
+            Some(vec![
+                wine_ordered(tab_id),
+                TabEvent::GiftCardPaymentReceived {
+                    id: tab_id,
+                    payment: gift_card(15),
+                },
+            ]),
+            TabCommand::CloseTab {
+                id: tab_id,
+                amount_paid: usd(Decimal::from(7)),
+                gift_card: None,
+            },
+        );
+
+        result.then_expect_events(vec![TabEvent::TabClosed {
+            id: tab_id,
+            amount_paid: usd(Decimal::from(7)),
+            order_value: usd(Decimal::from(20)),
+            tip_value: usd(Decimal::from(2)),
+            amount_tendered: usd(Decimal::from(7)),
+            exchange_rate: None,
+        }]);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_open_tab_when_CloseTab_with_gift_card_then_payment_is_recorded_before_TabClosed() {
+        let tab_id = TabId::new();
+
+        let result = arrange_and_act(
+            tab_id,
+            Some(vec![wine_ordered(tab_id)]),
+            TabCommand::CloseTab {
+                id: tab_id,
+                amount_paid: usd(Decimal::ZERO),
+                gift_card: Some(gift_card(20)),
             },
         );
 
+        result.then_expect_events(vec![
+            TabEvent::GiftCardPaymentReceived {
+                id: tab_id,
+                payment: gift_card(20),
+            },
+            TabEvent::TabClosed {
+                id: tab_id,
+                amount_paid: usd(Decimal::ZERO),
+                order_value: usd(Decimal::from(20)),
+                tip_value: usd(Decimal::ZERO),
+                amount_tendered: usd(Decimal::ZERO),
+                exchange_rate: None,
+            },
+        ]);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_happy_hour_when_PlaceOrder_then_PromotionApplied_follows_the_order() {
+        // Arrange
+        let tab_id = TabId::new();
+        let executor =
+            TestFramework::<Tab>::with(promotion_services()).given(vec![TabEvent::TabOpened {
+                id: tab_id,
+                waiter_id: WaiterId::new(),
+                table: 1,
+            }]);
+
+        // Act
+        let result = executor.when(TabCommand::PlaceOrder {
+            order_items: vec![OrderItem {
+                menu_number: 7,
+                description: "Wine".into(),
+                is_drink: true,
+                price: usd(Decimal::from(20)),
+                notes: None,
+            }],
+            priority: OrderPriority::Normal,
+        });
+
+        // Assert
+        result.then_expect_events(vec![
+            wine_ordered(tab_id),
+            TabEvent::PromotionApplied {
+                id: tab_id,
+                promotion: happy_hour(),
+            },
+        ]);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_two_wines_when_CloseTab_then_one_is_free_and_order_value_is_discounted() {
+        // Arrange
+        let tab_id = TabId::new();
+        let executor = TestFramework::<Tab>::with(promotion_services()).given(vec![
+            TabEvent::TabOpened {
+                id: tab_id,
+                waiter_id: WaiterId::new(),
+                table: 1,
+            },
+            wine_ordered(tab_id),
+            wine_ordered(tab_id),
+        ]);
+
+        // Act
+        let result = executor.when(TabCommand::CloseTab {
+            id: tab_id,
+            amount_paid: usd(Decimal::from(20)),
+            gift_card: None,
+        });
+
+        // Assert
+        result.then_expect_events(vec![
+            TabEvent::PromotionApplied {
+                id: tab_id,
+                promotion: wine_bogof(),
+            },
+            TabEvent::TabClosed {
+                id: tab_id,
+                amount_paid: usd(Decimal::from(20)),
+                order_value: usd(Decimal::from(20)),
+                tip_value: usd(Decimal::ZERO),
+                amount_tendered: usd(Decimal::from(20)),
+                exchange_rate: None,
+            },
+        ]);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_wine_bought_in_happy_hour_when_CloseTab_then_promotions_do_not_stack() {
+        // Promotions recorded before their units were only know quantities.
+        let recorded_without_units = AppliedPromotion {
+            units: vec![],
+            ..happy_hour()
+        };
+        for promotion in [happy_hour(), recorded_without_units] {
+            // Arrange
+            let tab_id = TabId::new();
+            let executor = TestFramework::<Tab>::with(promotion_services()).given(vec![
+                TabEvent::TabOpened {
+                    id: tab_id,
+                    waiter_id: WaiterId::new(),
+                    table: 1,
+                },
+                wine_ordered(tab_id),
+                TabEvent::PromotionApplied {
+                    id: tab_id,
+                    promotion,
+                },
+                wine_ordered(tab_id),
+                wine_ordered(tab_id),
+            ]);
+
+            // Act
+            let result = executor.when(TabCommand::CloseTab {
+                id: tab_id,
+                amount_paid: usd(Decimal::from(35)),
+                gift_card: None,
+            });
+
+            // Assert
+            result.then_expect_events(vec![
+                TabEvent::PromotionApplied {
+                    id: tab_id,
+                    promotion: wine_bogof(),
+                },
+                TabEvent::TabClosed {
+                    id: tab_id,
+                    amount_paid: usd(Decimal::from(35)),
+                    order_value: usd(Decimal::from(35)),
+                    tip_value: usd(Decimal::ZERO),
+                    amount_tendered: usd(Decimal::from(35)),
+                    exchange_rate: None,
+                },
+            ]);
+        }
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_later_wine_discounted_in_happy_hour_when_CloseTab_then_earlier_wines_are_still_offered(
+    ) {
+        // Arrange
+        let tab_id = TabId::new();
+        let promotions = Promotions::from_json(
+            r#"{ "promotions": [
+                { "id": "wine-bogof", "name": "Wine: buy one get one free",
+                  "schedule": { "from": "11:00", "until": "13:00" },
+                  "rule": { "type": "buy_get_free", "menu_numbers": [7], "buy": 1, "free": 1 } }
+            ] }"#,
+        )
+        .unwrap();
+        let later = now() + chrono::Duration::hours(2);
+        let services = TabServices::default()
+            .with_promotions(Arc::new(promotions))
+            .with_clock(Arc::new(FixedClock(later)));
+        let later_wine = TabEvent::DrinkOrderPlaced {
+            id: tab_id,
+            menu_item: MenuItem {
+                ordered_at: Some(later),
+                ..wine()
+            },
+        };
+        let executor = TestFramework::<Tab>::with(services).given(vec![
+            TabEvent::TabOpened {
+                id: tab_id,
+                waiter_id: WaiterId::new(),
+                table: 1,
+            },
+            wine_ordered(tab_id),
+            wine_ordered(tab_id),
+            later_wine,
+            TabEvent::PromotionApplied {
+                id: tab_id,
+                promotion: AppliedPromotion {
+                    units: vec![(7, later)],
+                    ..happy_hour()
+                },
+            },
+        ]);
+
+        // Act
+        let result = executor.when(TabCommand::CloseTab {
+            id: tab_id,
+            amount_paid: usd(Decimal::from(35)),
+            gift_card: None,
+        });
+
+        // Assert
+        result.then_expect_events(vec![
+            TabEvent::PromotionApplied {
+                id: tab_id,
+                promotion: wine_bogof(),
+            },
+            TabEvent::TabClosed {
+                id: tab_id,
+                amount_paid: usd(Decimal::from(35)),
+                order_value: usd(Decimal::from(35)),
+                tip_value: usd(Decimal::ZERO),
+                amount_tendered: usd(Decimal::from(35)),
+                exchange_rate: None,
+            },
+        ]);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_promotion_due_at_close_when_PayWithGiftCard_for_full_price_then_GiftCardExceedsBalance_error(
+    ) {
+        // Arrange
+        let tab_id = TabId::new();
+        let executor = TestFramework::<Tab>::with(promotion_services()).given(vec![
+            TabEvent::TabOpened {
+                id: tab_id,
+                waiter_id: WaiterId::new(),
+                table: 1,
+            },
+            wine_ordered(tab_id),
+            wine_ordered(tab_id),
+        ]);
+
+        // Act
+        let result = executor.when(TabCommand::PayWithGiftCard {
+            id: tab_id,
+            payment: gift_card(40),
+        });
+
+        // Assert
+        result.then_expect_error(TabError::GiftCardExceedsBalance {
+            outstanding: usd(Decimal::from(20)),
+        });
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_promotion_due_at_close_when_PayWithGiftCard_then_promotion_is_applied_with_payment() {
+        // Arrange
+        let tab_id = TabId::new();
+        let executor = TestFramework::<Tab>::with(promotion_services()).given(vec![
+            TabEvent::TabOpened {
+                id: tab_id,
+                waiter_id: WaiterId::new(),
+                table: 1,
+            },
+            wine_ordered(tab_id),
+            wine_ordered(tab_id),
+        ]);
+
+        // Act
+        let result = executor.when(TabCommand::PayWithGiftCard {
+            id: tab_id,
+            payment: gift_card(20),
+        });
+
+        // Assert
+        result.then_expect_events(vec![
+            TabEvent::PromotionApplied {
+                id: tab_id,
+                promotion: wine_bogof(),
+            },
+            TabEvent::GiftCardPaymentReceived {
+                id: tab_id,
+                payment: gift_card(20),
+            },
+        ]);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_wine_ordered_while_promotion_ran_when_CloseTab_after_it_ends_then_it_still_applies() {
+        // Arrange
+        let tab_id = TabId::new();
+        let promotions = Promotions::from_json(
+            r#"{ "promotions": [
+                { "id": "wine-bogof", "name": "Wine: buy one get one free",
+                  "schedule": { "from": "11:00", "until": "13:00" },
+                  "rule": { "type": "buy_get_free", "menu_numbers": [7], "buy": 1, "free": 1 } }
+            ] }"#,
+        )
+        .unwrap();
+        let closed_at = now() + chrono::Duration::hours(2);
+        let services = TabServices::default()
+            .with_promotions(Arc::new(promotions))
+            .with_clock(Arc::new(FixedClock(closed_at)));
+        let executor = TestFramework::<Tab>::with(services).given(vec![
+            TabEvent::TabOpened {
+                id: tab_id,
+                waiter_id: WaiterId::new(),
+                table: 1,
+            },
+            wine_ordered(tab_id),
+            wine_ordered(tab_id),
+        ]);
+
+        // Act
+        let result = executor.when(TabCommand::CloseTab {
+            id: tab_id,
+            amount_paid: usd(Decimal::from(20)),
+            gift_card: None,
+        });
+
         // Assert
+        result.then_expect_events(vec![
+            TabEvent::PromotionApplied {
+                id: tab_id,
+                promotion: wine_bogof(),
+            },
+            TabEvent::TabClosed {
+                id: tab_id,
+                amount_paid: usd(Decimal::from(20)),
+                order_value: usd(Decimal::from(20)),
+                tip_value: usd(Decimal::ZERO),
+                amount_tendered: usd(Decimal::from(20)),
+                exchange_rate: None,
+            },
+        ]);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_open_tab_when_CloseTab_with_gift_card_and_too_little_cash_then_MustPayEnough_error() {
+        let tab_id = TabId::new();
+
+        let result = arrange_and_act(
+            tab_id,
+            Some(vec![wine_ordered(tab_id)]),
+            TabCommand::CloseTab {
+                id: tab_id,
+                amount_paid: usd(Decimal::from(4)),
+                gift_card: Some(gift_card(10)),
+            },
+        );
+
         result.then_expect_error(TabError::MustPayEnough);
     }
 
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_cash_rounding_when_CloseTab_then_order_value_is_rounded_to_005() {
+        let tab_id = TabId::new();
+        let services =
+            TabServices::new(Currency::USD, Rounding::Cash).with_clock(Arc::new(FixedClock(now())));
+
+        let result = TestFramework::<Tab>::with(services)
+            .given(vec![
+                TabEvent::TabOpened {
+                    id: tab_id,
+                    waiter_id: WaiterId::new(),
+                    table: 1,
+                },
+                TabEvent::DrinkOrderPlaced {
+                    id: tab_id,
+                    menu_item: MenuItem {
+                        menu_number: 2,
+                        description: "Coca-Cola".into(),
+                        price: usd(Decimal::from_str("4.99").unwrap()),
+                        quantity: 1,
+                        priority: OrderPriority::Normal,
+                        notes: None,
+                        ordered_at: Some(now()),
+                    },
+                },
+            ])
+            .when(TabCommand::CloseTab {
+                id: tab_id,
+                amount_paid: usd(Decimal::from(6)),
+                gift_card: None,
+            });
+
+        result.then_expect_events(vec![TabEvent::TabClosed {
+            id: tab_id,
+            amount_paid: usd(Decimal::from(6)),
+            order_value: usd(Decimal::from(5)),
+            tip_value: usd(Decimal::from(1)),
+            amount_tendered: usd(Decimal::from(6)),
+            exchange_rate: None,
+        }]);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_open_tab_when_PlaceOrder_in_another_currency_then_CurrencyMismatch_error() {
+        let tab_id = TabId::new();
+
+        let result = arrange_and_act(
+            tab_id,
+            Some(vec![]),
+            TabCommand::PlaceOrder {
+                order_items: vec![OrderItem {
+                    menu_number: 1,
+                    description: "Tibs".into(),
+                    is_drink: false,
+                    price: Money::new(Decimal::from(100), Currency::ETB).unwrap(),
+                    notes: None,
+                }],
+                priority: OrderPriority::Normal,
+            },
+        );
+
+        result.then_expect_error(TabError::CurrencyMismatch {
+            expected: Currency::USD,
+            actual: Currency::ETB,
+        });
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_no_exchange_rate_when_CloseTab_in_another_currency_then_ExchangeRateUnavailable_error()
+    {
+        let tab_id = TabId::new();
+
+        let result = arrange_and_act(
+            tab_id,
+            Some(vec![]),
+            TabCommand::CloseTab {
+                id: tab_id,
+                amount_paid: Money::new(Decimal::from(100), Currency::ETB).unwrap(),
+                gift_card: None,
+            },
+        );
+
+        result.then_expect_error(TabError::ExchangeRateUnavailable {
+            from: Currency::ETB,
+            to: Currency::USD,
+        });
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_rates_unreachable_when_CloseTab_in_another_currency_then_ExchangeRateLookupFailed_error(
+    ) {
+        let tab_id = TabId::new();
+        let tab_services = services().with_exchange_rates(Arc::new(UnreachableRates));
+        let executor = TestFramework::<Tab>::with(tab_services).given(vec![TabEvent::TabOpened {
+            id: tab_id,
+            waiter_id: WaiterId::new(),
+            table: 1,
+        }]);
+
+        let result = executor.when(TabCommand::CloseTab {
+            id: tab_id,
+            amount_paid: Money::new(Decimal::from(100), Currency::ETB).unwrap(),
+            gift_card: None,
+        });
+
+        result.then_expect_error(TabError::ExchangeRateLookupFailed {
+            from: Currency::ETB,
+            to: Currency::USD,
+        });
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_exchange_rate_when_CloseTab_in_euros_then_TabClosed_records_tendered_amount_and_rate()
+    {
+        let tab_id = TabId::new();
+        let rate = ExchangeRate::new(
+            Currency::EUR,
+            Currency::USD,
+            Decimal::from_str("1.08").unwrap(),
+            Utc.with_ymd_and_hms(2024, 4, 19, 6, 0, 0).unwrap(),
+        )
+        .unwrap();
+        let tab_services =
+            services().with_exchange_rates(Arc::new(ExchangeRateTable::default().with_rate(rate)));
+        let euros = Money::new(Decimal::from(20), Currency::EUR).unwrap();
+        let executor = TestFramework::<Tab>::with(tab_services).given(vec![
+            TabEvent::TabOpened {
+                id: tab_id,
+                waiter_id: WaiterId::new(),
+                table: 1,
+            },
+            TabEvent::FoodOrderPlaced {
+                id: tab_id,
+                menu_item: MenuItem {
+                    menu_number: 1,
+                    description: "Steak".into(),
+                    price: usd(Decimal::from(20)),
+                    quantity: 1,
+                    priority: OrderPriority::Normal,
+                    notes: None,
+                    ordered_at: Some(now()),
+                },
+            },
+            TabEvent::FoodPrepared {
+                id: tab_id,
+                menu_number: 1,
+            },
+            TabEvent::FoodServed {
+                id: tab_id,
+                menu_number: 1,
+            },
+        ]);
+
+        let result = executor.when(TabCommand::CloseTab {
+            id: tab_id,
+            amount_paid: euros,
+            gift_card: None,
+        });
+
+        result.then_expect_events(vec![TabEvent::TabClosed {
+            id: tab_id,
+            amount_paid: usd(Decimal::from_str("21.60").unwrap()),
+            order_value: usd(Decimal::from(20)),
+            tip_value: usd(Decimal::from_str("1.60").unwrap()),
+            amount_tendered: euros,
+            exchange_rate: Some(rate),
+        }]);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_open_tab_when_MarkDrinksServed_for_another_tab_then_TabIdMismatch_error() {
+        let tab_id = TabId::new();
+        let other_id = TabId::new();
+
+        let result = arrange_and_act(
+            tab_id,
+            Some(vec![TabEvent::DrinkOrderPlaced {
+                id: tab_id,
+                menu_item: MenuItem {
+                    menu_number: 2,
+                    description: "Coca-Cola".into(),
+                    price: usd(Decimal::from(5)),
+                    quantity: 1,
+                    priority: OrderPriority::Normal,
+                    notes: None,
+                    ordered_at: Some(now()),
+                },
+            }]),
+            TabCommand::MarkDrinksServed {
+                id: other_id,
+                items: vec![(2, 1)],
+            },
+        );
+
+        result.then_expect_error(TabError::TabIdMismatch {
+            expected: tab_id,
+            actual: other_id,
+        });
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_open_tab_when_RushOrder_or_CloseTab_for_another_tab_then_TabIdMismatch_error() {
+        let tab_id = TabId::new();
+        let other_id = TabId::new();
+        let expected = TabError::TabIdMismatch {
+            expected: tab_id,
+            actual: other_id,
+        };
+
+        let rush = arrange_and_act(
+            tab_id,
+            Some(Vec::new()),
+            TabCommand::RushOrder {
+                id: other_id,
+                menu_numbers: vec![1],
+            },
+        );
+        let close = arrange_and_act(
+            tab_id,
+            Some(Vec::new()),
+            TabCommand::CloseTab {
+                id: other_id,
+                amount_paid: usd(Decimal::ZERO),
+                gift_card: None,
+            },
+        );
+
+        rush.then_expect_error(expected.clone());
+        close.then_expect_error(expected);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_open_tab_when_PlaceOrder_with_High_priority_then_FoodOrderPlaced_event_has_priority() {
+        let tab_id = TabId::new();
+        let order_items = vec![OrderItem {
+            menu_number: 1,
+            description: "Steak".into(),
+            is_drink: false,
+            price: usd(Decimal::from(10)),
+            notes: None,
+        }];
+
+        let event = arrange_and_act(
+            tab_id,
+            Some(Vec::new()),
+            TabCommand::PlaceOrder {
+                order_items,
+                priority: OrderPriority::High,
+            },
+        )
+        .inspect_result()
+        .expect("failed to execute command: PlaceOrder");
+
+        assert_eq!(event.len(), 1);
+        assert_eq!(
+            event[0],
+            TabEvent::FoodOrderPlaced {
+                id: tab_id,
+                menu_item: MenuItem {
+                    menu_number: 1,
+                    description: "Steak".into(),
+                    price: usd(Decimal::from(10)),
+                    quantity: 1,
+                    priority: OrderPriority::High,
+                    notes: None,
+                    ordered_at: Some(now()),
+                }
+            }
+        );
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_open_tab_and_food_ordered_when_RushOrder_command_then_FoodOrderRushed_event() {
+        let tab_id = TabId::new();
+
+        let event = arrange_and_act(
+            tab_id,
+            Some(vec![TabEvent::FoodOrderPlaced {
+                id: tab_id,
+                menu_item: MenuItem {
+                    menu_number: 1,
+                    description: "Steak".into(),
+                    price: usd(Decimal::from(10)),
+                    quantity: 1,
+                    priority: OrderPriority::Normal,
+                    notes: None,
+                    ordered_at: Some(now()),
+                },
+            }]),
+            TabCommand::RushOrder {
+                id: tab_id,
+                menu_numbers: vec![1],
+            },
+        )
+        .inspect_result()
+        .expect("command RushOrder failed");
+
+        assert_eq!(event.len(), 1);
+        assert_eq!(
+            event[0],
+            TabEvent::FoodOrderRushed {
+                id: tab_id,
+                menu_number: 1
+            }
+        );
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_open_tab_and_food_prepared_when_RushOrder_command_then_FoodNotOutstanding_error() {
+        let tab_id = TabId::new();
+
+        let result = arrange_and_act(
+            tab_id,
+            Some(vec![
+                TabEvent::FoodOrderPlaced {
+                    id: tab_id,
+                    menu_item: MenuItem {
+                        menu_number: 1,
+                        description: "Steak".into(),
+                        price: usd(Decimal::from(10)),
+                        quantity: 1,
+                        priority: OrderPriority::Normal,
+                        notes: None,
+                        ordered_at: Some(now()),
+                    },
+                },
+                TabEvent::FoodPrepared {
+                    id: tab_id,
+                    menu_number: 1,
+                },
+            ]),
+            TabCommand::RushOrder {
+                id: tab_id,
+                menu_numbers: vec![1],
+            },
+        );
+
+        result.then_expect_error(TabError::FoodNotOutstanding { menu_number: 1 });
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_food_already_rushed_when_RushOrder_command_then_FoodAlreadyRushed_error() {
+        let tab_id = TabId::new();
+
+        let result = arrange_and_act(
+            tab_id,
+            Some(vec![
+                steak_ordered(tab_id),
+                TabEvent::FoodOrderRushed {
+                    id: tab_id,
+                    menu_number: 1,
+                },
+            ]),
+            TabCommand::RushOrder {
+                id: tab_id,
+                menu_numbers: vec![1],
+            },
+        );
+
+        result.then_expect_error(TabError::FoodAlreadyRushed { menu_number: 1 });
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_two_steaks_one_rushed_when_RushOrder_twice_then_only_the_other_can_be_rushed() {
+        let tab_id = TabId::new();
+        let given = || {
+            Some(vec![
+                steak_ordered(tab_id),
+                steak_ordered(tab_id),
+                TabEvent::FoodOrderRushed {
+                    id: tab_id,
+                    menu_number: 1,
+                },
+            ])
+        };
+
+        arrange_and_act(
+            tab_id,
+            given(),
+            TabCommand::RushOrder {
+                id: tab_id,
+                menu_numbers: vec![1],
+            },
+        )
+        .then_expect_events(vec![TabEvent::FoodOrderRushed {
+            id: tab_id,
+            menu_number: 1,
+        }]);
+        arrange_and_act(
+            tab_id,
+            given(),
+            TabCommand::RushOrder {
+                id: tab_id,
+                menu_numbers: vec![1, 1],
+            },
+        )
+        .then_expect_error(TabError::FoodAlreadyRushed { menu_number: 1 });
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_rushed_steak_prepared_when_RushOrder_then_the_next_steak_can_be_rushed() {
+        let tab_id = TabId::new();
+
+        let result = arrange_and_act(
+            tab_id,
+            Some(vec![
+                steak_ordered(tab_id),
+                steak_ordered(tab_id),
+                TabEvent::FoodOrderRushed {
+                    id: tab_id,
+                    menu_number: 1,
+                },
+                TabEvent::FoodPrepared {
+                    id: tab_id,
+                    menu_number: 1,
+                },
+            ]),
+            TabCommand::RushOrder {
+                id: tab_id,
+                menu_numbers: vec![1],
+            },
+        );
+
+        result.then_expect_events(vec![TabEvent::FoodOrderRushed {
+            id: tab_id,
+            menu_number: 1,
+        }]);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn given_closed_tab_when_PlaceOrder_command_then_TabNotOpened_error() {
+        let tab_id = TabId::new();
+
+        let result = arrange_and_act(
+            tab_id,
+            Some(vec![TabEvent::TabClosed {
+                id: tab_id,
+                amount_paid: usd(Decimal::ZERO),
+                order_value: usd(Decimal::ZERO),
+                tip_value: usd(Decimal::ZERO),
+                amount_tendered: usd(Decimal::ZERO),
+                exchange_rate: None,
+            }]),
+            TabCommand::PlaceOrder {
+                order_items: vec![OrderItem::default()],
+                priority: OrderPriority::Normal,
+            },
+        );
+
+        result.then_expect_error(TabError::TabNotOpened);
+    }
+
+    #[test]
+    fn given_snapshot_from_older_schema_then_missing_fields_default_and_unknown_fields_are_ignored()
+    {
+        // Arrange
+        let snapshot = serde_json::json!({
+            "id": "7b4a3c1e-2f1d-4c8e-9a57-3e2b8f0d6a11",
+            "table": 5,
+            "opened": true,
+            "waiter_id": "00000000-0000-0000-0000-000000000000",
+            "food_items": [],
+            "foods_prepared": {},
+            "drink_items": [],
+            "removed_field": 42
+        });
+
+        // Act
+        let tab: Tab = serde_json::from_value(snapshot).expect("snapshot should deserialize");
+
+        // Assert
+        assert_eq!(tab.table, 5);
+        assert!(tab.opened);
+        assert!(tab.foods_served.is_empty());
+        assert!(tab.drinks_served.is_empty());
+    }
+
+    fn steak_ordered(tab_id: TabId) -> TabEvent {
+        TabEvent::FoodOrderPlaced {
+            id: tab_id,
+            menu_item: MenuItem {
+                menu_number: 1,
+                description: "Steak".into(),
+                price: usd(Decimal::from(10)),
+                quantity: 1,
+                priority: OrderPriority::Normal,
+                notes: None,
+                ordered_at: Some(now()),
+            },
+        }
+    }
+
+    fn wine() -> MenuItem {
+        MenuItem {
+            menu_number: 7,
+            description: "Wine".into(),
+            price: usd(Decimal::from(20)),
+            quantity: 1,
+            priority: OrderPriority::Normal,
+            notes: None,
+            ordered_at: Some(now()),
+        }
+    }
+
+    fn wine_ordered(tab_id: TabId) -> TabEvent {
+        TabEvent::DrinkOrderPlaced {
+            id: tab_id,
+            menu_item: wine(),
+        }
+    }
+
+    // 25% off wine as it is ordered, and one free of every two at close.
+    fn promotion_services() -> TabServices {
+        let promotions = Promotions::from_json(
+            r#"{ "promotions": [
+                { "id": "happy-hour", "name": "Happy hour",
+                  "rule": { "type": "percent_off", "menu_numbers": [7], "percent": 25 } },
+                { "id": "wine-bogof", "name": "Wine: buy one get one free",
+                  "rule": { "type": "buy_get_free", "menu_numbers": [7], "buy": 1, "free": 1 } }
+            ] }"#,
+        )
+        .unwrap();
+
+        services().with_promotions(Arc::new(promotions))
+    }
+
+    fn happy_hour() -> AppliedPromotion {
+        AppliedPromotion {
+            promotion_id: "happy-hour".into(),
+            name: "Happy hour".into(),
+            items: vec![(7, 1)],
+            units: vec![(7, now())],
+            discount: usd(Decimal::from(5)),
+        }
+    }
+
+    fn wine_bogof() -> AppliedPromotion {
+        AppliedPromotion {
+            promotion_id: "wine-bogof".into(),
+            name: "Wine: buy one get one free".into(),
+            items: vec![(7, 2)],
+            units: vec![(7, now()), (7, now())],
+            discount: usd(Decimal::from(20)),
+        }
+    }
+
+    // Each amount stands for one redemption.
+    fn gift_card(amount: i64) -> GiftCardPayment {
+        GiftCardPayment {
+            code: "GC-1001".into(),
+            amount: usd(Decimal::from(amount)),
+            redemption: RedemptionId::from(uuid::Uuid::from_u128(amount as u128)),
+        }
+    }
+
+    // 2024-04-19 is a Friday.
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 4, 19, 12, 0, 0).unwrap()
+    }
+
+    fn services() -> TabServices {
+        TabServices::default().with_clock(Arc::new(FixedClock(now())))
+    }
+
+    #[test]
+    fn every_money_error_is_reported_as_a_tab_error() {
+        let cases = [
+            (MoneyError::Negative, TabError::NegativeAmount),
+            (
+                MoneyError::InvalidCurrency(String::from("usd")),
+                TabError::InvalidCurrency {
+                    code: String::from("usd"),
+                },
+            ),
+            (
+                MoneyError::InvalidRate(Decimal::ZERO),
+                TabError::InvalidExchangeRate {
+                    rate: Decimal::ZERO,
+                },
+            ),
+            (
+                MoneyError::CurrencyMismatch {
+                    expected: Currency::USD,
+                    actual: Currency::EUR,
+                },
+                TabError::CurrencyMismatch {
+                    expected: Currency::USD,
+                    actual: Currency::EUR,
+                },
+            ),
+            (MoneyError::Overflow, TabError::AmountTooLarge),
+        ];
+
+        for (error, expected) in cases {
+            assert_eq!(currency_error(error), expected);
+        }
+    }
+
     fn arrange_and_act(
         tab_id: TabId,
         given: Option<Vec<TabEvent>>,
@@ -1225,7 +2843,7 @@ pub mod tests {
         given_events: Option<Vec<TabEvent>>,
     ) -> AggregateTestExecutor<Tab> {
         let waiter_id = WaiterId::new();
-        let tab_services = TabServices {};
+        let tab_services = services();
 
         match given_events {
             Some(mut events) => {
@@ -1,9 +1,12 @@
-use rust_decimal::Decimal;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
-use super::{tab_id::TabId, waiter_id::WaiterId};
+use crate::{domain::gift_card::redemption_id::RedemptionId, shared_kernel::money::Money};
 
-#[derive(Debug, Deserialize)]
+use super::{
+    event::GiftCardPayment, order_priority::OrderPriority, tab_id::TabId, waiter_id::WaiterId,
+};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum TabCommand {
     OpenTab {
         id: TabId,
@@ -12,22 +15,35 @@ pub enum TabCommand {
     },
     PlaceOrder {
         order_items: Vec<OrderItem>,
+        #[serde(default)]
+        priority: OrderPriority,
     },
-    MarkDrinksServed {
+    RushOrder {
         id: TabId,
         menu_numbers: Vec<usize>,
     },
+    MarkDrinksServed {
+        id: TabId,
+        items: Vec<(usize, usize)>,
+    },
     MarkFoodPrepared {
         id: TabId,
-        menu_numbers: Vec<usize>,
+        items: Vec<(usize, usize)>,
     },
     MarkFoodServed {
         id: TabId,
-        menu_numbers: Vec<usize>,
+        items: Vec<(usize, usize)>,
+    },
+    PayWithGiftCard {
+        id: TabId,
+        #[serde(deserialize_with = "unredeemed")]
+        payment: GiftCardPayment,
     },
     CloseTab {
         id: TabId,
-        amount_paid: Decimal,
+        amount_paid: Money,
+        #[serde(default, deserialize_with = "unredeemed_option")]
+        gift_card: Option<GiftCardPayment>,
     },
 }
 
@@ -36,5 +52,61 @@ pub struct OrderItem {
     pub menu_number: usize,
     pub description: String,
     pub is_drink: bool,
-    pub price: Decimal,
+    pub price: Money,
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+// Redemptions are issued by the gift card saga, never taken from clients.
+fn unredeemed<'de, D: Deserializer<'de>>(deserializer: D) -> Result<GiftCardPayment, D::Error> {
+    GiftCardPayment::deserialize(deserializer).map(without_redemption)
+}
+
+fn unredeemed_option<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<GiftCardPayment>, D::Error> {
+    Ok(Option::<GiftCardPayment>::deserialize(deserializer)?.map(without_redemption))
+}
+
+fn without_redemption(payment: GiftCardPayment) -> GiftCardPayment {
+    GiftCardPayment {
+        redemption: RedemptionId::default(),
+        ..payment
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::TabCommand;
+
+    #[test]
+    fn redemptions_sent_by_clients_are_dropped() {
+        let payment = json!({
+            "code": "GC-1001",
+            "amount": {"amount": "10", "currency": "USD"},
+            "redemption": "7f1c2a9e-5a4b-4c3d-9e8f-0123456789ab"
+        });
+
+        let pay: TabCommand = serde_json::from_value(json!({"PayWithGiftCard": {
+            "id": "00000000-0000-0000-0000-000000000001",
+            "payment": payment
+        }}))
+        .unwrap();
+        let close: TabCommand = serde_json::from_value(json!({"CloseTab": {
+            "id": "00000000-0000-0000-0000-000000000001",
+            "amount_paid": {"amount": "0", "currency": "USD"},
+            "gift_card": payment
+        }}))
+        .unwrap();
+
+        assert!(
+            matches!(pay, TabCommand::PayWithGiftCard { payment, .. } if payment.redemption.is_nil())
+        );
+        assert!(matches!(
+            close,
+            TabCommand::CloseTab { gift_card: Some(payment), .. } if payment.redemption.is_nil()
+        ));
+    }
 }
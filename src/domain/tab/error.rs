@@ -1,15 +1,195 @@
+use cqrs_es::AggregateError;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::shared_kernel::money::{Currency, Money};
+
 use super::tab_id::TabId;
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub enum TabError {
     CannotCancelServedItem,
     TabHasUnservedItems,
     MustPayEnough,
     TabNotOpened,
-    DrinkNotOutstanding { menu_number: usize },
-    TabIsOpen { id: TabId },
-    FoodNotOutstanding { menu_number: usize },
-    FoodNotPrepared { menu_number: usize },
+    DrinkNotOutstanding {
+        menu_number: usize,
+    },
+    TabIsOpen {
+        id: TabId,
+    },
+    FoodNotOutstanding {
+        menu_number: usize,
+    },
+    FoodNotPrepared {
+        menu_number: usize,
+    },
+    FoodAlreadyRushed {
+        menu_number: usize,
+    },
+    TabIdMismatch {
+        expected: TabId,
+        actual: TabId,
+    },
+    CurrencyMismatch {
+        expected: Currency,
+        actual: Currency,
+    },
+    ExchangeRateUnavailable {
+        from: Currency,
+        to: Currency,
+    },
+    GiftCardExceedsBalance {
+        outstanding: Money,
+    },
+    IdempotencyKeyReused,
+    ExchangeRateLookupFailed {
+        from: Currency,
+        to: Currency,
+    },
+    GiftCardNotRedeemed,
+    CommandInFlight,
+    NegativeAmount,
+    InvalidCurrency {
+        code: String,
+    },
+    InvalidExchangeRate {
+        rate: Decimal,
+    },
+    AmountTooLarge,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+    Validation,
+    NotFound,
+    Conflict,
+    State,
+    Internal,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ErrorBody {
+    pub code: String,
+    pub category: ErrorCategory,
+    pub status: u16,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+}
+
+impl ErrorCategory {
+    pub fn http_status(&self) -> u16 {
+        match self {
+            ErrorCategory::Validation => 400,
+            ErrorCategory::NotFound => 404,
+            ErrorCategory::Conflict => 409,
+            ErrorCategory::State => 422,
+            ErrorCategory::Internal => 500,
+        }
+    }
+}
+
+impl TabError {
+    // Codes are part of the public API: never change or reuse one.
+    pub fn code(&self) -> &'static str {
+        match self {
+            TabError::CannotCancelServedItem => "TAB_ITEM_ALREADY_SERVED",
+            TabError::TabHasUnservedItems => "TAB_HAS_UNSERVED_ITEMS",
+            TabError::MustPayEnough => "TAB_PAYMENT_TOO_LOW",
+            TabError::TabNotOpened => "TAB_NOT_OPEN",
+            TabError::DrinkNotOutstanding { .. } => "TAB_DRINK_NOT_OUTSTANDING",
+            TabError::TabIsOpen { .. } => "TAB_ALREADY_OPEN",
+            TabError::FoodNotOutstanding { .. } => "TAB_FOOD_NOT_OUTSTANDING",
+            TabError::FoodNotPrepared { .. } => "TAB_FOOD_NOT_PREPARED",
+            TabError::FoodAlreadyRushed { .. } => "TAB_FOOD_ALREADY_RUSHED",
+            TabError::TabIdMismatch { .. } => "TAB_ID_MISMATCH",
+            TabError::CurrencyMismatch { .. } => "TAB_CURRENCY_MISMATCH",
+            TabError::ExchangeRateUnavailable { .. } => "TAB_EXCHANGE_RATE_UNAVAILABLE",
+            TabError::GiftCardExceedsBalance { .. } => "TAB_GIFT_CARD_EXCEEDS_BALANCE",
+            TabError::IdempotencyKeyReused => "TAB_IDEMPOTENCY_KEY_REUSED",
+            TabError::ExchangeRateLookupFailed { .. } => "TAB_EXCHANGE_RATE_LOOKUP_FAILED",
+            TabError::GiftCardNotRedeemed => "TAB_GIFT_CARD_NOT_REDEEMED",
+            TabError::CommandInFlight => "TAB_COMMAND_IN_FLIGHT",
+            TabError::NegativeAmount => "TAB_NEGATIVE_AMOUNT",
+            TabError::InvalidCurrency { .. } => "TAB_INVALID_CURRENCY",
+            TabError::InvalidExchangeRate { .. } => "TAB_INVALID_EXCHANGE_RATE",
+            TabError::AmountTooLarge => "TAB_AMOUNT_TOO_LARGE",
+        }
+    }
+
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            TabError::MustPayEnough
+            | TabError::TabIdMismatch { .. }
+            | TabError::CurrencyMismatch { .. }
+            | TabError::ExchangeRateUnavailable { .. }
+            | TabError::GiftCardExceedsBalance { .. }
+            | TabError::GiftCardNotRedeemed
+            | TabError::NegativeAmount
+            | TabError::InvalidCurrency { .. }
+            | TabError::AmountTooLarge => ErrorCategory::Validation,
+            TabError::DrinkNotOutstanding { .. } | TabError::FoodNotOutstanding { .. } => {
+                ErrorCategory::NotFound
+            }
+            TabError::TabIsOpen { .. }
+            | TabError::FoodAlreadyRushed { .. }
+            | TabError::IdempotencyKeyReused
+            | TabError::CommandInFlight => ErrorCategory::Conflict,
+            TabError::CannotCancelServedItem
+            | TabError::TabHasUnservedItems
+            | TabError::TabNotOpened
+            | TabError::FoodNotPrepared { .. } => ErrorCategory::State,
+            TabError::ExchangeRateLookupFailed { .. } | TabError::InvalidExchangeRate { .. } => {
+                ErrorCategory::Internal
+            }
+        }
+    }
+
+    pub fn http_status(&self) -> u16 {
+        self.category().http_status()
+    }
+
+    // The variant's fields, e.g. `{"menu_number": 2}`, or None for unit variants.
+    fn details(&self) -> Option<Value> {
+        match serde_json::to_value(self).ok()? {
+            Value::Object(variant) => variant.into_iter().next().map(|(_, fields)| fields),
+            _ => None,
+        }
+    }
+}
+
+impl From<&TabError> for ErrorBody {
+    fn from(error: &TabError) -> Self {
+        let category = error.category();
+        Self {
+            code: error.code().to_string(),
+            category,
+            status: category.http_status(),
+            message: error.to_string(),
+            details: error.details(),
+        }
+    }
+}
+
+impl From<&AggregateError<TabError>> for ErrorBody {
+    fn from(error: &AggregateError<TabError>) -> Self {
+        let (code, category) = match error {
+            AggregateError::UserError(e) => return e.into(),
+            AggregateError::AggregateConflict => ("CONCURRENCY_CONFLICT", ErrorCategory::Conflict),
+            _ => ("INTERNAL_ERROR", ErrorCategory::Internal),
+        };
+
+        Self {
+            code: code.to_string(),
+            category,
+            status: category.http_status(),
+            message: error.to_string(),
+            details: None,
+        }
+    }
 }
 
 impl std::error::Error for TabError {}
@@ -31,6 +211,39 @@ impl std::fmt::Display for TabError {
             TabError::FoodNotPrepared { menu_number } => {
                 format!("food has not been prepared: menu number {menu_number}")
             }
+            TabError::FoodAlreadyRushed { menu_number } => {
+                format!("food has already been rushed: menu number {menu_number}")
+            }
+            TabError::TabIdMismatch { expected, actual } => {
+                format!("command for tab {actual} sent to tab {expected}")
+            }
+            TabError::CurrencyMismatch { expected, actual } => {
+                format!("amount in {actual} where {expected} was expected")
+            }
+            TabError::ExchangeRateUnavailable { from, to } => {
+                format!("no exchange rate from {from} to {to}")
+            }
+            TabError::GiftCardExceedsBalance { outstanding } => {
+                format!("gift card payment exceeds the outstanding {outstanding}")
+            }
+            TabError::IdempotencyKeyReused => {
+                String::from("idempotency key was already used for another command")
+            }
+            TabError::ExchangeRateLookupFailed { from, to } => {
+                format!("could not load the exchange rate from {from} to {to}")
+            }
+            TabError::GiftCardNotRedeemed => {
+                String::from("gift card payment was not redeemed from the card")
+            }
+            TabError::CommandInFlight => {
+                String::from("a command with this idempotency key is still running")
+            }
+            TabError::NegativeAmount => String::from("amount must not be negative"),
+            TabError::InvalidCurrency { code } => format!("invalid currency code: {code}"),
+            TabError::InvalidExchangeRate { rate } => {
+                format!("exchange rate must be positive: {rate}")
+            }
+            TabError::AmountTooLarge => String::from("amount is too large"),
         };
 
         write!(f, "tab error: {msg}")
@@ -39,9 +252,121 @@ impl std::fmt::Display for TabError {
 
 #[cfg(test)]
 pub mod tests {
-    use crate::domain::tab::tab_id::TabId;
+    use std::collections::HashSet;
+
+    use cqrs_es::AggregateError;
+    use rust_decimal::Decimal;
+    use serde_json::json;
+
+    use crate::{
+        domain::tab::tab_id::TabId,
+        shared_kernel::money::{Currency, Money},
+    };
+
+    use super::{ErrorBody, ErrorCategory, TabError};
+
+    fn all_errors() -> Vec<TabError> {
+        vec![
+            TabError::CannotCancelServedItem,
+            TabError::TabHasUnservedItems,
+            TabError::MustPayEnough,
+            TabError::TabNotOpened,
+            TabError::DrinkNotOutstanding { menu_number: 1 },
+            TabError::TabIsOpen {
+                id: TabId::default(),
+            },
+            TabError::FoodNotOutstanding { menu_number: 1 },
+            TabError::FoodNotPrepared { menu_number: 1 },
+            TabError::FoodAlreadyRushed { menu_number: 1 },
+            TabError::TabIdMismatch {
+                expected: TabId::default(),
+                actual: TabId::default(),
+            },
+            TabError::CurrencyMismatch {
+                expected: Currency::USD,
+                actual: Currency::EUR,
+            },
+            TabError::ExchangeRateUnavailable {
+                from: Currency::EUR,
+                to: Currency::USD,
+            },
+            TabError::GiftCardExceedsBalance {
+                outstanding: Money::zero(Currency::USD),
+            },
+            TabError::IdempotencyKeyReused,
+            TabError::ExchangeRateLookupFailed {
+                from: Currency::EUR,
+                to: Currency::USD,
+            },
+            TabError::GiftCardNotRedeemed,
+            TabError::CommandInFlight,
+            TabError::NegativeAmount,
+            TabError::InvalidCurrency {
+                code: String::from("usd"),
+            },
+            TabError::InvalidExchangeRate {
+                rate: Decimal::ZERO,
+            },
+            TabError::AmountTooLarge,
+        ]
+    }
+
+    #[test]
+    fn every_error_has_a_unique_code() {
+        let errors = all_errors();
+
+        let codes: HashSet<_> = errors.iter().map(TabError::code).collect();
+
+        assert_eq!(codes.len(), errors.len());
+    }
+
+    #[test]
+    fn error_categories_map_to_http_status() {
+        assert_eq!(TabError::MustPayEnough.http_status(), 400);
+        assert_eq!(
+            TabError::FoodNotOutstanding { menu_number: 1 }.http_status(),
+            404
+        );
+        assert_eq!(
+            TabError::TabIsOpen {
+                id: TabId::default()
+            }
+            .http_status(),
+            409
+        );
+        assert_eq!(TabError::TabNotOpened.http_status(), 422);
+    }
+
+    #[test]
+    fn error_body_serializes_code_category_and_details() {
+        let body = ErrorBody::from(&TabError::DrinkNotOutstanding { menu_number: 7 });
+
+        let actual = serde_json::to_value(&body).unwrap();
+
+        assert_eq!(
+            actual,
+            json!({
+                "code": "TAB_DRINK_NOT_OUTSTANDING",
+                "category": "not_found",
+                "status": 404,
+                "message": "tab error: drink is not outstanding: menu number 7",
+                "details": { "menu_number": 7 }
+            })
+        );
+        assert_eq!(serde_json::from_value::<ErrorBody>(actual).unwrap(), body);
+    }
 
-    use super::TabError;
+    #[test]
+    fn aggregate_errors_map_to_error_bodies() {
+        let rejected = ErrorBody::from(&AggregateError::UserError(TabError::TabNotOpened));
+        let conflict = ErrorBody::from(&AggregateError::<TabError>::AggregateConflict);
+
+        assert_eq!(rejected.code, "TAB_NOT_OPEN");
+        assert_eq!(rejected.details, None);
+        assert_eq!(conflict.code, "CONCURRENCY_CONFLICT");
+        assert_eq!(conflict.category, ErrorCategory::Conflict);
+        assert_eq!(conflict.status, 409);
+    }
 
     #[test]
     fn error_to_string() {
@@ -74,5 +399,15 @@ pub mod tests {
             format!("{}", TabError::FoodNotPrepared { menu_number: 1 }),
             "tab error: food has not been prepared: menu number 1"
         );
+        assert_eq!(
+            format!(
+                "{}",
+                TabError::TabIdMismatch {
+                    expected: TabId::default(),
+                    actual: TabId::default()
+                }
+            ),
+            "tab error: command for tab 00000000-0000-0000-0000-000000000000 sent to tab 00000000-0000-0000-0000-000000000000"
+        );
     }
 }
@@ -1,16 +1,47 @@
 #![allow(unused_variables)]
+use chrono::{DateTime, Utc};
 use cqrs_es::DomainEvent;
-use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
-use super::{tab_id::TabId, waiter_id::WaiterId};
+use crate::{
+    domain::gift_card::redemption_id::RedemptionId,
+    shared_kernel::{exchange_rates::ExchangeRate, money::Money},
+};
+
+use super::{order_priority::OrderPriority, tab_id::TabId, waiter_id::WaiterId};
 
 #[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
 pub struct MenuItem {
     pub menu_number: usize,
     pub description: String,
-    pub price: Decimal,
+    pub price: Money,
     pub quantity: usize,
+    #[serde(default)]
+    pub priority: OrderPriority,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub ordered_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct GiftCardPayment {
+    pub code: String,
+    pub amount: Money,
+    #[serde(default)]
+    pub redemption: RedemptionId,
+}
+
+// `items` are the menu numbers and quantities the promotion priced, and
+// `units` the menu number and order time of each of those units.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct AppliedPromotion {
+    pub promotion_id: String,
+    pub name: String,
+    pub items: Vec<(usize, usize)>,
+    #[serde(default)]
+    pub units: Vec<(usize, DateTime<Utc>)>,
+    pub discount: Money,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -32,6 +63,10 @@ pub enum TabEvent {
         id: TabId,
         menu_number: usize,
     },
+    FoodOrderRushed {
+        id: TabId,
+        menu_number: usize,
+    },
     FoodPrepared {
         id: TabId,
         menu_number: usize,
@@ -40,11 +75,21 @@ pub enum TabEvent {
         id: TabId,
         menu_number: usize,
     },
+    GiftCardPaymentReceived {
+        id: TabId,
+        payment: GiftCardPayment,
+    },
+    PromotionApplied {
+        id: TabId,
+        promotion: AppliedPromotion,
+    },
     TabClosed {
         id: TabId,
-        amount_paid: Decimal,
-        order_value: Decimal,
-        tip_value: Decimal,
+        amount_paid: Money,
+        order_value: Money,
+        tip_value: Money,
+        amount_tendered: Money,
+        exchange_rate: Option<ExchangeRate>,
     },
 }
 
@@ -55,14 +100,28 @@ impl DomainEvent for TabEvent {
             TabEvent::FoodOrderPlaced { .. } => "FoodOrderPlaced".into(),
             TabEvent::DrinkOrderPlaced { .. } => "DrinkOrderPlaced".into(),
             TabEvent::DrinkServed { .. } => "DrinkServed".into(),
+            TabEvent::FoodOrderRushed { .. } => "FoodOrderRushed".into(),
             TabEvent::FoodPrepared { .. } => "FoodPrepared".into(),
             TabEvent::FoodServed { .. } => "FoodServed".into(),
+            TabEvent::GiftCardPaymentReceived { .. } => "GiftCardPaymentReceived".into(),
+            TabEvent::PromotionApplied { .. } => "PromotionApplied".into(),
             TabEvent::TabClosed { .. } => "TabClosed".into(),
         }
     }
 
     fn event_version(&self) -> String {
-        "1.0".into()
+        match self {
+            TabEvent::TabOpened { .. } => "1.0.0".into(),
+            TabEvent::FoodOrderPlaced { .. } => "1.3.0".into(),
+            TabEvent::DrinkOrderPlaced { .. } => "1.3.0".into(),
+            TabEvent::DrinkServed { .. } => "1.0.0".into(),
+            TabEvent::FoodOrderRushed { .. } => "1.0.0".into(),
+            TabEvent::FoodPrepared { .. } => "1.0.0".into(),
+            TabEvent::FoodServed { .. } => "1.0.0".into(),
+            TabEvent::GiftCardPaymentReceived { .. } => "1.1.0".into(),
+            TabEvent::PromotionApplied { .. } => "1.1.0".into(),
+            TabEvent::TabClosed { .. } => "1.2.0".into(),
+        }
     }
 }
 
@@ -71,9 +130,12 @@ mod tests {
     use cqrs_es::DomainEvent;
     use rust_decimal::Decimal;
 
-    use crate::domain::tab::{tab_id::TabId, waiter_id::WaiterId};
+    use crate::{
+        domain::tab::{order_priority::OrderPriority, tab_id::TabId, waiter_id::WaiterId},
+        shared_kernel::fixtures::usd,
+    };
 
-    use super::{MenuItem, TabEvent};
+    use super::{AppliedPromotion, GiftCardPayment, MenuItem, TabEvent};
 
     #[test]
     #[allow(non_snake_case)]
@@ -83,8 +145,11 @@ mod tests {
         let menu_item = MenuItem {
             menu_number: 1,
             description: "MenuItem".into(),
-            price: Decimal::ZERO,
+            price: usd(Decimal::ZERO),
             quantity: 0,
+            priority: OrderPriority::Normal,
+            notes: None,
+            ordered_at: None,
         };
         let event1 = TabEvent::DrinkOrderPlaced {
             id,
@@ -104,9 +169,30 @@ mod tests {
         let event6 = TabEvent::FoodServed { id, menu_number: 1 };
         let event7 = TabEvent::TabClosed {
             id,
-            amount_paid: Decimal::ZERO,
-            order_value: Decimal::ZERO,
-            tip_value: Decimal::ZERO,
+            amount_paid: usd(Decimal::ZERO),
+            order_value: usd(Decimal::ZERO),
+            tip_value: usd(Decimal::ZERO),
+            amount_tendered: usd(Decimal::ZERO),
+            exchange_rate: None,
+        };
+        let event8 = TabEvent::FoodOrderRushed { id, menu_number: 1 };
+        let event9 = TabEvent::GiftCardPaymentReceived {
+            id,
+            payment: GiftCardPayment {
+                code: "GC-1001".into(),
+                amount: usd(Decimal::ZERO),
+                redemption: Default::default(),
+            },
+        };
+        let event10 = TabEvent::PromotionApplied {
+            id,
+            promotion: AppliedPromotion {
+                promotion_id: "happy-hour".into(),
+                name: "Happy hour".into(),
+                items: vec![(1, 1)],
+                units: vec![],
+                discount: usd(Decimal::ZERO),
+            },
         };
 
         assert_eq!(event1.event_type(), format!("DrinkOrderPlaced"),);
@@ -116,17 +202,23 @@ mod tests {
         assert_eq!(event5.event_type(), format!("FoodPrepared"),);
         assert_eq!(event6.event_type(), format!("FoodServed"),);
         assert_eq!(event7.event_type(), format!("TabClosed"),);
+        assert_eq!(event8.event_type(), format!("FoodOrderRushed"),);
+        assert_eq!(event9.event_type(), format!("GiftCardPaymentReceived"),);
+        assert_eq!(event10.event_type(), format!("PromotionApplied"),);
     }
 
     #[test]
-    fn event_version_is_1_0() {
+    fn event_version_is_per_variant() {
         let id = TabId::new();
         let waiter_id = WaiterId::new();
         let menu_item = MenuItem {
             menu_number: 1,
             description: "MenuItem".into(),
-            price: Decimal::ZERO,
+            price: usd(Decimal::ZERO),
             quantity: 0,
+            priority: OrderPriority::Normal,
+            notes: None,
+            ordered_at: None,
         };
         let event1 = TabEvent::DrinkOrderPlaced {
             id,
@@ -146,17 +238,42 @@ mod tests {
         let event6 = TabEvent::FoodServed { id, menu_number: 1 };
         let event7 = TabEvent::TabClosed {
             id,
-            amount_paid: Decimal::from(0),
-            order_value: Decimal::from(0),
-            tip_value: Decimal::from(0),
+            amount_paid: usd(Decimal::from(0)),
+            order_value: usd(Decimal::from(0)),
+            tip_value: usd(Decimal::from(0)),
+            amount_tendered: usd(Decimal::from(0)),
+            exchange_rate: None,
+        };
+
+        let event8 = TabEvent::FoodOrderRushed { id, menu_number: 1 };
+        let event9 = TabEvent::GiftCardPaymentReceived {
+            id,
+            payment: GiftCardPayment {
+                code: "GC-1001".into(),
+                amount: usd(Decimal::ZERO),
+                redemption: Default::default(),
+            },
+        };
+        let event10 = TabEvent::PromotionApplied {
+            id,
+            promotion: AppliedPromotion {
+                promotion_id: "happy-hour".into(),
+                name: "Happy hour".into(),
+                items: vec![(1, 1)],
+                units: vec![],
+                discount: usd(Decimal::ZERO),
+            },
         };
 
-        assert_eq!(event1.event_version(), String::from("1.0"));
-        assert_eq!(event2.event_version(), event2.event_version(),);
-        assert_eq!(event3.event_version(), event3.event_version(),);
-        assert_eq!(event4.event_version(), event4.event_version(),);
-        assert_eq!(event5.event_version(), event4.event_version(),);
-        assert_eq!(event6.event_version(), event5.event_version(),);
-        assert_eq!(event7.event_version(), event6.event_version(),);
+        assert_eq!(event1.event_version(), String::from("1.3.0"));
+        assert_eq!(event2.event_version(), String::from("1.0.0"));
+        assert_eq!(event3.event_version(), String::from("1.3.0"));
+        assert_eq!(event4.event_version(), String::from("1.0.0"));
+        assert_eq!(event5.event_version(), String::from("1.0.0"));
+        assert_eq!(event6.event_version(), String::from("1.0.0"));
+        assert_eq!(event7.event_version(), String::from("1.2.0"));
+        assert_eq!(event8.event_version(), String::from("1.0.0"));
+        assert_eq!(event9.event_version(), String::from("1.1.0"));
+        assert_eq!(event10.event_version(), String::from("1.1.0"));
     }
 }
@@ -2,6 +2,8 @@ pub mod aggregate;
 pub mod command;
 pub mod error;
 pub mod event;
+pub mod order_priority;
+pub mod promotions;
 pub mod queries;
 pub mod services;
 pub mod tab_id;
@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    Copy, Clone, Debug, Default, Hash, Eq, PartialEq, PartialOrd, Ord, Serialize, Deserialize,
+)]
+pub enum OrderPriority {
+    #[default]
+    Normal,
+    High,
+    Rush,
+}
+
+impl std::fmt::Display for OrderPriority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let flag = match self {
+            OrderPriority::Normal => "NORMAL",
+            OrderPriority::High => "PRIORITY",
+            OrderPriority::Rush => "RUSH",
+        };
+
+        write!(f, "{flag}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OrderPriority;
+
+    #[test]
+    fn rush_is_more_urgent_than_high_and_high_more_than_normal() {
+        assert!(OrderPriority::Rush > OrderPriority::High);
+        assert!(OrderPriority::High > OrderPriority::Normal);
+        assert_eq!(OrderPriority::default(), OrderPriority::Normal);
+    }
+
+    #[test]
+    fn every_priority_displays_its_name() {
+        assert_eq!(OrderPriority::Normal.to_string(), "NORMAL");
+        assert_eq!(OrderPriority::High.to_string(), "PRIORITY");
+        assert_eq!(OrderPriority::Rush.to_string(), "RUSH");
+    }
+}
@@ -0,0 +1,469 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDateTime, NaiveTime, Utc, Weekday};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::shared_kernel::money::{Money, Rounding};
+
+use super::event::AppliedPromotion;
+
+// The promotions on offer, loaded from configuration. Promotions are tried in
+// the order they are listed and never stack: an item discounted by one
+// promotion is not offered to the next.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Promotions {
+    // Schedules are in the cafe's local time, this many minutes ahead of UTC.
+    #[serde(default)]
+    utc_offset_minutes: i32,
+    #[serde(default)]
+    promotions: Vec<Promotion>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Promotion {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub schedule: Option<Schedule>,
+    pub rule: PromotionRule,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PromotionRule {
+    // Priced as the items are ordered, e.g. happy hour. No menu numbers means
+    // the whole menu.
+    PercentOff {
+        #[serde(default)]
+        menu_numbers: Vec<usize>,
+        percent: Decimal,
+    },
+    // Priced when the tab is closed: of every `buy` + `free` items, the
+    // cheapest `free` are on the house.
+    BuyGetFree {
+        menu_numbers: Vec<usize>,
+        buy: usize,
+        free: usize,
+    },
+    // Priced when the tab is closed: one of each menu number for `price`.
+    Bundle {
+        menu_numbers: Vec<usize>,
+        price: Decimal,
+    },
+}
+
+// Windows ending before they start run past midnight. No days means every day.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Schedule {
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    pub from: NaiveTime,
+    pub until: NaiveTime,
+}
+
+// A menu number, its price and when it was ordered.
+pub type Unit = (usize, Money, DateTime<Utc>);
+
+#[derive(Debug)]
+pub enum PromotionsError {
+    Parse(serde_json::Error),
+    Invalid { id: String, reason: String },
+}
+
+impl Promotions {
+    pub fn new(
+        utc_offset_minutes: i32,
+        promotions: Vec<Promotion>,
+    ) -> Result<Self, PromotionsError> {
+        let promotions = Self {
+            utc_offset_minutes,
+            promotions,
+        };
+        promotions.validate()?;
+
+        Ok(promotions)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, PromotionsError> {
+        let promotions: Self = serde_json::from_str(json).map_err(PromotionsError::Parse)?;
+        promotions.validate()?;
+
+        Ok(promotions)
+    }
+
+    pub fn promotions(&self) -> &[Promotion] {
+        &self.promotions
+    }
+
+    // Promotions priced as `units` are ordered.
+    pub fn on_order(&self, units: &[Unit], rounding: Rounding) -> Vec<AppliedPromotion> {
+        self.evaluate(units, rounding, |rule| {
+            matches!(rule, PromotionRule::PercentOff { .. })
+        })
+    }
+
+    // Promotions priced over the `units` not yet discounted when the tab closes.
+    // A scheduled promotion only covers units ordered while it was running.
+    pub fn on_close(&self, units: &[Unit], rounding: Rounding) -> Vec<AppliedPromotion> {
+        self.evaluate(units, rounding, |rule| {
+            !matches!(rule, PromotionRule::PercentOff { .. })
+        })
+    }
+
+    fn evaluate(
+        &self,
+        units: &[Unit],
+        rounding: Rounding,
+        priced_now: fn(&PromotionRule) -> bool,
+    ) -> Vec<AppliedPromotion> {
+        let mut remaining = units.to_vec();
+        self.promotions
+            .iter()
+            .filter(|p| priced_now(&p.rule))
+            .filter_map(|p| {
+                let (mut active, inactive): (Vec<Unit>, Vec<Unit>) = remaining
+                    .drain(..)
+                    .partition(|(_, _, at)| p.is_active(self.local(*at)));
+                let applied = p.apply(&mut active, rounding);
+                remaining = inactive;
+                remaining.extend(active);
+                applied
+            })
+            .collect()
+    }
+
+    fn local(&self, at: DateTime<Utc>) -> NaiveDateTime {
+        at.with_timezone(&self.utc_offset()).naive_local()
+    }
+
+    fn utc_offset(&self) -> FixedOffset {
+        FixedOffset::east_opt(self.utc_offset_minutes * 60)
+            .unwrap_or(FixedOffset::east_opt(0).expect("UTC is a valid offset"))
+    }
+
+    fn validate(&self) -> Result<(), PromotionsError> {
+        let invalid = |id: &str, reason: &str| PromotionsError::Invalid {
+            id: id.to_string(),
+            reason: reason.to_string(),
+        };
+        if FixedOffset::east_opt(self.utc_offset_minutes * 60).is_none() {
+            return Err(invalid("", "UTC offset must be less than a day"));
+        }
+        let mut ids = HashSet::new();
+        for promotion in self.promotions.iter() {
+            let id = promotion.id.as_str();
+            if !ids.insert(id) {
+                return Err(invalid(id, "id is used more than once"));
+            }
+            match &promotion.rule {
+                PromotionRule::PercentOff { percent, .. }
+                    if *percent <= Decimal::ZERO || *percent > Decimal::ONE_HUNDRED =>
+                {
+                    return Err(invalid(id, "percent must be above 0 and at most 100"));
+                }
+                PromotionRule::BuyGetFree {
+                    menu_numbers,
+                    buy,
+                    free,
+                } if menu_numbers.is_empty() || *buy == 0 || *free == 0 => {
+                    return Err(invalid(id, "needs menu numbers and non-zero buy and free"));
+                }
+                PromotionRule::Bundle {
+                    menu_numbers,
+                    price,
+                } if menu_numbers.len() < 2 || price.is_sign_negative() => {
+                    return Err(invalid(id, "needs two or more menu numbers and a price"));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Promotion {
+    fn is_active(&self, local: NaiveDateTime) -> bool {
+        self.schedule.as_ref().is_none_or(|s| s.contains(local))
+    }
+
+    // Takes the units this promotion covers out of `remaining`.
+    fn apply(&self, remaining: &mut Vec<Unit>, rounding: Rounding) -> Option<AppliedPromotion> {
+        let currency = remaining.first()?.1.currency();
+        let (covered, discount) = match &self.rule {
+            PromotionRule::PercentOff {
+                menu_numbers,
+                percent,
+            } => {
+                let covered = take_all(remaining, |m| {
+                    menu_numbers.is_empty() || menu_numbers.contains(&m)
+                });
+                let total: Decimal = covered.iter().map(|(_, p, _)| p.amount()).sum();
+                (covered, total * percent / Decimal::ONE_HUNDRED)
+            }
+            PromotionRule::BuyGetFree {
+                menu_numbers,
+                buy,
+                free,
+            } => {
+                let mut eligible = take_all(remaining, |m| menu_numbers.contains(&m));
+                eligible.sort_by_key(|(_, price, _)| std::cmp::Reverse(price.amount()));
+                let covered_len = eligible.len() - eligible.len() % (buy + free);
+                remaining.extend(eligible.drain(covered_len..));
+                let discount = eligible
+                    .chunks(buy + free)
+                    .flat_map(|chunk| chunk[*buy..].iter())
+                    .map(|(_, p, _)| p.amount())
+                    .sum();
+                (eligible, discount)
+            }
+            PromotionRule::Bundle {
+                menu_numbers,
+                price,
+            } => {
+                let mut covered = Vec::new();
+                let mut discount = Decimal::ZERO;
+                while let Some(bundle) = take_each(remaining, menu_numbers) {
+                    let total: Decimal = bundle.iter().map(|(_, p, _)| p.amount()).sum();
+                    discount += (total - price).max(Decimal::ZERO);
+                    covered.extend(bundle);
+                }
+                (covered, discount)
+            }
+        };
+        if covered.is_empty() || discount.is_zero() {
+            remaining.extend(covered);
+            return None;
+        }
+
+        Some(AppliedPromotion {
+            promotion_id: self.id.clone(),
+            name: self.name.clone(),
+            items: quantities(&covered),
+            units: covered.iter().map(|(m, _, at)| (*m, *at)).collect(),
+            discount: Money::new(discount, currency)
+                .unwrap_or(Money::zero(currency))
+                .round(rounding),
+        })
+    }
+}
+
+impl Schedule {
+    fn contains(&self, local: NaiveDateTime) -> bool {
+        let (day, time) = (local.weekday(), local.time());
+        let on = |day: Weekday| self.days.is_empty() || self.days.contains(&day);
+        if self.from <= self.until {
+            on(day) && self.from <= time && time < self.until
+        } else {
+            // The early hours belong to the window that opened the day before.
+            (on(day) && self.from <= time) || (on(day.pred()) && time < self.until)
+        }
+    }
+}
+
+impl std::error::Error for PromotionsError {}
+
+impl std::fmt::Display for PromotionsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PromotionsError::Parse(e) => write!(f, "invalid promotions: {e}"),
+            PromotionsError::Invalid { id, reason } => {
+                write!(f, "invalid promotion {id}: {reason}")
+            }
+        }
+    }
+}
+
+fn take_all(units: &mut Vec<Unit>, covers: impl Fn(usize) -> bool) -> Vec<Unit> {
+    let (taken, kept) = units.drain(..).partition(|(m, _, _)| covers(*m));
+    *units = kept;
+
+    taken
+}
+
+// One unit of each menu number, or none at all if any is missing.
+fn take_each(units: &mut Vec<Unit>, menu_numbers: &[usize]) -> Option<Vec<Unit>> {
+    let mut left = units.clone();
+    let mut taken = Vec::new();
+    for menu_number in menu_numbers.iter() {
+        let index = left.iter().position(|(m, _, _)| m == menu_number)?;
+        taken.push(left.remove(index));
+    }
+    *units = left;
+
+    Some(taken)
+}
+
+fn quantities(units: &[Unit]) -> Vec<(usize, usize)> {
+    let mut quantities: Vec<(usize, usize)> = Vec::new();
+    for (menu_number, _, _) in units.iter() {
+        match quantities.iter_mut().find(|(m, _)| m == menu_number) {
+            Some((_, quantity)) => *quantity += 1,
+            None => quantities.push((*menu_number, 1)),
+        }
+    }
+
+    quantities
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeZone, Utc};
+    use rust_decimal::Decimal;
+
+    use crate::shared_kernel::{fixtures::usd, money::Rounding};
+
+    use super::{PromotionRule, Promotions, PromotionsError, Unit};
+
+    const CONFIG: &str = r#"{
+        "utc_offset_minutes": 180,
+        "promotions": [
+            {
+                "id": "happy-hour",
+                "name": "Happy hour",
+                "schedule": { "days": ["Fri", "Sat"], "from": "22:00", "until": "01:00" },
+                "rule": { "type": "percent_off", "menu_numbers": [7, 8], "percent": 50 }
+            },
+            {
+                "id": "wine-bogof",
+                "name": "Wine: buy one get one free",
+                "rule": { "type": "buy_get_free", "menu_numbers": [7], "buy": 1, "free": 1 }
+            },
+            {
+                "id": "lunch-deal",
+                "name": "Soup and sandwich",
+                "rule": { "type": "bundle", "menu_numbers": [3, 4], "price": 10 }
+            }
+        ]
+    }"#;
+
+    fn units(items: &[(usize, i64)], ordered_at: DateTime<Utc>) -> Vec<Unit> {
+        items
+            .iter()
+            .map(|(m, p)| (*m, usd(Decimal::from(*p)), ordered_at))
+            .collect()
+    }
+
+    // 2024-04-19 is a Friday.
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 4, day, hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn given_config_then_promotions_are_loaded_in_order() {
+        let promotions = Promotions::from_json(CONFIG).unwrap();
+
+        let ids: Vec<_> = promotions
+            .promotions()
+            .iter()
+            .map(|p| p.id.as_str())
+            .collect();
+
+        assert_eq!(ids, vec!["happy-hour", "wine-bogof", "lunch-deal"]);
+        assert!(matches!(
+            promotions.promotions()[1].rule,
+            PromotionRule::BuyGetFree {
+                buy: 1,
+                free: 1,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn given_shipped_example_config_then_it_is_valid() {
+        let promotions = Promotions::from_json(include_str!("../../../config/promotions.json"));
+
+        assert!(promotions.is_ok());
+    }
+
+    #[test]
+    fn given_invalid_promotion_then_loading_fails() {
+        let config = r#"{ "promotions": [{ "id": "free-for-all", "name": "Free", "rule": { "type": "percent_off", "percent": 150 } }] }"#;
+
+        let actual = Promotions::from_json(config);
+
+        assert!(matches!(actual, Err(PromotionsError::Invalid { id, .. }) if id == "free-for-all"));
+    }
+
+    #[test]
+    fn given_happy_hour_then_it_applies_in_local_time_including_after_midnight() {
+        let promotions = Promotions::from_json(CONFIG).unwrap();
+        let ordered = |at| units(&[(7, 8), (1, 5)], at);
+
+        // 19:00 UTC is 22:00 on Friday locally, 21:00 UTC is after midnight on Saturday.
+        let friday_night = promotions.on_order(&ordered(at(19, 19)), Rounding::HalfUp);
+        let small_hours = promotions.on_order(&ordered(at(19, 21)), Rounding::HalfUp);
+        let thursday_night = promotions.on_order(&ordered(at(18, 19)), Rounding::HalfUp);
+        let friday_afternoon = promotions.on_order(&ordered(at(19, 12)), Rounding::HalfUp);
+
+        assert_eq!(friday_night.len(), 1);
+        assert_eq!(friday_night[0].items, vec![(7, 1)]);
+        assert_eq!(friday_night[0].units, vec![(7, at(19, 19))]);
+        assert_eq!(friday_night[0].discount, usd(Decimal::from(4)));
+        assert_eq!(small_hours.len(), 1);
+        assert_eq!(small_hours[0].items, friday_night[0].items);
+        assert_eq!(small_hours[0].discount, friday_night[0].discount);
+        assert!(thursday_night.is_empty());
+        assert!(friday_afternoon.is_empty());
+    }
+
+    #[test]
+    fn given_buy_one_get_one_free_then_the_cheaper_of_each_pair_is_free() {
+        let promotions = Promotions::from_json(CONFIG).unwrap();
+
+        let actual = promotions.on_close(
+            &units(&[(7, 8), (7, 6), (7, 8)], at(17, 12)),
+            Rounding::HalfUp,
+        );
+
+        assert_eq!(actual.len(), 1);
+        assert_eq!(actual[0].promotion_id, "wine-bogof");
+        assert_eq!(actual[0].items, vec![(7, 2)]);
+        assert_eq!(actual[0].discount, usd(Decimal::from(8)));
+    }
+
+    #[test]
+    fn given_bundle_then_each_complete_set_is_priced_as_a_bundle() {
+        let promotions = Promotions::from_json(CONFIG).unwrap();
+
+        let actual = promotions.on_close(
+            &units(&[(3, 6), (4, 7), (3, 6), (1, 5)], at(17, 12)),
+            Rounding::HalfUp,
+        );
+
+        assert_eq!(actual.len(), 1);
+        assert_eq!(actual[0].items, vec![(3, 1), (4, 1)]);
+        assert_eq!(actual[0].discount, usd(Decimal::from(3)));
+    }
+
+    #[test]
+    fn given_no_promotions_then_nothing_is_applied() {
+        let promotions = Promotions::default();
+
+        let actual = promotions.on_close(&units(&[(7, 8), (7, 8)], at(19, 19)), Rounding::HalfUp);
+
+        assert!(actual.is_empty());
+    }
+
+    #[test]
+    fn given_scheduled_close_promotion_then_it_covers_units_ordered_while_it_ran() {
+        let promotions = Promotions::from_json(
+            r#"{ "promotions": [{
+                "id": "coffee-bogof", "name": "Morning coffee: buy one get one free",
+                "schedule": { "from": "07:00", "until": "10:00" },
+                "rule": { "type": "buy_get_free", "menu_numbers": [2], "buy": 1, "free": 1 }
+            }] }"#,
+        )
+        .unwrap();
+        let mut ordered = units(&[(2, 4), (2, 4)], at(17, 8));
+        ordered.extend(units(&[(2, 4), (2, 4)], at(17, 11)));
+
+        let actual = promotions.on_close(&ordered, Rounding::HalfUp);
+
+        assert_eq!(actual.len(), 1);
+        assert_eq!(actual[0].items, vec![(2, 2)]);
+        assert_eq!(actual[0].discount, usd(Decimal::from(4)));
+    }
+}
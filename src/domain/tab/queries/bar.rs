@@ -0,0 +1,190 @@
+use cqrs_es::View;
+use serde::{Deserialize, Serialize};
+
+use crate::domain::tab::{aggregate::Tab, command::TabCommand, event::TabEvent, tab_id::TabId};
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct BarTodoList {
+    tab_id: TabId,
+    table: usize,
+    open: bool,
+    drinks: Vec<BarTodoItem>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct BarTodoItem {
+    menu_number: usize,
+    description: String,
+}
+
+impl BarTodoList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn tab_id(&self) -> TabId {
+        self.tab_id
+    }
+
+    pub fn table(&self) -> usize {
+        self.table
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn drinks(&self) -> Vec<BarTodoItem> {
+        self.drinks.clone()
+    }
+
+    pub fn outstanding(lists: impl IntoIterator<Item = Self>) -> Vec<Self> {
+        let mut result: Vec<Self> = lists
+            .into_iter()
+            .filter(|l| l.is_open() && !l.is_empty())
+            .collect();
+        result.sort_by_key(|l| l.table());
+
+        result
+    }
+
+    pub fn bump(&self, menu_number: usize) -> TabCommand {
+        TabCommand::MarkDrinksServed {
+            id: self.tab_id,
+            items: vec![(menu_number, 1)],
+        }
+    }
+}
+
+impl BarTodoItem {
+    pub fn menu_number(&self) -> usize {
+        self.menu_number
+    }
+
+    pub fn description(&self) -> String {
+        self.description.clone()
+    }
+}
+
+impl std::ops::Deref for BarTodoList {
+    type Target = Vec<BarTodoItem>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.drinks
+    }
+}
+
+impl View<Tab> for BarTodoList {
+    fn update(&mut self, event: &cqrs_es::EventEnvelope<Tab>) {
+        match &event.payload {
+            TabEvent::TabOpened { id, table, .. } => {
+                self.tab_id = *id;
+                self.table = *table;
+                self.open = true;
+                self.drinks.clear();
+            }
+            TabEvent::DrinkOrderPlaced { menu_item, .. } => self.drinks.push(BarTodoItem {
+                menu_number: menu_item.menu_number,
+                description: menu_item.description.clone(),
+            }),
+            TabEvent::DrinkServed { menu_number, .. } => {
+                if let Some(pos) = self
+                    .drinks
+                    .iter()
+                    .position(|d| d.menu_number == *menu_number)
+                {
+                    self.drinks.remove(pos);
+                }
+            }
+            TabEvent::TabClosed { .. } => {
+                self.open = false;
+                self.drinks.clear();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cqrs_es::View;
+
+    use crate::domain::tab::{
+        command::TabCommand,
+        event::TabEvent,
+        queries::fixtures::{drink_ordered, envelope, menu_item},
+        tab_id::TabId,
+        waiter_id::WaiterId,
+    };
+
+    use super::BarTodoList;
+
+    fn opened_list(tab_id: TabId) -> BarTodoList {
+        let mut list = BarTodoList::new();
+        list.update(&envelope(
+            tab_id,
+            1,
+            TabEvent::TabOpened {
+                id: tab_id,
+                waiter_id: WaiterId::new(),
+                table: 7,
+            },
+        ));
+
+        list
+    }
+
+    #[test]
+    fn given_open_tab_when_drink_ordered_then_bar_list_shows_drink_with_table() {
+        let tab_id = TabId::new();
+        let mut list = opened_list(tab_id);
+
+        list.update(&envelope(
+            tab_id,
+            2,
+            drink_ordered(tab_id, menu_item(2, "Coca-Cola", 3)),
+        ));
+
+        assert!(list.is_open());
+        assert_eq!(list.table(), 7);
+        assert_eq!(list.len(), 1);
+        assert_eq!(list.drinks()[0].menu_number(), 2);
+        assert_eq!(list.drinks()[0].description(), "Coca-Cola");
+    }
+
+    #[test]
+    fn given_ordered_drink_when_served_then_bar_list_is_empty() {
+        let tab_id = TabId::new();
+        let mut list = opened_list(tab_id);
+        list.update(&envelope(
+            tab_id,
+            2,
+            drink_ordered(tab_id, menu_item(2, "Coca-Cola", 3)),
+        ));
+
+        list.update(&envelope(
+            tab_id,
+            3,
+            TabEvent::DrinkServed {
+                id: tab_id,
+                menu_number: 2,
+            },
+        ));
+
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn bump_maps_to_mark_drinks_served_command() {
+        let tab_id = TabId::new();
+        let list = opened_list(tab_id);
+
+        match list.bump(2) {
+            TabCommand::MarkDrinksServed { id, items } => {
+                assert_eq!(id, tab_id);
+                assert_eq!(items, vec![(2, 1)]);
+            }
+            other => panic!("expected MarkDrinksServed command, got {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+
+use cqrs_es::EventEnvelope;
+use rust_decimal::Decimal;
+
+use crate::{
+    domain::tab::{
+        aggregate::Tab,
+        event::{MenuItem, TabEvent},
+        order_priority::OrderPriority,
+        tab_id::TabId,
+    },
+    shared_kernel::fixtures::usd,
+};
+
+pub fn envelope(tab_id: TabId, sequence: usize, payload: TabEvent) -> EventEnvelope<Tab> {
+    EventEnvelope {
+        aggregate_id: tab_id.to_string(),
+        sequence,
+        payload,
+        metadata: HashMap::new(),
+    }
+}
+
+pub fn menu_item(menu_number: usize, description: &str, price: i64) -> MenuItem {
+    MenuItem {
+        menu_number,
+        description: description.into(),
+        price: usd(Decimal::from(price)),
+        quantity: 1,
+        priority: OrderPriority::Normal,
+        notes: None,
+        ordered_at: None,
+    }
+}
+
+pub fn food_ordered(tab_id: TabId, menu_item: MenuItem) -> TabEvent {
+    TabEvent::FoodOrderPlaced {
+        id: tab_id,
+        menu_item,
+    }
+}
+
+pub fn drink_ordered(tab_id: TabId, menu_item: MenuItem) -> TabEvent {
+    TabEvent::DrinkOrderPlaced {
+        id: tab_id,
+        menu_item,
+    }
+}
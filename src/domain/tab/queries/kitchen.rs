@@ -1,15 +1,18 @@
+use std::cmp::Ordering;
+
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use cqrs_es::View;
 use serde::{Deserialize, Serialize};
 
-use crate::domain::tab::{aggregate::Tab, tab_id::TabId};
+use crate::domain::tab::{aggregate::Tab, order_priority::OrderPriority, tab_id::TabId};
 
 #[async_trait]
 pub trait KitchenTodoListQuery: Sized {
     async fn get_kitchen_todo_list(&self) -> Vec<TodoListGroup>;
 }
 
-#[derive(Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct KitchenTodoList {
     inner: Vec<TodoListGroup>,
 }
@@ -24,12 +27,45 @@ pub struct TodoListGroup {
 pub struct TodoListItem {
     pub menu_number: usize,
     pub description: String,
+    #[serde(default)]
+    pub priority: OrderPriority,
+    #[serde(default)]
+    pub ordered_sequence: usize,
+    #[serde(default)]
+    pub ordered_at: Option<DateTime<Utc>>,
 }
 
 impl KitchenTodoList {
     pub fn new() -> Self {
         Self { inner: Vec::new() }
     }
+
+    // Every tab's outstanding food, most urgent first and then oldest first.
+    pub fn queue(lists: impl IntoIterator<Item = Self>) -> Vec<(TabId, TodoListItem)> {
+        let mut result: Vec<(TabId, TodoListItem)> = lists
+            .into_iter()
+            .flat_map(|list| list.inner)
+            .flat_map(|group| {
+                let tab_id = group.tab_id;
+                group.food_items.into_iter().map(move |item| (tab_id, item))
+            })
+            .collect();
+        result.sort_by(|(_, a), (_, b)| a.cmp_urgency(b));
+
+        result
+    }
+}
+
+impl KitchenTodoList {
+    fn group_mut(&mut self, tab_id: TabId) -> Option<&mut TodoListGroup> {
+        self.inner.iter_mut().find(|g| g.tab_id == tab_id)
+    }
+
+    fn sort(&mut self) {
+        for group in self.inner.iter_mut() {
+            group.sort();
+        }
+    }
 }
 
 impl TodoListGroup {
@@ -37,6 +73,18 @@ impl TodoListGroup {
         self.tab_id
     }
 
+    pub fn priority(&self) -> OrderPriority {
+        self.food_items
+            .iter()
+            .map(|i| i.priority)
+            .max()
+            .unwrap_or_default()
+    }
+
+    fn sort(&mut self) {
+        self.food_items.sort_by(|a, b| a.cmp_urgency(b));
+    }
+
     pub fn food_items(&self) -> Vec<TodoListItem> {
         let mut result = Vec::new();
         for item in self.food_items.iter() {
@@ -55,6 +103,27 @@ impl TodoListItem {
     pub fn description(&self) -> String {
         self.description.clone()
     }
+
+    pub fn priority(&self) -> OrderPriority {
+        self.priority
+    }
+
+    pub fn is_rushed(&self) -> bool {
+        self.priority == OrderPriority::Rush
+    }
+
+    pub fn ordered_at(&self) -> Option<DateTime<Utc>> {
+        self.ordered_at
+    }
+
+    // The sequence only orders units within a tab, e.g. those of one order.
+    fn cmp_urgency(&self, other: &Self) -> Ordering {
+        other
+            .priority
+            .cmp(&self.priority)
+            .then(self.ordered_at.cmp(&other.ordered_at))
+            .then(self.ordered_sequence.cmp(&other.ordered_sequence))
+    }
 }
 
 // impl View<Tab> for TodoListGroup {
@@ -91,6 +160,9 @@ impl View<Tab> for KitchenTodoList {
                 let tab_item = TodoListItem {
                     menu_number: menu_item.menu_number,
                     description: menu_item.description.clone(),
+                    priority: menu_item.priority,
+                    ordered_sequence: event.sequence,
+                    ordered_at: menu_item.ordered_at,
                 };
                 match todo_group {
                     Some(group) => group.food_items.push(tab_item),
@@ -99,27 +171,39 @@ impl View<Tab> for KitchenTodoList {
                         food_items: vec![tab_item],
                     }),
                 };
+                self.sort();
+            }
+            crate::domain::tab::event::TabEvent::FoodOrderRushed { id, menu_number } => {
+                if let Some(item) = self.group_mut(*id).and_then(|g| {
+                    g.food_items
+                        .iter_mut()
+                        .filter(|f| {
+                            f.menu_number == *menu_number && f.priority != OrderPriority::Rush
+                        })
+                        .min_by_key(|f| f.ordered_sequence)
+                }) {
+                    item.priority = OrderPriority::Rush;
+                }
+                self.sort();
             }
             crate::domain::tab::event::TabEvent::FoodPrepared { id, menu_number } => {
-                let mut remove_tab = false;
-                for tab in self.inner.iter_mut() {
-                    if tab.tab_id == *id {
-                        tab.food_items.remove(
-                            tab.food_items
-                                .iter()
-                                .position(|f| f.menu_number == *menu_number)
-                                .unwrap(),
-                        );
-                        if tab.food_items.is_empty() {
-                            remove_tab = true;
-                        }
-                        break;
+                // The oldest unit is the one that comes off the pass first.
+                if let Some(group) = self.group_mut(*id) {
+                    if let Some(oldest) = group
+                        .food_items
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, f)| f.menu_number == *menu_number)
+                        .min_by_key(|(_, f)| f.ordered_sequence)
+                        .map(|(index, _)| index)
+                    {
+                        group.food_items.remove(oldest);
                     }
                 }
-                if remove_tab {
-                    self.inner
-                        .remove(self.inner.iter().position(|t| t.tab_id == *id).unwrap());
-                }
+                self.inner.retain(|group| !group.food_items.is_empty());
+            }
+            crate::domain::tab::event::TabEvent::TabClosed { id, .. } => {
+                self.inner.retain(|group| group.tab_id != *id);
             }
             _ => {}
         }
@@ -148,10 +232,28 @@ impl std::ops::Deref for KitchenTodoList {
 
 #[cfg(test)]
 mod tests {
-    use crate::domain::tab::queries::kitchen::KitchenTodoListQuery;
+    use chrono::{TimeZone, Utc};
+    use cqrs_es::View;
+
+    use crate::domain::tab::{
+        event::{MenuItem, TabEvent},
+        order_priority::OrderPriority,
+        queries::{
+            fixtures::{envelope, food_ordered, menu_item},
+            kitchen::KitchenTodoListQuery,
+        },
+        tab_id::TabId,
+    };
 
     use super::KitchenTodoList;
 
+    fn dish(menu_number: usize, priority: OrderPriority) -> MenuItem {
+        MenuItem {
+            priority,
+            ..menu_item(menu_number, &format!("Dish {menu_number}"), 10)
+        }
+    }
+
     #[tokio::test]
     #[allow(non_snake_case)]
     async fn given_new_KitchenTodoList_then_it_is_empty() {
@@ -159,4 +261,160 @@ mod tests {
 
         assert!(list.get_kitchen_todo_list().await.is_empty())
     }
+
+    #[test]
+    fn given_orders_with_different_priorities_then_items_sorted_by_priority_then_age() {
+        let tab_id = TabId::new();
+        let mut list = KitchenTodoList::new();
+
+        list.update(&envelope(
+            tab_id,
+            2,
+            food_ordered(tab_id, dish(1, OrderPriority::Normal)),
+        ));
+        list.update(&envelope(
+            tab_id,
+            3,
+            food_ordered(tab_id, dish(2, OrderPriority::Normal)),
+        ));
+        list.update(&envelope(
+            tab_id,
+            4,
+            food_ordered(tab_id, dish(3, OrderPriority::High)),
+        ));
+
+        let items = list[0].food_items();
+        assert_eq!(items[0].menu_number(), 3);
+        assert_eq!(items[0].priority(), OrderPriority::High);
+        assert_eq!(items[1].menu_number(), 1);
+        assert_eq!(items[2].menu_number(), 2);
+    }
+
+    #[test]
+    fn given_ordered_food_when_rushed_then_item_is_flagged_and_moves_to_front() {
+        let tab_id = TabId::new();
+        let mut list = KitchenTodoList::new();
+        list.update(&envelope(
+            tab_id,
+            2,
+            food_ordered(tab_id, dish(1, OrderPriority::High)),
+        ));
+        list.update(&envelope(
+            tab_id,
+            3,
+            food_ordered(tab_id, dish(2, OrderPriority::Normal)),
+        ));
+
+        list.update(&envelope(
+            tab_id,
+            4,
+            TabEvent::FoodOrderRushed {
+                id: tab_id,
+                menu_number: 2,
+            },
+        ));
+
+        let items = list[0].food_items();
+        assert_eq!(items[0].menu_number(), 2);
+        assert!(items[0].is_rushed());
+        assert!(!items[1].is_rushed());
+        assert_eq!(list[0].priority(), OrderPriority::Rush);
+    }
+
+    #[test]
+    fn given_newer_high_priority_unit_when_food_prepared_then_the_oldest_unit_is_removed() {
+        let tab_id = TabId::new();
+        let mut list = KitchenTodoList::new();
+        list.update(&envelope(
+            tab_id,
+            2,
+            food_ordered(tab_id, dish(1, OrderPriority::Normal)),
+        ));
+        list.update(&envelope(
+            tab_id,
+            3,
+            food_ordered(tab_id, dish(1, OrderPriority::High)),
+        ));
+
+        list.update(&envelope(
+            tab_id,
+            4,
+            TabEvent::FoodPrepared {
+                id: tab_id,
+                menu_number: 1,
+            },
+        ));
+
+        let items = list[0].food_items();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].priority(), OrderPriority::High);
+    }
+
+    #[test]
+    fn given_no_matching_item_when_food_prepared_then_list_is_unchanged() {
+        let tab_id = TabId::new();
+        let mut list = KitchenTodoList::new();
+        list.update(&envelope(
+            tab_id,
+            2,
+            food_ordered(tab_id, dish(1, OrderPriority::Normal)),
+        ));
+
+        list.update(&envelope(
+            tab_id,
+            3,
+            TabEvent::FoodPrepared {
+                id: TabId::new(),
+                menu_number: 1,
+            },
+        ));
+        list.update(&envelope(
+            tab_id,
+            4,
+            TabEvent::FoodPrepared {
+                id: tab_id,
+                menu_number: 9,
+            },
+        ));
+
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].food_items().len(), 1);
+    }
+
+    #[test]
+    fn given_interleaved_orders_on_two_tabs_then_queue_is_sorted_by_priority_then_order_time() {
+        let (tab_a, tab_b) = (TabId::new(), TabId::new());
+        let ordered = |menu_number, priority, minute| MenuItem {
+            ordered_at: Some(Utc.with_ymd_and_hms(2024, 4, 19, 12, minute, 0).unwrap()),
+            ..dish(menu_number, priority)
+        };
+        let (mut list_a, mut list_b) = (KitchenTodoList::new(), KitchenTodoList::new());
+
+        list_a.update(&envelope(
+            tab_a,
+            2,
+            food_ordered(tab_a, ordered(1, OrderPriority::Normal, 0)),
+        ));
+        list_b.update(&envelope(
+            tab_b,
+            2,
+            food_ordered(tab_b, ordered(2, OrderPriority::Normal, 5)),
+        ));
+        list_a.update(&envelope(
+            tab_a,
+            3,
+            food_ordered(tab_a, ordered(3, OrderPriority::Normal, 10)),
+        ));
+        list_b.update(&envelope(
+            tab_b,
+            3,
+            food_ordered(tab_b, ordered(4, OrderPriority::High, 15)),
+        ));
+
+        let queue: Vec<_> = KitchenTodoList::queue([list_a, list_b])
+            .into_iter()
+            .map(|(tab_id, item)| (tab_id, item.menu_number()))
+            .collect();
+        assert_eq!(queue, vec![(tab_b, 4), (tab_a, 1), (tab_b, 2), (tab_a, 3)]);
+    }
 }
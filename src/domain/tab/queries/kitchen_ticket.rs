@@ -0,0 +1,591 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use cqrs_es::{
+    persist::{PersistenceError, ViewRepository},
+    EventEnvelope, Query,
+};
+
+use crate::{
+    domain::tab::{
+        aggregate::Tab, event::TabEvent, order_priority::OrderPriority, tab_id::TabId,
+        waiter_id::WaiterId,
+    },
+    infrasctructure::{
+        localization::{args, messages, DEFAULT_LOCALE},
+        printing::{
+            escpos::{Alignment, EscPosBuilder},
+            sink::PrinterSink,
+        },
+    },
+    shared_kernel::command_context::CommandContext,
+};
+
+use super::open_tabs::TabStatus;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct KitchenTicket {
+    pub tab_id: TabId,
+    pub table: usize,
+    pub waiter_id: WaiterId,
+    pub items: Vec<TicketItem>,
+    pub ordered_at: DateTime<Local>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TicketItem {
+    pub menu_number: usize,
+    pub description: String,
+    pub quantity: usize,
+    pub priority: OrderPriority,
+    pub notes: Option<String>,
+}
+
+pub struct KitchenTicketQuery<R, S>
+where
+    R: ViewRepository<TabStatus, Tab>,
+    S: PrinterSink,
+{
+    tabs: Arc<R>,
+    sink: S,
+    locale: String,
+    error_handler: Option<KitchenTicketErrorHandler>,
+}
+
+// Kitchen ticket printing as configured by the caller, for registration with
+// whichever framework or projector applies the tab's events.
+pub struct KitchenPrinter {
+    sink: Arc<dyn PrinterSink>,
+    locale: String,
+    error_handler: Option<KitchenTicketErrorHandler>,
+}
+
+pub type KitchenTicketErrorHandler = Box<dyn Fn(&str, KitchenTicketError) + Send + Sync>;
+
+#[derive(Debug)]
+pub enum KitchenTicketError {
+    TabStatus(PersistenceError),
+    Print(std::io::Error),
+}
+
+impl KitchenTicket {
+    fn new(status: &TabStatus, items: Vec<TicketItem>, ordered_at: DateTime<Local>) -> Self {
+        Self {
+            tab_id: status.tab_id(),
+            table: status.table(),
+            waiter_id: status.waiter_id(),
+            items,
+            ordered_at,
+        }
+    }
+
+    pub fn to_escpos(&self, locale: &str) -> Vec<u8> {
+        let messages = messages();
+        let table = args(&[("table", self.table.into())]);
+        let waiter = args(&[("waiter", self.waiter_id.to_string().into())]);
+        let mut builder = EscPosBuilder::new()
+            .align(Alignment::Center)
+            .double_size(true)
+            .line(&messages.format(locale, "ticket-table", Some(&table)))
+            .double_size(false)
+            .line(&self.ordered_at.format("%Y-%m-%d %H:%M").to_string())
+            .align(Alignment::Left)
+            .line(&messages.format(locale, "ticket-waiter", Some(&waiter)))
+            .line(&"-".repeat(32));
+        for item in self.items.iter() {
+            let flag = match item.priority {
+                OrderPriority::Normal => String::new(),
+                OrderPriority::High => {
+                    format!(
+                        " [{}]",
+                        messages.format(locale, "ticket-priority-high", None)
+                    )
+                }
+                OrderPriority::Rush => {
+                    format!(
+                        " [{}]",
+                        messages.format(locale, "ticket-priority-rush", None)
+                    )
+                }
+            };
+            builder = builder
+                .bold(true)
+                .line(&format!("{} x {}{flag}", item.quantity, item.description))
+                .bold(false);
+            if let Some(notes) = &item.notes {
+                builder = builder.line(&format!("   * {notes}"));
+            }
+        }
+
+        builder.feed(3).cut().build()
+    }
+}
+
+impl<R, S> KitchenTicketQuery<R, S>
+where
+    R: ViewRepository<TabStatus, Tab>,
+    S: PrinterSink,
+{
+    pub fn new(tabs: Arc<R>, sink: S) -> Self {
+        Self {
+            tabs,
+            sink,
+            locale: DEFAULT_LOCALE.to_owned(),
+            error_handler: None,
+        }
+    }
+
+    pub fn with_locale(self, locale: &str) -> Self {
+        Self {
+            locale: locale.to_owned(),
+            ..self
+        }
+    }
+
+    pub fn with_error_handler(self, error_handler: KitchenTicketErrorHandler) -> Self {
+        Self {
+            error_handler: Some(error_handler),
+            ..self
+        }
+    }
+
+    pub async fn print(
+        &self,
+        aggregate_id: &str,
+        events: &[EventEnvelope<Tab>],
+    ) -> Result<(), KitchenTicketError> {
+        let Some(ticket) = self
+            .ticket(aggregate_id, events)
+            .await
+            .map_err(KitchenTicketError::TabStatus)?
+        else {
+            return Ok(());
+        };
+
+        self.sink
+            .print(&ticket.to_escpos(&self.locale))
+            .await
+            .map_err(KitchenTicketError::Print)
+    }
+
+    async fn ticket(
+        &self,
+        aggregate_id: &str,
+        events: &[EventEnvelope<Tab>],
+    ) -> Result<Option<KitchenTicket>, PersistenceError> {
+        let Some((items, ordered_at)) = ticket_items(events) else {
+            return Ok(None);
+        };
+        let status = self.tabs.load(aggregate_id).await?.unwrap_or_default();
+
+        Ok(Some(KitchenTicket::new(&status, items, ordered_at)))
+    }
+}
+
+impl KitchenPrinter {
+    pub fn new(sink: Arc<dyn PrinterSink>) -> Self {
+        Self {
+            sink,
+            locale: DEFAULT_LOCALE.to_owned(),
+            error_handler: None,
+        }
+    }
+
+    pub fn with_locale(self, locale: &str) -> Self {
+        Self {
+            locale: locale.to_owned(),
+            ..self
+        }
+    }
+
+    pub fn with_error_handler(self, error_handler: KitchenTicketErrorHandler) -> Self {
+        Self {
+            error_handler: Some(error_handler),
+            ..self
+        }
+    }
+
+    pub fn query<R>(self, tabs: Arc<R>) -> KitchenTicketQuery<R, Arc<dyn PrinterSink>>
+    where
+        R: ViewRepository<TabStatus, Tab>,
+    {
+        KitchenTicketQuery {
+            tabs,
+            sink: self.sink,
+            locale: self.locale,
+            error_handler: self.error_handler,
+        }
+    }
+
+    // Prints the food ordered in `events` for a tab whose status already
+    // reflects them.
+    pub async fn print(
+        &self,
+        aggregate_id: &str,
+        status: &TabStatus,
+        events: &[EventEnvelope<Tab>],
+    ) {
+        let Some((items, ordered_at)) = ticket_items(events) else {
+            return;
+        };
+        let ticket = KitchenTicket::new(status, items, ordered_at);
+        if let Err(e) = self.sink.print(&ticket.to_escpos(&self.locale)).await {
+            if let Some(handler) = &self.error_handler {
+                handler(aggregate_id, KitchenTicketError::Print(e));
+            }
+        }
+    }
+}
+
+fn ticket_items(events: &[EventEnvelope<Tab>]) -> Option<(Vec<TicketItem>, DateTime<Local>)> {
+    let mut items: Vec<TicketItem> = Vec::new();
+    let mut ordered_at = None;
+    for event in events {
+        if let TabEvent::FoodOrderPlaced { menu_item, .. } = &event.payload {
+            ordered_at = ordered_at.or_else(|| CommandContext::from_envelope(event));
+            match items.iter_mut().find(|i| {
+                i.menu_number == menu_item.menu_number
+                    && i.priority == menu_item.priority
+                    && i.notes == menu_item.notes
+            }) {
+                Some(item) => item.quantity += menu_item.quantity,
+                None => items.push(TicketItem {
+                    menu_number: menu_item.menu_number,
+                    description: menu_item.description.clone(),
+                    quantity: menu_item.quantity,
+                    priority: menu_item.priority,
+                    notes: menu_item.notes.clone(),
+                }),
+            }
+        }
+    }
+    if items.is_empty() {
+        return None;
+    }
+
+    Some((
+        items,
+        ordered_at
+            .map(|ctx| ctx.issued_at().with_timezone(&Local))
+            .unwrap_or_else(Local::now),
+    ))
+}
+
+impl std::error::Error for KitchenTicketError {}
+
+impl std::fmt::Display for KitchenTicketError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KitchenTicketError::TabStatus(e) => write!(f, "tab status unavailable: {e}"),
+            KitchenTicketError::Print(e) => write!(f, "kitchen ticket not printed: {e}"),
+        }
+    }
+}
+
+#[async_trait]
+impl<R, S> Query<Tab> for KitchenTicketQuery<R, S>
+where
+    R: ViewRepository<TabStatus, Tab>,
+    S: PrinterSink,
+{
+    async fn dispatch(&self, aggregate_id: &str, events: &[EventEnvelope<Tab>]) {
+        if let Err(e) = self.print(aggregate_id, events).await {
+            if let Some(handler) = &self.error_handler {
+                handler(aggregate_id, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use async_trait::async_trait;
+    use chrono::{Local, TimeZone};
+    use cqrs_es::{
+        persist::{PersistenceError, ViewContext, ViewRepository},
+        Query, View,
+    };
+
+    use crate::{
+        domain::tab::{
+            aggregate::Tab,
+            event::{MenuItem, TabEvent},
+            order_priority::OrderPriority,
+            queries::{
+                fixtures::{envelope, food_ordered, menu_item},
+                open_tabs::TabStatus,
+            },
+            tab_id::TabId,
+            waiter_id::WaiterId,
+        },
+        infrasctructure::printing::sink::{MemoryPrinterSink, PrinterSink},
+        shared_kernel::command_context::CommandContext,
+    };
+
+    use super::{KitchenPrinter, KitchenTicketError, KitchenTicketQuery, TicketItem};
+
+    #[derive(Default)]
+    struct StubTabs {
+        views: Mutex<HashMap<String, TabStatus>>,
+        unavailable: bool,
+    }
+
+    struct OfflinePrinter;
+
+    #[async_trait]
+    impl PrinterSink for OfflinePrinter {
+        async fn print(&self, _job: &[u8]) -> std::io::Result<()> {
+            Err(std::io::ErrorKind::ConnectionRefused.into())
+        }
+    }
+
+    #[async_trait]
+    impl ViewRepository<TabStatus, Tab> for StubTabs {
+        async fn load(&self, view_id: &str) -> Result<Option<TabStatus>, PersistenceError> {
+            if self.unavailable {
+                return Err(PersistenceError::OptimisticLockError);
+            }
+            Ok(self.views.lock().unwrap().get(view_id).cloned())
+        }
+
+        async fn load_with_context(
+            &self,
+            view_id: &str,
+        ) -> Result<Option<(TabStatus, ViewContext)>, PersistenceError> {
+            Ok(self
+                .load(view_id)
+                .await?
+                .map(|v| (v, ViewContext::new(view_id.to_string(), 0))))
+        }
+
+        async fn update_view(
+            &self,
+            view: TabStatus,
+            context: ViewContext,
+        ) -> Result<(), PersistenceError> {
+            self.views
+                .lock()
+                .unwrap()
+                .insert(context.view_instance_id, view);
+            Ok(())
+        }
+    }
+
+    fn rushed_steak(menu_number: usize, notes: Option<&str>) -> MenuItem {
+        MenuItem {
+            priority: OrderPriority::Rush,
+            notes: notes.map(String::from),
+            ..menu_item(menu_number, "Steak", 10)
+        }
+    }
+
+    fn query_for_open_tab(tab_id: TabId) -> KitchenTicketQuery<StubTabs, MemoryPrinterSink> {
+        let mut status = TabStatus::default();
+        status.update(&envelope(
+            tab_id,
+            1,
+            TabEvent::TabOpened {
+                id: tab_id,
+                waiter_id: WaiterId::default(),
+                table: 12,
+            },
+        ));
+        let tabs = StubTabs::default();
+        tabs.views
+            .lock()
+            .unwrap()
+            .insert(tab_id.to_string(), status);
+
+        KitchenTicketQuery::new(Arc::new(tabs), MemoryPrinterSink::new())
+    }
+
+    #[tokio::test]
+    async fn given_food_order_batch_then_one_ticket_is_printed_with_table_items_and_notes() {
+        let tab_id = TabId::new();
+        let query = query_for_open_tab(tab_id);
+
+        query
+            .dispatch(
+                &tab_id.to_string(),
+                &[
+                    envelope(
+                        tab_id,
+                        2,
+                        food_ordered(tab_id, rushed_steak(1, Some("medium rare"))),
+                    ),
+                    envelope(
+                        tab_id,
+                        3,
+                        food_ordered(tab_id, rushed_steak(1, Some("medium rare"))),
+                    ),
+                ],
+            )
+            .await;
+
+        let jobs = query.sink.jobs();
+        assert_eq!(jobs.len(), 1);
+        let ticket = String::from_utf8_lossy(&jobs[0]);
+        assert!(ticket.contains("TABLE 12"));
+        assert!(ticket.contains("Waiter: 00000000-0000-0000-0000-000000000000"));
+        assert!(ticket.contains("2 x Steak [RUSH]"));
+        assert!(ticket.contains("   * medium rare"));
+    }
+
+    #[tokio::test]
+    async fn given_locale_then_ticket_labels_are_translated() {
+        let tab_id = TabId::new();
+        let query = query_for_open_tab(tab_id).with_locale("fr");
+
+        query
+            .dispatch(
+                &tab_id.to_string(),
+                &[envelope(
+                    tab_id,
+                    2,
+                    food_ordered(tab_id, rushed_steak(1, None)),
+                )],
+            )
+            .await;
+
+        let ticket = String::from_utf8_lossy(&query.sink.jobs()[0]).to_string();
+        assert!(ticket.contains("TABLE 12"));
+        assert!(ticket.contains("Serveur : 00000000-0000-0000-0000-000000000000"));
+        assert!(ticket.contains("1 x Steak [URGENT]"));
+    }
+
+    #[tokio::test]
+    async fn given_events_without_food_orders_then_nothing_is_printed() {
+        let tab_id = TabId::new();
+        let query = query_for_open_tab(tab_id);
+
+        query
+            .dispatch(
+                &tab_id.to_string(),
+                &[envelope(
+                    tab_id,
+                    2,
+                    TabEvent::FoodPrepared {
+                        id: tab_id,
+                        menu_number: 1,
+                    },
+                )],
+            )
+            .await;
+
+        assert!(query.sink.jobs().is_empty());
+    }
+
+    #[tokio::test]
+    async fn given_command_context_then_ticket_is_stamped_with_issue_time() {
+        let tab_id = TabId::new();
+        let query = query_for_open_tab(tab_id);
+        let issued_at = Local.with_ymd_and_hms(2024, 4, 12, 19, 45, 0).unwrap();
+        let mut event = envelope(tab_id, 2, food_ordered(tab_id, rushed_steak(3, None)));
+        event.metadata = CommandContext::new("waiter-7")
+            .with_issued_at(issued_at.into())
+            .to_metadata();
+
+        let ticket = query
+            .ticket(&tab_id.to_string(), &[event])
+            .await
+            .unwrap()
+            .expect("food order should produce a ticket");
+
+        assert_eq!(ticket.ordered_at, issued_at);
+        assert_eq!(
+            ticket.items,
+            vec![TicketItem {
+                menu_number: 3,
+                description: "Steak".into(),
+                quantity: 1,
+                priority: OrderPriority::Rush,
+                notes: None,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn given_tab_status_unavailable_then_error_is_handed_to_the_handler_and_nothing_printed()
+    {
+        let tab_id = TabId::new();
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let handled = errors.clone();
+        let tabs = StubTabs {
+            unavailable: true,
+            ..StubTabs::default()
+        };
+        let query = KitchenTicketQuery::new(Arc::new(tabs), MemoryPrinterSink::new())
+            .with_error_handler(Box::new(move |id, e| {
+                handled.lock().unwrap().push((id.to_string(), e))
+            }));
+
+        query
+            .dispatch(
+                &tab_id.to_string(),
+                &[envelope(
+                    tab_id,
+                    2,
+                    food_ordered(tab_id, rushed_steak(1, None)),
+                )],
+            )
+            .await;
+
+        let errors = errors.lock().unwrap();
+        assert!(query.sink.jobs().is_empty());
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, tab_id.to_string());
+        assert!(matches!(errors[0].1, KitchenTicketError::TabStatus(_)));
+    }
+
+    #[tokio::test]
+    async fn given_offline_printer_then_print_returns_the_sink_error() {
+        let tab_id = TabId::new();
+        let query = KitchenTicketQuery::new(Arc::new(StubTabs::default()), OfflinePrinter);
+
+        let actual = query
+            .print(
+                &tab_id.to_string(),
+                &[envelope(
+                    tab_id,
+                    2,
+                    food_ordered(tab_id, rushed_steak(1, None)),
+                )],
+            )
+            .await;
+
+        assert!(matches!(actual, Err(KitchenTicketError::Print(_))));
+    }
+
+    #[tokio::test]
+    async fn given_offline_printer_then_kitchen_printer_hands_the_error_to_the_handler() {
+        let tab_id = TabId::new();
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let handled = errors.clone();
+        let printer = KitchenPrinter::new(Arc::new(OfflinePrinter)).with_error_handler(Box::new(
+            move |id, e| handled.lock().unwrap().push((id.to_string(), e)),
+        ));
+
+        printer
+            .print(
+                &tab_id.to_string(),
+                &TabStatus::default(),
+                &[envelope(
+                    tab_id,
+                    2,
+                    food_ordered(tab_id, rushed_steak(1, None)),
+                )],
+            )
+            .await;
+
+        let errors = errors.lock().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, tab_id.to_string());
+        assert!(matches!(errors[0].1, KitchenTicketError::Print(_)));
+    }
+}
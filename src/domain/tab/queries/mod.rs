@@ -1,3 +1,7 @@
+pub mod bar;
+#[cfg(test)]
+pub(crate) mod fixtures;
 pub mod kitchen;
+pub mod kitchen_ticket;
 pub mod open_tabs;
 pub mod simple_logging;
@@ -1,14 +1,75 @@
-use cqrs_es::View;
+use async_trait::async_trait;
+use cqrs_es::{persist::PersistenceError, View};
 use serde::{Deserialize, Serialize};
 
-use crate::domain::tab::{aggregate::Tab, tab_id::TabId};
+use crate::{
+    domain::tab::{
+        aggregate::Tab,
+        event::{AppliedPromotion, TabEvent},
+        tab_id::TabId,
+        waiter_id::WaiterId,
+    },
+    shared_kernel::money::{Money, MoneyError},
+};
+
+#[async_trait]
+pub trait OpenTabQuery {
+    async fn active_table_numbers(&self) -> Result<Vec<usize>, PersistenceError>;
+    async fn invoice_for_table(&self, table: usize)
+        -> Result<Option<TabInvoice>, PersistenceError>;
+    async fn tab_for_table(&self, table: usize) -> Result<Option<TabStatus>, PersistenceError>;
+    async fn waiter_todo_list(&self, id: WaiterId) -> Result<WaiterTodoList, PersistenceError>;
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct TabStatus {
+    tab_id: TabId,
+    table: usize,
+    waiter_id: WaiterId,
+    open: bool,
+    items: Vec<TabItem>,
+    #[serde(default)]
+    gift_card_payments: Vec<Money>,
+    #[serde(default)]
+    promotions: Vec<AppliedPromotion>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct TabItem {
+    menu_number: usize,
+    description: String,
+    is_drink: bool,
+    price: Money,
+    prepared: bool,
+    served: bool,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct TabInvoice {
+    tab_id: TabId,
+    table: usize,
+    lines: Vec<InvoiceLine>,
+    total: Money,
+    #[serde(default)]
+    paid_by_gift_card: Money,
+    #[serde(default)]
+    promotions: Vec<AppliedPromotion>,
+    has_unserved_items: bool,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct InvoiceLine {
+    menu_number: usize,
+    description: String,
+    quantity: usize,
+    unit_price: Money,
+    total: Money,
+}
 
-// pub trait OpenTabQuery {
-//     fn active_table_numbers(&self) -> Vec<usize>;
-//     fn invoice_for_table(&self, table: usize) -> TabInvoice;
-//     fn tab_for_table(&self, table: usize) -> TabStatus;
-//     fn waiter_todo_list(&self, id: WaiterId) -> WaiterTodoList;
-// }
+#[derive(Clone, Debug, Default)]
+pub struct OpenTabs {
+    inner: Vec<TabStatus>,
+}
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct WaiterTodoList {
@@ -27,6 +88,277 @@ pub struct OpenTab {
     open_items: Vec<OpenItem>,
 }
 
+impl TabStatus {
+    pub fn tab_id(&self) -> TabId {
+        self.tab_id
+    }
+
+    pub fn table(&self) -> usize {
+        self.table
+    }
+
+    pub fn waiter_id(&self) -> WaiterId {
+        self.waiter_id
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn items(&self) -> Vec<TabItem> {
+        self.items.clone()
+    }
+
+    // Fails when the view holds amounts that cannot be summed, e.g. items
+    // priced in different currencies.
+    pub fn invoice(&self) -> Result<TabInvoice, MoneyError> {
+        let mut lines: Vec<InvoiceLine> = Vec::new();
+        for item in self.items.iter() {
+            match lines
+                .iter_mut()
+                .find(|l| l.menu_number == item.menu_number && l.unit_price == item.price)
+            {
+                Some(line) => {
+                    line.quantity += 1;
+                    line.total = line.total.checked_add(item.price)?;
+                }
+                None => lines.push(InvoiceLine {
+                    menu_number: item.menu_number,
+                    description: item.description.clone(),
+                    quantity: 1,
+                    unit_price: item.price,
+                    total: item.price,
+                }),
+            }
+        }
+
+        Ok(TabInvoice {
+            tab_id: self.tab_id,
+            table: self.table,
+            total: sum(lines.iter().map(|l| l.total))?,
+            paid_by_gift_card: sum(self.gift_card_payments.iter().copied())?,
+            promotions: self.promotions.clone(),
+            has_unserved_items: self.items.iter().any(|i| !i.served),
+            lines,
+        })
+    }
+
+    pub fn open_tab(&self) -> OpenTab {
+        let mut tab = OpenTab::new(self.tab_id);
+        for item in self.items.iter().filter(|i| !i.served) {
+            tab.add_item(OpenItem {
+                menu_number: item.menu_number,
+                description: item.description.clone(),
+            });
+        }
+
+        tab
+    }
+
+    fn mark_next(
+        &mut self,
+        menu_number: usize,
+        is_drink: bool,
+        pending: fn(&TabItem) -> bool,
+        mark: fn(&mut TabItem),
+    ) {
+        if let Some(item) = self
+            .items
+            .iter_mut()
+            .find(|i| i.menu_number == menu_number && i.is_drink == is_drink && pending(i))
+        {
+            mark(item);
+        }
+    }
+}
+
+impl TabItem {
+    pub fn menu_number(&self) -> usize {
+        self.menu_number
+    }
+
+    pub fn description(&self) -> String {
+        self.description.clone()
+    }
+
+    pub fn is_drink(&self) -> bool {
+        self.is_drink
+    }
+
+    pub fn price(&self) -> Money {
+        self.price
+    }
+
+    pub fn is_prepared(&self) -> bool {
+        self.prepared
+    }
+
+    pub fn is_served(&self) -> bool {
+        self.served
+    }
+}
+
+impl TabInvoice {
+    pub fn tab_id(&self) -> TabId {
+        self.tab_id
+    }
+
+    pub fn table(&self) -> usize {
+        self.table
+    }
+
+    pub fn lines(&self) -> Vec<InvoiceLine> {
+        self.lines.clone()
+    }
+
+    pub fn total(&self) -> Money {
+        self.total
+    }
+
+    pub fn paid_by_gift_card(&self) -> Money {
+        self.paid_by_gift_card
+    }
+
+    pub fn promotions(&self) -> Vec<AppliedPromotion> {
+        self.promotions.clone()
+    }
+
+    pub fn amount_due(&self) -> Money {
+        self.promotions
+            .iter()
+            .map(|p| p.discount)
+            .chain([self.paid_by_gift_card])
+            .try_fold(self.total, |due, deduction| due.checked_sub(deduction))
+            .unwrap_or(Money::zero(self.total.currency()))
+    }
+
+    pub fn has_unserved_items(&self) -> bool {
+        self.has_unserved_items
+    }
+}
+
+impl InvoiceLine {
+    pub fn menu_number(&self) -> usize {
+        self.menu_number
+    }
+
+    pub fn description(&self) -> String {
+        self.description.clone()
+    }
+
+    pub fn quantity(&self) -> usize {
+        self.quantity
+    }
+
+    pub fn unit_price(&self) -> Money {
+        self.unit_price
+    }
+
+    pub fn total(&self) -> Money {
+        self.total
+    }
+}
+
+impl OpenTabs {
+    pub fn new(tabs: Vec<TabStatus>) -> Self {
+        Self {
+            inner: tabs.into_iter().filter(|t| t.open).collect(),
+        }
+    }
+
+    fn find_by_table(&self, table: usize) -> Option<&TabStatus> {
+        self.inner.iter().find(|t| t.table == table)
+    }
+}
+
+#[async_trait]
+impl OpenTabQuery for OpenTabs {
+    async fn active_table_numbers(&self) -> Result<Vec<usize>, PersistenceError> {
+        let mut tables: Vec<usize> = self.inner.iter().map(|t| t.table).collect();
+        tables.sort();
+
+        Ok(tables)
+    }
+
+    async fn invoice_for_table(
+        &self,
+        table: usize,
+    ) -> Result<Option<TabInvoice>, PersistenceError> {
+        self.find_by_table(table)
+            .map(TabStatus::invoice)
+            .transpose()
+            .map_err(|e| PersistenceError::DeserializationError(Box::new(e)))
+    }
+
+    async fn tab_for_table(&self, table: usize) -> Result<Option<TabStatus>, PersistenceError> {
+        Ok(self.find_by_table(table).cloned())
+    }
+
+    async fn waiter_todo_list(&self, id: WaiterId) -> Result<WaiterTodoList, PersistenceError> {
+        Ok(WaiterTodoList {
+            inner: self
+                .inner
+                .iter()
+                .filter(|t| t.waiter_id == id)
+                .map(|t| t.open_tab())
+                .filter(|t| !t.open_items.is_empty())
+                .collect(),
+        })
+    }
+}
+
+impl View<Tab> for TabStatus {
+    fn update(&mut self, event: &cqrs_es::EventEnvelope<Tab>) {
+        match &event.payload {
+            TabEvent::TabOpened {
+                id,
+                waiter_id,
+                table,
+            } => {
+                self.tab_id = *id;
+                self.waiter_id = *waiter_id;
+                self.table = *table;
+                self.open = true;
+                self.items.clear();
+                self.gift_card_payments.clear();
+                self.promotions.clear();
+            }
+            TabEvent::FoodOrderPlaced { menu_item, .. }
+            | TabEvent::DrinkOrderPlaced { menu_item, .. } => {
+                let is_drink = matches!(event.payload, TabEvent::DrinkOrderPlaced { .. });
+                for _ in 0..menu_item.quantity {
+                    self.items.push(TabItem {
+                        menu_number: menu_item.menu_number,
+                        description: menu_item.description.clone(),
+                        is_drink,
+                        price: menu_item.price,
+                        prepared: is_drink,
+                        served: false,
+                    });
+                }
+            }
+            TabEvent::FoodPrepared { menu_number, .. } => {
+                self.mark_next(*menu_number, false, |i| !i.prepared, |i| i.prepared = true)
+            }
+            TabEvent::FoodServed { menu_number, .. } => self.mark_next(
+                *menu_number,
+                false,
+                |i| i.prepared && !i.served,
+                |i| i.served = true,
+            ),
+            TabEvent::DrinkServed { menu_number, .. } => {
+                self.mark_next(*menu_number, true, |i| !i.served, |i| i.served = true)
+            }
+            TabEvent::GiftCardPaymentReceived { payment, .. } => {
+                self.gift_card_payments.push(payment.amount)
+            }
+            TabEvent::PromotionApplied { promotion, .. } => self.promotions.push(promotion.clone()),
+            TabEvent::TabClosed { .. } => self.open = false,
+            _ => {}
+        }
+    }
+}
+
 impl OpenItem {
     pub fn description(&self) -> String {
         self.description.clone()
@@ -73,6 +405,14 @@ impl WaiterTodoList {
     }
 }
 
+impl FromIterator<OpenTab> for WaiterTodoList {
+    fn from_iter<T: IntoIterator<Item = OpenTab>>(iter: T) -> Self {
+        Self {
+            inner: iter.into_iter().collect(),
+        }
+    }
+}
+
 impl std::ops::Deref for WaiterTodoList {
     type Target = Vec<OpenTab>;
 
@@ -81,30 +421,273 @@ impl std::ops::Deref for WaiterTodoList {
     }
 }
 
+impl WaiterTodoList {
+    fn tab_mut(&mut self, id: TabId) -> &mut OpenTab {
+        match self.inner.iter().position(|t| t.id == id) {
+            Some(pos) => &mut self.inner[pos],
+            None => {
+                self.inner.push(OpenTab::new(id));
+                self.inner.last_mut().unwrap()
+            }
+        }
+    }
+
+    fn remove_item(&mut self, id: TabId, menu_number: usize) {
+        if let Some(pos) = self.inner.iter().position(|t| t.id == id) {
+            let tab = &mut self.inner[pos];
+            if tab.open_items.iter().any(|i| i.menu_number == menu_number) {
+                tab.remove_item(menu_number);
+            }
+            if tab.open_items.is_empty() {
+                self.inner.remove(pos);
+            }
+        }
+    }
+}
+
 impl View<Tab> for WaiterTodoList {
     fn update(&mut self, event: &cqrs_es::EventEnvelope<Tab>) {
         match &event.payload {
-            crate::domain::tab::event::TabEvent::FoodOrderPlaced {
-                id: _,
-                menu_item: _,
-            } => {}
-            crate::domain::tab::event::TabEvent::FoodPrepared {
-                id: _,
-                menu_number: _,
-            } => {}
+            TabEvent::FoodOrderPlaced { id, menu_item }
+            | TabEvent::DrinkOrderPlaced { id, menu_item } => {
+                self.tab_mut(*id).add_item(OpenItem {
+                    menu_number: menu_item.menu_number,
+                    description: menu_item.description.clone(),
+                })
+            }
+            TabEvent::FoodServed { id, menu_number }
+            | TabEvent::DrinkServed { id, menu_number } => self.remove_item(*id, *menu_number),
+            TabEvent::TabClosed { id, .. } => self.inner.retain(|t| t.id != *id),
             _ => {}
         }
     }
 }
 
+fn sum(mut amounts: impl Iterator<Item = Money>) -> Result<Money, MoneyError> {
+    match amounts.next() {
+        Some(first) => amounts.try_fold(first, Money::checked_add),
+        None => Ok(Money::default()),
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use crate::domain::tab::{
-        queries::open_tabs::{OpenItem, WaiterTodoList},
-        tab_id::TabId,
+    use cqrs_es::View;
+    use rust_decimal::Decimal;
+
+    use crate::{
+        domain::{
+            gift_card::redemption_id::RedemptionId,
+            tab::{
+                event::{AppliedPromotion, GiftCardPayment, TabEvent},
+                queries::{
+                    fixtures::{envelope, menu_item},
+                    open_tabs::{OpenItem, WaiterTodoList},
+                },
+                tab_id::TabId,
+                waiter_id::WaiterId,
+            },
+        },
+        shared_kernel::{
+            fixtures::usd,
+            money::{Currency, Money, MoneyError},
+        },
     };
 
-    use super::OpenTab;
+    use super::{OpenTab, OpenTabQuery, OpenTabs, TabStatus};
+
+    fn apply(status: &mut TabStatus, tab_id: TabId, events: Vec<TabEvent>) {
+        for (sequence, payload) in events.into_iter().enumerate() {
+            status.update(&envelope(tab_id, sequence + 1, payload));
+        }
+    }
+
+    fn tab_status(tab_id: TabId, waiter_id: WaiterId, table: usize) -> TabStatus {
+        let mut status = TabStatus::default();
+        apply(
+            &mut status,
+            tab_id,
+            vec![
+                TabEvent::TabOpened {
+                    id: tab_id,
+                    waiter_id,
+                    table,
+                },
+                TabEvent::FoodOrderPlaced {
+                    id: tab_id,
+                    menu_item: menu_item(1, "Steak", 10),
+                },
+                TabEvent::FoodOrderPlaced {
+                    id: tab_id,
+                    menu_item: menu_item(1, "Steak", 10),
+                },
+                TabEvent::DrinkOrderPlaced {
+                    id: tab_id,
+                    menu_item: menu_item(2, "Coca-Cola", 3),
+                },
+                TabEvent::FoodPrepared {
+                    id: tab_id,
+                    menu_number: 1,
+                },
+                TabEvent::DrinkServed {
+                    id: tab_id,
+                    menu_number: 2,
+                },
+            ],
+        );
+
+        status
+    }
+
+    #[test]
+    fn given_tab_events_then_tab_status_tracks_prepared_and_served_flags() {
+        let tab_id = TabId::new();
+        let status = tab_status(tab_id, WaiterId::new(), 4);
+
+        let items = status.items();
+        assert_eq!(items.len(), 3);
+        assert!(items[0].is_prepared() && !items[0].is_served());
+        assert!(!items[1].is_prepared() && !items[1].is_served());
+        assert!(items[2].is_drink() && items[2].is_served());
+    }
+
+    #[test]
+    fn given_items_in_different_currencies_then_invoice_is_an_error() {
+        let tab_id = TabId::new();
+        let mut status = tab_status(tab_id, WaiterId::new(), 4);
+        let mut euro_steak = menu_item(1, "Steak", 10);
+        euro_steak.price = Money::new(Decimal::from(10), Currency::EUR).unwrap();
+        apply(
+            &mut status,
+            tab_id,
+            vec![TabEvent::FoodOrderPlaced {
+                id: tab_id,
+                menu_item: euro_steak,
+            }],
+        );
+
+        let actual = status.invoice();
+
+        assert!(matches!(actual, Err(MoneyError::CurrencyMismatch { .. })));
+    }
+
+    #[test]
+    fn given_tab_status_then_invoice_groups_lines_and_totals() {
+        let status = tab_status(TabId::new(), WaiterId::new(), 4);
+
+        let invoice = status.invoice().unwrap();
+
+        assert_eq!(invoice.table(), 4);
+        assert_eq!(invoice.lines().len(), 2);
+        assert_eq!(invoice.lines()[0].quantity(), 2);
+        assert_eq!(invoice.lines()[0].total(), usd(Decimal::from(20)));
+        assert_eq!(invoice.lines()[1].quantity(), 1);
+        assert_eq!(invoice.total(), usd(Decimal::from(23)));
+        assert!(invoice.has_unserved_items());
+    }
+
+    #[test]
+    fn given_gift_card_payment_then_invoice_shows_amount_due() {
+        let tab_id = TabId::new();
+        let mut status = tab_status(tab_id, WaiterId::new(), 4);
+        apply(
+            &mut status,
+            tab_id,
+            vec![TabEvent::GiftCardPaymentReceived {
+                id: tab_id,
+                payment: GiftCardPayment {
+                    code: "GC-1001".into(),
+                    amount: usd(Decimal::from(15)),
+                    redemption: RedemptionId::new(),
+                },
+            }],
+        );
+
+        let invoice = status.invoice().unwrap();
+
+        assert_eq!(invoice.total(), usd(Decimal::from(23)));
+        assert_eq!(invoice.paid_by_gift_card(), usd(Decimal::from(15)));
+        assert_eq!(invoice.amount_due(), usd(Decimal::from(8)));
+    }
+
+    #[test]
+    fn given_promotion_and_gift_card_payment_then_both_reduce_amount_due() {
+        let tab_id = TabId::new();
+        let mut status = tab_status(tab_id, WaiterId::new(), 4);
+        apply(
+            &mut status,
+            tab_id,
+            vec![
+                TabEvent::PromotionApplied {
+                    id: tab_id,
+                    promotion: AppliedPromotion {
+                        promotion_id: "happy-hour".into(),
+                        name: "Happy hour".into(),
+                        items: vec![(1, 2)],
+                        units: vec![],
+                        discount: usd(Decimal::from(5)),
+                    },
+                },
+                TabEvent::GiftCardPaymentReceived {
+                    id: tab_id,
+                    payment: GiftCardPayment {
+                        code: "GC-1001".into(),
+                        amount: usd(Decimal::from(15)),
+                        redemption: RedemptionId::new(),
+                    },
+                },
+            ],
+        );
+
+        let invoice = status.invoice().unwrap();
+
+        assert_eq!(invoice.total(), usd(Decimal::from(23)));
+        assert_eq!(invoice.promotions().len(), 1);
+        assert_eq!(invoice.amount_due(), usd(Decimal::from(3)));
+    }
+
+    #[tokio::test]
+    async fn given_open_and_closed_tabs_then_only_open_tables_are_active() {
+        let waiter_id = WaiterId::new();
+        let open = tab_status(TabId::new(), waiter_id, 4);
+        let closed_id = TabId::new();
+        let mut closed = tab_status(closed_id, waiter_id, 2);
+        apply(
+            &mut closed,
+            closed_id,
+            vec![TabEvent::TabClosed {
+                id: closed_id,
+                amount_paid: usd(Decimal::from(25)),
+                order_value: usd(Decimal::from(23)),
+                tip_value: usd(Decimal::from(2)),
+                amount_tendered: usd(Decimal::from(25)),
+                exchange_rate: None,
+            }],
+        );
+
+        let tabs = OpenTabs::new(vec![open, closed]);
+
+        assert_eq!(tabs.active_table_numbers().await.unwrap(), vec![4]);
+        assert!(tabs.tab_for_table(2).await.unwrap().is_none());
+        assert!(tabs.invoice_for_table(4).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn waiter_todo_list_only_contains_unserved_items_of_the_waiters_tabs() {
+        let waiter_id = WaiterId::new();
+        let tab_id = TabId::new();
+        let tabs = OpenTabs::new(vec![
+            tab_status(tab_id, waiter_id, 4),
+            tab_status(TabId::new(), WaiterId::new(), 5),
+        ]);
+
+        let list = tabs.waiter_todo_list(waiter_id).await.unwrap();
+
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].id(), tab_id);
+        assert_eq!(list[0].open_items().len(), 2);
+        assert_eq!(list[0].open_items()[0].description(), "Steak");
+    }
 
     #[test]
     fn when_new_tab_then_open_items_is_empty() {
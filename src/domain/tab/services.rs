@@ -1,2 +1,73 @@
-#[derive(Debug, Default)]
-pub struct TabServices {}
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+
+use crate::shared_kernel::{
+    clock::{Clock, SystemClock},
+    exchange_rates::{ExchangeRateTable, ExchangeRates},
+    money::{Currency, Rounding},
+};
+
+use super::promotions::Promotions;
+
+#[derive(Clone, Debug)]
+pub struct TabServices {
+    currency: Currency,
+    rounding: Rounding,
+    exchange_rates: Arc<dyn ExchangeRates>,
+    promotions: Arc<Promotions>,
+    clock: Arc<dyn Clock>,
+}
+
+impl TabServices {
+    pub fn new(currency: Currency, rounding: Rounding) -> Self {
+        Self {
+            currency,
+            rounding,
+            exchange_rates: Arc::new(ExchangeRateTable::default()),
+            promotions: Arc::new(Promotions::default()),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    pub fn with_exchange_rates(self, exchange_rates: Arc<dyn ExchangeRates>) -> Self {
+        Self {
+            exchange_rates,
+            ..self
+        }
+    }
+
+    pub fn with_promotions(self, promotions: Arc<Promotions>) -> Self {
+        Self { promotions, ..self }
+    }
+
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> Self {
+        Self { clock, ..self }
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn rounding(&self) -> Rounding {
+        self.rounding
+    }
+
+    pub fn exchange_rates(&self) -> &dyn ExchangeRates {
+        self.exchange_rates.as_ref()
+    }
+
+    pub fn promotions(&self) -> &Promotions {
+        self.promotions.as_ref()
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+}
+
+impl Default for TabServices {
+    fn default() -> Self {
+        Self::new(Currency::default(), Rounding::default())
+    }
+}
@@ -0,0 +1,48 @@
+use std::path::Path;
+
+use crate::domain::tab::promotions::{Promotions, PromotionsError};
+
+#[derive(Debug)]
+pub enum ConfigurationError {
+    Io(std::io::Error),
+    Promotions(PromotionsError),
+}
+
+pub fn promotions_from_file(path: impl AsRef<Path>) -> Result<Promotions, ConfigurationError> {
+    let json = std::fs::read_to_string(path).map_err(ConfigurationError::Io)?;
+
+    Promotions::from_json(&json).map_err(ConfigurationError::Promotions)
+}
+
+impl std::error::Error for ConfigurationError {}
+
+impl std::fmt::Display for ConfigurationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigurationError::Io(e) => write!(f, "failed to read configuration: {e}"),
+            ConfigurationError::Promotions(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{promotions_from_file, ConfigurationError};
+
+    #[test]
+    fn given_shipped_example_config_then_promotions_load_from_file() {
+        let promotions = promotions_from_file(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/config/promotions.json"
+        ));
+
+        assert!(!promotions.unwrap().promotions().is_empty());
+    }
+
+    #[test]
+    fn given_missing_file_then_loading_fails_with_io_error() {
+        let actual = promotions_from_file("config/no-such-promotions.json");
+
+        assert!(matches!(actual, Err(ConfigurationError::Io(_))));
+    }
+}
@@ -0,0 +1,235 @@
+use std::sync::OnceLock;
+
+use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource, FluentValue};
+use unic_langid::LanguageIdentifier;
+
+use crate::domain::tab::error::TabError;
+
+pub const DEFAULT_LOCALE: &str = "en";
+
+const CATALOGS: [(&str, &str); 3] = [
+    ("en", include_str!("../../locales/en/main.ftl")),
+    ("am", include_str!("../../locales/am/main.ftl")),
+    ("fr", include_str!("../../locales/fr/main.ftl")),
+];
+
+pub struct Messages {
+    bundles: Vec<FluentBundle<FluentResource>>,
+}
+
+// The catalogs compiled into the binary, parsed on first use.
+pub fn messages() -> &'static Messages {
+    static MESSAGES: OnceLock<Messages> = OnceLock::new();
+    MESSAGES.get_or_init(Messages::default)
+}
+
+impl Messages {
+    pub fn new(catalogs: &[(&str, &str)]) -> Self {
+        let bundles = catalogs
+            .iter()
+            .map(|(locale, source)| {
+                let langid: LanguageIdentifier = locale
+                    .parse()
+                    .unwrap_or_else(|e| panic!("invalid locale {locale}: {e}"));
+                let resource = FluentResource::try_new(source.to_string())
+                    .unwrap_or_else(|(_, e)| panic!("invalid catalog for {locale}: {e:?}"));
+                let mut bundle = FluentBundle::new_concurrent(vec![langid]);
+                // Isolation marks around arguments show up as garbage on printers.
+                bundle.set_use_isolating(false);
+                bundle
+                    .add_resource(resource)
+                    .unwrap_or_else(|e| panic!("duplicate messages for {locale}: {e:?}"));
+                bundle
+            })
+            .collect();
+
+        Self { bundles }
+    }
+
+    // Renders `id` in `locale`, e.g. "fr" or "fr-CA", falling back to English
+    // and finally to the message id itself when no translation exists.
+    pub fn format(&self, locale: &str, id: &str, args: Option<&FluentArgs>) -> String {
+        let language = locale
+            .parse::<LanguageIdentifier>()
+            .map(|l| l.language)
+            .ok();
+        let requested = self
+            .bundles
+            .iter()
+            .find(|b| Some(b.locales[0].language) == language);
+        let fallback = self
+            .bundles
+            .iter()
+            .find(|b| b.locales[0].language.as_str() == DEFAULT_LOCALE);
+        for bundle in requested.into_iter().chain(fallback) {
+            if let Some(pattern) = bundle.get_message(id).and_then(|m| m.value()) {
+                let mut errors = Vec::new();
+                let text = bundle.format_pattern(pattern, args, &mut errors);
+                if !errors.is_empty() {
+                    eprintln!("failed to format {id} for {locale}: {errors:?}");
+                }
+                return text.into_owned();
+            }
+        }
+
+        id.to_string()
+    }
+
+    pub fn tab_error(&self, locale: &str, error: &TabError) -> String {
+        let mut args = FluentArgs::new();
+        match error {
+            TabError::DrinkNotOutstanding { menu_number }
+            | TabError::FoodNotOutstanding { menu_number }
+            | TabError::FoodNotPrepared { menu_number }
+            | TabError::FoodAlreadyRushed { menu_number } => args.set("menu_number", *menu_number),
+            TabError::TabIsOpen { id } => args.set("id", id.to_string()),
+            TabError::TabIdMismatch { expected, actual } => {
+                args.set("expected", expected.to_string());
+                args.set("actual", actual.to_string());
+            }
+            TabError::CurrencyMismatch { expected, actual } => {
+                args.set("expected", expected.to_string());
+                args.set("actual", actual.to_string());
+            }
+            TabError::ExchangeRateUnavailable { from, to }
+            | TabError::ExchangeRateLookupFailed { from, to } => {
+                args.set("from", from.to_string());
+                args.set("to", to.to_string());
+            }
+            TabError::GiftCardExceedsBalance { outstanding } => {
+                args.set("outstanding", outstanding.to_string())
+            }
+            TabError::InvalidCurrency { code } => args.set("code", code.clone()),
+            TabError::InvalidExchangeRate { rate } => args.set("rate", rate.to_string()),
+            _ => {}
+        }
+
+        self.format(locale, &message_id(error.code()), Some(&args))
+    }
+}
+
+impl Default for Messages {
+    fn default() -> Self {
+        Self::new(&CATALOGS)
+    }
+}
+
+pub fn args<'a>(values: &[(&'a str, FluentValue<'a>)]) -> FluentArgs<'a> {
+    values.iter().cloned().collect()
+}
+
+// TAB_NOT_OPEN becomes tab-not-open.
+fn message_id(code: &str) -> String {
+    code.to_lowercase().replace('_', "-")
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use crate::{
+        domain::tab::{error::TabError, tab_id::TabId},
+        shared_kernel::money::{Currency, Money},
+    };
+
+    use super::{args, messages, Messages, CATALOGS};
+
+    #[test]
+    fn every_catalog_translates_every_english_message() {
+        let ids: Vec<_> = CATALOGS[0]
+            .1
+            .lines()
+            .filter_map(|l| l.split_once(" = ").map(|(id, _)| id))
+            .collect();
+
+        for bundle in messages().bundles.iter() {
+            for id in ids.iter() {
+                assert!(
+                    bundle.has_message(id),
+                    "{} is missing {id}",
+                    bundle.locales[0]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn given_locale_then_tab_error_is_rendered_with_its_parameters() {
+        let error = TabError::FoodNotPrepared { menu_number: 4 };
+
+        assert_eq!(
+            messages().tab_error("en", &error),
+            "food has not been prepared: menu number 4"
+        );
+        assert_eq!(
+            messages().tab_error("fr-CA", &error),
+            "plat pas encore préparé : numéro 4"
+        );
+        assert_eq!(
+            messages().tab_error("am", &error),
+            "ምግቡ ገና አልተዘጋጀም፦ የምናሌ ቁጥር 4"
+        );
+    }
+
+    #[test]
+    fn given_missing_translation_then_english_is_used() {
+        let messages = Messages::new(&[
+            ("en", "receipt-tip = Tip\nreceipt-table = Table { $table }"),
+            ("fr", "receipt-tip = Pourboire"),
+        ]);
+
+        assert_eq!(messages.format("fr", "receipt-tip", None), "Pourboire");
+        assert_eq!(
+            messages.format("fr", "receipt-table", Some(&args(&[("table", 3.into())]))),
+            "Table 3"
+        );
+        assert_eq!(messages.format("de", "receipt-tip", None), "Tip");
+        assert_eq!(
+            messages.format("fr", "receipt-unknown", None),
+            "receipt-unknown"
+        );
+    }
+
+    #[test]
+    fn english_tab_errors_match_their_display_text() {
+        let errors = [
+            TabError::CannotCancelServedItem,
+            TabError::TabHasUnservedItems,
+            TabError::MustPayEnough,
+            TabError::TabNotOpened,
+            TabError::DrinkNotOutstanding { menu_number: 1 },
+            TabError::TabIsOpen { id: TabId::new() },
+            TabError::FoodNotOutstanding { menu_number: 2 },
+            TabError::FoodNotPrepared { menu_number: 3 },
+            TabError::FoodAlreadyRushed { menu_number: 3 },
+            TabError::TabIdMismatch {
+                expected: TabId::new(),
+                actual: TabId::new(),
+            },
+            TabError::CurrencyMismatch {
+                expected: Currency::USD,
+                actual: Currency::ETB,
+            },
+            TabError::ExchangeRateUnavailable {
+                from: Currency::EUR,
+                to: Currency::ETB,
+            },
+            TabError::GiftCardExceedsBalance {
+                outstanding: Money::new(Decimal::from(12), Currency::USD).unwrap(),
+            },
+            TabError::InvalidCurrency {
+                code: String::from("usd"),
+            },
+            TabError::InvalidExchangeRate {
+                rate: Decimal::new(-5, 1),
+            },
+        ];
+
+        for error in errors {
+            assert_eq!(
+                format!("tab error: {}", messages().tab_error("en", &error)),
+                error.to_string()
+            );
+        }
+    }
+}
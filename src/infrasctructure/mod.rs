@@ -1,2 +1,6 @@
+pub mod configuration;
+pub mod localization;
 pub mod persistence;
+pub mod printing;
+pub mod publishing;
 pub mod respository;
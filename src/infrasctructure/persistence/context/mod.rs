@@ -1,2 +1,4 @@
 pub mod connection_parameters;
 pub mod postgres;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
@@ -0,0 +1,26 @@
+use std::str::FromStr;
+
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions},
+    Pool, Sqlite,
+};
+
+pub async fn sqlite_pool(path: &str) -> Pool<Sqlite> {
+    let options = SqliteConnectOptions::from_str(&format!("sqlite://{path}"))
+        .expect("invalid SQLite database path")
+        .create_if_missing(true)
+        .journal_mode(SqliteJournalMode::Wal);
+
+    SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(options)
+        .await
+        .expect("unable to connect to the SQLite database")
+}
+
+pub async fn migrate_sqlite_db(pool: &Pool<Sqlite>) {
+    sqlx::migrate!("./migrations_sqlite")
+        .run(pool)
+        .await
+        .expect("database migration failed");
+}
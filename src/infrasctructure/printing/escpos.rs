@@ -0,0 +1,117 @@
+const ESC: u8 = 0x1B;
+const GS: u8 = 0x1D;
+const LF: u8 = 0x0A;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Alignment {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Default)]
+pub struct EscPosBuilder {
+    bytes: Vec<u8>,
+}
+
+impl EscPosBuilder {
+    pub fn new() -> Self {
+        Self {
+            bytes: vec![ESC, b'@'],
+        }
+    }
+
+    pub fn align(mut self, alignment: Alignment) -> Self {
+        let n = match alignment {
+            Alignment::Left => 0,
+            Alignment::Center => 1,
+            Alignment::Right => 2,
+        };
+        self.bytes.extend_from_slice(&[ESC, b'a', n]);
+
+        self
+    }
+
+    pub fn bold(mut self, on: bool) -> Self {
+        self.bytes.extend_from_slice(&[ESC, b'E', u8::from(on)]);
+
+        self
+    }
+
+    pub fn double_size(mut self, on: bool) -> Self {
+        let n = if on { 0x11 } else { 0x00 };
+        self.bytes.extend_from_slice(&[GS, b'!', n]);
+
+        self
+    }
+
+    pub fn text(mut self, text: &str) -> Self {
+        self.bytes.extend(
+            text.chars()
+                .map(|c| if c.is_ascii() { c as u8 } else { b'?' }),
+        );
+
+        self
+    }
+
+    pub fn line(self, text: &str) -> Self {
+        self.text(text).feed(1)
+    }
+
+    pub fn feed(mut self, lines: u8) -> Self {
+        match lines {
+            0 => {}
+            1 => self.bytes.push(LF),
+            n => self.bytes.extend_from_slice(&[ESC, b'd', n]),
+        }
+
+        self
+    }
+
+    pub fn cut(mut self) -> Self {
+        self.bytes.extend_from_slice(&[GS, b'V', 0x42, 0x00]);
+
+        self
+    }
+
+    pub fn build(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Alignment, EscPosBuilder};
+
+    #[test]
+    fn given_new_builder_then_printer_is_initialized() {
+        assert_eq!(EscPosBuilder::new().build(), vec![0x1B, b'@']);
+    }
+
+    #[test]
+    fn given_builder_when_formatting_commands_then_escpos_bytes_are_emitted() {
+        let bytes = EscPosBuilder::new()
+            .align(Alignment::Center)
+            .bold(true)
+            .line("Hi")
+            .bold(false)
+            .feed(3)
+            .cut()
+            .build();
+
+        assert_eq!(
+            bytes,
+            vec![
+                0x1B, b'@', 0x1B, b'a', 1, 0x1B, b'E', 1, b'H', b'i', 0x0A, 0x1B, b'E', 0, 0x1B,
+                b'd', 3, 0x1D, b'V', 0x42, 0x00
+            ]
+        );
+    }
+
+    #[test]
+    fn given_non_ascii_text_then_it_is_replaced() {
+        let bytes = EscPosBuilder::new().text("Café").build();
+
+        assert_eq!(&bytes[2..], b"Caf?");
+    }
+}
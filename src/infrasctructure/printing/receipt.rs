@@ -0,0 +1,648 @@
+use std::io::Write;
+
+use fluent_bundle::FluentArgs;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::Serialize;
+
+use crate::{
+    domain::tab::{event::TabEvent, tab_id::TabId},
+    infrasctructure::localization::{args, messages, DEFAULT_LOCALE},
+    shared_kernel::money::Currency,
+};
+
+use super::escpos::{Alignment, EscPosBuilder};
+
+#[derive(Clone, Debug)]
+pub struct ReceiptConfig {
+    header: Vec<String>,
+    footer: Vec<String>,
+    tax_rate: Decimal,
+    decimal_places: u32,
+    currency_symbol: String,
+    width: usize,
+    locale: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Receipt {
+    pub tab_id: TabId,
+    pub table: usize,
+    pub currency: Currency,
+    pub header: Vec<String>,
+    pub lines: Vec<ReceiptLine>,
+    pub discounts: Vec<ReceiptAdjustment>,
+    pub subtotal: Decimal,
+    pub tax_rate: Decimal,
+    pub tax: Decimal,
+    pub total: Decimal,
+    pub payments: Vec<ReceiptAdjustment>,
+    pub tip: Decimal,
+    pub footer: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ReceiptLine {
+    pub menu_number: usize,
+    pub description: String,
+    pub quantity: usize,
+    pub unit_price: Decimal,
+    pub total: Decimal,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ReceiptAdjustment {
+    pub description: String,
+    pub amount: Decimal,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ReceiptRenderer {
+    config: ReceiptConfig,
+}
+
+impl ReceiptConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_header(self, lines: &[&str]) -> Self {
+        Self {
+            header: lines.iter().map(|l| l.to_string()).collect(),
+            ..self
+        }
+    }
+
+    pub fn with_footer(self, lines: &[&str]) -> Self {
+        Self {
+            footer: lines.iter().map(|l| l.to_string()).collect(),
+            ..self
+        }
+    }
+
+    pub fn with_tax_rate(self, tax_rate: Decimal) -> Self {
+        Self { tax_rate, ..self }
+    }
+
+    pub fn with_decimal_places(self, decimal_places: u32) -> Self {
+        Self {
+            decimal_places,
+            ..self
+        }
+    }
+
+    pub fn with_currency_symbol(self, symbol: &str) -> Self {
+        Self {
+            currency_symbol: symbol.to_owned(),
+            ..self
+        }
+    }
+
+    pub fn with_width(self, width: usize) -> Self {
+        Self { width, ..self }
+    }
+
+    pub fn with_locale(self, locale: &str) -> Self {
+        Self {
+            locale: locale.to_owned(),
+            ..self
+        }
+    }
+
+    pub fn format_amount(&self, amount: Decimal) -> String {
+        let dp = self.decimal_places as usize;
+        let rounded = amount
+            .round_dp_with_strategy(self.decimal_places, RoundingStrategy::MidpointAwayFromZero);
+
+        format!("{rounded:.dp$}")
+    }
+
+    fn label(&self, id: &str, args: Option<&FluentArgs>) -> String {
+        messages().format(&self.locale, id, args)
+    }
+}
+
+impl Default for ReceiptConfig {
+    fn default() -> Self {
+        Self {
+            header: Vec::new(),
+            footer: Vec::new(),
+            tax_rate: Decimal::ZERO,
+            decimal_places: 2,
+            currency_symbol: String::new(),
+            width: 42,
+            locale: DEFAULT_LOCALE.to_owned(),
+        }
+    }
+}
+
+impl ReceiptRenderer {
+    pub fn new(config: ReceiptConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn receipt(&self, events: &[TabEvent]) -> Receipt {
+        let mut receipt = Receipt {
+            header: self.config.header.clone(),
+            footer: self.config.footer.clone(),
+            tax_rate: self.config.tax_rate,
+            ..Default::default()
+        };
+        for event in events {
+            match event {
+                TabEvent::TabOpened { id, table, .. } => {
+                    receipt.tab_id = *id;
+                    receipt.table = *table;
+                }
+                TabEvent::FoodOrderPlaced { menu_item, .. }
+                | TabEvent::DrinkOrderPlaced { menu_item, .. } => {
+                    let quantity = menu_item.quantity;
+                    let unit_price = menu_item.price.amount();
+                    let total = unit_price * Decimal::from(quantity);
+                    receipt.currency = menu_item.price.currency();
+                    match receipt.lines.iter_mut().find(|l| {
+                        l.menu_number == menu_item.menu_number && l.unit_price == unit_price
+                    }) {
+                        Some(line) => {
+                            line.quantity += quantity;
+                            line.total += total;
+                        }
+                        None => receipt.lines.push(ReceiptLine {
+                            menu_number: menu_item.menu_number,
+                            description: menu_item.description.clone(),
+                            quantity,
+                            unit_price,
+                            total,
+                        }),
+                    }
+                }
+                TabEvent::PromotionApplied { promotion, .. } => {
+                    receipt.discounts.push(ReceiptAdjustment {
+                        description: promotion.name.clone(),
+                        amount: promotion.discount.amount(),
+                    });
+                }
+                TabEvent::GiftCardPaymentReceived { payment, .. } => {
+                    receipt.payments.push(ReceiptAdjustment {
+                        description: self.config.label(
+                            "receipt-gift-card",
+                            Some(&args(&[("code", masked_code(&payment.code).into())])),
+                        ),
+                        amount: payment.amount.amount(),
+                    });
+                }
+                TabEvent::TabClosed {
+                    amount_paid,
+                    tip_value,
+                    amount_tendered,
+                    exchange_rate,
+                    ..
+                } => {
+                    let description = match exchange_rate {
+                        Some(rate) => self.config.label(
+                            "receipt-paid-foreign",
+                            Some(&args(&[
+                                ("tendered", amount_tendered.to_string().into()),
+                                ("rate", rate.rate().normalize().to_string().into()),
+                            ])),
+                        ),
+                        None => self.config.label("receipt-paid", None),
+                    };
+                    receipt.payments.push(ReceiptAdjustment {
+                        description,
+                        amount: amount_paid.amount(),
+                    });
+                    receipt.tip = tip_value.amount();
+                }
+                _ => {}
+            }
+        }
+        receipt.subtotal = receipt.lines.iter().map(|l| l.total).sum();
+        receipt.total =
+            receipt.subtotal - receipt.discounts.iter().map(|d| d.amount).sum::<Decimal>();
+        receipt.tax = if receipt.tax_rate.is_zero() {
+            Decimal::ZERO
+        } else {
+            receipt.total * receipt.tax_rate / (Decimal::ONE + receipt.tax_rate)
+        };
+
+        receipt
+    }
+
+    pub fn render_text(&self, receipt: &Receipt) -> String {
+        self.text_lines(receipt)
+            .into_iter()
+            .map(|(_, line)| line + "\n")
+            .collect()
+    }
+
+    pub fn render_escpos(&self, receipt: &Receipt) -> Vec<u8> {
+        let mut builder = EscPosBuilder::new();
+        for (style, line) in self.text_lines(receipt) {
+            builder = match style {
+                LineStyle::Header => builder
+                    .align(Alignment::Center)
+                    .bold(true)
+                    .line(line.trim())
+                    .bold(false)
+                    .align(Alignment::Left),
+                LineStyle::Footer => builder
+                    .align(Alignment::Center)
+                    .line(line.trim())
+                    .align(Alignment::Left),
+                LineStyle::Total => builder.bold(true).line(&line).bold(false),
+                LineStyle::Body => builder.line(&line),
+            };
+        }
+
+        builder.feed(3).cut().build()
+    }
+
+    pub fn write_escpos<W: Write>(&self, receipt: &Receipt, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(&self.render_escpos(receipt))?;
+        writer.flush()
+    }
+
+    pub fn render_json(&self, receipt: &Receipt) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(receipt)
+    }
+
+    fn text_lines(&self, receipt: &Receipt) -> Vec<(LineStyle, String)> {
+        let config = &self.config;
+        let mut lines = Vec::new();
+        for header in receipt.header.iter() {
+            lines.push((LineStyle::Header, self.centered(header)));
+        }
+        lines.push((
+            LineStyle::Body,
+            config.label(
+                "receipt-table",
+                Some(&args(&[("table", receipt.table.into())])),
+            ),
+        ));
+        lines.push((LineStyle::Body, self.rule()));
+        for line in receipt.lines.iter() {
+            lines.push((
+                LineStyle::Body,
+                self.columns(
+                    &format!("{} x {}", line.quantity, line.description),
+                    &config.format_amount(line.total),
+                ),
+            ));
+        }
+        lines.push((LineStyle::Body, self.rule()));
+        lines.push((
+            LineStyle::Body,
+            self.columns(
+                &config.label("receipt-subtotal", None),
+                &config.format_amount(receipt.subtotal),
+            ),
+        ));
+        for discount in receipt.discounts.iter() {
+            lines.push((
+                LineStyle::Body,
+                self.columns(
+                    &discount.description,
+                    &format!("-{}", config.format_amount(discount.amount)),
+                ),
+            ));
+        }
+        if !receipt.tax_rate.is_zero() {
+            let rate = (receipt.tax_rate * Decimal::ONE_HUNDRED).normalize();
+            lines.push((
+                LineStyle::Body,
+                self.columns(
+                    &config.label(
+                        "receipt-tax",
+                        Some(&args(&[("rate", rate.to_string().into())])),
+                    ),
+                    &config.format_amount(receipt.tax),
+                ),
+            ));
+        }
+        lines.push((
+            LineStyle::Total,
+            self.columns(
+                &config.label("receipt-total", None),
+                &format!(
+                    "{}{}",
+                    config.currency_symbol,
+                    config.format_amount(receipt.total)
+                ),
+            ),
+        ));
+        for payment in receipt.payments.iter() {
+            lines.push((
+                LineStyle::Body,
+                self.columns(&payment.description, &config.format_amount(payment.amount)),
+            ));
+        }
+        if !receipt.tip.is_zero() {
+            lines.push((
+                LineStyle::Body,
+                self.columns(
+                    &config.label("receipt-tip", None),
+                    &config.format_amount(receipt.tip),
+                ),
+            ));
+        }
+        for footer in receipt.footer.iter() {
+            lines.push((LineStyle::Footer, self.centered(footer)));
+        }
+
+        lines
+    }
+
+    fn columns(&self, left: &str, right: &str) -> String {
+        let width = self.config.width;
+        let right_len = right.chars().count();
+        let max_left = width.saturating_sub(right_len + 1);
+        let left: String = left.chars().take(max_left).collect();
+        let padding = width.saturating_sub(left.chars().count() + right_len);
+
+        format!("{left}{}{right}", " ".repeat(padding))
+    }
+
+    fn centered(&self, text: &str) -> String {
+        let padding = self.config.width.saturating_sub(text.chars().count()) / 2;
+
+        format!("{}{text}", " ".repeat(padding))
+    }
+
+    fn rule(&self) -> String {
+        "-".repeat(self.config.width)
+    }
+}
+
+enum LineStyle {
+    Header,
+    Body,
+    Total,
+    Footer,
+}
+
+// Only the last four characters, so a receipt cannot be used to spend the card.
+fn masked_code(code: &str) -> String {
+    let chars: Vec<char> = code.chars().collect();
+    let visible: String = chars[chars.len().saturating_sub(4)..].iter().collect();
+
+    format!("*{visible}")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chrono::Utc;
+    use rust_decimal::Decimal;
+
+    use crate::{
+        domain::{
+            gift_card::redemption_id::RedemptionId,
+            tab::{
+                event::{AppliedPromotion, GiftCardPayment, MenuItem, TabEvent},
+                order_priority::OrderPriority,
+                tab_id::TabId,
+                waiter_id::WaiterId,
+            },
+        },
+        shared_kernel::{
+            exchange_rates::ExchangeRate,
+            fixtures::usd,
+            money::{Currency, Money},
+        },
+    };
+
+    use super::{ReceiptConfig, ReceiptRenderer};
+
+    fn closed_tab_events(tab_id: TabId) -> Vec<TabEvent> {
+        let steak = MenuItem {
+            menu_number: 1,
+            description: "Steak".into(),
+            price: usd(Decimal::from_str("10.50").unwrap()),
+            quantity: 1,
+            priority: OrderPriority::Normal,
+            notes: None,
+            ordered_at: None,
+        };
+        vec![
+            TabEvent::TabOpened {
+                id: tab_id,
+                waiter_id: WaiterId::new(),
+                table: 3,
+            },
+            TabEvent::FoodOrderPlaced {
+                id: tab_id,
+                menu_item: steak.clone(),
+            },
+            TabEvent::FoodOrderPlaced {
+                id: tab_id,
+                menu_item: steak,
+            },
+            TabEvent::DrinkOrderPlaced {
+                id: tab_id,
+                menu_item: MenuItem {
+                    menu_number: 2,
+                    description: "Coca-Cola".into(),
+                    price: usd(Decimal::from(4)),
+                    quantity: 1,
+                    priority: OrderPriority::Normal,
+                    notes: None,
+                    ordered_at: None,
+                },
+            },
+            TabEvent::TabClosed {
+                id: tab_id,
+                amount_paid: usd(Decimal::from(30)),
+                order_value: usd(Decimal::from(25)),
+                tip_value: usd(Decimal::from(5)),
+                amount_tendered: usd(Decimal::from(30)),
+                exchange_rate: None,
+            },
+        ]
+    }
+
+    fn renderer() -> ReceiptRenderer {
+        ReceiptRenderer::new(
+            ReceiptConfig::new()
+                .with_header(&["Cafe Tab"])
+                .with_footer(&["Thank you!"])
+                .with_tax_rate(Decimal::from_str("0.15").unwrap())
+                .with_currency_symbol("$")
+                .with_width(32),
+        )
+    }
+
+    #[test]
+    fn given_closed_tab_events_then_receipt_has_lines_totals_tax_tip_and_payments() {
+        let tab_id = TabId::new();
+
+        let receipt = renderer().receipt(&closed_tab_events(tab_id));
+
+        assert_eq!(receipt.tab_id, tab_id);
+        assert_eq!(receipt.table, 3);
+        assert_eq!(receipt.lines.len(), 2);
+        assert_eq!(receipt.lines[0].quantity, 2);
+        assert_eq!(receipt.lines[0].total, Decimal::from(21));
+        assert_eq!(receipt.subtotal, Decimal::from(25));
+        assert_eq!(receipt.total, Decimal::from(25));
+        assert_eq!(receipt.tax.round_dp(2), Decimal::from_str("3.26").unwrap());
+        assert_eq!(receipt.payments[0].amount, Decimal::from(30));
+        assert_eq!(receipt.tip, Decimal::from(5));
+    }
+
+    #[test]
+    fn given_receipt_when_rendered_as_text_then_amounts_use_configured_decimals() {
+        let renderer = renderer();
+        let receipt = renderer.receipt(&closed_tab_events(TabId::new()));
+
+        let text = renderer.render_text(&receipt);
+
+        assert!(text.starts_with("            Cafe Tab\n"));
+        assert!(text.contains("2 x Steak                  21.00\n"));
+        assert!(text.contains("TOTAL                     $25.00\n"));
+        assert!(text.contains("Incl. tax 15%               3.26\n"));
+        assert!(text.contains("Tip                         5.00\n"));
+        assert!(text.ends_with("Thank you!\n"));
+    }
+
+    #[test]
+    fn given_locale_when_rendered_as_text_then_labels_are_translated() {
+        let renderer = ReceiptRenderer::new(
+            ReceiptConfig::new()
+                .with_tax_rate(Decimal::from_str("0.15").unwrap())
+                .with_width(32)
+                .with_locale("fr"),
+        );
+        let receipt = renderer.receipt(&closed_tab_events(TabId::new()));
+
+        let text = renderer.render_text(&receipt);
+
+        assert!(text.starts_with("Table 3\n"));
+        assert!(text.contains("Sous-total                 25.00\n"));
+        assert!(text.contains("Dont TVA 15 %               3.26\n"));
+        assert!(text.contains("Payé                       30.00\n"));
+        assert!(text.contains("Pourboire                   5.00\n"));
+    }
+
+    #[test]
+    fn given_receipt_when_written_as_escpos_then_stream_is_initialized_and_cut() {
+        let renderer = renderer();
+        let receipt = renderer.receipt(&closed_tab_events(TabId::new()));
+        let mut output = Vec::new();
+
+        renderer
+            .write_escpos(&receipt, &mut output)
+            .expect("failed to write receipt");
+
+        assert_eq!(&output[..2], &[0x1B, b'@']);
+        assert_eq!(&output[output.len() - 4..], &[0x1D, b'V', 0x42, 0x00]);
+        let printable = String::from_utf8_lossy(&output);
+        assert!(printable.contains("2 x Steak                  21.00"));
+    }
+
+    #[test]
+    fn given_receipt_when_rendered_as_json_then_structure_is_preserved() {
+        let renderer = renderer();
+        let receipt = renderer.receipt(&closed_tab_events(TabId::new()));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&renderer.render_json(&receipt).unwrap()).unwrap();
+
+        assert_eq!(json["table"], 3);
+        assert_eq!(json["lines"][0]["description"], "Steak");
+        assert_eq!(json["lines"][0]["quantity"], 2);
+        assert_eq!(json["tip"], "5");
+        assert_eq!(json["header"][0], "Cafe Tab");
+    }
+
+    #[test]
+    fn given_tab_paid_in_foreign_currency_then_payment_shows_tendered_amount_and_rate() {
+        let renderer = renderer();
+        let mut events = closed_tab_events(TabId::new());
+        if let Some(TabEvent::TabClosed {
+            amount_tendered,
+            exchange_rate,
+            ..
+        }) = events.last_mut()
+        {
+            *amount_tendered = Money::new(Decimal::from(25), Currency::EUR).unwrap();
+            *exchange_rate = Some(
+                ExchangeRate::new(
+                    Currency::EUR,
+                    Currency::USD,
+                    Decimal::from_str("1.20").unwrap(),
+                    Utc::now(),
+                )
+                .unwrap(),
+            );
+        }
+
+        let receipt = renderer.receipt(&events);
+
+        assert_eq!(receipt.payments[0].description, "Paid 25 EUR @ 1.2");
+        assert_eq!(receipt.payments[0].amount, Decimal::from(30));
+    }
+
+    #[test]
+    fn given_gift_card_payment_then_receipt_lists_it_with_a_masked_code() {
+        let renderer = renderer();
+        let tab_id = TabId::new();
+        let mut events = closed_tab_events(tab_id);
+        events.insert(
+            events.len() - 1,
+            TabEvent::GiftCardPaymentReceived {
+                id: tab_id,
+                payment: GiftCardPayment {
+                    code: "GC-2024-1001".into(),
+                    amount: usd(Decimal::from(20)),
+                    redemption: RedemptionId::new(),
+                },
+            },
+        );
+
+        let receipt = renderer.receipt(&events);
+
+        assert_eq!(receipt.payments.len(), 2);
+        assert_eq!(receipt.payments[0].description, "Gift card *1001");
+        assert_eq!(receipt.payments[0].amount, Decimal::from(20));
+    }
+
+    #[test]
+    fn given_promotion_then_receipt_lists_it_as_a_discount_off_the_total() {
+        let renderer = renderer();
+        let tab_id = TabId::new();
+        let mut events = closed_tab_events(tab_id);
+        events.insert(
+            events.len() - 1,
+            TabEvent::PromotionApplied {
+                id: tab_id,
+                promotion: AppliedPromotion {
+                    promotion_id: "happy-hour".into(),
+                    name: "Happy hour".into(),
+                    items: vec![(2, 1)],
+                    units: vec![],
+                    discount: usd(Decimal::from(2)),
+                },
+            },
+        );
+
+        let receipt = renderer.receipt(&events);
+
+        assert_eq!(receipt.discounts.len(), 1);
+        assert_eq!(receipt.discounts[0].description, "Happy hour");
+        assert_eq!(receipt.subtotal, Decimal::from(25));
+        assert_eq!(receipt.total, Decimal::from(23));
+        assert!(renderer.render_text(&receipt).contains("Happy hour"));
+    }
+
+    #[test]
+    fn format_amount_rounds_half_away_from_zero() {
+        let config = ReceiptConfig::new().with_decimal_places(1);
+
+        assert_eq!(
+            config.format_amount(Decimal::from_str("2.25").unwrap()),
+            "2.3"
+        );
+        assert_eq!(config.format_amount(Decimal::from(2)), "2.0");
+    }
+}
@@ -0,0 +1,153 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use tokio::{io::AsyncWriteExt, net::TcpStream};
+
+#[async_trait]
+pub trait PrinterSink: Send + Sync {
+    async fn print(&self, job: &[u8]) -> std::io::Result<()>;
+}
+
+#[derive(Clone, Debug)]
+pub struct FilePrinterSink {
+    path: PathBuf,
+}
+
+#[derive(Clone, Debug)]
+pub struct TcpPrinterSink {
+    address: String,
+    timeout: Duration,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MemoryPrinterSink {
+    jobs: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+impl FilePrinterSink {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl TcpPrinterSink {
+    pub fn new(host: &str, port: Option<u16>) -> Self {
+        let port = port.unwrap_or(9100);
+        Self {
+            address: format!("{host}:{port}"),
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self {
+            address: self.address,
+            timeout,
+        }
+    }
+}
+
+impl MemoryPrinterSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn jobs(&self) -> Vec<Vec<u8>> {
+        self.jobs.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl PrinterSink for FilePrinterSink {
+    async fn print(&self, job: &[u8]) -> std::io::Result<()> {
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        file.write_all(job).await?;
+        file.flush().await
+    }
+}
+
+#[async_trait]
+impl PrinterSink for TcpPrinterSink {
+    async fn print(&self, job: &[u8]) -> std::io::Result<()> {
+        let write = async {
+            let mut stream = TcpStream::connect(&self.address).await?;
+            stream.write_all(job).await?;
+            stream.shutdown().await
+        };
+        tokio::time::timeout(self.timeout, write)
+            .await
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "printer timed out"))?
+    }
+}
+
+#[async_trait]
+impl PrinterSink for MemoryPrinterSink {
+    async fn print(&self, job: &[u8]) -> std::io::Result<()> {
+        self.jobs.lock().unwrap().push(job.to_vec());
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<T: PrinterSink + ?Sized> PrinterSink for Arc<T> {
+    async fn print(&self, job: &[u8]) -> std::io::Result<()> {
+        self.as_ref().print(job).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{io::AsyncReadExt, net::TcpListener};
+
+    use super::{FilePrinterSink, MemoryPrinterSink, PrinterSink, TcpPrinterSink};
+
+    #[tokio::test]
+    async fn memory_sink_keeps_every_job() {
+        let sink = MemoryPrinterSink::new();
+
+        sink.print(b"one").await.unwrap();
+        sink.print(b"two").await.unwrap();
+
+        assert_eq!(sink.jobs(), vec![b"one".to_vec(), b"two".to_vec()]);
+    }
+
+    #[tokio::test]
+    async fn file_sink_appends_jobs_to_the_file() {
+        let path = std::env::temp_dir().join(format!("{}.prn", uuid::Uuid::new_v4()));
+        let sink = FilePrinterSink::new(&path);
+
+        sink.print(b"one").await.unwrap();
+        sink.print(b"two").await.unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"onetwo");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn tcp_sink_sends_job_to_raw_port() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut received = Vec::new();
+            socket.read_to_end(&mut received).await.unwrap();
+            received
+        });
+
+        TcpPrinterSink::new("127.0.0.1", Some(port))
+            .print(b"ticket")
+            .await
+            .unwrap();
+
+        assert_eq!(server.await.unwrap(), b"ticket");
+    }
+}
@@ -0,0 +1,149 @@
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::Sha256;
+
+pub const EVENT_HEADER: &str = "X-Cafe-Event";
+pub const DELIVERY_HEADER: &str = "X-Cafe-Delivery";
+pub const SIGNATURE_HEADER: &str = "X-Cafe-Signature";
+
+#[derive(Clone, Debug)]
+pub struct Webhook {
+    name: String,
+    url: String,
+    secret: Secret<String>,
+    event_types: Vec<String>,
+    timeout: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct WebhookMessage {
+    pub id: i64,
+    pub aggregate_id: String,
+    pub sequence: i64,
+    pub event_type: String,
+    pub payload: Value,
+    pub metadata: Value,
+}
+
+#[derive(Debug)]
+pub enum WebhookError {
+    Request(reqwest::Error),
+    Status(u16),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct WebhookClient {
+    client: reqwest::Client,
+}
+
+impl Webhook {
+    pub fn new(name: &str, url: &str, secret: Secret<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            url: url.to_string(),
+            secret,
+            event_types: Vec::new(),
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    // Narrows the events delivered to this webhook. Without a filter it gets
+    // every event type the outbox publishes, see `publish_event_types`.
+    pub fn with_event_types(self, event_types: &[&str]) -> Self {
+        Self {
+            event_types: event_types.iter().map(|t| t.to_string()).collect(),
+            ..self
+        }
+    }
+
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn event_types(&self) -> &[String] {
+        &self.event_types
+    }
+
+    pub fn sign(&self, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.expose_secret().as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(body);
+
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+}
+
+impl WebhookClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn deliver(
+        &self,
+        webhook: &Webhook,
+        message: &WebhookMessage,
+    ) -> Result<(), WebhookError> {
+        let body = serde_json::to_vec(message).expect("webhook messages serialize to JSON");
+        let response = self
+            .client
+            .post(&webhook.url)
+            .timeout(webhook.timeout)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, &message.event_type)
+            .header(DELIVERY_HEADER, message.id.to_string())
+            .header(SIGNATURE_HEADER, webhook.sign(&body))
+            .body(body)
+            .send()
+            .await
+            .map_err(WebhookError::Request)?;
+        if !response.status().is_success() {
+            return Err(WebhookError::Status(response.status().as_u16()));
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for WebhookError {}
+
+impl std::fmt::Display for WebhookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WebhookError::Request(e) => write!(f, "webhook request failed: {e}"),
+            WebhookError::Status(status) => write!(f, "webhook responded with status {status}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::Webhook;
+
+    fn webhook() -> Webhook {
+        Webhook::new("accounting", "http://localhost", Secret::new("key".into()))
+    }
+
+    #[test]
+    fn signature_is_hex_encoded_hmac_sha256_of_the_body() {
+        let actual = webhook().sign(b"The quick brown fox jumps over the lazy dog");
+
+        assert_eq!(
+            actual,
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+}
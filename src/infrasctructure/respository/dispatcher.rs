@@ -0,0 +1,540 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use cqrs_es::{persist::PersistenceError, AggregateError, CqrsFramework, EventStore};
+
+use crate::{
+    domain::tab::{aggregate::Tab, command::TabCommand, error::TabError},
+    shared_kernel::command_context::CommandContext,
+};
+
+use super::idempotency::{command_hash, Claim, CommandOutcome, IdempotencyStore};
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+const DEFAULT_BACKOFF: Duration = Duration::from_millis(10);
+const IN_FLIGHT_POLLS: u32 = 50;
+const IN_FLIGHT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Re-runs a command when another writer appended to the same tab between
+// loading the aggregate and committing. Each attempt reloads the aggregate,
+// so `Tab::handle` decides again against the latest state. Domain errors are
+// returned as they are.
+//
+// With an idempotency store, a command whose context carries an idempotency
+// key runs at most once; repeats get the original outcome back, and reusing
+// the key for a different command is rejected. Once the command has run,
+// failing to record its outcome is reported to the error handler rather than
+// returned, since the events are already committed.
+pub struct TabCommandDispatcher<ES: EventStore<Tab>> {
+    cqrs: Arc<CqrsFramework<Tab, ES>>,
+    idempotency: Option<Arc<dyn IdempotencyStore>>,
+    error_handler: Option<IdempotencyErrorHandler>,
+    max_attempts: u32,
+    backoff: Duration,
+}
+
+pub type IdempotencyErrorHandler = Box<dyn Fn(&str, PersistenceError) + Send + Sync>;
+
+impl<ES: EventStore<Tab>> TabCommandDispatcher<ES> {
+    pub fn new(cqrs: Arc<CqrsFramework<Tab, ES>>) -> Self {
+        Self {
+            cqrs,
+            idempotency: None,
+            error_handler: None,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            backoff: DEFAULT_BACKOFF,
+        }
+    }
+
+    pub fn with_max_attempts(self, max_attempts: u32) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            ..self
+        }
+    }
+
+    pub fn with_backoff(self, backoff: Duration) -> Self {
+        Self { backoff, ..self }
+    }
+
+    pub fn with_idempotency(self, idempotency: Arc<dyn IdempotencyStore>) -> Self {
+        Self {
+            idempotency: Some(idempotency),
+            ..self
+        }
+    }
+
+    pub fn with_error_handler(self, error_handler: IdempotencyErrorHandler) -> Self {
+        Self {
+            error_handler: Some(error_handler),
+            ..self
+        }
+    }
+
+    pub async fn execute(
+        &self,
+        aggregate_id: &str,
+        command: TabCommand,
+        context: &CommandContext,
+    ) -> Result<(), AggregateError<TabError>> {
+        let metadata = context.to_metadata();
+        match (&self.idempotency, context.idempotency_key()) {
+            (Some(idempotency), Some(key)) => {
+                self.execute_once(idempotency.as_ref(), key, aggregate_id, command, metadata)
+                    .await
+            }
+            _ => self.execute_retrying(aggregate_id, command, metadata).await,
+        }
+    }
+
+    async fn execute_once(
+        &self,
+        idempotency: &dyn IdempotencyStore,
+        key: &str,
+        aggregate_id: &str,
+        command: TabCommand,
+        metadata: HashMap<String, String>,
+    ) -> Result<(), AggregateError<TabError>> {
+        let hash = command_hash(&command)?;
+        for _ in 0..IN_FLIGHT_POLLS {
+            match idempotency.claim(key, aggregate_id, &hash).await? {
+                Claim::New => {
+                    let result = self.execute_retrying(aggregate_id, command, metadata).await;
+                    let recorded = match CommandOutcome::from_result(&result) {
+                        Some(outcome) => idempotency.complete(key, &outcome).await,
+                        None => idempotency.release(key).await,
+                    };
+                    if let (Err(e), Some(handler)) = (recorded, &self.error_handler) {
+                        handler(key, e);
+                    }
+                    return result;
+                }
+                Claim::Completed(outcome) => return outcome.into_result(),
+                Claim::KeyReused => {
+                    return Err(AggregateError::UserError(TabError::IdempotencyKeyReused))
+                }
+                Claim::InFlight => tokio::time::sleep(IN_FLIGHT_POLL_INTERVAL).await,
+            }
+        }
+
+        Err(AggregateError::UserError(TabError::CommandInFlight))
+    }
+
+    async fn execute_retrying(
+        &self,
+        aggregate_id: &str,
+        command: TabCommand,
+        metadata: HashMap<String, String>,
+    ) -> Result<(), AggregateError<TabError>> {
+        let mut attempt = 1;
+        loop {
+            let result = self
+                .cqrs
+                .execute_with_metadata(aggregate_id, command.clone(), metadata.clone())
+                .await;
+            match result {
+                Err(AggregateError::AggregateConflict) if attempt < self.max_attempts => {
+                    tokio::time::sleep(self.backoff * attempt).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use async_trait::async_trait;
+    use cqrs_es::{
+        mem_store::{MemStore, MemStoreAggregateContext},
+        persist::PersistenceError,
+        AggregateError, CqrsFramework, EventEnvelope, EventStore,
+    };
+
+    use crate::{
+        domain::tab::{
+            aggregate::Tab, command::TabCommand, error::TabError, event::TabEvent,
+            services::TabServices, tab_id::TabId, waiter_id::WaiterId,
+        },
+        infrasctructure::respository::idempotency::{Claim, CommandOutcome, IdempotencyStore},
+        shared_kernel::command_context::CommandContext,
+    };
+
+    use super::TabCommandDispatcher;
+
+    type Claimed = (String, String, Option<CommandOutcome>);
+
+    #[derive(Default)]
+    struct TestIdempotency {
+        outcomes: Mutex<HashMap<String, Claimed>>,
+        unavailable: bool,
+    }
+
+    #[async_trait]
+    impl IdempotencyStore for TestIdempotency {
+        async fn claim(
+            &self,
+            key: &str,
+            aggregate_id: &str,
+            command_hash: &str,
+        ) -> Result<Claim, PersistenceError> {
+            let mut outcomes = self.outcomes.lock().unwrap();
+            Ok(match outcomes.get(key) {
+                None => {
+                    let claimed = (aggregate_id.to_string(), command_hash.to_string(), None);
+                    outcomes.insert(key.to_string(), claimed);
+                    Claim::New
+                }
+                Some((claimed_for, hash, _))
+                    if claimed_for != aggregate_id || hash != command_hash =>
+                {
+                    Claim::KeyReused
+                }
+                Some((_, _, None)) => Claim::InFlight,
+                Some((_, _, Some(outcome))) => Claim::Completed(outcome.clone()),
+            })
+        }
+
+        async fn complete(
+            &self,
+            key: &str,
+            outcome: &CommandOutcome,
+        ) -> Result<(), PersistenceError> {
+            if self.unavailable {
+                return Err(PersistenceError::ConnectionError("store offline".into()));
+            }
+            if let Some((_, _, recorded)) = self.outcomes.lock().unwrap().get_mut(key) {
+                *recorded = Some(outcome.clone());
+            }
+
+            Ok(())
+        }
+
+        async fn release(&self, key: &str) -> Result<(), PersistenceError> {
+            self.outcomes.lock().unwrap().remove(key);
+
+            Ok(())
+        }
+    }
+
+    struct ConflictingStore {
+        inner: Arc<MemStore<Tab>>,
+        conflicts: Mutex<u32>,
+        loads: Arc<Mutex<u32>>,
+    }
+
+    #[async_trait]
+    impl EventStore<Tab> for ConflictingStore {
+        type AC = MemStoreAggregateContext<Tab>;
+
+        async fn load_events(
+            &self,
+            aggregate_id: &str,
+        ) -> Result<Vec<EventEnvelope<Tab>>, AggregateError<TabError>> {
+            self.inner.load_events(aggregate_id).await
+        }
+
+        async fn load_aggregate(
+            &self,
+            aggregate_id: &str,
+        ) -> Result<Self::AC, AggregateError<TabError>> {
+            *self.loads.lock().unwrap() += 1;
+            self.inner.load_aggregate(aggregate_id).await
+        }
+
+        async fn commit(
+            &self,
+            events: Vec<TabEvent>,
+            context: Self::AC,
+            metadata: HashMap<String, String>,
+        ) -> Result<Vec<EventEnvelope<Tab>>, AggregateError<TabError>> {
+            {
+                let mut conflicts = self.conflicts.lock().unwrap();
+                if *conflicts > 0 {
+                    *conflicts -= 1;
+                    return Err(AggregateError::AggregateConflict);
+                }
+            }
+            self.inner.commit(events, context, metadata).await
+        }
+    }
+
+    struct Fixture {
+        dispatcher: TabCommandDispatcher<ConflictingStore>,
+        events: Arc<MemStore<Tab>>,
+        loads: Arc<Mutex<u32>>,
+    }
+
+    fn fixture(conflicts: u32) -> Fixture {
+        let events = Arc::new(MemStore::default());
+        let loads = Arc::new(Mutex::new(0));
+        let store = ConflictingStore {
+            inner: events.clone(),
+            conflicts: Mutex::new(conflicts),
+            loads: loads.clone(),
+        };
+        let cqrs = CqrsFramework::new(store, vec![], TabServices::default());
+        let dispatcher = TabCommandDispatcher::new(Arc::new(cqrs))
+            .with_max_attempts(3)
+            .with_backoff(Duration::ZERO);
+
+        Fixture {
+            dispatcher,
+            events,
+            loads,
+        }
+    }
+
+    fn context() -> CommandContext {
+        CommandContext::new("waiter-7")
+    }
+
+    fn open_tab(id: TabId) -> TabCommand {
+        TabCommand::OpenTab {
+            id,
+            waiter_id: WaiterId::new(),
+            table: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn given_transient_conflicts_when_executing_then_command_is_retried_until_committed() {
+        let fixture = fixture(2);
+        let id = TabId::new();
+
+        fixture
+            .dispatcher
+            .execute(&id.to_string(), open_tab(id), &context())
+            .await
+            .unwrap();
+
+        let events = fixture.events.load_events(&id.to_string()).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(*fixture.loads.lock().unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn given_command_context_when_executing_then_events_carry_it() {
+        let fixture = fixture(0);
+        let id = TabId::new();
+        let context = context().with_terminal("bar-2");
+
+        fixture
+            .dispatcher
+            .execute(&id.to_string(), open_tab(id), &context)
+            .await
+            .unwrap();
+
+        let events = fixture.events.load_events(&id.to_string()).await.unwrap();
+        assert_eq!(CommandContext::from_envelope(&events[0]), Some(context));
+    }
+
+    #[tokio::test]
+    async fn given_persistent_conflicts_when_attempts_exhausted_then_conflict_is_returned() {
+        let fixture = fixture(10);
+        let id = TabId::new();
+
+        let actual = fixture
+            .dispatcher
+            .execute(&id.to_string(), open_tab(id), &context())
+            .await;
+
+        assert!(matches!(actual, Err(AggregateError::AggregateConflict)));
+        assert_eq!(*fixture.loads.lock().unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn given_domain_error_when_executing_then_command_is_not_retried() {
+        let fixture = fixture(0);
+        let id = TabId::new();
+
+        let actual = fixture
+            .dispatcher
+            .execute(
+                &id.to_string(),
+                TabCommand::MarkDrinksServed {
+                    id,
+                    items: vec![(1, 1)],
+                },
+                &context(),
+            )
+            .await;
+
+        assert!(matches!(
+            actual,
+            Err(AggregateError::UserError(TabError::TabNotOpened))
+        ));
+        assert_eq!(*fixture.loads.lock().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn given_idempotency_key_when_command_repeated_then_it_runs_once_and_outcome_is_replayed()
+    {
+        let fixture = fixture(0);
+        let dispatcher = fixture
+            .dispatcher
+            .with_idempotency(Arc::new(TestIdempotency::default()));
+        let id = TabId::new();
+        let context = context().with_idempotency_key("open-1");
+        let command = open_tab(id);
+
+        let first = dispatcher
+            .execute(&id.to_string(), command.clone(), &context)
+            .await;
+        let second = dispatcher.execute(&id.to_string(), command, &context).await;
+
+        assert!(first.is_ok());
+        assert!(second.is_ok());
+        let events = fixture.events.load_events(&id.to_string()).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(*fixture.loads.lock().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn given_rejected_command_when_repeated_with_same_key_then_original_error_is_returned() {
+        let fixture = fixture(0);
+        let dispatcher = fixture
+            .dispatcher
+            .with_idempotency(Arc::new(TestIdempotency::default()));
+        let id = TabId::new();
+        let keyed = context().with_idempotency_key("open-2");
+        dispatcher
+            .execute(&id.to_string(), open_tab(id), &context())
+            .await
+            .unwrap();
+
+        let command = open_tab(id);
+
+        let first = dispatcher
+            .execute(&id.to_string(), command.clone(), &keyed)
+            .await;
+        let second = dispatcher.execute(&id.to_string(), command, &keyed).await;
+
+        let expected = TabError::TabIsOpen { id };
+        assert!(matches!(first, Err(AggregateError::UserError(e)) if e == expected));
+        assert!(matches!(second, Err(AggregateError::UserError(e)) if e == expected));
+        assert_eq!(*fixture.loads.lock().unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn given_idempotency_key_used_for_another_tab_then_command_is_rejected_without_running() {
+        let fixture = fixture(0);
+        let dispatcher = fixture
+            .dispatcher
+            .with_idempotency(Arc::new(TestIdempotency::default()));
+        let (first, second) = (TabId::new(), TabId::new());
+        let context = context().with_idempotency_key("open-3");
+        dispatcher
+            .execute(&first.to_string(), open_tab(first), &context)
+            .await
+            .unwrap();
+
+        let actual = dispatcher
+            .execute(&second.to_string(), open_tab(second), &context)
+            .await;
+
+        assert!(matches!(
+            actual,
+            Err(AggregateError::UserError(TabError::IdempotencyKeyReused))
+        ));
+        let events = fixture
+            .events
+            .load_events(&second.to_string())
+            .await
+            .unwrap();
+        assert!(events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn given_outcome_cannot_be_recorded_when_command_commits_then_it_succeeds_and_is_reported(
+    ) {
+        let fixture = fixture(0);
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let sink = reported.clone();
+        let dispatcher = fixture
+            .dispatcher
+            .with_idempotency(Arc::new(TestIdempotency {
+                unavailable: true,
+                ..TestIdempotency::default()
+            }))
+            .with_error_handler(Box::new(move |key, _| {
+                sink.lock().unwrap().push(key.to_string())
+            }));
+        let id = TabId::new();
+
+        let actual = dispatcher
+            .execute(
+                &id.to_string(),
+                open_tab(id),
+                &context().with_idempotency_key("open-4"),
+            )
+            .await;
+
+        assert!(actual.is_ok());
+        assert_eq!(*reported.lock().unwrap(), vec!["open-4".to_string()]);
+        let events = fixture.events.load_events(&id.to_string()).await.unwrap();
+        assert_eq!(events.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn given_idempotency_key_reused_with_different_payload_then_command_is_rejected() {
+        let fixture = fixture(0);
+        let dispatcher = fixture
+            .dispatcher
+            .with_idempotency(Arc::new(TestIdempotency::default()));
+        let id = TabId::new();
+        let keyed = context().with_idempotency_key("serve-1");
+        dispatcher
+            .execute(&id.to_string(), open_tab(id), &context())
+            .await
+            .unwrap();
+        let serve = |items| TabCommand::MarkDrinksServed { id, items };
+        let _ = dispatcher
+            .execute(&id.to_string(), serve(vec![(1, 1)]), &keyed)
+            .await;
+
+        let actual = dispatcher
+            .execute(&id.to_string(), serve(vec![(2, 1)]), &keyed)
+            .await;
+
+        assert!(matches!(
+            actual,
+            Err(AggregateError::UserError(TabError::IdempotencyKeyReused))
+        ));
+        assert_eq!(*fixture.loads.lock().unwrap(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn given_earlier_command_still_running_when_polls_exhausted_then_in_flight_is_returned() {
+        let fixture = fixture(0);
+        let idempotency = Arc::new(TestIdempotency::default());
+        let dispatcher = fixture.dispatcher.with_idempotency(idempotency.clone());
+        let id = TabId::new();
+        let command = open_tab(id);
+        let hash = super::command_hash(&command).unwrap();
+        idempotency
+            .claim("open-5", &id.to_string(), &hash)
+            .await
+            .unwrap();
+
+        let actual = dispatcher
+            .execute(
+                &id.to_string(),
+                command,
+                &context().with_idempotency_key("open-5"),
+            )
+            .await;
+
+        assert!(matches!(
+            actual,
+            Err(AggregateError::UserError(TabError::CommandInFlight))
+        ));
+        assert_eq!(*fixture.loads.lock().unwrap(), 0);
+    }
+}
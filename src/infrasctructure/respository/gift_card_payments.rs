@@ -0,0 +1,706 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use cqrs_es::{persist::PersistenceError, AggregateError, CqrsFramework, EventStore};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    domain::{
+        gift_card::{
+            aggregate::GiftCard, command::GiftCardCommand, error::GiftCardError,
+            redemption_id::RedemptionId,
+        },
+        tab::{
+            aggregate::Tab, command::TabCommand, error::TabError, event::GiftCardPayment,
+            tab_id::TabId,
+        },
+    },
+    shared_kernel::{command_context::CommandContext, money::Money},
+};
+
+const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+pub enum GiftCardPaymentError {
+    GiftCard(AggregateError<GiftCardError>),
+    Tab(AggregateError<TabError>),
+    Log(PersistenceError),
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedemptionState {
+    Redeeming,
+    Redeemed,
+    Reversing,
+}
+
+// The tab command the card pays for, so `resume` runs the same one again.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedemptionPurpose {
+    #[default]
+    Payment,
+    CloseTab {
+        amount_paid: Money,
+    },
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PendingRedemption {
+    pub tab_id: TabId,
+    pub payment: GiftCardPayment,
+    pub state: RedemptionState,
+    #[serde(default)]
+    pub purpose: RedemptionPurpose,
+}
+
+// Where the saga keeps each redemption until the tab has the payment or the
+// card has its money back.
+#[async_trait]
+pub trait RedemptionLog: Send + Sync {
+    async fn record(&self, redemption: &PendingRedemption) -> Result<(), PersistenceError>;
+
+    async fn remove(&self, redemption: RedemptionId) -> Result<(), PersistenceError>;
+
+    // Redemptions whose state has not changed for at least `idle`.
+    async fn stalled(&self, idle: Duration) -> Result<Vec<PendingRedemption>, PersistenceError>;
+}
+
+pub type SagaErrorHandler = Box<dyn Fn(&PendingRedemption, GiftCardPaymentError) + Send + Sync>;
+
+// Runs tab commands that pay with a gift card as a saga. The card is redeemed
+// first, so its balance can never be spent twice, and the redemption is
+// reversed if the tab then rejects the payment. Each step is logged first, so
+// `resume` can finish a saga that was interrupted: the tab command of a
+// redeemed payment is run again, which the tab ignores for redemptions it
+// already has, and anything else is reversed, which the card ignores for
+// redemptions it never made.
+// Commands that don't involve a gift card go straight to the tab.
+pub struct GiftCardPayments<TES: EventStore<Tab>, GES: EventStore<GiftCard>> {
+    tabs: Arc<CqrsFramework<Tab, TES>>,
+    gift_cards: Arc<CqrsFramework<GiftCard, GES>>,
+    log: Arc<dyn RedemptionLog>,
+    error_handler: Option<SagaErrorHandler>,
+    stall_timeout: Duration,
+}
+
+impl<TES: EventStore<Tab>, GES: EventStore<GiftCard>> GiftCardPayments<TES, GES> {
+    pub fn new(
+        tabs: Arc<CqrsFramework<Tab, TES>>,
+        gift_cards: Arc<CqrsFramework<GiftCard, GES>>,
+        log: Arc<dyn RedemptionLog>,
+    ) -> Self {
+        Self {
+            tabs,
+            gift_cards,
+            log,
+            error_handler: None,
+            stall_timeout: DEFAULT_STALL_TIMEOUT,
+        }
+    }
+
+    pub fn with_error_handler(self, error_handler: SagaErrorHandler) -> Self {
+        Self {
+            error_handler: Some(error_handler),
+            ..self
+        }
+    }
+
+    pub fn with_stall_timeout(self, stall_timeout: Duration) -> Self {
+        Self {
+            stall_timeout,
+            ..self
+        }
+    }
+
+    pub async fn execute(
+        &self,
+        aggregate_id: &str,
+        command: TabCommand,
+        context: &CommandContext,
+    ) -> Result<(), GiftCardPaymentError> {
+        let metadata = context.to_metadata();
+        let Some((tab_id, payment, purpose)) = gift_card_payment(&command) else {
+            return Ok(self
+                .tabs
+                .execute_with_metadata(aggregate_id, command, metadata)
+                .await?);
+        };
+        let mut pending = PendingRedemption {
+            tab_id,
+            payment: GiftCardPayment {
+                redemption: RedemptionId::new(),
+                ..payment
+            },
+            state: RedemptionState::Redeeming,
+            purpose,
+        };
+        self.log.record(&pending).await?;
+        let redeem = GiftCardCommand::Redeem {
+            code: pending.payment.code.clone(),
+            tab_id,
+            amount: pending.payment.amount,
+            redemption: pending.payment.redemption,
+        };
+        match self
+            .gift_cards
+            .execute_with_metadata(&pending.payment.code, redeem, metadata.clone())
+            .await
+        {
+            Ok(()) => {}
+            Err(e @ AggregateError::UserError(_)) => {
+                self.forget(&pending).await;
+                return Err(e.into());
+            }
+            Err(e) => return Err(e.into()),
+        }
+        pending.state = RedemptionState::Redeemed;
+        if let Err(e) = self.log.record(&pending).await {
+            self.reverse(pending, metadata).await;
+            return Err(e.into());
+        }
+        let result = self
+            .tabs
+            .execute_with_metadata(aggregate_id, pending.tab_command(), metadata.clone())
+            .await;
+        match &result {
+            Ok(()) => self.forget(&pending).await,
+            Err(AggregateError::UserError(_) | AggregateError::AggregateConflict) => {
+                self.reverse(pending, metadata).await
+            }
+            // The payment may have been committed; `resume` will find out.
+            Err(_) => {}
+        }
+
+        Ok(result?)
+    }
+
+    // Finishes sagas that stalled part way, e.g. because the process stopped.
+    pub async fn resume(&self, context: &CommandContext) -> Result<usize, PersistenceError> {
+        let metadata = context.to_metadata();
+        let stalled = self.log.stalled(self.stall_timeout).await?;
+        let count = stalled.len();
+        for pending in stalled {
+            if pending.state != RedemptionState::Redeemed {
+                self.reverse(pending, metadata.clone()).await;
+                continue;
+            }
+            let command = pending.tab_command();
+            match self
+                .tabs
+                .execute_with_metadata(&pending.tab_id.to_string(), command, metadata.clone())
+                .await
+            {
+                Ok(()) => self.forget(&pending).await,
+                Err(AggregateError::UserError(_)) => self.reverse(pending, metadata.clone()).await,
+                Err(e) => self.report(&pending, e.into()),
+            }
+        }
+
+        Ok(count)
+    }
+
+    async fn reverse(&self, mut pending: PendingRedemption, metadata: HashMap<String, String>) {
+        pending.state = RedemptionState::Reversing;
+        if let Err(e) = self.log.record(&pending).await {
+            self.report(&pending, e.into());
+        }
+        let reverse = GiftCardCommand::ReverseRedemption {
+            code: pending.payment.code.clone(),
+            tab_id: pending.tab_id,
+            amount: pending.payment.amount,
+            redemption: pending.payment.redemption,
+        };
+        match self
+            .gift_cards
+            .execute_with_metadata(&pending.payment.code, reverse, metadata)
+            .await
+        {
+            Ok(()) => self.forget(&pending).await,
+            Err(e) => self.report(&pending, e.into()),
+        }
+    }
+
+    async fn forget(&self, pending: &PendingRedemption) {
+        if let Err(e) = self.log.remove(pending.payment.redemption).await {
+            self.report(pending, e.into());
+        }
+    }
+
+    fn report(&self, pending: &PendingRedemption, error: GiftCardPaymentError) {
+        if let Some(handler) = &self.error_handler {
+            handler(pending, error);
+        }
+    }
+}
+
+impl PendingRedemption {
+    fn tab_command(&self) -> TabCommand {
+        let payment = self.payment.clone();
+        match self.purpose {
+            RedemptionPurpose::Payment => TabCommand::PayWithGiftCard {
+                id: self.tab_id,
+                payment,
+            },
+            RedemptionPurpose::CloseTab { amount_paid } => TabCommand::CloseTab {
+                id: self.tab_id,
+                amount_paid,
+                gift_card: Some(payment),
+            },
+        }
+    }
+}
+
+impl From<AggregateError<GiftCardError>> for GiftCardPaymentError {
+    fn from(value: AggregateError<GiftCardError>) -> Self {
+        Self::GiftCard(value)
+    }
+}
+
+impl From<AggregateError<TabError>> for GiftCardPaymentError {
+    fn from(value: AggregateError<TabError>) -> Self {
+        Self::Tab(value)
+    }
+}
+
+impl From<PersistenceError> for GiftCardPaymentError {
+    fn from(value: PersistenceError) -> Self {
+        Self::Log(value)
+    }
+}
+
+impl std::error::Error for GiftCardPaymentError {}
+
+impl std::fmt::Display for GiftCardPaymentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GiftCardPaymentError::GiftCard(e) => write!(f, "{e}"),
+            GiftCardPaymentError::Tab(e) => write!(f, "{e}"),
+            GiftCardPaymentError::Log(e) => write!(f, "redemption log: {e}"),
+        }
+    }
+}
+
+fn gift_card_payment(command: &TabCommand) -> Option<(TabId, GiftCardPayment, RedemptionPurpose)> {
+    match command {
+        TabCommand::PayWithGiftCard { id, payment } => {
+            Some((*id, payment.clone(), RedemptionPurpose::Payment))
+        }
+        TabCommand::CloseTab {
+            id,
+            amount_paid,
+            gift_card: Some(payment),
+        } => Some((
+            *id,
+            payment.clone(),
+            RedemptionPurpose::CloseTab {
+                amount_paid: *amount_paid,
+            },
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use async_trait::async_trait;
+    use cqrs_es::{
+        mem_store::MemStore, Aggregate, AggregateError, CqrsFramework, EventEnvelope, Query,
+    };
+
+    use crate::{
+        domain::{
+            gift_card::{
+                aggregate::GiftCard, command::GiftCardCommand, error::GiftCardError,
+                event::GiftCardEvent, redemption_id::RedemptionId, services::GiftCardServices,
+            },
+            tab::{
+                aggregate::Tab,
+                command::{OrderItem, TabCommand},
+                error::TabError,
+                event::{GiftCardPayment, TabEvent},
+                order_priority::OrderPriority,
+                services::TabServices,
+                tab_id::TabId,
+                waiter_id::WaiterId,
+            },
+        },
+        infrasctructure::respository::memory::redemption_log::MemRedemptionLog,
+        shared_kernel::{command_context::CommandContext, fixtures::usd},
+    };
+
+    use super::{
+        GiftCardPaymentError, GiftCardPayments, PendingRedemption, RedemptionLog,
+        RedemptionPurpose, RedemptionState,
+    };
+
+    const CODE: &str = "GC-1001";
+
+    struct Recorded<A: Aggregate>(Arc<Mutex<Vec<A::Event>>>);
+
+    #[async_trait]
+    impl<A: Aggregate> Query<A> for Recorded<A> {
+        async fn dispatch(&self, _aggregate_id: &str, events: &[EventEnvelope<A>]) {
+            let mut recorded = self.0.lock().unwrap();
+            recorded.extend(events.iter().map(|e| e.payload.clone()));
+        }
+    }
+
+    struct Fixture {
+        payments: GiftCardPayments<MemStore<Tab>, MemStore<GiftCard>>,
+        tab_events: Arc<Mutex<Vec<TabEvent>>>,
+        gift_card_events: Arc<Mutex<Vec<GiftCardEvent>>>,
+        log: Arc<MemRedemptionLog>,
+        tab_id: TabId,
+    }
+
+    fn gift_card(amount: i64) -> GiftCardPayment {
+        GiftCardPayment {
+            code: CODE.into(),
+            amount: usd(amount),
+            redemption: RedemptionId::default(),
+        }
+    }
+
+    fn context() -> CommandContext {
+        CommandContext::new("cashier-1")
+    }
+
+    // A $20 tab and a $50 gift card.
+    async fn fixture() -> Fixture {
+        let tab_events = Arc::new(Mutex::new(Vec::new()));
+        let gift_card_events = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::new(MemRedemptionLog::new());
+        let payments = GiftCardPayments::new(
+            Arc::new(CqrsFramework::new(
+                MemStore::default(),
+                vec![Box::new(Recorded(tab_events.clone()))],
+                TabServices::default(),
+            )),
+            Arc::new(CqrsFramework::new(
+                MemStore::default(),
+                vec![Box::new(Recorded(gift_card_events.clone()))],
+                GiftCardServices::default(),
+            )),
+            log.clone(),
+        )
+        .with_stall_timeout(Duration::ZERO);
+        let tab_id = TabId::new();
+        payments
+            .gift_cards
+            .execute_with_metadata(
+                CODE,
+                GiftCardCommand::Issue {
+                    code: CODE.into(),
+                    amount: usd(50),
+                    expires_at: None,
+                },
+                context().to_metadata(),
+            )
+            .await
+            .unwrap();
+        for command in [
+            TabCommand::OpenTab {
+                id: tab_id,
+                waiter_id: WaiterId::new(),
+                table: 1,
+            },
+            TabCommand::PlaceOrder {
+                order_items: vec![OrderItem {
+                    menu_number: 7,
+                    description: "Wine".into(),
+                    is_drink: true,
+                    price: usd(20),
+                    notes: None,
+                }],
+                priority: OrderPriority::Normal,
+            },
+        ] {
+            payments
+                .execute(&tab_id.to_string(), command, &context())
+                .await
+                .unwrap();
+        }
+
+        Fixture {
+            payments,
+            tab_events,
+            gift_card_events,
+            log,
+            tab_id,
+        }
+    }
+
+    impl Fixture {
+        fn tab_events(&self) -> Vec<TabEvent> {
+            self.tab_events.lock().unwrap().clone()
+        }
+
+        fn gift_card_events(&self) -> Vec<GiftCardEvent> {
+            self.gift_card_events.lock().unwrap().clone()
+        }
+
+        async fn pending(&self) -> Vec<PendingRedemption> {
+            self.log.stalled(Duration::ZERO).await.unwrap()
+        }
+
+        // What the saga leaves behind when it stops after redeeming the card.
+        async fn interrupted(
+            &self,
+            state: RedemptionState,
+            purpose: RedemptionPurpose,
+        ) -> RedemptionId {
+            let redemption = RedemptionId::new();
+            self.payments
+                .gift_cards
+                .execute_with_metadata(
+                    CODE,
+                    GiftCardCommand::Redeem {
+                        code: CODE.into(),
+                        tab_id: self.tab_id,
+                        amount: usd(20),
+                        redemption,
+                    },
+                    context().to_metadata(),
+                )
+                .await
+                .unwrap();
+            self.log
+                .record(&PendingRedemption {
+                    tab_id: self.tab_id,
+                    payment: GiftCardPayment {
+                        redemption,
+                        ..gift_card(20)
+                    },
+                    state,
+                    purpose,
+                })
+                .await
+                .unwrap();
+
+            redemption
+        }
+    }
+
+    #[tokio::test]
+    async fn given_gift_card_when_tab_closed_with_it_then_card_is_charged_and_tab_closed() {
+        let fixture = fixture().await;
+
+        fixture
+            .payments
+            .execute(
+                &fixture.tab_id.to_string(),
+                TabCommand::CloseTab {
+                    id: fixture.tab_id,
+                    amount_paid: usd(0),
+                    gift_card: Some(gift_card(20)),
+                },
+                &context(),
+            )
+            .await
+            .unwrap();
+
+        let tab_events = fixture.tab_events();
+        let [TabEvent::GiftCardPaymentReceived { payment, .. }, TabEvent::TabClosed { .. }] =
+            &tab_events[2..]
+        else {
+            panic!("unexpected tab events: {tab_events:?}");
+        };
+        assert!(!payment.redemption.is_nil());
+        assert_eq!(
+            fixture.gift_card_events().last(),
+            Some(&GiftCardEvent::GiftCardRedeemed {
+                code: CODE.into(),
+                tab_id: fixture.tab_id,
+                amount: usd(20),
+                redemption: payment.redemption,
+            })
+        );
+        assert!(fixture.pending().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn given_tab_rejects_payment_when_paying_with_gift_card_then_redemption_is_reversed() {
+        let fixture = fixture().await;
+
+        let actual = fixture
+            .payments
+            .execute(
+                &fixture.tab_id.to_string(),
+                TabCommand::PayWithGiftCard {
+                    id: fixture.tab_id,
+                    payment: gift_card(30),
+                },
+                &context(),
+            )
+            .await;
+
+        assert!(matches!(
+            actual,
+            Err(GiftCardPaymentError::Tab(AggregateError::UserError(
+                TabError::GiftCardExceedsBalance { .. }
+            )))
+        ));
+        assert!(matches!(
+            fixture.gift_card_events()[1..],
+            [
+                GiftCardEvent::GiftCardRedeemed { .. },
+                GiftCardEvent::GiftCardRedemptionReversed { .. }
+            ]
+        ));
+        assert_eq!(fixture.tab_events().len(), 2);
+        assert!(fixture.pending().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn given_card_without_enough_balance_when_paying_then_tab_is_left_untouched() {
+        let fixture = fixture().await;
+
+        let actual = fixture
+            .payments
+            .execute(
+                &fixture.tab_id.to_string(),
+                TabCommand::CloseTab {
+                    id: fixture.tab_id,
+                    amount_paid: usd(0),
+                    gift_card: Some(gift_card(60)),
+                },
+                &context(),
+            )
+            .await;
+
+        assert!(matches!(
+            actual,
+            Err(GiftCardPaymentError::GiftCard(AggregateError::UserError(
+                GiftCardError::InsufficientBalance { .. }
+            )))
+        ));
+        assert_eq!(fixture.gift_card_events().len(), 1);
+        assert_eq!(fixture.tab_events().len(), 2);
+        assert!(fixture.pending().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn given_saga_stopped_after_redeeming_when_resumed_then_tab_is_paid_once() {
+        let fixture = fixture().await;
+        let redemption = fixture
+            .interrupted(RedemptionState::Redeemed, RedemptionPurpose::Payment)
+            .await;
+
+        let resumed = fixture.payments.resume(&context()).await.unwrap();
+        // As if the saga stopped again before forgetting the redemption.
+        fixture
+            .log
+            .record(&PendingRedemption {
+                tab_id: fixture.tab_id,
+                payment: GiftCardPayment {
+                    redemption,
+                    ..gift_card(20)
+                },
+                state: RedemptionState::Redeemed,
+                purpose: RedemptionPurpose::Payment,
+            })
+            .await
+            .unwrap();
+        fixture.payments.resume(&context()).await.unwrap();
+
+        assert_eq!(resumed, 1);
+        let payments: Vec<_> = fixture
+            .tab_events()
+            .into_iter()
+            .filter_map(|e| match e {
+                TabEvent::GiftCardPaymentReceived { payment, .. } => Some(payment.redemption),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(payments, vec![redemption]);
+        assert!(fixture.pending().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn given_saga_stopped_while_closing_tab_when_resumed_then_tab_is_closed_once() {
+        let fixture = fixture().await;
+        let purpose = RedemptionPurpose::CloseTab {
+            amount_paid: usd(2),
+        };
+        let redemption = fixture
+            .interrupted(RedemptionState::Redeemed, purpose.clone())
+            .await;
+
+        fixture.payments.resume(&context()).await.unwrap();
+        fixture
+            .log
+            .record(&PendingRedemption {
+                tab_id: fixture.tab_id,
+                payment: GiftCardPayment {
+                    redemption,
+                    ..gift_card(20)
+                },
+                state: RedemptionState::Redeemed,
+                purpose,
+            })
+            .await
+            .unwrap();
+        fixture.payments.resume(&context()).await.unwrap();
+
+        let tab_events = fixture.tab_events();
+        let [TabEvent::GiftCardPaymentReceived { payment, .. }, TabEvent::TabClosed { tip_value, .. }] =
+            &tab_events[2..]
+        else {
+            panic!("unexpected tab events: {tab_events:?}");
+        };
+        assert_eq!(payment.redemption, redemption);
+        assert_eq!(*tip_value, usd(2));
+        assert_eq!(fixture.gift_card_events().len(), 2);
+        assert!(fixture.pending().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn given_saga_stopped_before_logging_redemption_when_resumed_then_it_is_reversed() {
+        let fixture = fixture().await;
+        fixture
+            .interrupted(RedemptionState::Redeeming, RedemptionPurpose::Payment)
+            .await;
+
+        fixture.payments.resume(&context()).await.unwrap();
+
+        assert!(matches!(
+            fixture.gift_card_events()[1..],
+            [
+                GiftCardEvent::GiftCardRedeemed { .. },
+                GiftCardEvent::GiftCardRedemptionReversed { .. }
+            ]
+        ));
+        assert_eq!(fixture.tab_events().len(), 2);
+        assert!(fixture.pending().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn given_card_never_redeemed_when_resumed_then_nothing_is_reversed() {
+        let fixture = fixture().await;
+        fixture
+            .log
+            .record(&PendingRedemption {
+                tab_id: fixture.tab_id,
+                payment: GiftCardPayment {
+                    redemption: RedemptionId::new(),
+                    ..gift_card(20)
+                },
+                state: RedemptionState::Redeeming,
+                purpose: RedemptionPurpose::Payment,
+            })
+            .await
+            .unwrap();
+
+        fixture.payments.resume(&context()).await.unwrap();
+
+        assert_eq!(fixture.gift_card_events().len(), 1);
+        assert!(fixture.pending().await.is_empty());
+    }
+}
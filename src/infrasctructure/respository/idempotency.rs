@@ -0,0 +1,120 @@
+use async_trait::async_trait;
+use cqrs_es::{persist::PersistenceError, AggregateError};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::domain::tab::{
+    command::TabCommand,
+    error::{ErrorCategory, TabError},
+};
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum CommandOutcome {
+    Succeeded,
+    Rejected(TabError),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Claim {
+    New,
+    InFlight,
+    Completed(CommandOutcome),
+    KeyReused,
+}
+
+#[async_trait]
+pub trait IdempotencyStore: Send + Sync {
+    // Registers the key unless it has been seen before, in which case the
+    // state of the earlier command is returned. A claim that has not been
+    // completed within the store's lease is handed to the new caller, and a
+    // key registered for another aggregate or command is reported as reused.
+    async fn claim(
+        &self,
+        key: &str,
+        aggregate_id: &str,
+        command_hash: &str,
+    ) -> Result<Claim, PersistenceError>;
+
+    async fn complete(&self, key: &str, outcome: &CommandOutcome) -> Result<(), PersistenceError>;
+
+    // Forgets a claim whose command failed for infrastructure reasons, so
+    // the client may try again.
+    async fn release(&self, key: &str) -> Result<(), PersistenceError>;
+}
+
+pub fn command_hash(command: &TabCommand) -> Result<String, PersistenceError> {
+    Ok(hex::encode(Sha256::digest(serde_json::to_vec(command)?)))
+}
+
+impl CommandOutcome {
+    // Only outcomes a retry would repeat are recorded; internal failures are
+    // left for the client to try again.
+    pub fn from_result(result: &Result<(), AggregateError<TabError>>) -> Option<Self> {
+        match result {
+            Ok(()) => Some(CommandOutcome::Succeeded),
+            Err(AggregateError::UserError(e)) if e.category() != ErrorCategory::Internal => {
+                Some(CommandOutcome::Rejected(e.clone()))
+            }
+            Err(_) => None,
+        }
+    }
+
+    pub fn into_result(self) -> Result<(), AggregateError<TabError>> {
+        match self {
+            CommandOutcome::Succeeded => Ok(()),
+            CommandOutcome::Rejected(e) => Err(AggregateError::UserError(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cqrs_es::AggregateError;
+
+    use crate::{
+        domain::tab::{command::TabCommand, error::TabError, tab_id::TabId},
+        shared_kernel::{fixtures::usd, money::Currency},
+    };
+
+    use super::{command_hash, CommandOutcome};
+
+    #[test]
+    fn rejections_are_recorded_but_internal_errors_are_not() {
+        let rejected = Err(AggregateError::UserError(TabError::TabNotOpened));
+        let internal = Err(AggregateError::UserError(
+            TabError::ExchangeRateLookupFailed {
+                from: Currency::EUR,
+                to: Currency::USD,
+            },
+        ));
+
+        assert_eq!(
+            CommandOutcome::from_result(&rejected),
+            Some(CommandOutcome::Rejected(TabError::TabNotOpened))
+        );
+        assert_eq!(CommandOutcome::from_result(&internal), None);
+        assert_eq!(
+            CommandOutcome::from_result(&Ok(())),
+            Some(CommandOutcome::Succeeded)
+        );
+    }
+
+    #[test]
+    fn commands_with_different_payloads_hash_differently() {
+        let id = TabId::new();
+        let close = |amount| TabCommand::CloseTab {
+            id,
+            amount_paid: usd(amount),
+            gift_card: None,
+        };
+
+        assert_eq!(
+            command_hash(&close(10)).unwrap(),
+            command_hash(&close(10)).unwrap()
+        );
+        assert_ne!(
+            command_hash(&close(10)).unwrap(),
+            command_hash(&close(12)).unwrap()
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use std::sync::Arc;
+
+use cqrs_es::{mem_store::MemStore, persist::PersistenceError, CqrsFramework, Query};
+
+use crate::{
+    domain::{
+        gift_card::{aggregate::GiftCard, services::GiftCardServices},
+        tab::{
+            aggregate::Tab,
+            queries::{
+                bar::BarTodoList,
+                kitchen::KitchenTodoList,
+                kitchen_ticket::KitchenPrinter,
+                open_tabs::{TabStatus, WaiterTodoList},
+                simple_logging::SimpleLoggingQuery,
+            },
+            services::TabServices,
+        },
+    },
+    infrasctructure::respository::query_errors::{QueryErrorPolicy, ResilientQuery},
+};
+
+use super::view_repository::MemViewRepository;
+
+pub type MemTabCqrsFramework = Arc<CqrsFramework<Tab, MemStore<Tab>>>;
+
+pub type MemGiftCardCqrsFramework = Arc<CqrsFramework<GiftCard, MemStore<GiftCard>>>;
+
+type MemQuery<V> = ResilientQuery<MemViewRepository<V, Tab>, V>;
+
+pub fn mem_cqrs_tab(
+    services: TabServices,
+    waiter_todo_repo: Arc<MemViewRepository<WaiterTodoList, Tab>>,
+    repo: Arc<MemViewRepository<KitchenTodoList, Tab>>,
+    bar_todo_repo: Arc<MemViewRepository<BarTodoList, Tab>>,
+    open_tabs_repo: Arc<MemViewRepository<TabStatus, Tab>>,
+    kitchen_printer: Option<KitchenPrinter>,
+    policy: QueryErrorPolicy,
+) -> MemTabCqrsFramework {
+    let logging_query = SimpleLoggingQuery {};
+    let (kitchen_tab_query, waiter_tab_query, bar_tab_query, tab_status_query) = tab_queries(
+        waiter_todo_repo,
+        repo,
+        bar_todo_repo,
+        open_tabs_repo.clone(),
+        policy,
+    );
+    let mut queries: Vec<Box<dyn Query<Tab>>> = vec![
+        Box::new(kitchen_tab_query),
+        Box::new(waiter_tab_query),
+        Box::new(bar_tab_query),
+        Box::new(tab_status_query),
+    ];
+    if let Some(printer) = kitchen_printer {
+        queries.push(Box::new(printer.query(open_tabs_repo)));
+    }
+    queries.push(Box::new(logging_query));
+
+    Arc::new(CqrsFramework::new(MemStore::default(), queries, services))
+}
+
+pub fn mem_cqrs_gift_card(services: GiftCardServices) -> MemGiftCardCqrsFramework {
+    Arc::new(CqrsFramework::new(MemStore::default(), vec![], services))
+}
+
+// Re-applies events whose view writes failed once the cause has been fixed.
+pub async fn mem_redrive_tab_queries(
+    waiter_todo_repo: Arc<MemViewRepository<WaiterTodoList, Tab>>,
+    repo: Arc<MemViewRepository<KitchenTodoList, Tab>>,
+    bar_todo_repo: Arc<MemViewRepository<BarTodoList, Tab>>,
+    open_tabs_repo: Arc<MemViewRepository<TabStatus, Tab>>,
+    policy: QueryErrorPolicy,
+) -> Result<usize, PersistenceError> {
+    let (kitchen_tab_query, waiter_tab_query, bar_tab_query, tab_status_query) = tab_queries(
+        waiter_todo_repo,
+        repo,
+        bar_todo_repo,
+        open_tabs_repo,
+        policy,
+    );
+
+    Ok(kitchen_tab_query.redrive().await?
+        + waiter_tab_query.redrive().await?
+        + bar_tab_query.redrive().await?
+        + tab_status_query.redrive().await?)
+}
+
+fn tab_queries(
+    waiter_todo_repo: Arc<MemViewRepository<WaiterTodoList, Tab>>,
+    repo: Arc<MemViewRepository<KitchenTodoList, Tab>>,
+    bar_todo_repo: Arc<MemViewRepository<BarTodoList, Tab>>,
+    open_tabs_repo: Arc<MemViewRepository<TabStatus, Tab>>,
+    policy: QueryErrorPolicy,
+) -> (
+    MemQuery<KitchenTodoList>,
+    MemQuery<WaiterTodoList>,
+    MemQuery<BarTodoList>,
+    MemQuery<TabStatus>,
+) {
+    (
+        MemQuery::new("kitchen_tab_query", repo, policy.clone()),
+        MemQuery::new("waiter_tab_query", waiter_todo_repo, policy.clone()),
+        MemQuery::new("bar_tab_query", bar_todo_repo, policy.clone()),
+        MemQuery::new("tab_query", open_tabs_repo, policy),
+    )
+}
@@ -0,0 +1,74 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use cqrs_es::{persist::PersistenceError, EventEnvelope};
+
+use crate::{
+    domain::tab::aggregate::Tab,
+    infrasctructure::respository::query_errors::{DeadLetterStore, QueryDeadLetter},
+};
+
+#[derive(Default)]
+pub struct MemQueryDeadLetters {
+    letters: Mutex<(i64, Vec<QueryDeadLetter>)>,
+}
+
+impl MemQueryDeadLetters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl DeadLetterStore for MemQueryDeadLetters {
+    async fn record(
+        &self,
+        query: &str,
+        view_id: &str,
+        events: &[EventEnvelope<Tab>],
+        error: &str,
+        attempts: u32,
+    ) -> Result<(), PersistenceError> {
+        let mut letters = self.letters.lock().unwrap();
+        letters.0 += 1;
+        let id = letters.0;
+        letters.1.push(QueryDeadLetter {
+            id,
+            query: query.to_string(),
+            view_id: view_id.to_string(),
+            events: events.to_vec(),
+            error: error.to_string(),
+            attempts,
+        });
+
+        Ok(())
+    }
+
+    async fn load(&self, query: &str) -> Result<Vec<QueryDeadLetter>, PersistenceError> {
+        Ok(self
+            .letters
+            .lock()
+            .unwrap()
+            .1
+            .iter()
+            .filter(|l| l.query == query)
+            .cloned()
+            .collect())
+    }
+
+    async fn has_pending(&self, query: &str, view_id: &str) -> Result<bool, PersistenceError> {
+        Ok(self
+            .letters
+            .lock()
+            .unwrap()
+            .1
+            .iter()
+            .any(|l| l.query == query && l.view_id == view_id))
+    }
+
+    async fn remove(&self, id: i64) -> Result<(), PersistenceError> {
+        self.letters.lock().unwrap().1.retain(|l| l.id != id);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,4 @@
+pub mod cqrs;
+pub mod dead_letters;
+pub mod redemption_log;
+pub mod view_repository;
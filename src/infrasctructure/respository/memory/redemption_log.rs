@@ -0,0 +1,53 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use cqrs_es::persist::PersistenceError;
+
+use crate::{
+    domain::gift_card::redemption_id::RedemptionId,
+    infrasctructure::respository::gift_card_payments::{PendingRedemption, RedemptionLog},
+};
+
+#[derive(Default)]
+pub struct MemRedemptionLog {
+    redemptions: Mutex<HashMap<RedemptionId, (PendingRedemption, Instant)>>,
+}
+
+impl MemRedemptionLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl RedemptionLog for MemRedemptionLog {
+    async fn record(&self, redemption: &PendingRedemption) -> Result<(), PersistenceError> {
+        self.redemptions.lock().unwrap().insert(
+            redemption.payment.redemption,
+            (redemption.clone(), Instant::now()),
+        );
+
+        Ok(())
+    }
+
+    async fn remove(&self, redemption: RedemptionId) -> Result<(), PersistenceError> {
+        self.redemptions.lock().unwrap().remove(&redemption);
+
+        Ok(())
+    }
+
+    async fn stalled(&self, idle: Duration) -> Result<Vec<PendingRedemption>, PersistenceError> {
+        Ok(self
+            .redemptions
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|(_, updated_at)| updated_at.elapsed() >= idle)
+            .map(|(redemption, _)| redemption.clone())
+            .collect())
+    }
+}
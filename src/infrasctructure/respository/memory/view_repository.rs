@@ -0,0 +1,169 @@
+use std::{collections::HashMap, marker::PhantomData, sync::Mutex};
+
+use async_trait::async_trait;
+use cqrs_es::{
+    persist::{PersistenceError, ViewContext, ViewRepository},
+    Aggregate, View,
+};
+
+use crate::domain::tab::{
+    aggregate::Tab,
+    queries::{
+        bar::BarTodoList,
+        kitchen::{KitchenTodoList, TodoListItem},
+        open_tabs::{OpenTabQuery, OpenTabs, TabInvoice, TabStatus, WaiterTodoList},
+    },
+    tab_id::TabId,
+    waiter_id::WaiterId,
+};
+
+pub struct MemViewRepository<V, A> {
+    views: Mutex<HashMap<String, (V, i64)>>,
+    _phantom: PhantomData<A>,
+}
+
+impl<V, A> Default for MemViewRepository<V, A> {
+    fn default() -> Self {
+        Self {
+            views: Mutex::new(HashMap::new()),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<V, A> MemViewRepository<V, A>
+where
+    V: View<A> + Clone,
+    A: Aggregate,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn load_all(&self) -> Result<Vec<V>, PersistenceError> {
+        Ok(self
+            .views
+            .lock()
+            .unwrap()
+            .values()
+            .map(|(view, _)| view.clone())
+            .collect())
+    }
+}
+
+impl MemViewRepository<BarTodoList, Tab> {
+    pub async fn load_outstanding(&self) -> Result<Vec<BarTodoList>, PersistenceError> {
+        Ok(BarTodoList::outstanding(self.load_all().await?))
+    }
+}
+
+impl MemViewRepository<KitchenTodoList, Tab> {
+    pub async fn load_queue(&self) -> Result<Vec<(TabId, TodoListItem)>, PersistenceError> {
+        Ok(KitchenTodoList::queue(self.load_all().await?))
+    }
+}
+
+#[async_trait]
+impl<V, A> ViewRepository<V, A> for MemViewRepository<V, A>
+where
+    V: View<A> + Clone,
+    A: Aggregate,
+{
+    async fn load(&self, view_id: &str) -> Result<Option<V>, PersistenceError> {
+        Ok(self
+            .views
+            .lock()
+            .unwrap()
+            .get(view_id)
+            .map(|(view, _)| view.clone()))
+    }
+
+    async fn load_with_context(
+        &self,
+        view_id: &str,
+    ) -> Result<Option<(V, ViewContext)>, PersistenceError> {
+        Ok(self
+            .views
+            .lock()
+            .unwrap()
+            .get(view_id)
+            .map(|(view, version)| {
+                (
+                    view.clone(),
+                    ViewContext::new(view_id.to_string(), *version),
+                )
+            }))
+    }
+
+    async fn update_view(&self, view: V, context: ViewContext) -> Result<(), PersistenceError> {
+        let mut views = self.views.lock().unwrap();
+        let current_version = views.get(&context.view_instance_id).map(|(_, v)| *v);
+        if current_version.unwrap_or(0) != context.version {
+            return Err(PersistenceError::OptimisticLockError);
+        }
+        views.insert(context.view_instance_id, (view, context.version + 1));
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl OpenTabQuery for MemViewRepository<TabStatus, Tab> {
+    async fn active_table_numbers(&self) -> Result<Vec<usize>, PersistenceError> {
+        OpenTabs::new(self.load_all().await?)
+            .active_table_numbers()
+            .await
+    }
+
+    async fn invoice_for_table(
+        &self,
+        table: usize,
+    ) -> Result<Option<TabInvoice>, PersistenceError> {
+        OpenTabs::new(self.load_all().await?)
+            .invoice_for_table(table)
+            .await
+    }
+
+    async fn tab_for_table(&self, table: usize) -> Result<Option<TabStatus>, PersistenceError> {
+        OpenTabs::new(self.load_all().await?)
+            .tab_for_table(table)
+            .await
+    }
+
+    async fn waiter_todo_list(&self, id: WaiterId) -> Result<WaiterTodoList, PersistenceError> {
+        OpenTabs::new(self.load_all().await?)
+            .waiter_todo_list(id)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cqrs_es::persist::{PersistenceError, ViewContext, ViewRepository};
+
+    use crate::domain::tab::{aggregate::Tab, queries::kitchen::KitchenTodoList};
+
+    use super::MemViewRepository;
+
+    #[tokio::test]
+    async fn given_stale_context_when_view_updated_then_optimistic_lock_error() {
+        let repo = MemViewRepository::<KitchenTodoList, Tab>::new();
+        repo.update_view(
+            KitchenTodoList::default(),
+            ViewContext::new("tab".into(), 0),
+        )
+        .await
+        .expect("first update should succeed");
+
+        let result = repo
+            .update_view(
+                KitchenTodoList::default(),
+                ViewContext::new("tab".into(), 0),
+            )
+            .await;
+
+        assert!(matches!(result, Err(PersistenceError::OptimisticLockError)));
+        let (_, context) = repo.load_with_context("tab").await.unwrap().unwrap();
+        assert_eq!(context.version, 1);
+    }
+}
@@ -1 +1,9 @@
+pub mod dispatcher;
+pub mod gift_card_payments;
+pub mod idempotency;
+pub mod memory;
 pub mod postgresql;
+pub mod query_errors;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod upcasters;
@@ -1,37 +1,195 @@
 use std::sync::Arc;
 
-use cqrs_es::{persist::PersistedEventStore, CqrsFramework, Query};
-use postgres_es::{postgres_cqrs, PostgresEventRepository};
+use cqrs_es::{
+    persist::{PersistedEventStore, PersistenceError},
+    CqrsFramework, Query,
+};
+use postgres_es::{PostgresEventRepository, PostgresViewRepository};
 use sqlx::{Pool, Postgres};
 
 use crate::{
-    domain::tab::{
-        aggregate::Tab, queries::simple_logging::SimpleLoggingQuery, services::TabServices,
+    domain::{
+        gift_card::{aggregate::GiftCard, services::GiftCardServices},
+        tab::{
+            aggregate::Tab,
+            queries::{
+                bar::BarTodoList, kitchen::KitchenTodoList, kitchen_ticket::KitchenPrinter,
+                open_tabs::WaiterTodoList, simple_logging::SimpleLoggingQuery,
+            },
+            services::TabServices,
+        },
     },
+    infrasctructure::respository::{query_errors::QueryErrorPolicy, upcasters::tab_upcasters},
     shared_kernel::{
-        KitchenTabQuery, KitchenTabViewRepository, WaiterTabQuery, WaiterTabViewRepository,
+        money::Currency, BarTabQuery, BarTabViewRepository, KitchenTabQuery,
+        KitchenTabViewRepository, OpenTabsViewRepository, TabStatusQuery, WaiterTabQuery,
+        WaiterTabViewRepository,
     },
 };
 
+use super::{
+    dead_letters::PostgresQueryDeadLetters,
+    projector::{Projector, TabStatusTable, ViewTable},
+};
+
 pub type TabCqrsFramework =
     Arc<CqrsFramework<Tab, PersistedEventStore<PostgresEventRepository, Tab>>>;
 
+pub type GiftCardCqrsFramework =
+    Arc<CqrsFramework<GiftCard, PersistedEventStore<PostgresEventRepository, GiftCard>>>;
+
+#[allow(clippy::too_many_arguments)]
 pub fn cqrs_tab(
     pool: Pool<Postgres>,
     services: TabServices,
     waiter_todo_repo: WaiterTabViewRepository,
     repo: KitchenTabViewRepository,
+    bar_todo_repo: BarTabViewRepository,
+    open_tabs_repo: OpenTabsViewRepository,
+    kitchen_printer: Option<KitchenPrinter>,
+    policy: QueryErrorPolicy,
+    snapshot_size: Option<usize>,
 ) -> TabCqrsFramework {
-    let logging_query = SimpleLoggingQuery {};
-    let mut kitchen_tab_query = KitchenTabQuery::new(repo.into());
-    let mut waiter_tab_query = WaiterTabQuery::new(waiter_todo_repo.into());
-    kitchen_tab_query.use_error_handler(Box::new(|e| eprintln!("{e}")));
-    waiter_tab_query.use_error_handler(Box::new(|e| eprintln!("{e}")));
-    let queries: Vec<Box<dyn Query<Tab>>> = vec![
+    let (kitchen_tab_query, waiter_tab_query, bar_tab_query, tab_status_query) = tab_queries(
+        waiter_todo_repo,
+        repo,
+        bar_todo_repo,
+        open_tabs_repo.clone(),
+        tab_query_policy(&pool, services.currency(), policy),
+    );
+    let mut queries: Vec<Box<dyn Query<Tab>>> = vec![
         Box::new(kitchen_tab_query),
         Box::new(waiter_tab_query),
-        Box::new(logging_query),
+        Box::new(bar_tab_query),
+        Box::new(tab_status_query),
     ];
+    if let Some(printer) = kitchen_printer {
+        let open_tabs_repo: Arc<PostgresViewRepository<_, _>> = open_tabs_repo.into();
+        queries.push(Box::new(printer.query(open_tabs_repo)));
+    }
+    queries.push(Box::new(SimpleLoggingQuery {}));
+
+    Arc::new(CqrsFramework::new(
+        tab_store(pool, services.currency(), snapshot_size),
+        queries,
+        services,
+    ))
+}
+
+// Projections run in background projectors instead of the command path, so
+// commands return once their events are committed.
+pub fn cqrs_tab_with_projectors(
+    pool: Pool<Postgres>,
+    services: TabServices,
+    kitchen_printer: Option<KitchenPrinter>,
+    snapshot_size: Option<usize>,
+) -> (TabCqrsFramework, Vec<Projector>) {
+    let tab_status_table = match kitchen_printer {
+        Some(printer) => TabStatusTable::new("tab_query").with_kitchen_printer(printer),
+        None => TabStatusTable::new("tab_query"),
+    };
+    let currency = services.currency();
+    let projectors = vec![
+        Projector::new(
+            "kitchen_tab_query",
+            pool.clone(),
+            currency,
+            Box::new(ViewTable::<KitchenTodoList>::new("kitchen_tab_query")),
+        ),
+        Projector::new(
+            "waiter_tab_query",
+            pool.clone(),
+            currency,
+            Box::new(ViewTable::<WaiterTodoList>::new("waiter_tab_query")),
+        ),
+        Projector::new(
+            "bar_tab_query",
+            pool.clone(),
+            currency,
+            Box::new(ViewTable::<BarTodoList>::new("bar_tab_query")),
+        ),
+        Projector::new(
+            "tab_query",
+            pool.clone(),
+            currency,
+            Box::new(tab_status_table),
+        ),
+    ];
+    let store = tab_store(pool, services.currency(), snapshot_size);
+    let cqrs = CqrsFramework::new(store, vec![], services);
+
+    (Arc::new(cqrs), projectors)
+}
+
+pub fn cqrs_gift_card(pool: Pool<Postgres>, services: GiftCardServices) -> GiftCardCqrsFramework {
+    let store = PersistedEventStore::new_event_store(PostgresEventRepository::new(pool));
+
+    Arc::new(CqrsFramework::new(store, vec![], services))
+}
 
-    Arc::new(postgres_cqrs(pool, queries, services))
+// Re-applies events whose view writes failed once the cause has been fixed.
+pub async fn redrive_tab_queries(
+    pool: Pool<Postgres>,
+    legacy_currency: Currency,
+    waiter_todo_repo: WaiterTabViewRepository,
+    repo: KitchenTabViewRepository,
+    bar_todo_repo: BarTabViewRepository,
+    open_tabs_repo: OpenTabsViewRepository,
+) -> Result<usize, PersistenceError> {
+    let (kitchen_tab_query, waiter_tab_query, bar_tab_query, tab_status_query) = tab_queries(
+        waiter_todo_repo,
+        repo,
+        bar_todo_repo,
+        open_tabs_repo,
+        tab_query_policy(&pool, legacy_currency, QueryErrorPolicy::default()),
+    );
+
+    Ok(kitchen_tab_query.redrive().await?
+        + waiter_tab_query.redrive().await?
+        + bar_tab_query.redrive().await?
+        + tab_status_query.redrive().await?)
+}
+
+fn tab_query_policy(
+    pool: &Pool<Postgres>,
+    legacy_currency: Currency,
+    policy: QueryErrorPolicy,
+) -> QueryErrorPolicy {
+    let dead_letters = PostgresQueryDeadLetters::new(pool.clone(), legacy_currency);
+
+    policy.with_dead_letters(Arc::new(dead_letters))
+}
+
+fn tab_queries(
+    waiter_todo_repo: WaiterTabViewRepository,
+    repo: KitchenTabViewRepository,
+    bar_todo_repo: BarTabViewRepository,
+    open_tabs_repo: OpenTabsViewRepository,
+    policy: QueryErrorPolicy,
+) -> (KitchenTabQuery, WaiterTabQuery, BarTabQuery, TabStatusQuery) {
+    let kitchen_tab_query = KitchenTabQuery::new("kitchen_tab_query", repo.into(), policy.clone());
+    let waiter_tab_query =
+        WaiterTabQuery::new("waiter_tab_query", waiter_todo_repo.into(), policy.clone());
+    let bar_tab_query = BarTabQuery::new("bar_tab_query", bar_todo_repo.into(), policy.clone());
+    let tab_status_query = TabStatusQuery::new("tab_query", open_tabs_repo.into(), policy);
+
+    (
+        kitchen_tab_query,
+        waiter_tab_query,
+        bar_tab_query,
+        tab_status_query,
+    )
+}
+
+fn tab_store(
+    pool: Pool<Postgres>,
+    legacy_currency: Currency,
+    snapshot_size: Option<usize>,
+) -> PersistedEventStore<PostgresEventRepository, Tab> {
+    let repo = PostgresEventRepository::new(pool);
+    match snapshot_size {
+        Some(size) => PersistedEventStore::new_snapshot_store(repo, size),
+        None => PersistedEventStore::new_event_store(repo),
+    }
+    .with_upcasters(tab_upcasters(legacy_currency))
 }
@@ -0,0 +1,111 @@
+use async_trait::async_trait;
+use cqrs_es::{
+    persist::{EventUpcaster, PersistenceError},
+    EventEnvelope,
+};
+use sqlx::{Pool, Postgres, Row};
+
+use crate::{
+    domain::tab::aggregate::Tab,
+    infrasctructure::respository::{
+        query_errors::{events_from_json, events_to_json, DeadLetterStore, QueryDeadLetter},
+        upcasters::tab_upcasters,
+    },
+    shared_kernel::money::Currency,
+};
+
+use super::connection_error;
+
+const INSERT_DEAD_LETTER: &str = "
+INSERT INTO query_dead_letters (query, view_id, events, error, attempts)
+VALUES ($1, $2, $3, $4, $5)";
+
+const SELECT_DEAD_LETTERS: &str = "
+SELECT id, query, view_id, events, error, attempts
+  FROM query_dead_letters
+  WHERE query = $1
+  ORDER BY id";
+
+const HAS_DEAD_LETTERS: &str = "
+SELECT EXISTS (SELECT 1 FROM query_dead_letters WHERE query = $1 AND view_id = $2)";
+
+const DELETE_DEAD_LETTER: &str = "DELETE FROM query_dead_letters WHERE id = $1";
+
+pub struct PostgresQueryDeadLetters {
+    pool: Pool<Postgres>,
+    upcasters: Vec<Box<dyn EventUpcaster>>,
+}
+
+impl PostgresQueryDeadLetters {
+    pub fn new(pool: Pool<Postgres>, legacy_currency: Currency) -> Self {
+        Self {
+            pool,
+            upcasters: tab_upcasters(legacy_currency),
+        }
+    }
+}
+
+#[async_trait]
+impl DeadLetterStore for PostgresQueryDeadLetters {
+    async fn record(
+        &self,
+        query: &str,
+        view_id: &str,
+        events: &[EventEnvelope<Tab>],
+        error: &str,
+        attempts: u32,
+    ) -> Result<(), PersistenceError> {
+        sqlx::query(INSERT_DEAD_LETTER)
+            .bind(query)
+            .bind(view_id)
+            .bind(events_to_json(events)?)
+            .bind(error)
+            .bind(attempts as i32)
+            .execute(&self.pool)
+            .await
+            .map_err(connection_error)?;
+
+        Ok(())
+    }
+
+    async fn load(&self, query: &str) -> Result<Vec<QueryDeadLetter>, PersistenceError> {
+        let rows = sqlx::query(SELECT_DEAD_LETTERS)
+            .bind(query)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(connection_error)?;
+        let mut letters = Vec::with_capacity(rows.len());
+        for row in rows {
+            let attempts: i32 = row.get("attempts");
+            letters.push(QueryDeadLetter {
+                id: row.get("id"),
+                query: row.get("query"),
+                view_id: row.get("view_id"),
+                events: events_from_json(&self.upcasters, row.get("events"))?,
+                error: row.get("error"),
+                attempts: attempts as u32,
+            });
+        }
+
+        Ok(letters)
+    }
+
+    async fn has_pending(&self, query: &str, view_id: &str) -> Result<bool, PersistenceError> {
+        sqlx::query_scalar(HAS_DEAD_LETTERS)
+            .bind(query)
+            .bind(view_id)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(connection_error)
+    }
+
+    async fn remove(&self, id: i64) -> Result<(), PersistenceError> {
+        sqlx::query(DELETE_DEAD_LETTER)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(connection_error)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use cqrs_es::persist::PersistenceError;
+use rust_decimal::Decimal;
+use sqlx::{Pool, Postgres};
+
+use crate::shared_kernel::{
+    exchange_rates::{ExchangeRate, ExchangeRates},
+    money::Currency,
+};
+
+use super::connection_error;
+
+const INSERT_RATE: &str = "
+INSERT INTO exchange_rates (from_currency, to_currency, rate, effective_at)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT (from_currency, to_currency, effective_at) DO UPDATE SET rate = EXCLUDED.rate";
+
+const CURRENT_RATE: &str = "
+SELECT rate, effective_at
+  FROM exchange_rates
+  WHERE from_currency = $1 AND to_currency = $2 AND effective_at <= now()
+  ORDER BY effective_at DESC
+  LIMIT 1";
+
+// Rates are kept as history; the latest one already in effect is used.
+#[derive(Debug)]
+pub struct PostgresExchangeRates {
+    pool: Pool<Postgres>,
+}
+
+impl PostgresExchangeRates {
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+
+    pub async fn record(&self, rate: &ExchangeRate) -> Result<(), PersistenceError> {
+        sqlx::query(INSERT_RATE)
+            .bind(rate.from().code())
+            .bind(rate.to().code())
+            .bind(rate.rate())
+            .bind(rate.effective_at())
+            .execute(&self.pool)
+            .await
+            .map_err(connection_error)?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ExchangeRates for PostgresExchangeRates {
+    async fn rate(
+        &self,
+        from: Currency,
+        to: Currency,
+    ) -> Result<Option<ExchangeRate>, PersistenceError> {
+        let row: Option<(Decimal, DateTime<Utc>)> = sqlx::query_as(CURRENT_RATE)
+            .bind(from.code())
+            .bind(to.code())
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(connection_error)?;
+
+        row.map(|(rate, effective_at)| ExchangeRate::new(from, to, rate, effective_at))
+            .transpose()
+            .map_err(|e| PersistenceError::DeserializationError(Box::new(e)))
+    }
+}
@@ -0,0 +1,123 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use cqrs_es::persist::PersistenceError;
+use serde_json::Value;
+use sqlx::{Pool, Postgres};
+
+use crate::infrasctructure::respository::idempotency::{Claim, CommandOutcome, IdempotencyStore};
+
+use super::connection_error;
+
+const INSERT_KEY: &str = "
+INSERT INTO command_idempotency (idempotency_key, aggregate_id, command_hash)
+VALUES ($1, $2, $3)
+ON CONFLICT (idempotency_key) DO NOTHING";
+
+const TAKE_OVER_KEY: &str = "
+UPDATE command_idempotency
+  SET claimed_at = now()
+  WHERE idempotency_key = $1
+    AND aggregate_id = $2
+    AND (command_hash IS NULL OR command_hash = $3)
+    AND outcome IS NULL
+    AND claimed_at < now() - make_interval(secs => $4)";
+
+const SELECT_OUTCOME: &str = "
+SELECT aggregate_id, command_hash, outcome
+  FROM command_idempotency
+  WHERE idempotency_key = $1";
+
+const COMPLETE_KEY: &str = "
+UPDATE command_idempotency
+  SET outcome = $2, completed_at = now()
+  WHERE idempotency_key = $1 AND outcome IS NULL";
+
+const DELETE_KEY: &str =
+    "DELETE FROM command_idempotency WHERE idempotency_key = $1 AND outcome IS NULL";
+
+const DEFAULT_LEASE: Duration = Duration::from_secs(60);
+
+pub struct PostgresIdempotencyStore {
+    pool: Pool<Postgres>,
+    lease: Duration,
+}
+
+impl PostgresIdempotencyStore {
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self {
+            pool,
+            lease: DEFAULT_LEASE,
+        }
+    }
+
+    pub fn with_lease(self, lease: Duration) -> Self {
+        Self { lease, ..self }
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for PostgresIdempotencyStore {
+    async fn claim(
+        &self,
+        key: &str,
+        aggregate_id: &str,
+        command_hash: &str,
+    ) -> Result<Claim, PersistenceError> {
+        let inserted = sqlx::query(INSERT_KEY)
+            .bind(key)
+            .bind(aggregate_id)
+            .bind(command_hash)
+            .execute(&self.pool)
+            .await
+            .map_err(connection_error)?;
+        if inserted.rows_affected() == 1 {
+            return Ok(Claim::New);
+        }
+        let taken_over = sqlx::query(TAKE_OVER_KEY)
+            .bind(key)
+            .bind(aggregate_id)
+            .bind(command_hash)
+            .bind(self.lease.as_secs_f64())
+            .execute(&self.pool)
+            .await
+            .map_err(connection_error)?;
+        if taken_over.rows_affected() == 1 {
+            return Ok(Claim::New);
+        }
+        let claimed: Option<(String, Option<String>, Option<Value>)> =
+            sqlx::query_as(SELECT_OUTCOME)
+                .bind(key)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(connection_error)?;
+
+        match claimed {
+            Some((claimed_for, _, _)) if claimed_for != aggregate_id => Ok(Claim::KeyReused),
+            Some((_, Some(hash), _)) if hash != command_hash => Ok(Claim::KeyReused),
+            Some((_, _, Some(outcome))) => Ok(Claim::Completed(serde_json::from_value(outcome)?)),
+            _ => Ok(Claim::InFlight),
+        }
+    }
+
+    async fn complete(&self, key: &str, outcome: &CommandOutcome) -> Result<(), PersistenceError> {
+        sqlx::query(COMPLETE_KEY)
+            .bind(key)
+            .bind(serde_json::to_value(outcome)?)
+            .execute(&self.pool)
+            .await
+            .map_err(connection_error)?;
+
+        Ok(())
+    }
+
+    async fn release(&self, key: &str) -> Result<(), PersistenceError> {
+        sqlx::query(DELETE_KEY)
+            .bind(key)
+            .execute(&self.pool)
+            .await
+            .map_err(connection_error)?;
+
+        Ok(())
+    }
+}
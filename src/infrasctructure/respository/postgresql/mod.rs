@@ -1 +1,30 @@
+use cqrs_es::persist::{PersistenceError, SerializedEvent};
+use sqlx::{postgres::PgRow, Row};
+
 pub mod cqrs;
+pub mod dead_letters;
+pub mod exchange_rates;
+pub mod idempotency;
+pub mod outbox;
+pub mod projector;
+pub mod redemption_log;
+pub mod replay;
+pub mod subscription;
+
+fn connection_error(err: sqlx::Error) -> PersistenceError {
+    PersistenceError::ConnectionError(Box::new(err))
+}
+
+fn serialized_event(row: PgRow) -> SerializedEvent {
+    let sequence: i64 = row.get("sequence");
+
+    SerializedEvent::new(
+        row.get("aggregate_id"),
+        sequence as usize,
+        row.get("aggregate_type"),
+        row.get("event_type"),
+        row.get("event_version"),
+        row.get("payload"),
+        row.get("metadata"),
+    )
+}
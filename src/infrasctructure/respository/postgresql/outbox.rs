@@ -0,0 +1,463 @@
+use std::{sync::Arc, time::Duration};
+
+use cqrs_es::persist::{EventUpcaster, PersistenceError, SerializedEvent};
+use sqlx::{postgres::PgRow, Pool, Postgres, Row};
+use tokio::{sync::watch, task::JoinHandle};
+
+use crate::{
+    infrasctructure::{
+        publishing::webhook::{Webhook, WebhookClient, WebhookMessage},
+        respository::upcasters::{tab_upcasters, upcast_event},
+    },
+    shared_kernel::money::Currency,
+};
+
+use super::connection_error;
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 8;
+const DEFAULT_BASE_BACKOFF: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(300);
+const DEFAULT_BATCH_SIZE: i64 = 50;
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// Entries up to the webhook's cursor are all delivered or dead-lettered, so
+// only the tail after it is scanned.
+const PENDING_ENTRIES: &str = "
+SELECT o.id, o.aggregate_type, o.aggregate_id, o.sequence, o.event_type, o.event_version, o.payload, o.metadata,
+       COALESCE(d.attempts, 0) AS attempts
+  FROM outbox o
+  LEFT JOIN outbox_deliveries d ON d.webhook = $1 AND d.outbox_id = o.id
+  WHERE o.id > GREATEST($2, COALESCE((SELECT position FROM outbox_cursors WHERE webhook = $1), 0))
+    AND (cardinality($3::text[]) = 0 OR o.event_type = ANY($3))
+    AND d.delivered_at IS NULL
+    AND (d.next_attempt_at IS NULL OR d.next_attempt_at <= now())
+    AND NOT EXISTS (SELECT 1 FROM outbox_dead_letters x WHERE x.webhook = $1 AND x.outbox_id = o.id)
+  ORDER BY o.id
+  LIMIT $4";
+
+// Moves the cursor up to `$2`, but never past an entry still waiting for a
+// retry.
+const ADVANCE_CURSOR: &str = "
+INSERT INTO outbox_cursors (webhook, position)
+SELECT $1, LEAST($2, COALESCE(
+    (SELECT MIN(d.outbox_id) - 1
+       FROM outbox_deliveries d
+       WHERE d.webhook = $1
+         AND d.delivered_at IS NULL
+         AND NOT EXISTS (SELECT 1 FROM outbox_dead_letters x WHERE x.webhook = $1 AND x.outbox_id = d.outbox_id)),
+    $2))
+ON CONFLICT (webhook)
+  DO UPDATE SET position = GREATEST(outbox_cursors.position, EXCLUDED.position), updated_at = now()";
+
+const REWIND_CURSOR: &str = "
+UPDATE outbox_cursors c
+  SET position = LEAST(c.position, x.first - 1), updated_at = now()
+  FROM (SELECT MIN(outbox_id) AS first FROM outbox_dead_letters WHERE webhook = $1) x
+  WHERE c.webhook = $1 AND x.first IS NOT NULL";
+
+const RECORD_DELIVERY: &str = "
+INSERT INTO outbox_deliveries (webhook, outbox_id, attempts, next_attempt_at, delivered_at, last_error)
+VALUES ($1, $2, $3, now(), now(), NULL)
+ON CONFLICT (webhook, outbox_id)
+  DO UPDATE SET attempts = EXCLUDED.attempts, delivered_at = EXCLUDED.delivered_at, last_error = NULL";
+
+const RECORD_FAILURE: &str = "
+INSERT INTO outbox_deliveries (webhook, outbox_id, attempts, next_attempt_at, last_error)
+VALUES ($1, $2, $3, now() + $4 * interval '1 millisecond', $5)
+ON CONFLICT (webhook, outbox_id)
+  DO UPDATE SET attempts = EXCLUDED.attempts, next_attempt_at = EXCLUDED.next_attempt_at, last_error = EXCLUDED.last_error";
+
+const INSERT_DEAD_LETTER: &str = "
+INSERT INTO outbox_dead_letters (webhook, outbox_id, attempts, last_error)
+VALUES ($1, $2, $3, $4)";
+
+const SELECT_DEAD_LETTERS: &str = "
+SELECT outbox_id, attempts, last_error
+  FROM outbox_dead_letters
+  WHERE webhook = $1
+  ORDER BY outbox_id";
+
+const REQUEUE_DEAD_LETTERS: &str = "
+UPDATE outbox_deliveries d
+  SET attempts = 0, next_attempt_at = now()
+  FROM outbox_dead_letters x
+  WHERE x.webhook = $1 AND d.webhook = x.webhook AND d.outbox_id = x.outbox_id";
+
+const DELETE_DEAD_LETTERS: &str = "DELETE FROM outbox_dead_letters WHERE webhook = $1";
+
+const CLEAR_PUBLISHED_EVENT_TYPES: &str = "DELETE FROM outbox_published_event_types";
+
+const INSERT_PUBLISHED_EVENT_TYPES: &str = "
+INSERT INTO outbox_published_event_types (event_type)
+SELECT unnest($1::text[])";
+
+const SELECT_PUBLISHED_EVENT_TYPES: &str =
+    "SELECT event_type FROM outbox_published_event_types ORDER BY event_type";
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DispatchReport {
+    pub delivered: usize,
+    pub retried: usize,
+    pub dead_lettered: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeadLetter {
+    pub outbox_id: i64,
+    pub attempts: u32,
+    pub last_error: String,
+}
+
+pub type DispatchErrorHandler = Box<dyn Fn(&str, PersistenceError) + Send + Sync>;
+
+pub struct OutboxDispatcher {
+    pool: Pool<Postgres>,
+    webhook: Webhook,
+    client: WebhookClient,
+    upcasters: Vec<Box<dyn EventUpcaster>>,
+    error_handler: Option<DispatchErrorHandler>,
+    start_after: i64,
+    max_attempts: u32,
+    base_backoff: Duration,
+    max_backoff: Duration,
+    batch_size: i64,
+    poll_interval: Duration,
+}
+
+pub struct DispatcherHandle {
+    stop: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+// Id of the newest outbox entry, for webhooks that should only receive what
+// is published from now on.
+pub async fn outbox_head(pool: &Pool<Postgres>) -> Result<i64, PersistenceError> {
+    sqlx::query_scalar("SELECT COALESCE(MAX(id), 0) FROM outbox")
+        .fetch_one(pool)
+        .await
+        .map_err(connection_error)
+}
+
+// Replaces the event types the outbox records. Only events appended after
+// the change are affected.
+pub async fn publish_event_types(
+    pool: &Pool<Postgres>,
+    event_types: &[&str],
+) -> Result<(), PersistenceError> {
+    let mut tx = pool.begin().await.map_err(connection_error)?;
+    sqlx::query(CLEAR_PUBLISHED_EVENT_TYPES)
+        .execute(&mut *tx)
+        .await
+        .map_err(connection_error)?;
+    sqlx::query(INSERT_PUBLISHED_EVENT_TYPES)
+        .bind(event_types)
+        .execute(&mut *tx)
+        .await
+        .map_err(connection_error)?;
+    tx.commit().await.map_err(connection_error)
+}
+
+pub async fn published_event_types(pool: &Pool<Postgres>) -> Result<Vec<String>, PersistenceError> {
+    sqlx::query_scalar(SELECT_PUBLISHED_EVENT_TYPES)
+        .fetch_all(pool)
+        .await
+        .map_err(connection_error)
+}
+
+impl OutboxDispatcher {
+    pub fn new(pool: Pool<Postgres>, webhook: Webhook, legacy_currency: Currency) -> Self {
+        Self {
+            pool,
+            webhook,
+            client: WebhookClient::new(),
+            upcasters: tab_upcasters(legacy_currency),
+            error_handler: None,
+            start_after: 0,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            base_backoff: DEFAULT_BASE_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            batch_size: DEFAULT_BATCH_SIZE,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+        }
+    }
+
+    pub fn starting_after(self, outbox_id: i64) -> Self {
+        Self {
+            start_after: outbox_id,
+            ..self
+        }
+    }
+
+    pub fn with_max_attempts(self, max_attempts: u32) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            ..self
+        }
+    }
+
+    pub fn with_backoff(self, base_backoff: Duration, max_backoff: Duration) -> Self {
+        Self {
+            base_backoff,
+            max_backoff,
+            ..self
+        }
+    }
+
+    pub fn with_poll_interval(self, poll_interval: Duration) -> Self {
+        Self {
+            poll_interval,
+            ..self
+        }
+    }
+
+    pub fn with_error_handler(self, error_handler: DispatchErrorHandler) -> Self {
+        Self {
+            error_handler: Some(error_handler),
+            ..self
+        }
+    }
+
+    pub fn webhook(&self) -> &Webhook {
+        &self.webhook
+    }
+
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let exponent = attempts.saturating_sub(1).min(31);
+        self.base_backoff
+            .saturating_mul(1 << exponent)
+            .min(self.max_backoff)
+    }
+
+    pub async fn run_once(&self) -> Result<DispatchReport, PersistenceError> {
+        let head = outbox_head(&self.pool).await?;
+        let rows = sqlx::query(PENDING_ENTRIES)
+            .bind(self.webhook.name())
+            .bind(self.start_after)
+            .bind(self.webhook.event_types())
+            .bind(self.batch_size)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(connection_error)?;
+        let scanned_to = match rows.last() {
+            Some(row) if rows.len() as i64 == self.batch_size => row.get("id"),
+            _ => head,
+        };
+        let mut report = DispatchReport::default();
+        for row in rows {
+            let previous_attempts: i32 = row.get("attempts");
+            let attempts = previous_attempts as u32 + 1;
+            let message = webhook_message(&self.upcasters, row);
+            match self.client.deliver(&self.webhook, &message).await {
+                Ok(()) => {
+                    self.record_delivery(&message, attempts).await?;
+                    report.delivered += 1;
+                }
+                Err(e) if attempts >= self.max_attempts => {
+                    self.dead_letter(&message, attempts, &e.to_string()).await?;
+                    report.dead_lettered += 1;
+                }
+                Err(e) => {
+                    self.record_failure(&message, attempts, &e.to_string())
+                        .await?;
+                    report.retried += 1;
+                }
+            }
+        }
+        sqlx::query(ADVANCE_CURSOR)
+            .bind(self.webhook.name())
+            .bind(scanned_to)
+            .execute(&self.pool)
+            .await
+            .map_err(connection_error)?;
+
+        Ok(report)
+    }
+
+    pub async fn dead_letters(&self) -> Result<Vec<DeadLetter>, PersistenceError> {
+        let rows = sqlx::query(SELECT_DEAD_LETTERS)
+            .bind(self.webhook.name())
+            .fetch_all(&self.pool)
+            .await
+            .map_err(connection_error)?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let attempts: i32 = row.get("attempts");
+                DeadLetter {
+                    outbox_id: row.get("outbox_id"),
+                    attempts: attempts as u32,
+                    last_error: row.get("last_error"),
+                }
+            })
+            .collect())
+    }
+
+    // Gives every dead-lettered entry of this webhook a fresh set of
+    // delivery attempts.
+    pub async fn redrive(&self) -> Result<usize, PersistenceError> {
+        let mut tx = self.pool.begin().await.map_err(connection_error)?;
+        sqlx::query(REQUEUE_DEAD_LETTERS)
+            .bind(self.webhook.name())
+            .execute(&mut *tx)
+            .await
+            .map_err(connection_error)?;
+        sqlx::query(REWIND_CURSOR)
+            .bind(self.webhook.name())
+            .execute(&mut *tx)
+            .await
+            .map_err(connection_error)?;
+        let redriven = sqlx::query(DELETE_DEAD_LETTERS)
+            .bind(self.webhook.name())
+            .execute(&mut *tx)
+            .await
+            .map_err(connection_error)?
+            .rows_affected();
+        tx.commit().await.map_err(connection_error)?;
+
+        Ok(redriven as usize)
+    }
+
+    pub fn spawn(self) -> DispatcherHandle {
+        let dispatcher = Arc::new(self);
+        let (stop, mut stopped) = watch::channel(false);
+        let task = tokio::spawn(async move {
+            while !*stopped.borrow() {
+                if let Err(e) = dispatcher.run_once().await {
+                    if let Some(handler) = &dispatcher.error_handler {
+                        handler(dispatcher.webhook.name(), e);
+                    }
+                }
+                tokio::select! {
+                    _ = tokio::time::sleep(dispatcher.poll_interval) => {}
+                    _ = stopped.changed() => {}
+                }
+            }
+        });
+
+        DispatcherHandle { stop, task }
+    }
+
+    async fn record_delivery(
+        &self,
+        message: &WebhookMessage,
+        attempts: u32,
+    ) -> Result<(), PersistenceError> {
+        sqlx::query(RECORD_DELIVERY)
+            .bind(self.webhook.name())
+            .bind(message.id)
+            .bind(attempts as i32)
+            .execute(&self.pool)
+            .await
+            .map_err(connection_error)?;
+
+        Ok(())
+    }
+
+    async fn record_failure(
+        &self,
+        message: &WebhookMessage,
+        attempts: u32,
+        error: &str,
+    ) -> Result<(), PersistenceError> {
+        sqlx::query(RECORD_FAILURE)
+            .bind(self.webhook.name())
+            .bind(message.id)
+            .bind(attempts as i32)
+            .bind(self.backoff(attempts).as_millis() as f64)
+            .bind(error)
+            .execute(&self.pool)
+            .await
+            .map_err(connection_error)?;
+
+        Ok(())
+    }
+
+    async fn dead_letter(
+        &self,
+        message: &WebhookMessage,
+        attempts: u32,
+        error: &str,
+    ) -> Result<(), PersistenceError> {
+        let mut tx = self.pool.begin().await.map_err(connection_error)?;
+        sqlx::query(RECORD_FAILURE)
+            .bind(self.webhook.name())
+            .bind(message.id)
+            .bind(attempts as i32)
+            .bind(0f64)
+            .bind(error)
+            .execute(&mut *tx)
+            .await
+            .map_err(connection_error)?;
+        sqlx::query(INSERT_DEAD_LETTER)
+            .bind(self.webhook.name())
+            .bind(message.id)
+            .bind(attempts as i32)
+            .bind(error)
+            .execute(&mut *tx)
+            .await
+            .map_err(connection_error)?;
+        tx.commit().await.map_err(connection_error)
+    }
+}
+
+impl DispatcherHandle {
+    pub async fn stop(self) {
+        let _ = self.stop.send(true);
+        let _ = self.task.await;
+    }
+}
+
+// Tab payloads are upcast so subscribers always see the current schema.
+fn webhook_message(upcasters: &[Box<dyn EventUpcaster>], row: PgRow) -> WebhookMessage {
+    let id = row.get("id");
+    let sequence: i64 = row.get("sequence");
+    let event = SerializedEvent::new(
+        row.get("aggregate_id"),
+        sequence as usize,
+        row.get("aggregate_type"),
+        row.get("event_type"),
+        row.get("event_version"),
+        row.get("payload"),
+        row.get("metadata"),
+    );
+    let event = match event.aggregate_type.as_str() {
+        "Tab" => upcast_event(upcasters, event),
+        _ => event,
+    };
+
+    WebhookMessage {
+        id,
+        aggregate_id: event.aggregate_id,
+        sequence,
+        event_type: event.event_type,
+        payload: event.payload,
+        metadata: event.metadata,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use secrecy::Secret;
+    use sqlx::postgres::PgPoolOptions;
+
+    use crate::{infrasctructure::publishing::webhook::Webhook, shared_kernel::money::Currency};
+
+    use super::OutboxDispatcher;
+
+    #[tokio::test]
+    async fn backoff_doubles_per_attempt_up_to_the_maximum() {
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost")
+            .unwrap();
+        let webhook = Webhook::new("accounting", "http://localhost", Secret::new("key".into()));
+        let dispatcher = OutboxDispatcher::new(pool, webhook, Currency::USD)
+            .with_backoff(Duration::from_secs(1), Duration::from_secs(10));
+
+        let actual: Vec<_> = (1..=6).map(|a| dispatcher.backoff(a).as_secs()).collect();
+
+        assert_eq!(actual, vec![1, 2, 4, 8, 10, 10]);
+    }
+}
@@ -0,0 +1,461 @@
+use std::{collections::HashSet, marker::PhantomData, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use cqrs_es::{
+    persist::{EventUpcaster, PersistenceError},
+    EventEnvelope, View,
+};
+use sqlx::{PgConnection, Pool, Postgres, Row};
+use tokio::{sync::watch, task::JoinHandle};
+
+use crate::{
+    domain::tab::{
+        aggregate::Tab,
+        queries::{kitchen_ticket::KitchenPrinter, open_tabs::TabStatus},
+    },
+    infrasctructure::respository::{
+        query_errors::RetryPolicy,
+        upcasters::{deserialize_tab_event, tab_upcasters},
+    },
+    shared_kernel::money::Currency,
+};
+
+use super::{connection_error, serialized_event};
+
+const DEFAULT_BATCH_SIZE: i64 = 100;
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+const MAX_FAILURE_BACKOFF: Duration = Duration::from_secs(30);
+
+const PENDING_EVENTS: &str = "
+SELECT position, aggregate_type, aggregate_id, sequence, event_type, event_version, payload, metadata
+  FROM events
+  WHERE aggregate_type = 'Tab' AND position > $1
+  ORDER BY position
+  LIMIT $2";
+
+const PENDING_COUNT: &str = "
+SELECT count(*)
+  FROM events
+  WHERE aggregate_type = 'Tab' AND position > $1";
+
+const LOAD_POSITION: &str = "
+SELECT COALESCE((SELECT position FROM projection_positions WHERE projection = $1), 0)";
+
+const SAVE_POSITION: &str = "
+INSERT INTO projection_positions (projection, position)
+VALUES ($1, $2)
+ON CONFLICT (projection)
+  DO UPDATE SET position = EXCLUDED.position, updated_at = now()";
+
+const CREATE_CHECKPOINT: &str = "
+INSERT INTO projection_checkpoints (projection, aggregate_id, last_sequence)
+VALUES ($1, $2, 0)
+ON CONFLICT (projection, aggregate_id) DO NOTHING";
+
+const LOCK_CHECKPOINT: &str = "
+SELECT last_sequence
+  FROM projection_checkpoints
+  WHERE projection = $1 AND aggregate_id = $2
+  FOR UPDATE";
+
+const SAVE_CHECKPOINT: &str = "
+UPDATE projection_checkpoints
+  SET last_sequence = $3, updated_at = now()
+  WHERE projection = $1 AND aggregate_id = $2";
+
+// Applies events to a view inside the projector's transaction, so the view
+// and the checkpoint are written together or not at all.
+#[async_trait]
+pub trait ProjectedView: Send + Sync {
+    async fn apply(
+        &self,
+        connection: &mut PgConnection,
+        view_id: &str,
+        events: &[EventEnvelope<Tab>],
+    ) -> Result<(), PersistenceError>;
+}
+
+pub struct ViewTable<V: View<Tab>> {
+    view_name: String,
+    _phantom: PhantomData<V>,
+}
+
+pub struct Projector {
+    name: String,
+    pool: Pool<Postgres>,
+    view: Box<dyn ProjectedView>,
+    upcasters: Vec<Box<dyn EventUpcaster>>,
+    batch_size: i64,
+    poll_interval: Duration,
+    error_handler: Option<ProjectorErrorHandler>,
+}
+
+pub type ProjectorErrorHandler = Box<dyn Fn(&str, ProjectorError) + Send + Sync>;
+
+// `Unreadable` events are reported and skipped, as subscriptions do, so one
+// bad row can't stall the projection.
+#[derive(Debug)]
+pub enum ProjectorError {
+    Projection(PersistenceError),
+    Unreadable {
+        position: i64,
+        error: PersistenceError,
+    },
+}
+
+pub struct ProjectorHandle {
+    projector: Arc<Projector>,
+    stop: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl<V: View<Tab>> ViewTable<V> {
+    pub fn new(view_name: &str) -> Self {
+        Self {
+            view_name: view_name.to_string(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+// Keeps the tab status view and prints kitchen tickets from it, so a ticket
+// names the table even when the tab was opened in the same batch.
+pub struct TabStatusTable {
+    view: ViewTable<TabStatus>,
+    kitchen_printer: Option<KitchenPrinter>,
+}
+
+impl TabStatusTable {
+    pub fn new(view_name: &str) -> Self {
+        Self {
+            view: ViewTable::new(view_name),
+            kitchen_printer: None,
+        }
+    }
+
+    pub fn with_kitchen_printer(self, kitchen_printer: KitchenPrinter) -> Self {
+        Self {
+            kitchen_printer: Some(kitchen_printer),
+            ..self
+        }
+    }
+}
+
+#[async_trait]
+impl ProjectedView for TabStatusTable {
+    async fn apply(
+        &self,
+        connection: &mut PgConnection,
+        view_id: &str,
+        events: &[EventEnvelope<Tab>],
+    ) -> Result<(), PersistenceError> {
+        let status = self.view.project(connection, view_id, events).await?;
+        if let Some(printer) = &self.kitchen_printer {
+            printer.print(view_id, &status, events).await;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<V: View<Tab>> ProjectedView for ViewTable<V> {
+    async fn apply(
+        &self,
+        connection: &mut PgConnection,
+        view_id: &str,
+        events: &[EventEnvelope<Tab>],
+    ) -> Result<(), PersistenceError> {
+        self.project(connection, view_id, events).await?;
+
+        Ok(())
+    }
+}
+
+impl<V: View<Tab>> ViewTable<V> {
+    async fn project(
+        &self,
+        connection: &mut PgConnection,
+        view_id: &str,
+        events: &[EventEnvelope<Tab>],
+    ) -> Result<V, PersistenceError> {
+        let select_sql = format!(
+            "SELECT payload, version FROM {} WHERE view_id = $1 FOR UPDATE",
+            self.view_name
+        );
+        let upsert_sql = format!(
+            "INSERT INTO {} (payload, version, view_id) VALUES ($1, $2, $3)
+             ON CONFLICT (view_id)
+               DO UPDATE SET payload = EXCLUDED.payload, version = EXCLUDED.version",
+            self.view_name
+        );
+        let (mut view, version) = match sqlx::query(&select_sql)
+            .bind(view_id)
+            .fetch_optional(&mut *connection)
+            .await
+            .map_err(connection_error)?
+        {
+            Some(row) => (
+                serde_json::from_value(row.get("payload"))?,
+                row.get::<i64, _>("version"),
+            ),
+            None => (V::default(), 0),
+        };
+        for event in events {
+            view.update(event);
+        }
+        sqlx::query(&upsert_sql)
+            .bind(serde_json::to_value(&view)?)
+            .bind(version + 1)
+            .bind(view_id)
+            .execute(&mut *connection)
+            .await
+            .map_err(connection_error)?;
+
+        Ok(view)
+    }
+}
+
+impl Projector {
+    pub fn new(
+        name: &str,
+        pool: Pool<Postgres>,
+        legacy_currency: Currency,
+        view: Box<dyn ProjectedView>,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            pool,
+            view,
+            upcasters: tab_upcasters(legacy_currency),
+            batch_size: DEFAULT_BATCH_SIZE,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            error_handler: None,
+        }
+    }
+
+    pub fn with_batch_size(self, batch_size: usize) -> Self {
+        Self {
+            batch_size: batch_size.max(1) as i64,
+            ..self
+        }
+    }
+
+    pub fn with_poll_interval(self, poll_interval: Duration) -> Self {
+        Self {
+            poll_interval,
+            ..self
+        }
+    }
+
+    pub fn with_error_handler(self, error_handler: ProjectorErrorHandler) -> Self {
+        Self {
+            error_handler: Some(error_handler),
+            ..self
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // Number of committed events this projection has not processed yet.
+    pub async fn lag(&self) -> Result<u64, PersistenceError> {
+        let position = self.position().await?;
+        let count: i64 = sqlx::query_scalar(PENDING_COUNT)
+            .bind(position)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(connection_error)?;
+
+        Ok(count as u64)
+    }
+
+    pub async fn run_once(&self) -> Result<usize, PersistenceError> {
+        let position = self.position().await?;
+        let rows = sqlx::query(PENDING_EVENTS)
+            .bind(position)
+            .bind(self.batch_size)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(connection_error)?;
+        let read = rows.len();
+        let mut last_position = position;
+        let mut positions = Vec::with_capacity(rows.len());
+        let mut events = Vec::with_capacity(rows.len());
+        for row in rows {
+            last_position = row.get("position");
+            match deserialize_tab_event(&self.upcasters, serialized_event(row)) {
+                Ok(event) => {
+                    positions.push(last_position);
+                    events.push(event);
+                }
+                Err(error) => self.report(ProjectorError::Unreadable {
+                    position: last_position,
+                    error,
+                }),
+            }
+        }
+        // A failed aggregate keeps its checkpoint, so its later events in
+        // this batch are skipped and retried in order on the next run. The
+        // cursor stops before its first event; events of other aggregates
+        // read again are skipped by their checkpoints.
+        let mut failed = HashSet::new();
+        let mut error = None;
+        let mut offset = 0;
+        for batch in events.chunk_by(|a, b| a.aggregate_id == b.aggregate_id) {
+            let first_position = positions[offset];
+            offset += batch.len();
+            let aggregate_id = &batch[0].aggregate_id;
+            if failed.contains(aggregate_id) {
+                continue;
+            }
+            if let Err(e) = self.project(batch).await {
+                failed.insert(aggregate_id.clone());
+                if error.is_none() {
+                    last_position = first_position - 1;
+                    error = Some(e);
+                }
+            }
+        }
+        if last_position > position {
+            self.save_position(last_position).await?;
+        }
+
+        match error {
+            Some(e) => Err(e),
+            None => Ok(read),
+        }
+    }
+
+    pub fn spawn(self) -> ProjectorHandle {
+        let projector = Arc::new(self);
+        let (stop, mut stopped) = watch::channel(false);
+        let worker = projector.clone();
+        let task = tokio::spawn(async move {
+            let backoff = RetryPolicy::new(u32::MAX, worker.poll_interval, MAX_FAILURE_BACKOFF);
+            let mut failures = 0;
+            while !*stopped.borrow() {
+                let (processed, delay) = match worker.run_once().await {
+                    Ok(processed) => {
+                        failures = 0;
+                        (processed, worker.poll_interval)
+                    }
+                    Err(e) => {
+                        failures += 1;
+                        worker.report(ProjectorError::Projection(e));
+                        (0, backoff.backoff(failures))
+                    }
+                };
+                if processed == 0 {
+                    tokio::select! {
+                        _ = tokio::time::sleep(delay) => {}
+                        _ = stopped.changed() => {}
+                    }
+                }
+            }
+        });
+
+        ProjectorHandle {
+            projector,
+            stop,
+            task,
+        }
+    }
+
+    async fn position(&self) -> Result<i64, PersistenceError> {
+        sqlx::query_scalar(LOAD_POSITION)
+            .bind(&self.name)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(connection_error)
+    }
+
+    async fn save_position(&self, position: i64) -> Result<(), PersistenceError> {
+        sqlx::query(SAVE_POSITION)
+            .bind(&self.name)
+            .bind(position)
+            .execute(&self.pool)
+            .await
+            .map_err(connection_error)?;
+
+        Ok(())
+    }
+
+    fn report(&self, error: ProjectorError) {
+        if let Some(handler) = &self.error_handler {
+            handler(&self.name, error);
+        }
+    }
+
+    async fn project(&self, events: &[EventEnvelope<Tab>]) -> Result<(), PersistenceError> {
+        let last = match events.last() {
+            Some(last) => last,
+            None => return Ok(()),
+        };
+        let mut tx = self.pool.begin().await.map_err(connection_error)?;
+        sqlx::query(CREATE_CHECKPOINT)
+            .bind(&self.name)
+            .bind(&last.aggregate_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(connection_error)?;
+        let checkpoint: i64 = sqlx::query_scalar(LOCK_CHECKPOINT)
+            .bind(&self.name)
+            .bind(&last.aggregate_id)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(connection_error)?;
+        let pending: Vec<_> = events
+            .iter()
+            .filter(|e| e.sequence as i64 > checkpoint)
+            .cloned()
+            .collect();
+        if pending.is_empty() {
+            return Ok(());
+        }
+        self.view
+            .apply(&mut tx, &last.aggregate_id, &pending)
+            .await?;
+        sqlx::query(SAVE_CHECKPOINT)
+            .bind(&self.name)
+            .bind(&last.aggregate_id)
+            .bind(last.sequence as i64)
+            .execute(&mut *tx)
+            .await
+            .map_err(connection_error)?;
+
+        tx.commit().await.map_err(connection_error)
+    }
+}
+
+impl std::error::Error for ProjectorError {}
+
+impl std::fmt::Display for ProjectorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProjectorError::Projection(e) => write!(f, "projection failed: {e}"),
+            ProjectorError::Unreadable { position, error } => {
+                write!(
+                    f,
+                    "skipped unreadable event at position {position}: {error}"
+                )
+            }
+        }
+    }
+}
+
+impl ProjectorHandle {
+    pub fn name(&self) -> &str {
+        self.projector.name()
+    }
+
+    pub async fn lag(&self) -> Result<u64, PersistenceError> {
+        self.projector.lag().await
+    }
+
+    pub async fn stop(self) {
+        let _ = self.stop.send(true);
+        let _ = self.task.await;
+    }
+}
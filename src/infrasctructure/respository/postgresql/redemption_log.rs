@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use cqrs_es::persist::PersistenceError;
+use serde_json::Value;
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    domain::gift_card::redemption_id::RedemptionId,
+    infrasctructure::respository::gift_card_payments::{PendingRedemption, RedemptionLog},
+};
+
+use super::connection_error;
+
+const UPSERT_REDEMPTION: &str = "
+INSERT INTO gift_card_redemptions (redemption, tab_id, saga)
+VALUES ($1, $2, $3)
+ON CONFLICT (redemption) DO UPDATE SET saga = excluded.saga, updated_at = now()";
+
+const DELETE_REDEMPTION: &str = "DELETE FROM gift_card_redemptions WHERE redemption = $1";
+
+const SELECT_STALLED: &str = "
+SELECT saga
+  FROM gift_card_redemptions
+  WHERE updated_at < now() - make_interval(secs => $1)
+  ORDER BY updated_at";
+
+pub struct PostgresRedemptionLog {
+    pool: Pool<Postgres>,
+}
+
+impl PostgresRedemptionLog {
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl RedemptionLog for PostgresRedemptionLog {
+    async fn record(&self, redemption: &PendingRedemption) -> Result<(), PersistenceError> {
+        sqlx::query(UPSERT_REDEMPTION)
+            .bind(redemption.payment.redemption.to_string())
+            .bind(redemption.tab_id.to_string())
+            .bind(serde_json::to_value(redemption)?)
+            .execute(&self.pool)
+            .await
+            .map_err(connection_error)?;
+
+        Ok(())
+    }
+
+    async fn remove(&self, redemption: RedemptionId) -> Result<(), PersistenceError> {
+        sqlx::query(DELETE_REDEMPTION)
+            .bind(redemption.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(connection_error)?;
+
+        Ok(())
+    }
+
+    async fn stalled(&self, idle: Duration) -> Result<Vec<PendingRedemption>, PersistenceError> {
+        let sagas: Vec<Value> = sqlx::query_scalar(SELECT_STALLED)
+            .bind(idle.as_secs_f64())
+            .fetch_all(&self.pool)
+            .await
+            .map_err(connection_error)?;
+
+        Ok(sagas
+            .into_iter()
+            .map(serde_json::from_value)
+            .collect::<Result<_, _>>()?)
+    }
+}
@@ -0,0 +1,140 @@
+use std::{collections::HashMap, marker::PhantomData};
+
+use cqrs_es::{
+    persist::{EventUpcaster, PersistedEventRepository, PersistenceError, ReplayStream},
+    View,
+};
+use postgres_es::PostgresEventRepository;
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    domain::tab::aggregate::Tab, infrasctructure::respository::upcasters::tab_upcasters,
+    shared_kernel::money::Currency,
+};
+
+use super::connection_error;
+
+const DEFAULT_PROGRESS_INTERVAL: usize = 500;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReplayProgress {
+    pub events: usize,
+    pub aggregates: usize,
+    pub finished: bool,
+}
+
+pub type ProgressReporter = Box<dyn Fn(&ReplayProgress) + Send + Sync>;
+
+pub struct ProjectionReplay<V: View<Tab>> {
+    pool: Pool<Postgres>,
+    view_name: String,
+    upcasters: Option<Vec<Box<dyn EventUpcaster>>>,
+    progress_interval: usize,
+    reporter: Option<ProgressReporter>,
+    _phantom: PhantomData<V>,
+}
+
+impl<V: View<Tab>> ProjectionReplay<V> {
+    pub fn new(pool: Pool<Postgres>, view_name: &str, legacy_currency: Currency) -> Self {
+        Self {
+            pool,
+            view_name: view_name.to_string(),
+            upcasters: Some(tab_upcasters(legacy_currency)),
+            progress_interval: DEFAULT_PROGRESS_INTERVAL,
+            reporter: None,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn with_progress(self, progress_interval: usize, reporter: ProgressReporter) -> Self {
+        Self {
+            progress_interval: progress_interval.max(1),
+            reporter: Some(reporter),
+            ..self
+        }
+    }
+
+    pub async fn rebuild_all(&self) -> Result<ReplayProgress, PersistenceError> {
+        let stream = PostgresEventRepository::new(self.pool.clone())
+            .stream_all_events::<Tab>()
+            .await?;
+        let views = self.fold(stream).await?;
+        let sql = format!("TRUNCATE {}", self.view_name);
+
+        self.store(&sql, None, views).await
+    }
+
+    pub async fn rebuild(&self, aggregate_id: &str) -> Result<ReplayProgress, PersistenceError> {
+        let stream = PostgresEventRepository::new(self.pool.clone())
+            .stream_events::<Tab>(aggregate_id)
+            .await?;
+        let views = self.fold(stream).await?;
+        let sql = format!("DELETE FROM {} WHERE view_id = $1", self.view_name);
+
+        self.store(&sql, Some(aggregate_id), views).await
+    }
+
+    async fn fold(
+        &self,
+        mut stream: ReplayStream,
+    ) -> Result<HashMap<String, (V, usize)>, PersistenceError> {
+        let mut views: HashMap<String, (V, usize)> = HashMap::new();
+        let mut progress = ReplayProgress::default();
+        while let Some(event) = stream.next::<Tab>(&self.upcasters).await {
+            let event = event?;
+            let (view, applied) = views.entry(event.aggregate_id.clone()).or_default();
+            view.update(&event);
+            *applied += 1;
+            progress.events += 1;
+            if progress.events % self.progress_interval == 0 {
+                progress.aggregates = views.len();
+                self.report(&progress);
+            }
+        }
+
+        Ok(views)
+    }
+
+    async fn store(
+        &self,
+        clear_sql: &str,
+        aggregate_id: Option<&str>,
+        views: HashMap<String, (V, usize)>,
+    ) -> Result<ReplayProgress, PersistenceError> {
+        let insert_sql = format!(
+            "INSERT INTO {} (payload, version, view_id) VALUES ($1, $2, $3)",
+            self.view_name
+        );
+        let mut tx = self.pool.begin().await.map_err(connection_error)?;
+        let mut clear = sqlx::query(clear_sql);
+        if let Some(aggregate_id) = aggregate_id {
+            clear = clear.bind(aggregate_id);
+        }
+        clear.execute(&mut *tx).await.map_err(connection_error)?;
+        let mut progress = ReplayProgress {
+            aggregates: views.len(),
+            ..ReplayProgress::default()
+        };
+        for (view_id, (view, applied)) in views {
+            sqlx::query(&insert_sql)
+                .bind(serde_json::to_value(&view)?)
+                .bind(applied as i64)
+                .bind(view_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(connection_error)?;
+            progress.events += applied;
+        }
+        tx.commit().await.map_err(connection_error)?;
+        progress.finished = true;
+        self.report(&progress);
+
+        Ok(progress)
+    }
+
+    fn report(&self, progress: &ReplayProgress) {
+        if let Some(reporter) = &self.reporter {
+            reporter(progress);
+        }
+    }
+}
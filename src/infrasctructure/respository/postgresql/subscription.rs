@@ -0,0 +1,202 @@
+use std::time::Duration;
+
+use cqrs_es::{
+    persist::{EventUpcaster, PersistenceError},
+    EventEnvelope,
+};
+use sqlx::{
+    postgres::{PgListener, PgRow},
+    Pool, Postgres, Row,
+};
+use tokio::{sync::mpsc, task::JoinHandle};
+
+use crate::{
+    domain::tab::aggregate::Tab,
+    infrasctructure::respository::{
+        query_errors::RetryPolicy,
+        upcasters::{deserialize_tab_event, tab_upcasters},
+    },
+    shared_kernel::money::Currency,
+};
+
+use super::{connection_error, serialized_event};
+
+pub const EVENTS_CHANNEL: &str = "tab_events";
+
+const BATCH_SIZE: i64 = 500;
+const CHANNEL_SIZE: usize = 200;
+// Safety net in case a notification is lost while the listener reconnects.
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const MIN_RECONNECT_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(30);
+
+const EVENTS_AFTER: &str = "
+SELECT position, aggregate_type, aggregate_id, sequence, event_type, event_version, payload, metadata
+  FROM events
+  WHERE aggregate_type = 'Tab' AND position > $1
+  ORDER BY position
+  LIMIT $2";
+
+#[derive(Debug)]
+pub struct PositionedEvent {
+    pub position: i64,
+    pub event: EventEnvelope<Tab>,
+}
+
+// `Unreadable` events are reported once and skipped, so one bad row can't
+// stall the subscription. `Connection` errors are retried with a backoff.
+#[derive(Debug)]
+pub enum SubscriptionError {
+    Connection(PersistenceError),
+    Unreadable {
+        position: i64,
+        error: PersistenceError,
+    },
+}
+
+pub struct EventSubscription {
+    events: mpsc::Receiver<Result<PositionedEvent, SubscriptionError>>,
+    task: JoinHandle<()>,
+}
+
+pub async fn current_position(pool: &Pool<Postgres>) -> Result<i64, PersistenceError> {
+    sqlx::query_scalar("SELECT COALESCE(MAX(position), 0) FROM events")
+        .fetch_one(pool)
+        .await
+        .map_err(connection_error)
+}
+
+pub async fn read_events_after(
+    pool: &Pool<Postgres>,
+    position: i64,
+    limit: usize,
+    legacy_currency: Currency,
+) -> Result<Vec<PositionedEvent>, PersistenceError> {
+    let upcasters = tab_upcasters(legacy_currency);
+    read_rows(pool, position, limit as i64)
+        .await?
+        .into_iter()
+        .map(|row| positioned_event(&upcasters, row).map_err(SubscriptionError::into_persistence))
+        .collect()
+}
+
+// Delivers every Tab event after `position`, then keeps streaming new ones as
+// they are committed.
+pub async fn subscribe(
+    pool: Pool<Postgres>,
+    position: i64,
+    legacy_currency: Currency,
+) -> Result<EventSubscription, PersistenceError> {
+    let mut listener = PgListener::connect_with(&pool)
+        .await
+        .map_err(connection_error)?;
+    listener
+        .listen(EVENTS_CHANNEL)
+        .await
+        .map_err(connection_error)?;
+    let (sender, events) = mpsc::channel(CHANNEL_SIZE);
+    let task = tokio::spawn(async move {
+        let upcasters = tab_upcasters(legacy_currency);
+        let backoff = RetryPolicy::new(u32::MAX, MIN_RECONNECT_BACKOFF, MAX_RECONNECT_BACKOFF);
+        let mut position = position;
+        let mut failures = 0;
+        loop {
+            match read_rows(&pool, position, BATCH_SIZE).await {
+                Ok(rows) if rows.is_empty() => failures = 0,
+                Ok(rows) => {
+                    failures = 0;
+                    for row in rows {
+                        position = row.get("position");
+                        if sender
+                            .send(positioned_event(&upcasters, row))
+                            .await
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
+                    continue;
+                }
+                Err(e) => {
+                    failures += 1;
+                    if sender
+                        .send(Err(SubscriptionError::Connection(e)))
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                    tokio::time::sleep(backoff.backoff(failures)).await;
+                    continue;
+                }
+            }
+            if let Ok(Err(_)) = tokio::time::timeout(IDLE_POLL_INTERVAL, listener.recv()).await {
+                failures += 1;
+                tokio::time::sleep(backoff.backoff(failures)).await;
+            }
+        }
+    });
+
+    Ok(EventSubscription { events, task })
+}
+
+impl EventSubscription {
+    pub async fn next(&mut self) -> Option<Result<PositionedEvent, SubscriptionError>> {
+        self.events.recv().await
+    }
+}
+
+impl Drop for EventSubscription {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl SubscriptionError {
+    fn into_persistence(self) -> PersistenceError {
+        match self {
+            SubscriptionError::Connection(e) => e,
+            SubscriptionError::Unreadable { error, .. } => error,
+        }
+    }
+}
+
+impl std::error::Error for SubscriptionError {}
+
+impl std::fmt::Display for SubscriptionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubscriptionError::Connection(e) => write!(f, "subscription failed: {e}"),
+            SubscriptionError::Unreadable { position, error } => {
+                write!(
+                    f,
+                    "skipped unreadable event at position {position}: {error}"
+                )
+            }
+        }
+    }
+}
+
+async fn read_rows(
+    pool: &Pool<Postgres>,
+    position: i64,
+    limit: i64,
+) -> Result<Vec<PgRow>, PersistenceError> {
+    sqlx::query(EVENTS_AFTER)
+        .bind(position)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .map_err(connection_error)
+}
+
+fn positioned_event(
+    upcasters: &[Box<dyn EventUpcaster>],
+    row: PgRow,
+) -> Result<PositionedEvent, SubscriptionError> {
+    let position = row.get("position");
+    match deserialize_tab_event(upcasters, serialized_event(row)) {
+        Ok(event) => Ok(PositionedEvent { position, event }),
+        Err(error) => Err(SubscriptionError::Unreadable { position, error }),
+    }
+}
@@ -0,0 +1,578 @@
+use std::{collections::HashSet, marker::PhantomData, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use cqrs_es::{
+    persist::{EventUpcaster, PersistenceError, SerializedEvent, ViewContext, ViewRepository},
+    EventEnvelope, Query, View,
+};
+use serde_json::{json, Value};
+
+use crate::domain::tab::aggregate::Tab;
+
+use super::upcasters::deserialize_tab_event;
+
+const PARKED: &str = "parked behind earlier dead letters";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_backoff: Duration,
+    max_backoff: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub struct QueryDeadLetter {
+    pub id: i64,
+    pub query: String,
+    pub view_id: String,
+    pub events: Vec<EventEnvelope<Tab>>,
+    pub error: String,
+    pub attempts: u32,
+}
+
+#[async_trait]
+pub trait DeadLetterStore: Send + Sync {
+    async fn record(
+        &self,
+        query: &str,
+        view_id: &str,
+        events: &[EventEnvelope<Tab>],
+        error: &str,
+        attempts: u32,
+    ) -> Result<(), PersistenceError>;
+
+    async fn load(&self, query: &str) -> Result<Vec<QueryDeadLetter>, PersistenceError>;
+
+    async fn has_pending(&self, query: &str, view_id: &str) -> Result<bool, PersistenceError>;
+
+    async fn remove(&self, id: i64) -> Result<(), PersistenceError>;
+}
+
+#[derive(Clone, Default)]
+pub struct QueryErrorPolicy {
+    retry: RetryPolicy,
+    dead_letters: Option<Arc<dyn DeadLetterStore>>,
+    error_handler: Option<QueryErrorHandler>,
+}
+
+// Called with the query name and view id. Shared, since the policy is cloned
+// into every query.
+pub type QueryErrorHandler = Arc<dyn Fn(&str, &str, QueryError) + Send + Sync>;
+
+#[derive(Debug)]
+pub enum QueryError {
+    GaveUp {
+        attempts: u32,
+        error: PersistenceError,
+    },
+    DeadLetter(PersistenceError),
+}
+
+// Like cqrs_es::persist::GenericQuery, but a failed view write is retried and
+// then handed to the error policy instead of only being printed. Events for a
+// view that already has dead letters are parked behind them, so the view is
+// only ever updated in event order.
+pub struct ResilientQuery<R, V>
+where
+    R: ViewRepository<V, Tab>,
+    V: View<Tab>,
+{
+    name: String,
+    view_repository: Arc<R>,
+    policy: QueryErrorPolicy,
+    _phantom: PhantomData<V>,
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32, base_backoff: Duration, max_backoff: Duration) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            base_backoff,
+            max_backoff,
+        }
+    }
+
+    pub fn none() -> Self {
+        Self::new(1, Duration::ZERO, Duration::ZERO)
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        self.base_backoff
+            .saturating_mul(1 << exponent)
+            .min(self.max_backoff)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(3, Duration::from_millis(50), Duration::from_secs(1))
+    }
+}
+
+impl QueryErrorPolicy {
+    pub fn new(retry: RetryPolicy) -> Self {
+        Self {
+            retry,
+            dead_letters: None,
+            error_handler: None,
+        }
+    }
+
+    pub fn with_dead_letters(self, dead_letters: Arc<dyn DeadLetterStore>) -> Self {
+        Self {
+            dead_letters: Some(dead_letters),
+            ..self
+        }
+    }
+
+    pub fn with_error_handler(self, error_handler: QueryErrorHandler) -> Self {
+        Self {
+            error_handler: Some(error_handler),
+            ..self
+        }
+    }
+
+    async fn has_pending(&self, query: &str, view_id: &str) -> Result<bool, PersistenceError> {
+        match &self.dead_letters {
+            Some(dead_letters) => dead_letters.has_pending(query, view_id).await,
+            None => Ok(false),
+        }
+    }
+
+    async fn park(
+        &self,
+        query: &str,
+        view_id: &str,
+        events: &[EventEnvelope<Tab>],
+    ) -> Result<(), PersistenceError> {
+        match &self.dead_letters {
+            Some(dead_letters) => dead_letters.record(query, view_id, events, PARKED, 0).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn give_up(
+        &self,
+        query: &str,
+        view_id: &str,
+        events: &[EventEnvelope<Tab>],
+        error: PersistenceError,
+        attempts: u32,
+    ) {
+        if let Some(dead_letters) = &self.dead_letters {
+            let recorded = dead_letters
+                .record(query, view_id, events, &error.to_string(), attempts)
+                .await;
+            if let Err(e) = recorded {
+                self.report(query, view_id, QueryError::DeadLetter(e));
+            }
+        }
+        self.report(query, view_id, QueryError::GaveUp { attempts, error });
+    }
+
+    fn report(&self, query: &str, view_id: &str, error: QueryError) {
+        if let Some(handler) = &self.error_handler {
+            handler(query, view_id, error);
+        }
+    }
+}
+
+impl<R, V> ResilientQuery<R, V>
+where
+    R: ViewRepository<V, Tab>,
+    V: View<Tab>,
+{
+    pub fn new(name: &str, view_repository: Arc<R>, policy: QueryErrorPolicy) -> Self {
+        Self {
+            name: name.to_string(),
+            view_repository,
+            policy,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // Applies this query's dead letters oldest first and removes them. A view
+    // whose letter fails again keeps it and the ones behind it.
+    pub async fn redrive(&self) -> Result<usize, PersistenceError> {
+        let Some(dead_letters) = &self.policy.dead_letters else {
+            return Ok(0);
+        };
+        let mut redriven = 0;
+        let mut stuck = HashSet::new();
+        loop {
+            let letters: Vec<_> = dead_letters
+                .load(&self.name)
+                .await?
+                .into_iter()
+                .filter(|l| !stuck.contains(&l.view_id))
+                .collect();
+            if letters.is_empty() {
+                return Ok(redriven);
+            }
+            for letter in letters {
+                if stuck.contains(&letter.view_id) {
+                    continue;
+                }
+                match self.apply(&letter.view_id, &letter.events).await {
+                    Ok(()) => {
+                        dead_letters.remove(letter.id).await?;
+                        redriven += 1;
+                    }
+                    Err(_) => {
+                        stuck.insert(letter.view_id);
+                    }
+                }
+            }
+        }
+    }
+
+    async fn apply_in_order(
+        &self,
+        view_id: &str,
+        events: &[EventEnvelope<Tab>],
+    ) -> Result<(), PersistenceError> {
+        if self.policy.has_pending(&self.name, view_id).await? {
+            return self.policy.park(&self.name, view_id, events).await;
+        }
+
+        self.apply(view_id, events).await
+    }
+
+    async fn apply(
+        &self,
+        view_id: &str,
+        events: &[EventEnvelope<Tab>],
+    ) -> Result<(), PersistenceError> {
+        let (mut view, context) = match self.view_repository.load_with_context(view_id).await? {
+            Some(loaded) => loaded,
+            None => (V::default(), ViewContext::new(view_id.to_string(), 0)),
+        };
+        for event in events {
+            view.update(event);
+        }
+
+        self.view_repository.update_view(view, context).await
+    }
+}
+
+#[async_trait]
+impl<R, V> Query<Tab> for ResilientQuery<R, V>
+where
+    R: ViewRepository<V, Tab>,
+    V: View<Tab>,
+{
+    async fn dispatch(&self, view_id: &str, events: &[EventEnvelope<Tab>]) {
+        let mut attempt = 1;
+        loop {
+            match self.apply_in_order(view_id, events).await {
+                Ok(()) => return,
+                Err(_) if attempt < self.policy.retry.max_attempts => {
+                    tokio::time::sleep(self.policy.retry.backoff(attempt)).await;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    self.policy
+                        .give_up(&self.name, view_id, events, e, attempt)
+                        .await;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryError::GaveUp { attempts, error } => {
+                write!(f, "failed after {attempts} attempts: {error}")
+            }
+            QueryError::DeadLetter(e) => write!(f, "could not dead-letter events: {e}"),
+        }
+    }
+}
+
+pub(crate) fn events_to_json(events: &[EventEnvelope<Tab>]) -> Result<Value, PersistenceError> {
+    let events = events
+        .iter()
+        .map(|event| {
+            SerializedEvent::try_from(event).map(|e| {
+                json!({
+                    "aggregate_id": e.aggregate_id,
+                    "sequence": e.sequence,
+                    "aggregate_type": e.aggregate_type,
+                    "event_type": e.event_type,
+                    "event_version": e.event_version,
+                    "payload": e.payload,
+                    "metadata": e.metadata,
+                })
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Value::Array(events))
+}
+
+pub(crate) fn events_from_json(
+    upcasters: &[Box<dyn EventUpcaster>],
+    events: Value,
+) -> Result<Vec<EventEnvelope<Tab>>, PersistenceError> {
+    let events: Vec<Value> = serde_json::from_value(events)?;
+    events
+        .into_iter()
+        .map(|mut event| {
+            let mut field = |name: &str| event[name].take();
+            let event = SerializedEvent::new(
+                serde_json::from_value(field("aggregate_id"))?,
+                serde_json::from_value(field("sequence"))?,
+                serde_json::from_value(field("aggregate_type"))?,
+                serde_json::from_value(field("event_type"))?,
+                serde_json::from_value(field("event_version"))?,
+                field("payload"),
+                field("metadata"),
+            );
+            deserialize_tab_event(upcasters, event)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use async_trait::async_trait;
+    use cqrs_es::{
+        persist::{PersistenceError, ViewContext, ViewRepository},
+        EventEnvelope, Query,
+    };
+
+    use crate::{
+        domain::tab::{
+            aggregate::Tab, event::TabEvent, queries::open_tabs::TabStatus, tab_id::TabId,
+            waiter_id::WaiterId,
+        },
+        infrasctructure::respository::memory::dead_letters::MemQueryDeadLetters,
+    };
+
+    use super::{DeadLetterStore, QueryError, QueryErrorPolicy, ResilientQuery, RetryPolicy};
+
+    #[derive(Default)]
+    struct FlakyViews {
+        failures: Mutex<u32>,
+        views: Mutex<HashMap<String, (TabStatus, i64)>>,
+    }
+
+    impl FlakyViews {
+        fn failing(failures: u32) -> Arc<Self> {
+            let views = Self::default();
+            *views.failures.lock().unwrap() = failures;
+            Arc::new(views)
+        }
+
+        fn version(&self, view_id: &str) -> Option<i64> {
+            self.views.lock().unwrap().get(view_id).map(|(_, v)| *v)
+        }
+    }
+
+    #[async_trait]
+    impl ViewRepository<TabStatus, Tab> for FlakyViews {
+        async fn load(&self, view_id: &str) -> Result<Option<TabStatus>, PersistenceError> {
+            Ok(self.load_with_context(view_id).await?.map(|(view, _)| view))
+        }
+
+        async fn load_with_context(
+            &self,
+            view_id: &str,
+        ) -> Result<Option<(TabStatus, ViewContext)>, PersistenceError> {
+            Ok(self
+                .views
+                .lock()
+                .unwrap()
+                .get(view_id)
+                .map(|(view, version)| {
+                    (
+                        view.clone(),
+                        ViewContext::new(view_id.to_string(), *version),
+                    )
+                }))
+        }
+
+        async fn update_view(
+            &self,
+            view: TabStatus,
+            context: ViewContext,
+        ) -> Result<(), PersistenceError> {
+            let mut failures = self.failures.lock().unwrap();
+            if *failures > 0 {
+                *failures -= 1;
+                return Err(PersistenceError::OptimisticLockError);
+            }
+            self.views
+                .lock()
+                .unwrap()
+                .insert(context.view_instance_id, (view, context.version + 1));
+
+            Ok(())
+        }
+    }
+
+    fn tab_opened() -> Vec<EventEnvelope<Tab>> {
+        vec![EventEnvelope {
+            aggregate_id: "tab-1".into(),
+            sequence: 1,
+            payload: TabEvent::TabOpened {
+                id: TabId::new(),
+                waiter_id: WaiterId::new(),
+                table: 3,
+            },
+            metadata: HashMap::new(),
+        }]
+    }
+
+    fn tab_closed() -> Vec<EventEnvelope<Tab>> {
+        vec![EventEnvelope {
+            aggregate_id: "tab-1".into(),
+            sequence: 2,
+            payload: TabEvent::TabClosed {
+                id: TabId::new(),
+                amount_paid: Default::default(),
+                order_value: Default::default(),
+                tip_value: Default::default(),
+                amount_tendered: Default::default(),
+                exchange_rate: None,
+            },
+            metadata: HashMap::new(),
+        }]
+    }
+
+    fn policy(max_attempts: u32, dead_letters: Arc<MemQueryDeadLetters>) -> QueryErrorPolicy {
+        QueryErrorPolicy::new(RetryPolicy::new(
+            max_attempts,
+            Duration::from_millis(1),
+            Duration::from_millis(1),
+        ))
+        .with_dead_letters(dead_letters)
+    }
+
+    #[test]
+    fn backoff_doubles_per_attempt_up_to_the_maximum() {
+        let retry = RetryPolicy::new(5, Duration::from_millis(100), Duration::from_millis(500));
+
+        let actual: Vec<_> = (1..=5).map(|a| retry.backoff(a).as_millis()).collect();
+
+        assert_eq!(actual, vec![100, 200, 400, 500, 500]);
+    }
+
+    #[tokio::test]
+    async fn given_transient_failure_when_dispatching_then_view_is_written_on_retry() {
+        let views = FlakyViews::failing(2);
+        let dead_letters = Arc::new(MemQueryDeadLetters::default());
+        let query =
+            ResilientQuery::new("tab_query", views.clone(), policy(3, dead_letters.clone()));
+
+        query.dispatch("tab-1", &tab_opened()).await;
+
+        assert_eq!(views.version("tab-1"), Some(1));
+        assert!(dead_letters.load("tab_query").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn given_persistent_failure_when_retries_exhausted_then_events_are_dead_lettered() {
+        let views = FlakyViews::failing(5);
+        let dead_letters = Arc::new(MemQueryDeadLetters::default());
+        let query =
+            ResilientQuery::new("tab_query", views.clone(), policy(3, dead_letters.clone()));
+
+        query.dispatch("tab-1", &tab_opened()).await;
+
+        let actual = dead_letters.load("tab_query").await.unwrap();
+        assert_eq!(views.version("tab-1"), None);
+        assert_eq!(actual.len(), 1);
+        assert_eq!(actual[0].view_id, "tab-1");
+        assert_eq!(actual[0].attempts, 3);
+        assert_eq!(actual[0].events.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn given_persistent_failure_when_retries_exhausted_then_error_is_handed_to_the_handler() {
+        let views = FlakyViews::failing(5);
+        let dead_letters = Arc::new(MemQueryDeadLetters::default());
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let sink = reported.clone();
+        let query = ResilientQuery::new(
+            "tab_query",
+            views.clone(),
+            policy(2, dead_letters).with_error_handler(Arc::new(move |query, view_id, e| {
+                sink.lock()
+                    .unwrap()
+                    .push((query.to_string(), view_id.to_string(), e))
+            })),
+        );
+
+        query.dispatch("tab-1", &tab_opened()).await;
+
+        let reported = reported.lock().unwrap();
+        assert_eq!(reported.len(), 1);
+        assert_eq!(reported[0].0, "tab_query");
+        assert_eq!(reported[0].1, "tab-1");
+        assert!(matches!(
+            reported[0].2,
+            QueryError::GaveUp { attempts: 2, .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn given_dead_letters_when_redriven_after_fix_then_view_is_written_and_letters_removed() {
+        let views = FlakyViews::failing(1);
+        let dead_letters = Arc::new(MemQueryDeadLetters::default());
+        let query =
+            ResilientQuery::new("tab_query", views.clone(), policy(1, dead_letters.clone()));
+        query.dispatch("tab-1", &tab_opened()).await;
+
+        let redriven = query.redrive().await.unwrap();
+
+        assert_eq!(redriven, 1);
+        assert_eq!(views.version("tab-1"), Some(1));
+        assert!(dead_letters.load("tab_query").await.unwrap().is_empty());
+        assert_eq!(views.load("tab-1").await.unwrap().unwrap().table(), 3);
+    }
+
+    #[tokio::test]
+    async fn given_dead_lettered_view_when_newer_events_dispatched_then_they_are_applied_in_order_on_redrive(
+    ) {
+        let views = FlakyViews::failing(1);
+        let dead_letters = Arc::new(MemQueryDeadLetters::default());
+        let query =
+            ResilientQuery::new("tab_query", views.clone(), policy(1, dead_letters.clone()));
+        query.dispatch("tab-1", &tab_opened()).await;
+
+        query.dispatch("tab-1", &tab_closed()).await;
+
+        assert_eq!(views.version("tab-1"), None);
+        assert_eq!(dead_letters.load("tab_query").await.unwrap().len(), 2);
+
+        let redriven = query.redrive().await.unwrap();
+
+        let actual = views.load("tab-1").await.unwrap().unwrap();
+        assert_eq!(redriven, 2);
+        assert_eq!(views.version("tab-1"), Some(2));
+        assert_eq!(actual.table(), 3);
+        assert!(!actual.is_open());
+        assert!(dead_letters.load("tab_query").await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,145 @@
+use std::sync::Arc;
+
+use cqrs_es::{
+    persist::{PersistedEventStore, PersistenceError},
+    CqrsFramework, Query,
+};
+use sqlx::{Pool, Sqlite};
+
+use crate::{
+    domain::{
+        gift_card::{aggregate::GiftCard, services::GiftCardServices},
+        tab::{
+            aggregate::Tab,
+            queries::{
+                bar::BarTodoList,
+                kitchen::KitchenTodoList,
+                kitchen_ticket::KitchenPrinter,
+                open_tabs::{TabStatus, WaiterTodoList},
+                simple_logging::SimpleLoggingQuery,
+            },
+            services::TabServices,
+        },
+    },
+    infrasctructure::respository::{
+        query_errors::{QueryErrorPolicy, ResilientQuery},
+        upcasters::tab_upcasters,
+    },
+    shared_kernel::money::Currency,
+};
+
+use super::{
+    dead_letters::SqliteQueryDeadLetters, event_repository::SqliteEventRepository,
+    view_repository::SqliteViewRepository,
+};
+
+pub type SqliteTabCqrsFramework =
+    Arc<CqrsFramework<Tab, PersistedEventStore<SqliteEventRepository, Tab>>>;
+
+pub type SqliteGiftCardCqrsFramework =
+    Arc<CqrsFramework<GiftCard, PersistedEventStore<SqliteEventRepository, GiftCard>>>;
+
+type SqliteQuery<V> = ResilientQuery<SqliteViewRepository<V, Tab>, V>;
+
+#[allow(clippy::too_many_arguments)]
+pub fn sqlite_cqrs_tab(
+    pool: Pool<Sqlite>,
+    services: TabServices,
+    waiter_todo_repo: Arc<SqliteViewRepository<WaiterTodoList, Tab>>,
+    repo: Arc<SqliteViewRepository<KitchenTodoList, Tab>>,
+    bar_todo_repo: Arc<SqliteViewRepository<BarTodoList, Tab>>,
+    open_tabs_repo: Arc<SqliteViewRepository<TabStatus, Tab>>,
+    kitchen_printer: Option<KitchenPrinter>,
+    policy: QueryErrorPolicy,
+    snapshot_size: Option<usize>,
+) -> SqliteTabCqrsFramework {
+    let logging_query = SimpleLoggingQuery {};
+    let (kitchen_tab_query, waiter_tab_query, bar_tab_query, tab_status_query) = tab_queries(
+        waiter_todo_repo,
+        repo,
+        bar_todo_repo,
+        open_tabs_repo.clone(),
+        tab_query_policy(&pool, services.currency(), policy),
+    );
+    let mut queries: Vec<Box<dyn Query<Tab>>> = vec![
+        Box::new(kitchen_tab_query),
+        Box::new(waiter_tab_query),
+        Box::new(bar_tab_query),
+        Box::new(tab_status_query),
+    ];
+    if let Some(printer) = kitchen_printer {
+        queries.push(Box::new(printer.query(open_tabs_repo)));
+    }
+    queries.push(Box::new(logging_query));
+
+    let repo = SqliteEventRepository::new(pool);
+    let store = match snapshot_size {
+        Some(size) => PersistedEventStore::new_snapshot_store(repo, size),
+        None => PersistedEventStore::new_event_store(repo),
+    }
+    .with_upcasters(tab_upcasters(services.currency()));
+
+    Arc::new(CqrsFramework::new(store, queries, services))
+}
+
+pub fn sqlite_cqrs_gift_card(
+    pool: Pool<Sqlite>,
+    services: GiftCardServices,
+) -> SqliteGiftCardCqrsFramework {
+    let store = PersistedEventStore::new_event_store(SqliteEventRepository::new(pool));
+
+    Arc::new(CqrsFramework::new(store, vec![], services))
+}
+
+// Re-applies events whose view writes failed once the cause has been fixed.
+pub async fn sqlite_redrive_tab_queries(
+    pool: Pool<Sqlite>,
+    legacy_currency: Currency,
+    waiter_todo_repo: Arc<SqliteViewRepository<WaiterTodoList, Tab>>,
+    repo: Arc<SqliteViewRepository<KitchenTodoList, Tab>>,
+    bar_todo_repo: Arc<SqliteViewRepository<BarTodoList, Tab>>,
+    open_tabs_repo: Arc<SqliteViewRepository<TabStatus, Tab>>,
+) -> Result<usize, PersistenceError> {
+    let (kitchen_tab_query, waiter_tab_query, bar_tab_query, tab_status_query) = tab_queries(
+        waiter_todo_repo,
+        repo,
+        bar_todo_repo,
+        open_tabs_repo,
+        tab_query_policy(&pool, legacy_currency, QueryErrorPolicy::default()),
+    );
+
+    Ok(kitchen_tab_query.redrive().await?
+        + waiter_tab_query.redrive().await?
+        + bar_tab_query.redrive().await?
+        + tab_status_query.redrive().await?)
+}
+
+fn tab_query_policy(
+    pool: &Pool<Sqlite>,
+    legacy_currency: Currency,
+    policy: QueryErrorPolicy,
+) -> QueryErrorPolicy {
+    let dead_letters = SqliteQueryDeadLetters::new(pool.clone(), legacy_currency);
+
+    policy.with_dead_letters(Arc::new(dead_letters))
+}
+
+fn tab_queries(
+    waiter_todo_repo: Arc<SqliteViewRepository<WaiterTodoList, Tab>>,
+    repo: Arc<SqliteViewRepository<KitchenTodoList, Tab>>,
+    bar_todo_repo: Arc<SqliteViewRepository<BarTodoList, Tab>>,
+    open_tabs_repo: Arc<SqliteViewRepository<TabStatus, Tab>>,
+    policy: QueryErrorPolicy,
+) -> (
+    SqliteQuery<KitchenTodoList>,
+    SqliteQuery<WaiterTodoList>,
+    SqliteQuery<BarTodoList>,
+    SqliteQuery<TabStatus>,
+) {
+    (
+        SqliteQuery::new("kitchen_tab_query", repo, policy.clone()),
+        SqliteQuery::new("waiter_tab_query", waiter_todo_repo, policy.clone()),
+        SqliteQuery::new("bar_tab_query", bar_todo_repo, policy.clone()),
+        SqliteQuery::new("tab_query", open_tabs_repo, policy),
+    )
+}
@@ -0,0 +1,177 @@
+use async_trait::async_trait;
+use cqrs_es::{
+    persist::{EventUpcaster, PersistenceError},
+    EventEnvelope,
+};
+use sqlx::{Pool, Row, Sqlite};
+
+use crate::{
+    domain::tab::aggregate::Tab,
+    infrasctructure::respository::{
+        query_errors::{events_from_json, events_to_json, DeadLetterStore, QueryDeadLetter},
+        upcasters::tab_upcasters,
+    },
+    shared_kernel::money::Currency,
+};
+
+use super::{deserialization_error, persistence_error};
+
+const INSERT_DEAD_LETTER: &str = "
+INSERT INTO query_dead_letters (query, view_id, events, error, attempts)
+VALUES ($1, $2, $3, $4, $5)";
+
+const SELECT_DEAD_LETTERS: &str = "
+SELECT id, query, view_id, events, error, attempts
+  FROM query_dead_letters
+  WHERE query = $1
+  ORDER BY id";
+
+const HAS_DEAD_LETTERS: &str = "
+SELECT EXISTS (SELECT 1 FROM query_dead_letters WHERE query = $1 AND view_id = $2)";
+
+const DELETE_DEAD_LETTER: &str = "DELETE FROM query_dead_letters WHERE id = $1";
+
+pub struct SqliteQueryDeadLetters {
+    pool: Pool<Sqlite>,
+    upcasters: Vec<Box<dyn EventUpcaster>>,
+}
+
+impl SqliteQueryDeadLetters {
+    pub fn new(pool: Pool<Sqlite>, legacy_currency: Currency) -> Self {
+        Self {
+            pool,
+            upcasters: tab_upcasters(legacy_currency),
+        }
+    }
+}
+
+#[async_trait]
+impl DeadLetterStore for SqliteQueryDeadLetters {
+    async fn record(
+        &self,
+        query: &str,
+        view_id: &str,
+        events: &[EventEnvelope<Tab>],
+        error: &str,
+        attempts: u32,
+    ) -> Result<(), PersistenceError> {
+        sqlx::query(INSERT_DEAD_LETTER)
+            .bind(query)
+            .bind(view_id)
+            .bind(events_to_json(events)?.to_string())
+            .bind(error)
+            .bind(attempts as i64)
+            .execute(&self.pool)
+            .await
+            .map_err(persistence_error)?;
+
+        Ok(())
+    }
+
+    async fn load(&self, query: &str) -> Result<Vec<QueryDeadLetter>, PersistenceError> {
+        let rows = sqlx::query(SELECT_DEAD_LETTERS)
+            .bind(query)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(persistence_error)?;
+        let mut letters = Vec::with_capacity(rows.len());
+        for row in rows {
+            let events = serde_json::from_str(row.get("events")).map_err(deserialization_error)?;
+            let attempts: i64 = row.get("attempts");
+            letters.push(QueryDeadLetter {
+                id: row.get("id"),
+                query: row.get("query"),
+                view_id: row.get("view_id"),
+                events: events_from_json(&self.upcasters, events)?,
+                error: row.get("error"),
+                attempts: attempts as u32,
+            });
+        }
+
+        Ok(letters)
+    }
+
+    async fn has_pending(&self, query: &str, view_id: &str) -> Result<bool, PersistenceError> {
+        sqlx::query_scalar(HAS_DEAD_LETTERS)
+            .bind(query)
+            .bind(view_id)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(persistence_error)
+    }
+
+    async fn remove(&self, id: i64) -> Result<(), PersistenceError> {
+        sqlx::query(DELETE_DEAD_LETTER)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(persistence_error)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use cqrs_es::EventEnvelope;
+
+    use crate::{
+        domain::tab::{event::TabEvent, tab_id::TabId, waiter_id::WaiterId},
+        infrasctructure::{
+            persistence::context::sqlite::{migrate_sqlite_db, sqlite_pool},
+            respository::query_errors::DeadLetterStore,
+        },
+        shared_kernel::money::Currency,
+    };
+
+    use super::SqliteQueryDeadLetters;
+
+    async fn dead_letters() -> SqliteQueryDeadLetters {
+        let path = std::env::temp_dir().join(format!("{}.db", uuid::Uuid::new_v4()));
+        let pool = sqlite_pool(path.to_str().unwrap()).await;
+        migrate_sqlite_db(&pool).await;
+
+        SqliteQueryDeadLetters::new(pool, Currency::USD)
+    }
+
+    #[tokio::test]
+    async fn given_recorded_letter_then_it_loads_and_is_pending_until_removed() {
+        let dead_letters = dead_letters().await;
+        let payload = TabEvent::TabOpened {
+            id: TabId::new(),
+            waiter_id: WaiterId::new(),
+            table: 4,
+        };
+        let events = vec![EventEnvelope {
+            aggregate_id: "tab-1".into(),
+            sequence: 1,
+            payload: payload.clone(),
+            metadata: HashMap::new(),
+        }];
+
+        dead_letters
+            .record("tab_query", "tab-1", &events, "disk full", 3)
+            .await
+            .unwrap();
+
+        let letters = dead_letters.load("tab_query").await.unwrap();
+        assert_eq!(letters.len(), 1);
+        assert_eq!(letters[0].events[0].payload, payload);
+        assert_eq!(letters[0].attempts, 3);
+        assert!(dead_letters
+            .has_pending("tab_query", "tab-1")
+            .await
+            .unwrap());
+        assert!(!dead_letters
+            .has_pending("tab_query", "tab-2")
+            .await
+            .unwrap());
+        dead_letters.remove(letters[0].id).await.unwrap();
+        assert!(!dead_letters
+            .has_pending("tab_query", "tab-1")
+            .await
+            .unwrap());
+    }
+}
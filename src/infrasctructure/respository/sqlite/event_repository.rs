@@ -0,0 +1,324 @@
+use async_trait::async_trait;
+use cqrs_es::{
+    persist::{
+        PersistedEventRepository, PersistenceError, ReplayStream, SerializedEvent,
+        SerializedSnapshot,
+    },
+    Aggregate,
+};
+use serde_json::Value;
+use sqlx::{sqlite::SqliteRow, Pool, Row, Sqlite, Transaction};
+
+use super::{deserialization_error, persistence_error};
+
+const SELECT_EVENTS: &str = "
+SELECT aggregate_type, aggregate_id, sequence, event_type, event_version, payload, metadata
+  FROM events
+  WHERE aggregate_type = $1 AND aggregate_id = $2
+  ORDER BY sequence";
+
+const SELECT_LAST_EVENTS: &str = "
+SELECT aggregate_type, aggregate_id, sequence, event_type, event_version, payload, metadata
+  FROM events
+  WHERE aggregate_type = $1 AND aggregate_id = $2 AND sequence > $3
+  ORDER BY sequence";
+
+const SELECT_ALL_EVENTS: &str = "
+SELECT aggregate_type, aggregate_id, sequence, event_type, event_version, payload, metadata
+  FROM events
+  WHERE aggregate_type = $1
+  ORDER BY aggregate_id, sequence";
+
+const INSERT_EVENT: &str = "
+INSERT INTO events (aggregate_type, aggregate_id, sequence, event_type, event_version, payload, metadata)
+VALUES ($1, $2, $3, $4, $5, $6, $7)";
+
+const SELECT_SNAPSHOT: &str = "
+SELECT aggregate_type, aggregate_id, last_sequence, current_snapshot, payload
+  FROM snapshots
+  WHERE aggregate_type = $1 AND aggregate_id = $2";
+
+const INSERT_SNAPSHOT: &str = "
+INSERT INTO snapshots (aggregate_type, aggregate_id, last_sequence, current_snapshot, payload)
+VALUES ($1, $2, $3, $4, $5)";
+
+const UPDATE_SNAPSHOT: &str = "
+UPDATE snapshots
+  SET last_sequence = $3, payload = $6, current_snapshot = $4
+  WHERE aggregate_type = $1 AND aggregate_id = $2 AND current_snapshot = $5";
+
+const STREAMING_CHANNEL_SIZE: usize = 200;
+
+pub struct SqliteEventRepository {
+    pool: Pool<Sqlite>,
+}
+
+impl SqliteEventRepository {
+    pub fn new(pool: Pool<Sqlite>) -> Self {
+        Self { pool }
+    }
+
+    async fn select_events<A: Aggregate>(
+        &self,
+        aggregate_id: &str,
+        last_sequence: usize,
+    ) -> Result<Vec<SerializedEvent>, PersistenceError> {
+        sqlx::query(SELECT_LAST_EVENTS)
+            .bind(A::aggregate_type())
+            .bind(aggregate_id)
+            .bind(last_sequence as i64)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(persistence_error)?
+            .into_iter()
+            .map(deser_event)
+            .collect()
+    }
+
+    async fn persist_events<A: Aggregate>(
+        tx: &mut Transaction<'_, Sqlite>,
+        events: &[SerializedEvent],
+    ) -> Result<usize, PersistenceError> {
+        let mut current_sequence = 0;
+        for event in events {
+            current_sequence = event.sequence;
+            sqlx::query(INSERT_EVENT)
+                .bind(A::aggregate_type())
+                .bind(&event.aggregate_id)
+                .bind(event.sequence as i64)
+                .bind(&event.event_type)
+                .bind(&event.event_version)
+                .bind(event.payload.to_string())
+                .bind(event.metadata.to_string())
+                .execute(&mut **tx)
+                .await
+                .map_err(persistence_error)?;
+        }
+
+        Ok(current_sequence)
+    }
+
+    fn stream(
+        &self,
+        query: &'static str,
+        aggregate_type: String,
+        aggregate_id: Option<String>,
+    ) -> ReplayStream {
+        let (mut feed, stream) = ReplayStream::new(STREAMING_CHANNEL_SIZE);
+        let pool = self.pool.clone();
+        tokio::spawn(async move {
+            let mut query = sqlx::query(query).bind(&aggregate_type);
+            if let Some(aggregate_id) = &aggregate_id {
+                query = query.bind(aggregate_id);
+            }
+            let rows = query.fetch_all(&pool).await;
+            let rows = match rows {
+                Ok(rows) => rows,
+                Err(e) => {
+                    let _ = feed.push(Err(persistence_error(e))).await;
+                    return;
+                }
+            };
+            for row in rows {
+                if feed.push(deser_event(row)).await.is_err() {
+                    return;
+                }
+            }
+        });
+
+        stream
+    }
+}
+
+#[async_trait]
+impl PersistedEventRepository for SqliteEventRepository {
+    async fn get_events<A: Aggregate>(
+        &self,
+        aggregate_id: &str,
+    ) -> Result<Vec<SerializedEvent>, PersistenceError> {
+        self.select_events::<A>(aggregate_id, 0).await
+    }
+
+    async fn get_last_events<A: Aggregate>(
+        &self,
+        aggregate_id: &str,
+        last_sequence: usize,
+    ) -> Result<Vec<SerializedEvent>, PersistenceError> {
+        self.select_events::<A>(aggregate_id, last_sequence).await
+    }
+
+    async fn get_snapshot<A: Aggregate>(
+        &self,
+        aggregate_id: &str,
+    ) -> Result<Option<SerializedSnapshot>, PersistenceError> {
+        sqlx::query(SELECT_SNAPSHOT)
+            .bind(A::aggregate_type())
+            .bind(aggregate_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(persistence_error)?
+            .map(deser_snapshot)
+            .transpose()
+    }
+
+    async fn persist<A: Aggregate>(
+        &self,
+        events: &[SerializedEvent],
+        snapshot_update: Option<(String, Value, usize)>,
+    ) -> Result<(), PersistenceError> {
+        let mut tx = self.pool.begin().await.map_err(persistence_error)?;
+        let current_sequence = Self::persist_events::<A>(&mut tx, events).await?;
+        if let Some((aggregate_id, aggregate, current_snapshot)) = snapshot_update {
+            let result = if current_snapshot == 1 {
+                sqlx::query(INSERT_SNAPSHOT)
+                    .bind(A::aggregate_type())
+                    .bind(&aggregate_id)
+                    .bind(current_sequence as i64)
+                    .bind(current_snapshot as i64)
+                    .bind(aggregate.to_string())
+                    .execute(&mut *tx)
+                    .await
+            } else {
+                sqlx::query(UPDATE_SNAPSHOT)
+                    .bind(A::aggregate_type())
+                    .bind(&aggregate_id)
+                    .bind(current_sequence as i64)
+                    .bind(current_snapshot as i64)
+                    .bind((current_snapshot - 1) as i64)
+                    .bind(aggregate.to_string())
+                    .execute(&mut *tx)
+                    .await
+            }
+            .map_err(persistence_error)?;
+            if result.rows_affected() != 1 {
+                return Err(PersistenceError::OptimisticLockError);
+            }
+        }
+        tx.commit().await.map_err(persistence_error)?;
+
+        Ok(())
+    }
+
+    async fn stream_events<A: Aggregate>(
+        &self,
+        aggregate_id: &str,
+    ) -> Result<ReplayStream, PersistenceError> {
+        Ok(self.stream(
+            SELECT_EVENTS,
+            A::aggregate_type(),
+            Some(aggregate_id.to_string()),
+        ))
+    }
+
+    async fn stream_all_events<A: Aggregate>(&self) -> Result<ReplayStream, PersistenceError> {
+        Ok(self.stream(SELECT_ALL_EVENTS, A::aggregate_type(), None))
+    }
+}
+
+fn json_column(row: &SqliteRow, column: &str) -> Result<Value, PersistenceError> {
+    serde_json::from_str(row.get(column)).map_err(deserialization_error)
+}
+
+fn deser_event(row: SqliteRow) -> Result<SerializedEvent, PersistenceError> {
+    let sequence: i64 = row.get("sequence");
+
+    Ok(SerializedEvent::new(
+        row.get("aggregate_id"),
+        sequence as usize,
+        row.get("aggregate_type"),
+        row.get("event_type"),
+        row.get("event_version"),
+        json_column(&row, "payload")?,
+        json_column(&row, "metadata")?,
+    ))
+}
+
+fn deser_snapshot(row: SqliteRow) -> Result<SerializedSnapshot, PersistenceError> {
+    let current_sequence: i64 = row.get("last_sequence");
+    let current_snapshot: i64 = row.get("current_snapshot");
+
+    Ok(SerializedSnapshot {
+        aggregate_id: row.get("aggregate_id"),
+        aggregate: json_column(&row, "payload")?,
+        current_sequence: current_sequence as usize,
+        current_snapshot: current_snapshot as usize,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use cqrs_es::persist::{PersistedEventRepository, PersistenceError, SerializedEvent};
+    use serde_json::json;
+
+    use crate::{
+        domain::tab::aggregate::Tab,
+        infrasctructure::persistence::context::sqlite::{migrate_sqlite_db, sqlite_pool},
+    };
+
+    use super::SqliteEventRepository;
+
+    async fn repository() -> SqliteEventRepository {
+        let path = std::env::temp_dir().join(format!("{}.db", uuid::Uuid::new_v4()));
+        let pool = sqlite_pool(path.to_str().unwrap()).await;
+        migrate_sqlite_db(&pool).await;
+
+        SqliteEventRepository::new(pool)
+    }
+
+    fn event(sequence: usize) -> SerializedEvent {
+        SerializedEvent::new(
+            "tab-1".into(),
+            sequence,
+            "Tab".into(),
+            "TabOpened".into(),
+            "1.0".into(),
+            json!({"TabOpened": {"table": sequence}}),
+            json!({"actor": "waiter-7"}),
+        )
+    }
+
+    #[tokio::test]
+    async fn given_persisted_events_then_they_load_in_order_with_metadata() {
+        let repo = repository().await;
+
+        repo.persist::<Tab>(&[event(1), event(2), event(3)], None)
+            .await
+            .unwrap();
+
+        let events = repo.get_events::<Tab>("tab-1").await.unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[2].payload["TabOpened"]["table"], 3);
+        assert_eq!(events[0].metadata["actor"], "waiter-7");
+        let last = repo.get_last_events::<Tab>("tab-1", 2).await.unwrap();
+        assert_eq!(last.len(), 1);
+        assert_eq!(last[0].sequence, 3);
+    }
+
+    #[tokio::test]
+    async fn given_duplicate_sequence_then_optimistic_lock_error_and_nothing_persists() {
+        let repo = repository().await;
+        repo.persist::<Tab>(&[event(1)], None).await.unwrap();
+
+        let result = repo.persist::<Tab>(&[event(2), event(1)], None).await;
+
+        assert!(matches!(result, Err(PersistenceError::OptimisticLockError)));
+        assert_eq!(repo.get_events::<Tab>("tab-1").await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn given_snapshot_update_then_latest_snapshot_is_loaded() {
+        let repo = repository().await;
+
+        repo.persist::<Tab>(&[event(1)], Some(("tab-1".into(), json!({"table": 1}), 1)))
+            .await
+            .unwrap();
+        repo.persist::<Tab>(&[event(2)], Some(("tab-1".into(), json!({"table": 2}), 2)))
+            .await
+            .unwrap();
+
+        let snapshot = repo.get_snapshot::<Tab>("tab-1").await.unwrap().unwrap();
+        assert_eq!(snapshot.current_sequence, 2);
+        assert_eq!(snapshot.current_snapshot, 2);
+        assert_eq!(snapshot.aggregate["table"], 2);
+    }
+}
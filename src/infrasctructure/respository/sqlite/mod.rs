@@ -0,0 +1,22 @@
+use cqrs_es::persist::PersistenceError;
+
+pub mod cqrs;
+pub mod dead_letters;
+pub mod event_repository;
+pub mod view_repository;
+
+fn persistence_error(err: sqlx::Error) -> PersistenceError {
+    match &err {
+        sqlx::Error::Database(e) if e.is_unique_violation() => {
+            PersistenceError::OptimisticLockError
+        }
+        sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed => {
+            PersistenceError::ConnectionError(Box::new(err))
+        }
+        _ => PersistenceError::UnknownError(Box::new(err)),
+    }
+}
+
+fn deserialization_error(err: serde_json::Error) -> PersistenceError {
+    PersistenceError::DeserializationError(Box::new(err))
+}
@@ -0,0 +1,168 @@
+use std::marker::PhantomData;
+
+use async_trait::async_trait;
+use cqrs_es::{
+    persist::{PersistenceError, ViewContext, ViewRepository},
+    Aggregate, View,
+};
+use sqlx::{Pool, Row, Sqlite};
+
+use crate::domain::tab::{
+    aggregate::Tab,
+    queries::{
+        bar::BarTodoList,
+        kitchen::{KitchenTodoList, TodoListItem},
+        open_tabs::{OpenTabQuery, OpenTabs, TabInvoice, TabStatus, WaiterTodoList},
+    },
+    tab_id::TabId,
+    waiter_id::WaiterId,
+};
+
+use super::{deserialization_error, persistence_error};
+
+pub struct SqliteViewRepository<V, A> {
+    insert_sql: String,
+    update_sql: String,
+    select_sql: String,
+    select_all_sql: String,
+    pool: Pool<Sqlite>,
+    _phantom: PhantomData<(V, A)>,
+}
+
+impl<V, A> SqliteViewRepository<V, A>
+where
+    V: View<A>,
+    A: Aggregate,
+{
+    pub fn new(view_name: &str, pool: Pool<Sqlite>) -> Self {
+        Self {
+            insert_sql: format!(
+                "INSERT INTO {view_name} (payload, version, view_id) VALUES ($1, $2, $3)"
+            ),
+            update_sql: format!(
+                "UPDATE {view_name} SET payload = $2, version = $3 WHERE view_id = $4 AND version = $1"
+            ),
+            select_sql: format!("SELECT version, payload FROM {view_name} WHERE view_id = $1"),
+            select_all_sql: format!("SELECT payload FROM {view_name}"),
+            pool,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub async fn load_all(&self) -> Result<Vec<V>, PersistenceError> {
+        self.fetch_all(&self.select_all_sql).await
+    }
+
+    async fn load_where(&self, status_column: &str) -> Result<Vec<V>, PersistenceError> {
+        self.fetch_all(&format!("{} WHERE {status_column}", self.select_all_sql))
+            .await
+    }
+
+    async fn fetch_all(&self, sql: &str) -> Result<Vec<V>, PersistenceError> {
+        sqlx::query(sql)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(persistence_error)?
+            .into_iter()
+            .map(|row| serde_json::from_str(row.get("payload")).map_err(deserialization_error))
+            .collect()
+    }
+}
+
+impl SqliteViewRepository<BarTodoList, Tab> {
+    pub async fn load_outstanding(&self) -> Result<Vec<BarTodoList>, PersistenceError> {
+        Ok(BarTodoList::outstanding(
+            self.load_where("outstanding").await?,
+        ))
+    }
+}
+
+impl SqliteViewRepository<KitchenTodoList, Tab> {
+    pub async fn load_queue(&self) -> Result<Vec<(TabId, TodoListItem)>, PersistenceError> {
+        Ok(KitchenTodoList::queue(
+            self.load_where("outstanding").await?,
+        ))
+    }
+}
+
+#[async_trait]
+impl<V, A> ViewRepository<V, A> for SqliteViewRepository<V, A>
+where
+    V: View<A>,
+    A: Aggregate,
+{
+    async fn load(&self, view_id: &str) -> Result<Option<V>, PersistenceError> {
+        Ok(self.load_with_context(view_id).await?.map(|(view, _)| view))
+    }
+
+    async fn load_with_context(
+        &self,
+        view_id: &str,
+    ) -> Result<Option<(V, ViewContext)>, PersistenceError> {
+        let row = sqlx::query(&self.select_sql)
+            .bind(view_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(persistence_error)?;
+        match row {
+            None => Ok(None),
+            Some(row) => {
+                let view =
+                    serde_json::from_str(row.get("payload")).map_err(deserialization_error)?;
+                Ok(Some((
+                    view,
+                    ViewContext::new(view_id.to_string(), row.get("version")),
+                )))
+            }
+        }
+    }
+
+    async fn update_view(&self, view: V, context: ViewContext) -> Result<(), PersistenceError> {
+        let payload = serde_json::to_string(&view).map_err(deserialization_error)?;
+        let query = match context.version {
+            0 => sqlx::query(&self.insert_sql),
+            version => sqlx::query(&self.update_sql).bind(version),
+        };
+        let result = query
+            .bind(payload)
+            .bind(context.version + 1)
+            .bind(&context.view_instance_id)
+            .execute(&self.pool)
+            .await
+            .map_err(persistence_error)?;
+        match result.rows_affected() {
+            1 => Ok(()),
+            _ => Err(PersistenceError::OptimisticLockError),
+        }
+    }
+}
+
+#[async_trait]
+impl OpenTabQuery for SqliteViewRepository<TabStatus, Tab> {
+    async fn active_table_numbers(&self) -> Result<Vec<usize>, PersistenceError> {
+        OpenTabs::new(self.load_where("open").await?)
+            .active_table_numbers()
+            .await
+    }
+
+    async fn invoice_for_table(
+        &self,
+        table: usize,
+    ) -> Result<Option<TabInvoice>, PersistenceError> {
+        OpenTabs::new(self.load_where("open").await?)
+            .invoice_for_table(table)
+            .await
+    }
+
+    async fn tab_for_table(&self, table: usize) -> Result<Option<TabStatus>, PersistenceError> {
+        OpenTabs::new(self.load_where("open").await?)
+            .tab_for_table(table)
+            .await
+    }
+
+    async fn waiter_todo_list(&self, id: WaiterId) -> Result<WaiterTodoList, PersistenceError> {
+        OpenTabs::new(self.load_where("open").await?)
+            .waiter_todo_list(id)
+            .await
+    }
+}
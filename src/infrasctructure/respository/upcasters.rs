@@ -0,0 +1,424 @@
+use cqrs_es::{
+    persist::{EventUpcaster, PersistenceError, SemanticVersionEventUpcaster, SerializedEvent},
+    EventEnvelope,
+};
+use serde_json::{json, Value};
+
+use crate::{
+    domain::{gift_card::redemption_id::RedemptionId, tab::aggregate::Tab},
+    shared_kernel::money::Currency,
+};
+
+// Amounts recorded before currencies existed are read in `legacy`, the
+// deployment's menu currency.
+pub fn tab_upcasters(legacy: Currency) -> Vec<Box<dyn EventUpcaster>> {
+    vec![
+        Box::new(SemanticVersionEventUpcaster::new(
+            "FoodOrderPlaced",
+            "1.1.0",
+            Box::new(|payload| menu_item_v1_1("FoodOrderPlaced", payload)),
+        )),
+        Box::new(SemanticVersionEventUpcaster::new(
+            "DrinkOrderPlaced",
+            "1.1.0",
+            Box::new(|payload| menu_item_v1_1("DrinkOrderPlaced", payload)),
+        )),
+        Box::new(SemanticVersionEventUpcaster::new(
+            "FoodOrderPlaced",
+            "1.2.0",
+            Box::new(move |payload| menu_item_v1_2("FoodOrderPlaced", payload, legacy)),
+        )),
+        Box::new(SemanticVersionEventUpcaster::new(
+            "DrinkOrderPlaced",
+            "1.2.0",
+            Box::new(move |payload| menu_item_v1_2("DrinkOrderPlaced", payload, legacy)),
+        )),
+        Box::new(SemanticVersionEventUpcaster::new(
+            "FoodOrderPlaced",
+            "1.3.0",
+            Box::new(|payload| menu_item_v1_3("FoodOrderPlaced", payload)),
+        )),
+        Box::new(SemanticVersionEventUpcaster::new(
+            "DrinkOrderPlaced",
+            "1.3.0",
+            Box::new(|payload| menu_item_v1_3("DrinkOrderPlaced", payload)),
+        )),
+        Box::new(SemanticVersionEventUpcaster::new(
+            "TabClosed",
+            "1.1.0",
+            Box::new(move |payload| tab_closed_v1_1(payload, legacy)),
+        )),
+        Box::new(SemanticVersionEventUpcaster::new(
+            "TabClosed",
+            "1.2.0",
+            Box::new(tab_closed_v1_2),
+        )),
+        Box::new(SemanticVersionEventUpcaster::new(
+            "GiftCardPaymentReceived",
+            "1.1.0",
+            Box::new(gift_card_payment_v1_1),
+        )),
+        Box::new(SemanticVersionEventUpcaster::new(
+            "PromotionApplied",
+            "1.1.0",
+            Box::new(promotion_applied_v1_1),
+        )),
+    ]
+}
+
+pub fn upcast_event(
+    upcasters: &[Box<dyn EventUpcaster>],
+    event: SerializedEvent,
+) -> SerializedEvent {
+    let mut event = event;
+    for upcaster in upcasters {
+        if upcaster.can_upcast(&event.event_type, &event.event_version) {
+            event = upcaster.upcast(event);
+        }
+    }
+
+    event
+}
+
+pub fn deserialize_tab_event(
+    upcasters: &[Box<dyn EventUpcaster>],
+    event: SerializedEvent,
+) -> Result<EventEnvelope<Tab>, PersistenceError> {
+    EventEnvelope::try_from(upcast_event(upcasters, event))
+}
+
+// 1.1 added the order priority and kitchen notes to MenuItem
+fn menu_item_v1_1(event_type: &str, mut payload: Value) -> Value {
+    if let Some(Value::Object(menu_item)) = payload
+        .get_mut(event_type)
+        .and_then(|e| e.get_mut("menu_item"))
+    {
+        menu_item
+            .entry("priority")
+            .or_insert_with(|| Value::String("Normal".into()));
+        menu_item.entry("notes").or_insert(Value::Null);
+    }
+
+    payload
+}
+
+// 1.2 gave prices a currency
+fn menu_item_v1_2(event_type: &str, mut payload: Value, legacy: Currency) -> Value {
+    if let Some(menu_item) = payload
+        .get_mut(event_type)
+        .and_then(|e| e.get_mut("menu_item"))
+    {
+        with_currency(menu_item, "price", legacy);
+    }
+
+    payload
+}
+
+// 1.3 recorded when the item was ordered; earlier items have no time
+fn menu_item_v1_3(event_type: &str, mut payload: Value) -> Value {
+    if let Some(Value::Object(menu_item)) = payload
+        .get_mut(event_type)
+        .and_then(|e| e.get_mut("menu_item"))
+    {
+        menu_item.entry("ordered_at").or_insert(Value::Null);
+    }
+
+    payload
+}
+
+// 1.1 gave the payment, order value and tip a currency
+fn tab_closed_v1_1(mut payload: Value, legacy: Currency) -> Value {
+    if let Some(tab_closed) = payload.get_mut("TabClosed") {
+        for field in ["amount_paid", "order_value", "tip_value"] {
+            with_currency(tab_closed, field, legacy);
+        }
+    }
+
+    payload
+}
+
+// 1.2 recorded what was handed over and the exchange rate used, if any
+fn tab_closed_v1_2(mut payload: Value) -> Value {
+    if let Some(Value::Object(tab_closed)) = payload.get_mut("TabClosed") {
+        let amount_paid = tab_closed
+            .get("amount_paid")
+            .cloned()
+            .unwrap_or(Value::Null);
+        tab_closed.entry("amount_tendered").or_insert(amount_paid);
+        tab_closed.entry("exchange_rate").or_insert(Value::Null);
+    }
+
+    payload
+}
+
+// 1.1 recorded the gift card redemption; earlier payments get the nil id
+fn gift_card_payment_v1_1(mut payload: Value) -> Value {
+    if let Some(Value::Object(payment)) = payload
+        .get_mut("GiftCardPaymentReceived")
+        .and_then(|e| e.get_mut("payment"))
+    {
+        payment
+            .entry("redemption")
+            .or_insert_with(|| json!(RedemptionId::default()));
+    }
+
+    payload
+}
+
+// 1.1 recorded when each discounted unit was ordered; earlier promotions
+// only know their quantities
+fn promotion_applied_v1_1(mut payload: Value) -> Value {
+    if let Some(Value::Object(promotion)) = payload
+        .get_mut("PromotionApplied")
+        .and_then(|e| e.get_mut("promotion"))
+    {
+        promotion.entry("units").or_insert_with(|| json!([]));
+    }
+
+    payload
+}
+
+fn with_currency(object: &mut Value, field: &str, currency: Currency) {
+    if let Some(amount) = object.get_mut(field) {
+        if !amount.is_object() {
+            *amount = json!({
+                "amount": amount.take(),
+                "currency": currency.code(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use cqrs_es::{persist::SerializedEvent, Aggregate, DomainEvent, EventEnvelope};
+    use rust_decimal::Decimal;
+    use serde_json::{json, Value};
+
+    use crate::{
+        domain::tab::{
+            aggregate::Tab, command::TabCommand, event::TabEvent, order_priority::OrderPriority,
+            services::TabServices, tab_id::TabId,
+        },
+        shared_kernel::money::{Currency, Money, Rounding},
+    };
+
+    use super::{deserialize_tab_event, tab_upcasters, upcast_event};
+
+    pub fn v1_fixture() -> Vec<SerializedEvent> {
+        let rows: Vec<Value> =
+            serde_json::from_str(include_str!("../../../tests/fixtures/tab_events_v1.json"))
+                .expect("invalid fixture");
+        rows.into_iter()
+            .map(|row| {
+                SerializedEvent::new(
+                    row["aggregate_id"].as_str().unwrap().to_string(),
+                    row["sequence"].as_u64().unwrap() as usize,
+                    row["aggregate_type"].as_str().unwrap().to_string(),
+                    row["event_type"].as_str().unwrap().to_string(),
+                    row["event_version"].as_str().unwrap().to_string(),
+                    row["payload"].clone(),
+                    row["metadata"].clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn given_v1_food_order_then_upcast_to_current_version_with_defaults() {
+        let upcasters = tab_upcasters(Currency::USD);
+        let event = v1_fixture().remove(1);
+
+        let upcasted = upcast_event(&upcasters, event);
+
+        assert_eq!(upcasted.event_version, "1.3.0");
+        assert_eq!(
+            upcasted.event_version,
+            EventEnvelope::<Tab>::try_from(upcasted.clone())
+                .unwrap()
+                .payload
+                .event_version()
+        );
+        let menu_item = &upcasted.payload["FoodOrderPlaced"]["menu_item"];
+        assert_eq!(menu_item["priority"], "Normal");
+        assert_eq!(menu_item["notes"], Value::Null);
+        assert_eq!(menu_item["ordered_at"], Value::Null);
+        assert_eq!(
+            menu_item["price"],
+            json!({"amount": "10", "currency": "USD"})
+        );
+    }
+
+    #[test]
+    fn given_v1_tab_closed_then_amounts_are_upcast_to_money() {
+        let upcasters = tab_upcasters(Currency::USD);
+        let event = SerializedEvent::new(
+            TabId::default().to_string(),
+            5,
+            "Tab".into(),
+            "TabClosed".into(),
+            "1.0".into(),
+            json!({"TabClosed": {
+                "id": TabId::default(),
+                "amount_paid": "15",
+                "order_value": "13",
+                "tip_value": "2"
+            }}),
+            json!({}),
+        );
+
+        let envelope = deserialize_tab_event(&upcasters, event).expect("failed to load event");
+
+        let usd = |amount| Money::new(Decimal::from(amount), Currency::USD).unwrap();
+        assert_eq!(
+            envelope.payload,
+            TabEvent::TabClosed {
+                id: TabId::default(),
+                amount_paid: usd(15),
+                order_value: usd(13),
+                tip_value: usd(2),
+                amount_tendered: usd(15),
+                exchange_rate: None,
+            }
+        );
+    }
+
+    #[test]
+    fn given_v1_promotion_applied_then_it_is_upcast_without_units() {
+        let upcasters = tab_upcasters(Currency::USD);
+        let event = SerializedEvent::new(
+            TabId::default().to_string(),
+            3,
+            "Tab".into(),
+            "PromotionApplied".into(),
+            "1.0.0".into(),
+            json!({"PromotionApplied": {
+                "id": TabId::default(),
+                "promotion": {
+                    "promotion_id": "happy-hour",
+                    "name": "Happy hour",
+                    "items": [[7, 1]],
+                    "discount": {"amount": "5", "currency": "USD"}
+                }
+            }}),
+            json!({}),
+        );
+
+        let upcasted = upcast_event(&upcasters, event);
+
+        assert_eq!(upcasted.event_version, "1.1.0");
+        assert_eq!(
+            upcasted.payload["PromotionApplied"]["promotion"]["units"],
+            json!([])
+        );
+        assert!(deserialize_tab_event(&upcasters, upcasted).is_ok());
+    }
+
+    #[test]
+    fn given_current_version_event_then_it_is_not_upcast() {
+        let upcasters = tab_upcasters(Currency::USD);
+        let mut event = v1_fixture().remove(1);
+        event.event_version = "1.3.0".into();
+        event.payload["FoodOrderPlaced"]["menu_item"]["priority"] = "Rush".into();
+        event.payload["FoodOrderPlaced"]["menu_item"]["price"] =
+            json!({"amount": "10", "currency": "ETB"});
+
+        let upcasted = upcast_event(&upcasters, event);
+
+        let menu_item = &upcasted.payload["FoodOrderPlaced"]["menu_item"];
+        assert_eq!(menu_item["priority"], "Rush");
+        assert_eq!(menu_item["price"]["currency"], "ETB");
+    }
+
+    #[tokio::test]
+    async fn given_v1_fixture_then_events_load_into_latest_tab() {
+        let upcasters = tab_upcasters(Currency::USD);
+        let mut tab = Tab::default();
+        let mut last_event = None;
+        for event in v1_fixture() {
+            let envelope = deserialize_tab_event(&upcasters, event).expect("failed to load event");
+            assert_eq!(
+                envelope.payload.event_version(),
+                match envelope.payload {
+                    TabEvent::FoodOrderPlaced { .. } | TabEvent::DrinkOrderPlaced { .. } => "1.3.0",
+                    _ => "1.0.0",
+                }
+            );
+            if let TabEvent::FoodOrderPlaced { menu_item, .. } = &envelope.payload {
+                assert_eq!(menu_item.priority, OrderPriority::Normal);
+                assert_eq!(menu_item.notes, None);
+                assert_eq!(menu_item.ordered_at, None);
+            }
+            last_event = Some(envelope.payload.clone());
+            tab.apply(envelope.payload);
+        }
+        let id = match last_event {
+            Some(TabEvent::DrinkServed { id, .. }) => id,
+            _ => TabId::default(),
+        };
+
+        let usd = |amount| Money::new(Decimal::from(amount), Currency::USD).unwrap();
+        let events = tab
+            .handle(
+                TabCommand::CloseTab {
+                    id,
+                    amount_paid: usd(15),
+                    gift_card: None,
+                },
+                &TabServices::default(),
+            )
+            .await
+            .expect("fixture tab should be open");
+
+        assert_eq!(
+            events,
+            vec![TabEvent::TabClosed {
+                id,
+                amount_paid: usd(15),
+                order_value: usd(13),
+                tip_value: usd(2),
+                amount_tendered: usd(15),
+                exchange_rate: None,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn given_v1_fixture_in_etb_deployment_then_legacy_tab_closes_in_etb() {
+        let upcasters = tab_upcasters(Currency::ETB);
+        let mut tab = Tab::default();
+        let mut id = TabId::default();
+        for event in v1_fixture() {
+            let envelope = deserialize_tab_event(&upcasters, event).expect("failed to load event");
+            if let TabEvent::DrinkServed { id: served, .. } = envelope.payload {
+                id = served;
+            }
+            tab.apply(envelope.payload);
+        }
+
+        let etb = |amount| Money::new(Decimal::from(amount), Currency::ETB).unwrap();
+        let events = tab
+            .handle(
+                TabCommand::CloseTab {
+                    id,
+                    amount_paid: etb(15),
+                    gift_card: None,
+                },
+                &TabServices::new(Currency::ETB, Rounding::default()),
+            )
+            .await
+            .expect("legacy tab should close in the menu currency");
+
+        assert_eq!(
+            events,
+            vec![TabEvent::TabClosed {
+                id,
+                amount_paid: etb(15),
+                order_value: etb(13),
+                tip_value: etb(2),
+                amount_tendered: etb(15),
+                exchange_rate: None,
+            }]
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use cqrs_es::{Aggregate, EventEnvelope};
+use uuid::Uuid;
+
+const ACTOR: &str = "actor";
+const TERMINAL: &str = "terminal";
+const ISSUED_AT: &str = "issued_at";
+const CORRELATION_ID: &str = "correlation_id";
+const CAUSATION_ID: &str = "causation_id";
+pub const IDEMPOTENCY_KEY: &str = "idempotency_key";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommandContext {
+    actor: String,
+    terminal: Option<String>,
+    issued_at: DateTime<Utc>,
+    correlation_id: Uuid,
+    causation_id: Option<String>,
+    idempotency_key: Option<String>,
+}
+
+impl CommandContext {
+    pub fn new(actor: &str) -> Self {
+        Self {
+            actor: actor.to_owned(),
+            terminal: None,
+            issued_at: Utc::now(),
+            correlation_id: Uuid::new_v4(),
+            causation_id: None,
+            idempotency_key: None,
+        }
+    }
+
+    pub fn caused_by<A: Aggregate>(actor: &str, event: &EventEnvelope<A>) -> Self {
+        let correlation_id = Self::from_envelope(event)
+            .map(|c| c.correlation_id)
+            .unwrap_or_else(Uuid::new_v4);
+
+        Self {
+            correlation_id,
+            causation_id: Some(format!("{}:{}", event.aggregate_id, event.sequence)),
+            ..Self::new(actor)
+        }
+    }
+
+    pub fn with_terminal(self, terminal: &str) -> Self {
+        Self {
+            terminal: Some(terminal.to_owned()),
+            ..self
+        }
+    }
+
+    pub fn with_issued_at(self, issued_at: DateTime<Utc>) -> Self {
+        Self { issued_at, ..self }
+    }
+
+    pub fn with_correlation_id(self, correlation_id: Uuid) -> Self {
+        Self {
+            correlation_id,
+            ..self
+        }
+    }
+
+    pub fn with_causation_id(self, causation_id: &str) -> Self {
+        Self {
+            causation_id: Some(causation_id.to_owned()),
+            ..self
+        }
+    }
+
+    pub fn with_idempotency_key(self, idempotency_key: &str) -> Self {
+        Self {
+            idempotency_key: Some(idempotency_key.to_owned()),
+            ..self
+        }
+    }
+
+    pub fn actor(&self) -> &str {
+        &self.actor
+    }
+
+    pub fn terminal(&self) -> Option<&str> {
+        self.terminal.as_deref()
+    }
+
+    pub fn issued_at(&self) -> DateTime<Utc> {
+        self.issued_at
+    }
+
+    pub fn correlation_id(&self) -> Uuid {
+        self.correlation_id
+    }
+
+    pub fn causation_id(&self) -> Option<&str> {
+        self.causation_id.as_deref()
+    }
+
+    pub fn idempotency_key(&self) -> Option<&str> {
+        self.idempotency_key.as_deref()
+    }
+
+    pub fn to_metadata(&self) -> HashMap<String, String> {
+        let mut metadata = HashMap::new();
+        metadata.insert(ACTOR.to_string(), self.actor.clone());
+        metadata.insert(ISSUED_AT.to_string(), self.issued_at.to_rfc3339());
+        metadata.insert(CORRELATION_ID.to_string(), self.correlation_id.to_string());
+        if let Some(terminal) = &self.terminal {
+            metadata.insert(TERMINAL.to_string(), terminal.clone());
+        }
+        if let Some(causation_id) = &self.causation_id {
+            metadata.insert(CAUSATION_ID.to_string(), causation_id.clone());
+        }
+        if let Some(idempotency_key) = &self.idempotency_key {
+            metadata.insert(IDEMPOTENCY_KEY.to_string(), idempotency_key.clone());
+        }
+
+        metadata
+    }
+
+    pub fn from_metadata(metadata: &HashMap<String, String>) -> Option<Self> {
+        let issued_at = DateTime::parse_from_rfc3339(metadata.get(ISSUED_AT)?)
+            .ok()?
+            .with_timezone(&Utc);
+
+        Some(Self {
+            actor: metadata.get(ACTOR)?.clone(),
+            terminal: metadata.get(TERMINAL).cloned(),
+            issued_at,
+            correlation_id: metadata.get(CORRELATION_ID)?.parse().ok()?,
+            causation_id: metadata.get(CAUSATION_ID).cloned(),
+            idempotency_key: metadata.get(IDEMPOTENCY_KEY).cloned(),
+        })
+    }
+
+    pub fn from_envelope<A: Aggregate>(event: &EventEnvelope<A>) -> Option<Self> {
+        Self::from_metadata(&event.metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::{TimeZone, Utc};
+    use cqrs_es::EventEnvelope;
+    use uuid::Uuid;
+
+    use crate::domain::tab::{aggregate::Tab, event::TabEvent, tab_id::TabId};
+
+    use super::CommandContext;
+
+    #[test]
+    fn given_context_when_converted_to_metadata_and_back_then_values_are_preserved() {
+        let ctx = CommandContext::new("waiter-7")
+            .with_terminal("handheld-2")
+            .with_issued_at(Utc.with_ymd_and_hms(2024, 4, 12, 18, 30, 0).unwrap())
+            .with_causation_id("tab-1:3")
+            .with_idempotency_key("order-42");
+
+        let metadata = ctx.to_metadata();
+
+        assert_eq!(metadata["actor"], "waiter-7");
+        assert_eq!(metadata["terminal"], "handheld-2");
+        assert_eq!(metadata["issued_at"], "2024-04-12T18:30:00+00:00");
+        assert_eq!(metadata["idempotency_key"], "order-42");
+        assert_eq!(CommandContext::from_metadata(&metadata), Some(ctx));
+    }
+
+    #[test]
+    fn given_empty_metadata_then_there_is_no_context() {
+        assert_eq!(CommandContext::from_metadata(&HashMap::new()), None);
+    }
+
+    #[test]
+    fn given_event_when_caused_by_then_correlation_is_kept_and_causation_points_to_event() {
+        let tab_id = TabId::new();
+        let correlation_id = Uuid::new_v4();
+        let event = EventEnvelope::<Tab> {
+            aggregate_id: tab_id.to_string(),
+            sequence: 4,
+            payload: TabEvent::FoodPrepared {
+                id: tab_id,
+                menu_number: 1,
+            },
+            metadata: CommandContext::new("chef")
+                .with_correlation_id(correlation_id)
+                .to_metadata(),
+        };
+
+        let ctx = CommandContext::caused_by("waiter-7", &event);
+
+        assert_eq!(ctx.actor(), "waiter-7");
+        assert_eq!(ctx.correlation_id(), correlation_id);
+        assert_eq!(ctx.causation_id(), Some(format!("{tab_id}:4").as_str()));
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use cqrs_es::persist::PersistenceError;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use super::money::{Currency, Money, MoneyError};
+
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ExchangeRate {
+    from: Currency,
+    to: Currency,
+    rate: Decimal,
+    effective_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait ExchangeRates: std::fmt::Debug + Send + Sync {
+    // The rate in effect now for converting `from` into `to`, if one is known.
+    async fn rate(
+        &self,
+        from: Currency,
+        to: Currency,
+    ) -> Result<Option<ExchangeRate>, PersistenceError>;
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ExchangeRateTable {
+    rates: HashMap<(Currency, Currency), ExchangeRate>,
+}
+
+impl ExchangeRate {
+    pub fn new(
+        from: Currency,
+        to: Currency,
+        rate: Decimal,
+        effective_at: DateTime<Utc>,
+    ) -> Result<Self, MoneyError> {
+        if rate <= Decimal::ZERO {
+            return Err(MoneyError::InvalidRate(rate));
+        }
+
+        Ok(Self {
+            from,
+            to,
+            rate,
+            effective_at,
+        })
+    }
+
+    pub fn from(&self) -> Currency {
+        self.from
+    }
+
+    pub fn to(&self) -> Currency {
+        self.to
+    }
+
+    pub fn rate(&self) -> Decimal {
+        self.rate
+    }
+
+    pub fn effective_at(&self) -> DateTime<Utc> {
+        self.effective_at
+    }
+
+    pub fn convert(&self, money: Money) -> Result<Money, MoneyError> {
+        if money.currency() != self.from {
+            return Err(MoneyError::CurrencyMismatch {
+                expected: self.from,
+                actual: money.currency(),
+            });
+        }
+
+        Money::new(money.amount() * self.rate, self.to)
+    }
+}
+
+impl ExchangeRateTable {
+    pub fn with_rate(mut self, rate: ExchangeRate) -> Self {
+        self.rates.insert((rate.from, rate.to), rate);
+
+        self
+    }
+}
+
+#[async_trait]
+impl ExchangeRates for ExchangeRateTable {
+    async fn rate(
+        &self,
+        from: Currency,
+        to: Currency,
+    ) -> Result<Option<ExchangeRate>, PersistenceError> {
+        Ok(self.rates.get(&(from, to)).copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chrono::Utc;
+    use rust_decimal::Decimal;
+
+    use crate::shared_kernel::money::{Currency, Money, MoneyError};
+
+    use super::ExchangeRate;
+
+    #[test]
+    fn given_rate_then_amount_is_converted_into_target_currency() {
+        let rate = ExchangeRate::new(
+            Currency::EUR,
+            Currency::USD,
+            Decimal::from_str("1.08").unwrap(),
+            Utc::now(),
+        )
+        .unwrap();
+
+        let actual = rate.convert(Money::new(Decimal::from(20), Currency::EUR).unwrap());
+
+        assert_eq!(
+            actual,
+            Money::new(Decimal::from_str("21.60").unwrap(), Currency::USD)
+        );
+        assert_eq!(
+            rate.convert(Money::zero(Currency::ETB)),
+            Err(MoneyError::CurrencyMismatch {
+                expected: Currency::EUR,
+                actual: Currency::ETB
+            })
+        );
+    }
+
+    #[test]
+    fn rates_must_be_positive() {
+        let actual = ExchangeRate::new(Currency::EUR, Currency::USD, Decimal::ZERO, Utc::now());
+
+        assert_eq!(actual, Err(MoneyError::InvalidRate(Decimal::ZERO)));
+    }
+}
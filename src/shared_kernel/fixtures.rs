@@ -0,0 +1,7 @@
+use rust_decimal::Decimal;
+
+use super::money::{Currency, Money};
+
+pub fn usd(amount: impl Into<Decimal>) -> Money {
+    Money::new(amount.into(), Currency::USD).unwrap()
+}
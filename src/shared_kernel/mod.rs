@@ -1,38 +1,101 @@
+pub mod clock;
+pub mod command_context;
+pub mod exchange_rates;
+#[cfg(test)]
+pub(crate) mod fixtures;
+pub mod money;
+
 use std::ops::Deref;
 use std::sync::Arc;
 
-use cqrs_es::persist::GenericQuery;
+use async_trait::async_trait;
 use cqrs_es::persist::PersistenceError;
 use cqrs_es::persist::ViewRepository;
 use postgres_es::PostgresViewRepository;
+use serde::de::DeserializeOwned;
 use sqlx::{Pool, Postgres};
 
 use crate::domain::tab::aggregate::Tab;
-use crate::domain::tab::queries::kitchen::KitchenTodoList;
-use crate::domain::tab::queries::open_tabs::WaiterTodoList;
+use crate::domain::tab::queries::bar::BarTodoList;
+use crate::domain::tab::queries::kitchen::{KitchenTodoList, TodoListItem};
+use crate::domain::tab::queries::open_tabs::{
+    OpenTabQuery, OpenTabs, TabInvoice, TabStatus, WaiterTodoList,
+};
+use crate::domain::tab::tab_id::TabId;
+use crate::domain::tab::waiter_id::WaiterId;
+use crate::infrasctructure::respository::query_errors::ResilientQuery;
 
 pub type KitchenTabQuery =
-    GenericQuery<PostgresViewRepository<KitchenTodoList, Tab>, KitchenTodoList, Tab>;
+    ResilientQuery<PostgresViewRepository<KitchenTodoList, Tab>, KitchenTodoList>;
 
 #[derive(Clone)]
-pub struct KitchenTabViewRepository(Arc<PostgresViewRepository<KitchenTodoList, Tab>>);
+pub struct KitchenTabViewRepository {
+    repo: Arc<PostgresViewRepository<KitchenTodoList, Tab>>,
+    pool: Pool<Postgres>,
+}
 
 pub type WaiterTabQuery =
-    GenericQuery<PostgresViewRepository<WaiterTodoList, Tab>, WaiterTodoList, Tab>;
+    ResilientQuery<PostgresViewRepository<WaiterTodoList, Tab>, WaiterTodoList>;
 
 #[derive(Clone)]
 pub struct WaiterTabViewRepository(Arc<PostgresViewRepository<WaiterTodoList, Tab>>);
 
+pub type BarTabQuery = ResilientQuery<PostgresViewRepository<BarTodoList, Tab>, BarTodoList>;
+
+#[derive(Clone)]
+pub struct BarTabViewRepository {
+    repo: Arc<PostgresViewRepository<BarTodoList, Tab>>,
+    pool: Pool<Postgres>,
+}
+
+pub type TabStatusQuery = ResilientQuery<PostgresViewRepository<TabStatus, Tab>, TabStatus>;
+
+#[derive(Clone)]
+pub struct OpenTabsViewRepository {
+    repo: Arc<PostgresViewRepository<TabStatus, Tab>>,
+    pool: Pool<Postgres>,
+}
+
+async fn load_all_views<V: DeserializeOwned>(
+    pool: &Pool<Postgres>,
+    view_name: &str,
+    status_column: &str,
+) -> Result<Vec<V>, PersistenceError> {
+    let sql = format!("SELECT payload FROM {view_name} WHERE {status_column}");
+    let rows: Vec<(serde_json::Value,)> = sqlx::query_as(&sql)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| PersistenceError::ConnectionError(Box::new(e)))?;
+    let mut result = Vec::with_capacity(rows.len());
+    for (payload,) in rows {
+        result.push(
+            serde_json::from_value(payload)
+                .map_err(|e| PersistenceError::DeserializationError(Box::new(e)))?,
+        );
+    }
+
+    Ok(result)
+}
+
 impl KitchenTabViewRepository {
     pub fn new(pool: Pool<Postgres>) -> Self {
-        Self(Arc::new(PostgresViewRepository::new(
-            "kitchen_tab_query",
-            pool.clone(),
-        )))
+        Self {
+            repo: Arc::new(PostgresViewRepository::new(
+                "kitchen_tab_query",
+                pool.clone(),
+            )),
+            pool,
+        }
     }
 
     pub async fn load(&self, view_id: &str) -> Result<Option<KitchenTodoList>, PersistenceError> {
-        self.0.load(view_id).await
+        self.repo.load(view_id).await
+    }
+
+    pub async fn load_queue(&self) -> Result<Vec<(TabId, TodoListItem)>, PersistenceError> {
+        Ok(KitchenTodoList::queue(
+            load_all_views(&self.pool, "kitchen_tab_query", "outstanding").await?,
+        ))
     }
 }
 
@@ -49,11 +112,71 @@ impl WaiterTabViewRepository {
     }
 }
 
+impl BarTabViewRepository {
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self {
+            repo: Arc::new(PostgresViewRepository::new("bar_tab_query", pool.clone())),
+            pool,
+        }
+    }
+
+    pub async fn load(&self, view_id: &str) -> Result<Option<BarTodoList>, PersistenceError> {
+        self.repo.load(view_id).await
+    }
+
+    pub async fn load_all(&self) -> Result<Vec<BarTodoList>, PersistenceError> {
+        Ok(BarTodoList::outstanding(
+            load_all_views(&self.pool, "bar_tab_query", "outstanding").await?,
+        ))
+    }
+}
+
+impl OpenTabsViewRepository {
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self {
+            repo: Arc::new(PostgresViewRepository::new("tab_query", pool.clone())),
+            pool,
+        }
+    }
+
+    pub async fn load(&self, view_id: &str) -> Result<Option<TabStatus>, PersistenceError> {
+        self.repo.load(view_id).await
+    }
+
+    pub async fn load_open_tabs(&self) -> Result<OpenTabs, PersistenceError> {
+        Ok(OpenTabs::new(
+            load_all_views(&self.pool, "tab_query", "open").await?,
+        ))
+    }
+}
+
+#[async_trait]
+impl OpenTabQuery for OpenTabsViewRepository {
+    async fn active_table_numbers(&self) -> Result<Vec<usize>, PersistenceError> {
+        self.load_open_tabs().await?.active_table_numbers().await
+    }
+
+    async fn invoice_for_table(
+        &self,
+        table: usize,
+    ) -> Result<Option<TabInvoice>, PersistenceError> {
+        self.load_open_tabs().await?.invoice_for_table(table).await
+    }
+
+    async fn tab_for_table(&self, table: usize) -> Result<Option<TabStatus>, PersistenceError> {
+        self.load_open_tabs().await?.tab_for_table(table).await
+    }
+
+    async fn waiter_todo_list(&self, id: WaiterId) -> Result<WaiterTodoList, PersistenceError> {
+        self.load_open_tabs().await?.waiter_todo_list(id).await
+    }
+}
+
 impl std::ops::Deref for KitchenTabViewRepository {
     type Target = Arc<PostgresViewRepository<KitchenTodoList, Tab>>;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.repo
     }
 }
 
@@ -65,6 +188,22 @@ impl std::ops::Deref for WaiterTabViewRepository {
     }
 }
 
+impl std::ops::Deref for BarTabViewRepository {
+    type Target = Arc<PostgresViewRepository<BarTodoList, Tab>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.repo
+    }
+}
+
+impl std::ops::Deref for OpenTabsViewRepository {
+    type Target = Arc<PostgresViewRepository<TabStatus, Tab>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.repo
+    }
+}
+
 impl From<KitchenTabViewRepository> for Arc<PostgresViewRepository<KitchenTodoList, Tab>> {
     fn from(value: KitchenTabViewRepository) -> Self {
         value.deref().clone()
@@ -76,3 +215,15 @@ impl From<WaiterTabViewRepository> for Arc<PostgresViewRepository<WaiterTodoList
         value.deref().clone()
     }
 }
+
+impl From<BarTabViewRepository> for Arc<PostgresViewRepository<BarTodoList, Tab>> {
+    fn from(value: BarTabViewRepository) -> Self {
+        value.deref().clone()
+    }
+}
+
+impl From<OpenTabsViewRepository> for Arc<PostgresViewRepository<TabStatus, Tab>> {
+    fn from(value: OpenTabsViewRepository) -> Self {
+        value.deref().clone()
+    }
+}
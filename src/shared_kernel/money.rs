@@ -0,0 +1,306 @@
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Currency([u8; 3]);
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub enum Rounding {
+    #[default]
+    HalfUp,
+    Bankers,
+    // To the nearest 0.05, for tabs settled in cash.
+    Cash,
+}
+
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq, Serialize)]
+pub struct Money {
+    amount: Decimal,
+    currency: Currency,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MoneyError {
+    Negative,
+    InvalidCurrency(String),
+    InvalidRate(Decimal),
+    CurrencyMismatch {
+        expected: Currency,
+        actual: Currency,
+    },
+    Overflow,
+}
+
+// Amounts stored before currencies were recorded are plain decimals. Events
+// are upcast with the menu currency before they are read; anything else
+// holding one is rejected rather than guessed.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredMoney {
+    Current { amount: Decimal, currency: Currency },
+    Legacy(Decimal),
+}
+
+impl Currency {
+    pub const ETB: Currency = Currency(*b"ETB");
+    pub const EUR: Currency = Currency(*b"EUR");
+    pub const USD: Currency = Currency(*b"USD");
+
+    pub fn new(code: &str) -> Result<Self, MoneyError> {
+        match code.as_bytes() {
+            &[a, b, c] if code.bytes().all(|b| b.is_ascii_uppercase()) => Ok(Self([a, b, c])),
+            _ => Err(MoneyError::InvalidCurrency(code.to_string())),
+        }
+    }
+
+    pub fn code(&self) -> &str {
+        std::str::from_utf8(&self.0).expect("currency codes are ASCII")
+    }
+
+    pub fn minor_units(&self) -> u32 {
+        match self.code() {
+            "JPY" | "KRW" | "VND" => 0,
+            _ => 2,
+        }
+    }
+}
+
+impl Default for Currency {
+    fn default() -> Self {
+        Self::USD
+    }
+}
+
+impl TryFrom<String> for Currency {
+    type Error = MoneyError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::new(&value)
+    }
+}
+
+impl From<Currency> for String {
+    fn from(value: Currency) -> Self {
+        value.code().to_string()
+    }
+}
+
+impl std::fmt::Display for Currency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+impl Money {
+    pub fn new(amount: Decimal, currency: Currency) -> Result<Self, MoneyError> {
+        if amount.is_sign_negative() && !amount.is_zero() {
+            return Err(MoneyError::Negative);
+        }
+
+        Ok(Self { amount, currency })
+    }
+
+    pub fn zero(currency: Currency) -> Self {
+        Self {
+            amount: Decimal::ZERO,
+            currency,
+        }
+    }
+
+    pub fn amount(&self) -> Decimal {
+        self.amount
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.amount.is_zero()
+    }
+
+    pub fn round(self, rounding: Rounding) -> Self {
+        let dp = self.currency.minor_units();
+        let amount = match rounding {
+            Rounding::HalfUp => self
+                .amount
+                .round_dp_with_strategy(dp, RoundingStrategy::MidpointAwayFromZero),
+            Rounding::Bankers => self
+                .amount
+                .round_dp_with_strategy(dp, RoundingStrategy::MidpointNearestEven),
+            Rounding::Cash => {
+                let increment = Decimal::new(5, 2);
+                ((self.amount / increment)
+                    .round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero)
+                    * increment)
+                    .round_dp(dp)
+            }
+        };
+
+        Self { amount, ..self }
+    }
+
+    pub fn times(self, quantity: usize) -> Result<Self, MoneyError> {
+        let amount = self
+            .amount
+            .checked_mul(Decimal::from(quantity))
+            .ok_or(MoneyError::Overflow)?;
+
+        Ok(Self { amount, ..self })
+    }
+
+    pub fn checked_add(self, other: Money) -> Result<Self, MoneyError> {
+        self.same_currency_or_error(other)?;
+        let amount = self
+            .amount
+            .checked_add(other.amount)
+            .ok_or(MoneyError::Overflow)?;
+
+        Ok(Self { amount, ..self })
+    }
+
+    pub fn checked_sub(self, other: Money) -> Result<Self, MoneyError> {
+        self.same_currency_or_error(other)?;
+        let amount = self
+            .amount
+            .checked_sub(other.amount)
+            .ok_or(MoneyError::Overflow)?;
+
+        Self::new(amount, self.currency)
+    }
+
+    fn same_currency_or_error(&self, other: Money) -> Result<(), MoneyError> {
+        if self.currency != other.currency {
+            return Err(MoneyError::CurrencyMismatch {
+                expected: self.currency,
+                actual: other.currency,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match StoredMoney::deserialize(deserializer)? {
+            StoredMoney::Current { amount, currency } => {
+                Money::new(amount, currency).map_err(serde::de::Error::custom)
+            }
+            StoredMoney::Legacy(amount) => Err(serde::de::Error::custom(format!(
+                "amount {amount} has no currency"
+            ))),
+        }
+    }
+}
+
+impl Default for Money {
+    fn default() -> Self {
+        Self::zero(Currency::default())
+    }
+}
+
+impl std::fmt::Display for Money {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.amount, self.currency)
+    }
+}
+
+impl std::error::Error for MoneyError {}
+
+impl std::fmt::Display for MoneyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoneyError::Negative => write!(f, "amount must not be negative"),
+            MoneyError::InvalidCurrency(code) => write!(f, "invalid currency code: {code}"),
+            MoneyError::InvalidRate(rate) => write!(f, "exchange rate must be positive: {rate}"),
+            MoneyError::CurrencyMismatch { expected, actual } => {
+                write!(f, "expected an amount in {expected} but got {actual}")
+            }
+            MoneyError::Overflow => write!(f, "amount is too large"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rust_decimal::Decimal;
+    use serde_json::json;
+
+    use crate::shared_kernel::fixtures::usd;
+
+    use super::{Currency, Money, MoneyError, Rounding};
+
+    fn dec(amount: &str) -> Decimal {
+        Decimal::from_str(amount).unwrap()
+    }
+
+    #[test]
+    fn negative_amounts_and_malformed_currencies_are_rejected() {
+        assert_eq!(
+            Money::new(Decimal::from(-1), Currency::USD),
+            Err(MoneyError::Negative)
+        );
+        assert_eq!(
+            Currency::new("usd"),
+            Err(MoneyError::InvalidCurrency("usd".into()))
+        );
+        assert!(
+            serde_json::from_value::<Money>(json!({"amount": "-2", "currency": "USD"})).is_err()
+        );
+    }
+
+    #[test]
+    fn rounding_strategies() {
+        assert_eq!(usd(dec("2.345")).round(Rounding::HalfUp), usd(dec("2.35")));
+        assert_eq!(usd(dec("2.345")).round(Rounding::Bankers), usd(dec("2.34")));
+        assert_eq!(usd(dec("2.355")).round(Rounding::Bankers), usd(dec("2.36")));
+        assert_eq!(usd(dec("13.02")).round(Rounding::Cash), usd(dec("13.00")));
+        assert_eq!(usd(dec("13.03")).round(Rounding::Cash), usd(dec("13.05")));
+        assert_eq!(usd(dec("13.075")).round(Rounding::Cash), usd(dec("13.10")));
+    }
+
+    #[test]
+    fn arithmetic_requires_matching_currencies() {
+        let euros = Money::new(Decimal::from(2), Currency::EUR).unwrap();
+
+        assert_eq!(
+            usd(dec("1.50")).checked_add(usd(dec("2"))),
+            Ok(usd(dec("3.50")))
+        );
+        assert_eq!(usd(dec("1.50")).times(3), Ok(usd(dec("4.50"))));
+        assert_eq!(
+            usd(dec("1")).checked_sub(usd(dec("2"))),
+            Err(MoneyError::Negative)
+        );
+        assert_eq!(
+            usd(dec("1")).checked_add(euros),
+            Err(MoneyError::CurrencyMismatch {
+                expected: Currency::USD,
+                actual: Currency::EUR
+            })
+        );
+    }
+
+    #[test]
+    fn arithmetic_that_overflows_is_an_error() {
+        let max = usd(Decimal::MAX);
+
+        assert_eq!(max.times(2), Err(MoneyError::Overflow));
+        assert_eq!(max.checked_add(usd(dec("1"))), Err(MoneyError::Overflow));
+    }
+
+    #[test]
+    fn serializes_with_currency_and_rejects_legacy_decimals() {
+        let money = Money::new(Decimal::from_str("10.50").unwrap(), Currency::ETB).unwrap();
+
+        let json = serde_json::to_value(money).unwrap();
+
+        assert_eq!(json, json!({"amount": "10.50", "currency": "ETB"}));
+        assert_eq!(serde_json::from_value::<Money>(json).unwrap(), money);
+        assert!(serde_json::from_value::<Money>(json!("10.50")).is_err());
+    }
+}
@@ -1,9 +1,69 @@
-use cafe_tab::domain::tab::command::{OrderItem, TabCommand};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use cafe_tab::{
+    domain::{
+        gift_card::{
+            command::GiftCardCommand, error::GiftCardError, redemption_id::RedemptionId,
+            services::GiftCardServices,
+        },
+        tab::{
+            command::{OrderItem, TabCommand},
+            error::TabError,
+            event::{GiftCardPayment, MenuItem, TabEvent},
+            order_priority::OrderPriority,
+            queries::{bar::BarTodoList, kitchen::KitchenTodoList},
+            tab_id::TabId,
+            waiter_id::WaiterId,
+        },
+    },
+    infrasctructure::{
+        publishing::webhook::Webhook,
+        respository::{
+            dispatcher::TabCommandDispatcher,
+            gift_card_payments::{
+                GiftCardPaymentError, GiftCardPayments, PendingRedemption, RedemptionLog,
+                RedemptionPurpose, RedemptionState,
+            },
+            idempotency::{command_hash, Claim, CommandOutcome, IdempotencyStore},
+            postgresql::{
+                cqrs::{cqrs_gift_card, redrive_tab_queries},
+                dead_letters::PostgresQueryDeadLetters,
+                exchange_rates::PostgresExchangeRates,
+                idempotency::PostgresIdempotencyStore,
+                outbox::{self, OutboxDispatcher},
+                projector::ProjectorError,
+                redemption_log::PostgresRedemptionLog,
+                replay::ProjectionReplay,
+                subscription::{self, EventSubscription, PositionedEvent, SubscriptionError},
+            },
+            query_errors::{DeadLetterStore, QueryErrorPolicy, RetryPolicy},
+        },
+    },
+    shared_kernel::{
+        command_context::CommandContext,
+        exchange_rates::{ExchangeRate, ExchangeRates},
+        money::Currency,
+        BarTabViewRepository, KitchenTabQuery, KitchenTabViewRepository, OpenTabsViewRepository,
+        WaiterTabViewRepository,
+    },
+};
+use chrono::{Duration as ChronoDuration, Utc};
+use cqrs_es::{AggregateError, EventEnvelope, Query};
+use postgres_es::PostgresViewRepository;
 use rust_decimal::Decimal;
+use secrecy::Secret;
 
-use crate::test_state::{AggregateState, TestState};
+use crate::{
+    test_state::{usd, AggregateState, TabAggregate, TestState},
+    webhook_stub::{ReceivedRequest, WebhookStub},
+};
 
 pub mod test_state;
+pub mod webhook_stub;
 
 #[tokio::test]
 async fn initially_kitchen_todo_list_is_empty() {
@@ -27,8 +87,10 @@ async fn given_new_tab_when_1_food_order_then_kitchen_list_view_shows_1_food_ord
                 menu_number: 1,
                 description: "Steak".into(),
                 is_drink: false,
-                price: Decimal::from(10),
+                price: usd(Decimal::from(10)),
+                notes: None,
             }],
+            priority: OrderPriority::Normal,
         })
         .await;
 
@@ -41,6 +103,44 @@ async fn given_new_tab_when_1_food_order_then_kitchen_list_view_shows_1_food_ord
     assert_eq!(actual[0].food_items()[0].description(), "Steak");
 }
 
+#[tokio::test]
+async fn given_new_tab_when_food_and_drink_ordered_then_one_kitchen_ticket_is_printed() {
+    // Arrange
+    let state = TestState::new(AggregateState::Open).await;
+
+    // Act
+    state
+        .execute_command(TabCommand::PlaceOrder {
+            order_items: vec![
+                OrderItem {
+                    menu_number: 1,
+                    description: "Steak".into(),
+                    is_drink: false,
+                    price: usd(Decimal::from(10)),
+                    notes: Some("medium rare".into()),
+                },
+                OrderItem {
+                    menu_number: 2,
+                    description: "Coca-Cola".into(),
+                    is_drink: true,
+                    price: usd(Decimal::from(3)),
+                    notes: None,
+                },
+            ],
+            priority: OrderPriority::Normal,
+        })
+        .await;
+
+    // Assert
+    let jobs = state.kitchen_printer.jobs();
+    assert_eq!(jobs.len(), 1);
+    let ticket = String::from_utf8_lossy(&jobs[0]);
+    assert!(ticket.contains("TABLE 1"));
+    assert!(ticket.contains("1 x Steak"));
+    assert!(ticket.contains("   * medium rare"));
+    assert!(!ticket.contains("Coca-Cola"));
+}
+
 #[tokio::test]
 async fn given_tab_with_1_food_order_when_another_food_order_then_kitchen_list_view_shows_2_food_orders(
 ) {
@@ -52,8 +152,10 @@ async fn given_tab_with_1_food_order_when_another_food_order_then_kitchen_list_v
                 menu_number: 1,
                 description: "Steak".into(),
                 is_drink: false,
-                price: Decimal::from(10),
+                price: usd(Decimal::from(10)),
+                notes: None,
             }],
+            priority: OrderPriority::Normal,
         })
         .await;
 
@@ -64,8 +166,10 @@ async fn given_tab_with_1_food_order_when_another_food_order_then_kitchen_list_v
                 menu_number: 1,
                 description: "Steak".into(),
                 is_drink: false,
-                price: Decimal::from(10),
+                price: usd(Decimal::from(10)),
+                notes: None,
             }],
+            priority: OrderPriority::Normal,
         })
         .await;
 
@@ -91,8 +195,10 @@ async fn given_tab_with_1_food_order_when_kitchen_marks_it_prepared_then_kitchen
                 menu_number: 1,
                 description: "Steak".into(),
                 is_drink: false,
-                price: Decimal::from(10),
+                price: usd(Decimal::from(10)),
+                notes: None,
             }],
+            priority: OrderPriority::Normal,
         })
         .await;
 
@@ -100,7 +206,7 @@ async fn given_tab_with_1_food_order_when_kitchen_marks_it_prepared_then_kitchen
     state
         .execute_command(TabCommand::MarkFoodPrepared {
             id: state.tab_id,
-            menu_numbers: vec![1],
+            items: vec![(1, 1)],
         })
         .await;
 
@@ -109,6 +215,114 @@ async fn given_tab_with_1_food_order_when_kitchen_marks_it_prepared_then_kitchen
     assert_eq!(actual.len(), 0);
 }
 
+#[tokio::test]
+async fn given_tab_with_2_food_orders_when_second_is_rushed_then_kitchen_todo_list_shows_it_first()
+{
+    // Arrange
+    let state = TestState::new(AggregateState::Open).await;
+    state
+        .execute_command(TabCommand::PlaceOrder {
+            order_items: vec![
+                OrderItem {
+                    menu_number: 1,
+                    description: "Steak".into(),
+                    is_drink: false,
+                    price: usd(Decimal::from(10)),
+                    notes: None,
+                },
+                OrderItem {
+                    menu_number: 3,
+                    description: "Salad".into(),
+                    is_drink: false,
+                    price: usd(Decimal::from(6)),
+                    notes: None,
+                },
+            ],
+            priority: OrderPriority::Normal,
+        })
+        .await;
+
+    // Act
+    state
+        .execute_command(TabCommand::RushOrder {
+            id: state.tab_id,
+            menu_numbers: vec![3],
+        })
+        .await;
+
+    // Assert
+    let actual = state.load_kitchen_todo_list().await;
+    assert_eq!(actual.len(), 1);
+    assert_eq!(actual[0].food_items()[0].menu_number(), 3);
+    assert!(actual[0].food_items()[0].is_rushed());
+    assert_eq!(actual[0].food_items()[1].menu_number(), 1);
+    assert!(!actual[0].food_items()[1].is_rushed());
+}
+
+#[tokio::test]
+async fn given_two_tabs_with_interleaved_food_orders_then_kitchen_queue_is_sorted_by_priority_then_age(
+) {
+    // Arrange
+    let state = TestState::new(AggregateState::Open).await;
+    let other_tab_id = TabId::new();
+    let food = |menu_number, description: &str| OrderItem {
+        menu_number,
+        description: description.into(),
+        is_drink: false,
+        price: usd(Decimal::from(10)),
+        notes: None,
+    };
+    let order = |tab_id: TabId, menu_number, description, priority| {
+        let aggregate = &state.tab_aggregate;
+        let command = TabCommand::PlaceOrder {
+            order_items: vec![food(menu_number, description)],
+            priority,
+        };
+        async move {
+            aggregate
+                .execute(&tab_id.to_string(), command, &TestState::command_context())
+                .await
+                .unwrap()
+        }
+    };
+    state
+        .tab_aggregate
+        .execute(
+            &other_tab_id.to_string(),
+            TabCommand::OpenTab {
+                id: other_tab_id,
+                waiter_id: WaiterId::new(),
+                table: 2,
+            },
+            &TestState::command_context(),
+        )
+        .await
+        .unwrap();
+
+    // Act
+    order(state.tab_id, 1, "Steak", OrderPriority::Normal).await;
+    order(other_tab_id, 2, "Soup", OrderPriority::Normal).await;
+    order(state.tab_id, 3, "Salad", OrderPriority::Normal).await;
+    order(other_tab_id, 4, "Fries", OrderPriority::High).await;
+
+    // Assert
+    let queue: Vec<_> = state
+        .load_kitchen_queue()
+        .await
+        .into_iter()
+        .map(|(tab_id, item)| (tab_id, item.menu_number()))
+        .collect();
+    assert_eq!(
+        queue,
+        vec![
+            (other_tab_id, 4),
+            (state.tab_id, 1),
+            (other_tab_id, 2),
+            (state.tab_id, 3)
+        ]
+    );
+}
+
 #[tokio::test]
 async fn initially_waiter_todo_list_is_empty() {
     // Act
@@ -131,8 +345,10 @@ async fn given_new_tab_when_1_food_order_then_waiter_list_view_shows_1_food_orde
                 menu_number: 1,
                 description: "Steak".into(),
                 is_drink: false,
-                price: Decimal::from(10),
+                price: usd(Decimal::from(10)),
+                notes: None,
             }],
+            priority: OrderPriority::Normal,
         })
         .await;
 
@@ -144,3 +360,1637 @@ async fn given_new_tab_when_1_food_order_then_waiter_list_view_shows_1_food_orde
     assert_eq!(actual[0].open_items()[0].menu_number(), 1);
     assert_eq!(actual[0].open_items()[0].description(), "Steak");
 }
+
+#[tokio::test]
+async fn given_new_tab_when_1_drink_order_then_bar_list_view_shows_1_drink_with_table() {
+    // Arrange
+    let state = TestState::new(AggregateState::Open).await;
+
+    // Act
+    state
+        .execute_command(TabCommand::PlaceOrder {
+            order_items: vec![OrderItem {
+                menu_number: 2,
+                description: "Coca-Cola".into(),
+                is_drink: true,
+                price: usd(Decimal::from(3)),
+                notes: None,
+            }],
+            priority: OrderPriority::Normal,
+        })
+        .await;
+
+    // Assert
+    let actual = state.load_bar_todo_list().await;
+    assert_eq!(actual.tab_id(), state.tab_id);
+    assert_eq!(actual.table(), 1);
+    assert_eq!(actual.len(), 1);
+    assert_eq!(actual[0].menu_number(), 2);
+    assert_eq!(actual[0].description(), "Coca-Cola");
+    let all = state.load_all_bar_todo_lists().await;
+    assert_eq!(all.len(), 1);
+    assert_eq!(all[0].tab_id(), state.tab_id);
+}
+
+#[tokio::test]
+async fn given_tab_with_1_drink_order_when_bartender_bumps_it_then_bar_list_is_empty() {
+    // Arrange
+    let state = TestState::new(AggregateState::Open).await;
+    state
+        .execute_command(TabCommand::PlaceOrder {
+            order_items: vec![OrderItem {
+                menu_number: 2,
+                description: "Coca-Cola".into(),
+                is_drink: true,
+                price: usd(Decimal::from(3)),
+                notes: None,
+            }],
+            priority: OrderPriority::Normal,
+        })
+        .await;
+    let list = state.load_bar_todo_list().await;
+
+    // Act
+    state
+        .execute_command(list.bump(list[0].menu_number()))
+        .await;
+
+    // Assert
+    let actual = state.load_bar_todo_list().await;
+    assert!(actual.is_empty());
+    assert!(state.load_all_bar_todo_lists().await.is_empty());
+}
+
+#[tokio::test]
+async fn given_open_tab_then_its_table_is_active() {
+    // Act
+    let state = TestState::new(AggregateState::Open).await;
+
+    // Assert
+    let actual = state
+        .open_tabs
+        .active_table_numbers()
+        .await
+        .expect("failed to query active tables");
+    assert_eq!(actual, vec![1]);
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_closed_tab_views_then_open_tabs_are_filtered_in_the_database() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::Open).await;
+    for view in ["tab_query", "bar_tab_query"] {
+        sqlx::query(&format!(
+            "INSERT INTO {view} (view_id, version, payload) VALUES ('closed', 1, '{{\"open\": false, \"drinks\": []}}')"
+        ))
+        .execute(state.pool())
+        .await
+        .unwrap();
+    }
+    sqlx::query(
+        "INSERT INTO kitchen_tab_query (view_id, version, payload) VALUES ('closed', 1, '{\"inner\": []}')",
+    )
+    .execute(state.pool())
+    .await
+    .unwrap();
+
+    // Act
+    let tables = state
+        .open_tabs
+        .active_table_numbers()
+        .await
+        .expect("failed to query active tables");
+
+    // Assert
+    assert_eq!(tables, vec![1]);
+    assert!(state.load_all_bar_todo_lists().await.is_empty());
+    assert!(state.load_kitchen_queue().await.is_empty());
+}
+
+#[tokio::test]
+async fn given_tab_with_orders_then_invoice_for_table_lists_items_and_total() {
+    // Arrange
+    let state = TestState::new(AggregateState::Open).await;
+
+    // Act
+    state
+        .execute_command(TabCommand::PlaceOrder {
+            order_items: vec![
+                OrderItem {
+                    menu_number: 1,
+                    description: "Steak".into(),
+                    is_drink: false,
+                    price: usd(Decimal::from(10)),
+                    notes: None,
+                },
+                OrderItem {
+                    menu_number: 2,
+                    description: "Coca-Cola".into(),
+                    is_drink: true,
+                    price: usd(Decimal::from(3)),
+                    notes: None,
+                },
+            ],
+            priority: OrderPriority::Normal,
+        })
+        .await;
+
+    // Assert
+    let invoice = state
+        .open_tabs
+        .invoice_for_table(1)
+        .await
+        .expect("failed to query invoice")
+        .expect("no invoice for table 1");
+    assert_eq!(invoice.tab_id(), state.tab_id);
+    assert_eq!(invoice.lines().len(), 2);
+    assert_eq!(invoice.total(), usd(Decimal::from(13)));
+    assert!(invoice.has_unserved_items());
+}
+
+#[tokio::test]
+async fn given_open_tab_when_tab_closed_then_table_is_no_longer_active() {
+    // Arrange
+    let state = TestState::new(AggregateState::Open).await;
+
+    // Act
+    state
+        .execute_command(TabCommand::CloseTab {
+            id: state.tab_id,
+            amount_paid: usd(Decimal::ZERO),
+            gift_card: None,
+        })
+        .await;
+
+    // Assert
+    let actual = state
+        .open_tabs
+        .active_table_numbers()
+        .await
+        .expect("failed to query active tables");
+    assert!(actual.is_empty());
+    assert!(state.open_tabs.tab_for_table(1).await.unwrap().is_none());
+}
+
+#[tokio::test]
+#[cfg_attr(feature = "in-memory", ignore = "requires a database backend")]
+async fn given_v1_events_in_store_when_command_executed_then_events_are_upcast_and_loaded() {
+    // Arrange
+    let state = TestState::new(AggregateState::None).await;
+    state
+        .insert_event_fixture(include_str!("fixtures/tab_events_v1.json"))
+        .await;
+
+    // Act
+    let result = state
+        .tab_aggregate
+        .execute(
+            &state.tab_id.to_string(),
+            TabCommand::MarkFoodPrepared {
+                id: state.tab_id,
+                items: vec![(1, 1)],
+            },
+            &TestState::command_context(),
+        )
+        .await;
+
+    // Assert
+    assert!(result.is_ok(), "{result:?}");
+}
+
+#[tokio::test]
+#[cfg_attr(feature = "in-memory", ignore = "requires a database backend")]
+async fn given_command_context_when_order_placed_then_events_carry_context_metadata() {
+    // Arrange
+    let state = TestState::new(AggregateState::Open).await;
+    let context = CommandContext::new("waiter-7").with_terminal("bar-terminal-1");
+
+    // Act
+    state
+        .execute_command_with_context(
+            TabCommand::PlaceOrder {
+                order_items: vec![OrderItem {
+                    menu_number: 2,
+                    description: "Coca-Cola".into(),
+                    is_drink: true,
+                    price: usd(Decimal::from(3)),
+                    notes: None,
+                }],
+                priority: OrderPriority::Normal,
+            },
+            context.clone(),
+        )
+        .await;
+
+    // Assert
+    let metadata = state.load_event_metadata().await;
+    assert_eq!(metadata.len(), 2);
+    assert_eq!(metadata[0]["actor"], "test-waiter");
+    assert_eq!(metadata[1]["actor"], "waiter-7");
+    assert_eq!(metadata[1]["terminal"], "bar-terminal-1");
+    assert_eq!(
+        metadata[1]["correlation_id"],
+        context.correlation_id().to_string()
+    );
+    assert!(metadata[1]["issued_at"].is_string());
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_snapshot_store_when_commands_executed_then_snapshot_is_taken_and_tab_still_loads() {
+    // Arrange
+    let state = TestState::with_snapshot_size(AggregateState::Open, Some(2)).await;
+    for _ in 0..3 {
+        state
+            .execute_command(TabCommand::PlaceOrder {
+                order_items: vec![OrderItem {
+                    menu_number: 2,
+                    description: "Coca-Cola".into(),
+                    is_drink: true,
+                    price: usd(Decimal::from(3)),
+                    notes: None,
+                }],
+                priority: OrderPriority::Normal,
+            })
+            .await;
+    }
+
+    // Act
+    state
+        .execute_command(TabCommand::MarkDrinksServed {
+            id: state.tab_id,
+            items: vec![(2, 3)],
+        })
+        .await;
+
+    // Assert
+    assert!(state.load_snapshot_sequence().await.is_some());
+    let invoice = state
+        .open_tabs
+        .invoice_for_table(1)
+        .await
+        .expect("failed to query invoice")
+        .expect("tab should be open");
+    assert_eq!(invoice.total(), usd(Decimal::from(9)));
+    assert!(!invoice.has_unserved_items());
+}
+
+#[tokio::test]
+async fn given_in_memory_backend_when_food_and_drink_ordered_then_views_show_them() {
+    // Arrange
+    let state = TestState::in_memory(AggregateState::Open).await;
+
+    // Act
+    state
+        .execute_command(TabCommand::PlaceOrder {
+            order_items: vec![
+                OrderItem {
+                    menu_number: 1,
+                    description: "Steak".into(),
+                    is_drink: false,
+                    price: usd(Decimal::from(10)),
+                    notes: None,
+                },
+                OrderItem {
+                    menu_number: 2,
+                    description: "Coca-Cola".into(),
+                    is_drink: true,
+                    price: usd(Decimal::from(3)),
+                    notes: None,
+                },
+            ],
+            priority: OrderPriority::Normal,
+        })
+        .await;
+
+    // Assert
+    let kitchen = state.load_kitchen_todo_list().await;
+    assert_eq!(kitchen.len(), 1);
+    assert_eq!(kitchen[0].food_items()[0].description(), "Steak");
+    let waiter = state.get_waiter_todo_list().await;
+    assert_eq!(waiter.len(), 1);
+    assert_eq!(waiter[0].open_items().len(), 2);
+    let bar = state.load_all_bar_todo_lists().await;
+    assert_eq!(bar.len(), 1);
+    assert_eq!(bar[0][0].description(), "Coca-Cola");
+    let invoice = state
+        .open_tabs
+        .invoice_for_table(1)
+        .await
+        .expect("failed to query invoice")
+        .expect("tab should be open");
+    assert_eq!(invoice.total(), usd(Decimal::from(13)));
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn given_sqlite_backend_when_order_served_then_views_and_invoice_reflect_it() {
+    // Arrange
+    let state = TestState::sqlite(AggregateState::Open).await;
+    state
+        .execute_command(TabCommand::PlaceOrder {
+            order_items: vec![OrderItem {
+                menu_number: 2,
+                description: "Coca-Cola".into(),
+                is_drink: true,
+                price: usd(Decimal::from(3)),
+                notes: None,
+            }],
+            priority: OrderPriority::Normal,
+        })
+        .await;
+
+    // Act
+    state
+        .execute_command(TabCommand::MarkDrinksServed {
+            id: state.tab_id,
+            items: vec![(2, 1)],
+        })
+        .await;
+
+    // Assert
+    assert!(state.load_bar_todo_list().await.is_empty());
+    let invoice = state
+        .open_tabs
+        .invoice_for_table(1)
+        .await
+        .expect("failed to query invoice")
+        .expect("tab should be open");
+    assert_eq!(invoice.total(), usd(Decimal::from(3)));
+    assert!(!invoice.has_unserved_items());
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_lost_kitchen_view_when_all_projections_replayed_then_view_is_rebuilt() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::Open).await;
+    state
+        .execute_command(TabCommand::PlaceOrder {
+            order_items: vec![OrderItem {
+                menu_number: 1,
+                description: "Steak".into(),
+                is_drink: false,
+                price: usd(Decimal::from(10)),
+                notes: None,
+            }],
+            priority: OrderPriority::Normal,
+        })
+        .await;
+    sqlx::query("DELETE FROM kitchen_tab_query")
+        .execute(state.pool())
+        .await
+        .unwrap();
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let sink = reported.clone();
+
+    // Act
+    let progress = ProjectionReplay::<KitchenTodoList>::new(
+        state.pool().clone(),
+        "kitchen_tab_query",
+        Currency::USD,
+    )
+    .with_progress(1, Box::new(move |p| sink.lock().unwrap().push(p.clone())))
+    .rebuild_all()
+    .await
+    .expect("replay failed");
+
+    // Assert
+    assert_eq!(progress.events, 2);
+    assert_eq!(progress.aggregates, 1);
+    assert!(progress.finished);
+    assert_eq!(reported.lock().unwrap().last(), Some(&progress));
+    let actual = state.load_kitchen_todo_list().await;
+    assert_eq!(actual.len(), 1);
+    assert_eq!(actual[0].food_items()[0].description(), "Steak");
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_corrupt_bar_view_when_single_aggregate_replayed_then_only_its_view_is_rebuilt() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::Open).await;
+    state
+        .execute_command(TabCommand::PlaceOrder {
+            order_items: vec![OrderItem {
+                menu_number: 2,
+                description: "Coca-Cola".into(),
+                is_drink: true,
+                price: usd(Decimal::from(3)),
+                notes: None,
+            }],
+            priority: OrderPriority::Normal,
+        })
+        .await;
+    sqlx::query("UPDATE bar_tab_query SET payload = '{\"drinks\": []}'")
+        .execute(state.pool())
+        .await
+        .unwrap();
+
+    // Act
+    ProjectionReplay::<BarTodoList>::new(state.pool().clone(), "bar_tab_query", Currency::USD)
+        .rebuild(&state.tab_id.to_string())
+        .await
+        .expect("replay failed");
+
+    // Assert
+    let actual = state.load_bar_todo_list().await;
+    assert_eq!(actual.table(), 1);
+    assert_eq!(actual.len(), 1);
+    assert_eq!(actual[0].description(), "Coca-Cola");
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_projected_framework_when_order_placed_then_view_updates_only_after_projector_runs() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::None).await;
+    let (cqrs, projectors) = state.projected_tab_aggregate();
+    let kitchen = projectors
+        .into_iter()
+        .find(|p| p.name() == "kitchen_tab_query")
+        .unwrap();
+    let tab_id = state.tab_id;
+
+    // Act
+    cqrs.execute_with_metadata(
+        &tab_id.to_string(),
+        TabCommand::OpenTab {
+            id: tab_id,
+            waiter_id: WaiterId::new(),
+            table: 4,
+        },
+        TestState::command_context().to_metadata(),
+    )
+    .await
+    .unwrap();
+    cqrs.execute_with_metadata(
+        &tab_id.to_string(),
+        TabCommand::PlaceOrder {
+            order_items: vec![OrderItem {
+                menu_number: 1,
+                description: "Steak".into(),
+                is_drink: false,
+                price: usd(Decimal::from(10)),
+                notes: None,
+            }],
+            priority: OrderPriority::Normal,
+        },
+        TestState::command_context().to_metadata(),
+    )
+    .await
+    .unwrap();
+
+    // Assert
+    assert!(state
+        .tab_kitchen_todo_list
+        .load(&tab_id.to_string())
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(kitchen.lag().await.unwrap(), 2);
+    assert_eq!(kitchen.run_once().await.unwrap(), 2);
+    assert_eq!(kitchen.lag().await.unwrap(), 0);
+    assert_eq!(kitchen.run_once().await.unwrap(), 0);
+    let actual = state.load_kitchen_todo_list().await;
+    assert_eq!(actual.len(), 1);
+    assert_eq!(actual[0].food_items()[0].description(), "Steak");
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_projected_framework_when_food_ordered_then_tab_status_projector_prints_ticket() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::None).await;
+    let (cqrs, projectors) = state.projected_tab_aggregate();
+    let tab_status = projectors
+        .into_iter()
+        .find(|p| p.name() == "tab_query")
+        .unwrap();
+    let tab_id = state.tab_id;
+    cqrs.execute_with_metadata(
+        &tab_id.to_string(),
+        TabCommand::OpenTab {
+            id: tab_id,
+            waiter_id: WaiterId::new(),
+            table: 7,
+        },
+        TestState::command_context().to_metadata(),
+    )
+    .await
+    .unwrap();
+    cqrs.execute_with_metadata(
+        &tab_id.to_string(),
+        TabCommand::PlaceOrder {
+            order_items: vec![OrderItem {
+                menu_number: 1,
+                description: "Steak".into(),
+                is_drink: false,
+                price: usd(Decimal::from(10)),
+                notes: None,
+            }],
+            priority: OrderPriority::Normal,
+        },
+        TestState::command_context().to_metadata(),
+    )
+    .await
+    .unwrap();
+    assert!(state.kitchen_printer.jobs().is_empty());
+
+    // Act
+    tab_status.run_once().await.unwrap();
+
+    // Assert
+    let jobs = state.kitchen_printer.jobs();
+    assert_eq!(jobs.len(), 1);
+    let ticket = String::from_utf8_lossy(&jobs[0]);
+    assert!(ticket.contains("TABLE 7"));
+    assert!(ticket.contains("1 x Steak"));
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_spawned_projectors_when_drink_ordered_then_bar_view_catches_up() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::None).await;
+    let (cqrs, projectors) = state.projected_tab_aggregate();
+    let handles: Vec<_> = projectors
+        .into_iter()
+        .map(|p| p.with_poll_interval(Duration::from_millis(10)).spawn())
+        .collect();
+    let tab_id = state.tab_id;
+
+    // Act
+    cqrs.execute_with_metadata(
+        &tab_id.to_string(),
+        TabCommand::OpenTab {
+            id: tab_id,
+            waiter_id: WaiterId::new(),
+            table: 1,
+        },
+        TestState::command_context().to_metadata(),
+    )
+    .await
+    .unwrap();
+    cqrs.execute_with_metadata(
+        &tab_id.to_string(),
+        TabCommand::PlaceOrder {
+            order_items: vec![OrderItem {
+                menu_number: 2,
+                description: "Coca-Cola".into(),
+                is_drink: true,
+                price: usd(Decimal::from(3)),
+                notes: None,
+            }],
+            priority: OrderPriority::Normal,
+        },
+        TestState::command_context().to_metadata(),
+    )
+    .await
+    .unwrap();
+
+    // Assert
+    for handle in handles.iter() {
+        let mut attempts = 0;
+        while handle.lag().await.unwrap() > 0 {
+            attempts += 1;
+            assert!(attempts < 500, "{} did not catch up", handle.name());
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+    for handle in handles {
+        handle.stop().await;
+    }
+    let actual = state.load_bar_todo_list().await;
+    assert_eq!(actual.len(), 1);
+    assert_eq!(actual[0].description(), "Coca-Cola");
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_view_write_fails_when_projecting_then_checkpoint_stays_and_retry_applies_once() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::None).await;
+    let (cqrs, projectors) = state.projected_tab_aggregate();
+    let kitchen = projectors
+        .into_iter()
+        .find(|p| p.name() == "kitchen_tab_query")
+        .unwrap();
+    let tab_id = state.tab_id;
+    for command in [
+        TabCommand::OpenTab {
+            id: tab_id,
+            waiter_id: WaiterId::new(),
+            table: 4,
+        },
+        steak_order(),
+    ] {
+        cqrs.execute_with_metadata(
+            &tab_id.to_string(),
+            command,
+            TestState::command_context().to_metadata(),
+        )
+        .await
+        .unwrap();
+    }
+    sqlx::query("ALTER TABLE kitchen_tab_query RENAME TO kitchen_tab_query_offline")
+        .execute(state.pool())
+        .await
+        .unwrap();
+
+    // Act
+    let failed = kitchen.run_once().await;
+    sqlx::query("ALTER TABLE kitchen_tab_query_offline RENAME TO kitchen_tab_query")
+        .execute(state.pool())
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(failed.is_err());
+    assert_eq!(kitchen.lag().await.unwrap(), 2);
+    assert_eq!(kitchen.run_once().await.unwrap(), 2);
+    assert_eq!(kitchen.run_once().await.unwrap(), 0);
+    let actual = state.load_kitchen_todo_list().await;
+    assert_eq!(actual.len(), 1);
+    assert_eq!(actual[0].food_items().len(), 1);
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_small_batches_when_projecting_then_events_are_taken_in_commit_order() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::None).await;
+    let (cqrs, projectors) = state.projected_tab_aggregate();
+    let tabs = projectors
+        .into_iter()
+        .find(|p| p.name() == "tab_query")
+        .unwrap()
+        .with_batch_size(1);
+    let mut ids = [TabId::new(), TabId::new()];
+    ids.sort_by_key(|id| std::cmp::Reverse(id.to_string()));
+    for (table, id) in ids.iter().enumerate() {
+        cqrs.execute_with_metadata(
+            &id.to_string(),
+            TabCommand::OpenTab {
+                id: *id,
+                waiter_id: WaiterId::new(),
+                table: table + 1,
+            },
+            TestState::command_context().to_metadata(),
+        )
+        .await
+        .unwrap();
+    }
+    let open_tabs = OpenTabsViewRepository::new(state.pool().clone());
+
+    // Act
+    tabs.run_once().await.unwrap();
+
+    // Assert
+    assert!(open_tabs.load(&ids[0].to_string()).await.unwrap().is_some());
+    assert!(open_tabs.load(&ids[1].to_string()).await.unwrap().is_none());
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_placed_order_when_reading_from_global_position_then_events_are_ordered() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::None).await;
+    let start = subscription::current_position(state.pool()).await.unwrap();
+    state
+        .execute_command(TabCommand::OpenTab {
+            id: state.tab_id,
+            waiter_id: WaiterId::new(),
+            table: 3,
+        })
+        .await;
+    state.execute_command(steak_order()).await;
+
+    // Act
+    let actual = subscription::read_events_after(state.pool(), start, 1000, Currency::USD)
+        .await
+        .unwrap();
+
+    // Assert
+    let actual: Vec<_> = actual
+        .into_iter()
+        .filter(|e| e.event.aggregate_id == state.tab_id.to_string())
+        .collect();
+    assert_eq!(actual.len(), 2);
+    assert!(actual[0].position > start);
+    assert!(actual.windows(2).all(|w| w[0].position < w[1].position));
+    assert_eq!(actual[0].event.sequence, 1);
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_subscription_when_caught_up_then_new_events_keep_streaming() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::None).await;
+    let start = subscription::current_position(state.pool()).await.unwrap();
+    state
+        .execute_command(TabCommand::OpenTab {
+            id: state.tab_id,
+            waiter_id: WaiterId::new(),
+            table: 3,
+        })
+        .await;
+    let mut events = subscription::subscribe(state.pool().clone(), start, Currency::USD)
+        .await
+        .unwrap();
+    let opened = next_event_for(&mut events, &state.tab_id.to_string()).await;
+
+    // Act
+    state.execute_command(steak_order()).await;
+
+    // Assert
+    let placed = next_event_for(&mut events, &state.tab_id.to_string()).await;
+    assert_eq!(opened.event.sequence, 1);
+    assert_eq!(placed.event.sequence, 2);
+    assert!(placed.position > opened.position);
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_unreadable_event_when_subscribed_then_it_is_reported_once_and_skipped() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::None).await;
+    let start = subscription::current_position(state.pool()).await.unwrap();
+    sqlx::query(
+        "INSERT INTO events (aggregate_type, aggregate_id, sequence, event_type, event_version, payload, metadata)
+         VALUES ('Tab', 'broken-tab', 1, 'TabRenamed', '1.0.0', '{\"TabRenamed\": {}}', '{}')",
+    )
+    .execute(state.pool())
+    .await
+    .unwrap();
+    state
+        .execute_command(TabCommand::OpenTab {
+            id: state.tab_id,
+            waiter_id: WaiterId::new(),
+            table: 3,
+        })
+        .await;
+
+    // Act
+    let mut events = subscription::subscribe(state.pool().clone(), start, Currency::USD)
+        .await
+        .unwrap();
+    let first = tokio::time::timeout(Duration::from_secs(10), events.next())
+        .await
+        .expect("subscription stalled")
+        .expect("subscription closed");
+
+    // Assert
+    assert!(matches!(
+        first,
+        Err(SubscriptionError::Unreadable { position, .. }) if position > start
+    ));
+    let opened = next_event_for(&mut events, &state.tab_id.to_string()).await;
+    assert_eq!(opened.event.sequence, 1);
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_unreadable_event_when_projecting_then_it_is_reported_and_later_events_are_applied() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::None).await;
+    let (cqrs, projectors) = state.projected_tab_aggregate();
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let sink = reported.clone();
+    let tabs = projectors
+        .into_iter()
+        .find(|p| p.name() == "tab_query")
+        .unwrap()
+        .with_error_handler(Box::new(move |name, e| {
+            sink.lock().unwrap().push((name.to_string(), e))
+        }));
+    sqlx::query(
+        "INSERT INTO events (aggregate_type, aggregate_id, sequence, event_type, event_version, payload, metadata)
+         VALUES ('Tab', 'broken-tab', 1, 'TabRenamed', '1.0.0', '{\"TabRenamed\": {}}', '{}')",
+    )
+    .execute(state.pool())
+    .await
+    .unwrap();
+    cqrs.execute_with_metadata(
+        &state.tab_id.to_string(),
+        TabCommand::OpenTab {
+            id: state.tab_id,
+            waiter_id: WaiterId::new(),
+            table: 3,
+        },
+        TestState::command_context().to_metadata(),
+    )
+    .await
+    .unwrap();
+
+    // Act
+    let read = tabs.run_once().await.unwrap();
+
+    // Assert
+    assert_eq!(read, 2);
+    assert_eq!(tabs.lag().await.unwrap(), 0);
+    assert_eq!(tabs.run_once().await.unwrap(), 0);
+    let open_tabs = OpenTabsViewRepository::new(state.pool().clone());
+    assert!(open_tabs
+        .load(&state.tab_id.to_string())
+        .await
+        .unwrap()
+        .is_some());
+    let reported = reported.lock().unwrap();
+    assert_eq!(reported.len(), 1);
+    assert_eq!(reported[0].0, "tab_query");
+    assert!(matches!(reported[0].1, ProjectorError::Unreadable { .. }));
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_gift_card_when_tab_closed_with_it_then_card_balance_and_tab_are_both_updated() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::Open).await;
+    let TabAggregate::Postgres(tabs) = &state.tab_aggregate else {
+        unreachable!("postgres state uses the postgres framework")
+    };
+    let gift_cards = cqrs_gift_card(state.pool().clone(), GiftCardServices::default());
+    let log = Arc::new(PostgresRedemptionLog::new(state.pool().clone()));
+    let payments = GiftCardPayments::new(tabs.clone(), gift_cards.clone(), log.clone());
+    let code = format!("GC-{}", state.tab_id);
+    gift_cards
+        .execute_with_metadata(
+            &code,
+            GiftCardCommand::Issue {
+                code: code.clone(),
+                amount: usd(Decimal::from(30)),
+                expires_at: None,
+            },
+            TestState::command_context().to_metadata(),
+        )
+        .await
+        .unwrap();
+    state.execute_command(steak_order()).await;
+
+    // Act
+    payments
+        .execute(
+            &state.tab_id.to_string(),
+            TabCommand::CloseTab {
+                id: state.tab_id,
+                amount_paid: usd(Decimal::ZERO),
+                gift_card: Some(GiftCardPayment {
+                    code: code.clone(),
+                    amount: usd(Decimal::from(20)),
+                    redemption: RedemptionId::default(),
+                }),
+            },
+            &TestState::command_context(),
+        )
+        .await
+        .unwrap();
+
+    // Assert
+    let tab = OpenTabsViewRepository::new(state.pool().clone())
+        .load(&state.tab_id.to_string())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!tab.is_open());
+    let overdraw = gift_cards
+        .execute_with_metadata(
+            &code,
+            GiftCardCommand::Redeem {
+                code: code.clone(),
+                tab_id: state.tab_id,
+                amount: usd(Decimal::from(11)),
+                redemption: RedemptionId::new(),
+            },
+            TestState::command_context().to_metadata(),
+        )
+        .await;
+    assert!(matches!(
+        overdraw,
+        Err(AggregateError::UserError(GiftCardError::InsufficientBalance { balance }))
+            if balance == usd(Decimal::from(10))
+    ));
+    assert!(!log
+        .stalled(Duration::ZERO)
+        .await
+        .unwrap()
+        .iter()
+        .any(|pending| pending.tab_id == state.tab_id));
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_logged_redemption_then_it_is_stalled_until_removed() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::Open).await;
+    let log = PostgresRedemptionLog::new(state.pool().clone());
+    let mut pending = PendingRedemption {
+        tab_id: state.tab_id,
+        payment: GiftCardPayment {
+            code: format!("GC-{}", state.tab_id),
+            amount: usd(Decimal::from(20)),
+            redemption: RedemptionId::new(),
+        },
+        state: RedemptionState::Redeeming,
+        purpose: RedemptionPurpose::CloseTab {
+            amount_paid: usd(Decimal::from(5)),
+        },
+    };
+    let logged = |stalled: Vec<PendingRedemption>| {
+        stalled
+            .into_iter()
+            .filter(|p| p.tab_id == state.tab_id)
+            .collect::<Vec<_>>()
+    };
+
+    // Act
+    log.record(&pending).await.unwrap();
+    pending.state = RedemptionState::Redeemed;
+    log.record(&pending).await.unwrap();
+
+    // Assert
+    assert!(logged(log.stalled(Duration::from_secs(3600)).await.unwrap()).is_empty());
+    assert_eq!(
+        logged(log.stalled(Duration::ZERO).await.unwrap()),
+        vec![pending.clone()]
+    );
+    log.remove(pending.payment.redemption).await.unwrap();
+    assert!(logged(log.stalled(Duration::ZERO).await.unwrap()).is_empty());
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_unknown_gift_card_when_paying_with_it_then_tab_is_not_paid() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::Open).await;
+    let TabAggregate::Postgres(tabs) = &state.tab_aggregate else {
+        unreachable!("postgres state uses the postgres framework")
+    };
+    let payments = GiftCardPayments::new(
+        tabs.clone(),
+        cqrs_gift_card(state.pool().clone(), GiftCardServices::default()),
+        Arc::new(PostgresRedemptionLog::new(state.pool().clone())),
+    );
+    state.execute_command(steak_order()).await;
+
+    // Act
+    let actual = payments
+        .execute(
+            &state.tab_id.to_string(),
+            TabCommand::PayWithGiftCard {
+                id: state.tab_id,
+                payment: GiftCardPayment {
+                    code: format!("GC-{}", state.tab_id),
+                    amount: usd(Decimal::from(20)),
+                    redemption: RedemptionId::default(),
+                },
+            },
+            &TestState::command_context(),
+        )
+        .await;
+
+    // Assert
+    assert!(matches!(
+        actual,
+        Err(GiftCardPaymentError::GiftCard(AggregateError::UserError(
+            GiftCardError::NotIssued
+        )))
+    ));
+    let tab = OpenTabsViewRepository::new(state.pool().clone())
+        .load(&state.tab_id.to_string())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(tab.is_open());
+    assert_eq!(tab.invoice().unwrap().amount_due(), usd(Decimal::from(20)));
+}
+
+fn steak_order() -> TabCommand {
+    TabCommand::PlaceOrder {
+        order_items: vec![OrderItem {
+            menu_number: 1,
+            description: "Steak".into(),
+            is_drink: false,
+            price: usd(Decimal::from(20)),
+            notes: None,
+        }],
+        priority: OrderPriority::Normal,
+    }
+}
+
+async fn next_event_for(events: &mut EventSubscription, aggregate_id: &str) -> PositionedEvent {
+    loop {
+        let event = tokio::time::timeout(Duration::from_secs(10), events.next())
+            .await
+            .expect("subscription stalled")
+            .expect("subscription closed")
+            .unwrap();
+        if event.event.aggregate_id == aggregate_id {
+            return event;
+        }
+    }
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_closed_tab_when_webhook_fails_once_then_signed_message_is_redelivered() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::Open).await;
+    let stub = WebhookStub::start(1).await;
+    let webhook = Webhook::new("accounting", stub.url(), Secret::new("s3cret".into()))
+        .with_event_types(&["TabClosed"]);
+    let dispatcher = OutboxDispatcher::new(state.pool().clone(), webhook.clone(), Currency::USD)
+        .starting_after(outbox::outbox_head(state.pool()).await.unwrap())
+        .with_backoff(Duration::from_millis(10), Duration::from_millis(10));
+    state
+        .execute_command(TabCommand::CloseTab {
+            id: state.tab_id,
+            amount_paid: usd(Decimal::ZERO),
+            gift_card: None,
+        })
+        .await;
+
+    // Act
+    dispatcher.run_once().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    dispatcher.run_once().await.unwrap();
+    dispatcher.run_once().await.unwrap();
+
+    // Assert
+    let requests = requests_for(&stub, &state.tab_id.to_string());
+    assert_eq!(requests.len(), 2);
+    let delivered = &requests[1];
+    assert_eq!(delivered.headers["x-cafe-event"], "TabClosed");
+    assert_eq!(
+        delivered.headers["x-cafe-signature"],
+        webhook.sign(&delivered.body)
+    );
+    assert_eq!(delivered.json()["sequence"], 2);
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_webhook_keeps_failing_when_attempts_exhausted_then_entry_is_dead_lettered() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::Open).await;
+    let stub = WebhookStub::start(usize::MAX).await;
+    let webhook = Webhook::new(
+        &format!("loyalty-{}", state.tab_id),
+        stub.url(),
+        Secret::new("s3cret".into()),
+    );
+    let dispatcher = OutboxDispatcher::new(state.pool().clone(), webhook, Currency::USD)
+        .starting_after(outbox::outbox_head(state.pool()).await.unwrap())
+        .with_max_attempts(2)
+        .with_backoff(Duration::ZERO, Duration::ZERO);
+    state
+        .execute_command(TabCommand::CloseTab {
+            id: state.tab_id,
+            amount_paid: usd(Decimal::ZERO),
+            gift_card: None,
+        })
+        .await;
+
+    // Act
+    for _ in 0..3 {
+        dispatcher.run_once().await.unwrap();
+    }
+
+    // Assert
+    let requests = requests_for(&stub, &state.tab_id.to_string());
+    assert_eq!(requests.len(), 2);
+    let dead_letter = dispatcher
+        .dead_letters()
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|d| d.outbox_id.to_string() == requests[0].headers["x-cafe-delivery"])
+        .unwrap();
+    assert_eq!(dead_letter.attempts, 2);
+    assert!(dead_letter.last_error.contains("503"));
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_dead_lettered_entry_when_redriven_then_it_is_delivered_again() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::Open).await;
+    let stub = WebhookStub::start(2).await;
+    let webhook = Webhook::new("accounting", stub.url(), Secret::new("s3cret".into()));
+    let dispatcher = OutboxDispatcher::new(state.pool().clone(), webhook, Currency::USD)
+        .starting_after(outbox::outbox_head(state.pool()).await.unwrap())
+        .with_max_attempts(2)
+        .with_backoff(Duration::ZERO, Duration::ZERO);
+    state
+        .execute_command(TabCommand::CloseTab {
+            id: state.tab_id,
+            amount_paid: usd(Decimal::ZERO),
+            gift_card: None,
+        })
+        .await;
+    for _ in 0..2 {
+        dispatcher.run_once().await.unwrap();
+    }
+
+    // Act
+    let redriven = dispatcher.redrive().await.unwrap();
+    let report = dispatcher.run_once().await.unwrap();
+
+    // Assert
+    assert_eq!(redriven, 1);
+    assert_eq!(report.delivered, 1);
+    assert!(dispatcher.dead_letters().await.unwrap().is_empty());
+    assert_eq!(requests_for(&stub, &state.tab_id.to_string()).len(), 3);
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_published_event_types_changed_when_tab_opened_then_webhook_receives_it() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::None).await;
+    let stub = WebhookStub::start(0).await;
+    let webhook = Webhook::new("accounting", stub.url(), Secret::new("s3cret".into()));
+    let dispatcher = OutboxDispatcher::new(state.pool().clone(), webhook, Currency::USD);
+    outbox::publish_event_types(state.pool(), &["TabOpened", "TabClosed"])
+        .await
+        .unwrap();
+
+    // Act
+    state
+        .execute_command(TabCommand::OpenTab {
+            id: state.tab_id,
+            waiter_id: WaiterId::new(),
+            table: 2,
+        })
+        .await;
+    dispatcher.run_once().await.unwrap();
+
+    // Assert
+    assert_eq!(
+        outbox::published_event_types(state.pool()).await.unwrap(),
+        vec!["TabClosed", "TabOpened"]
+    );
+    let requests = requests_for(&stub, &state.tab_id.to_string());
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].headers["x-cafe-event"], "TabOpened");
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_legacy_event_in_outbox_when_dispatched_then_payload_is_upcast_and_cursor_advances() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::None).await;
+    let stub = WebhookStub::start(0).await;
+    let webhook = Webhook::new("kitchen-display", stub.url(), Secret::new("s3cret".into()));
+    let dispatcher = OutboxDispatcher::new(state.pool().clone(), webhook, Currency::USD);
+    outbox::publish_event_types(state.pool(), &["FoodOrderPlaced"])
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO events (aggregate_type, aggregate_id, sequence, event_type, event_version, payload, metadata)
+         VALUES ('Tab', $1, 1, 'FoodOrderPlaced', '1.0', $2, '{}')",
+    )
+    .bind(state.tab_id.to_string())
+    .bind(serde_json::json!({
+        "FoodOrderPlaced": {
+            "id": state.tab_id.to_string(),
+            "menu_item": {"menu_number": 1, "description": "Steak", "price": "10", "quantity": 1}
+        }
+    }))
+    .execute(state.pool())
+    .await
+    .unwrap();
+
+    // Act
+    let report = dispatcher.run_once().await.unwrap();
+
+    // Assert
+    assert_eq!(report.delivered, 1);
+    let requests = requests_for(&stub, &state.tab_id.to_string());
+    let menu_item = &requests[0].json()["payload"]["FoodOrderPlaced"]["menu_item"];
+    assert_eq!(menu_item["priority"], "Normal");
+    assert!(menu_item["price"].is_object());
+    let cursor: i64 =
+        sqlx::query_scalar("SELECT position FROM outbox_cursors WHERE webhook = 'kitchen-display'")
+            .fetch_one(state.pool())
+            .await
+            .unwrap();
+    assert_eq!(cursor, outbox::outbox_head(state.pool()).await.unwrap());
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_entry_waiting_for_retry_then_cursor_stays_behind_it() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::Open).await;
+    let stub = WebhookStub::start(1).await;
+    let webhook = Webhook::new("accounting", stub.url(), Secret::new("s3cret".into()));
+    let dispatcher = OutboxDispatcher::new(state.pool().clone(), webhook, Currency::USD)
+        .with_backoff(Duration::from_secs(60), Duration::from_secs(60));
+    state
+        .execute_command(TabCommand::CloseTab {
+            id: state.tab_id,
+            amount_paid: usd(Decimal::ZERO),
+            gift_card: None,
+        })
+        .await;
+    let head = outbox::outbox_head(state.pool()).await.unwrap();
+
+    // Act
+    let report = dispatcher.run_once().await.unwrap();
+
+    // Assert
+    assert_eq!(report.retried, 1);
+    let cursor: i64 =
+        sqlx::query_scalar("SELECT position FROM outbox_cursors WHERE webhook = 'accounting'")
+            .fetch_one(state.pool())
+            .await
+            .unwrap();
+    assert_eq!(cursor, head - 1);
+}
+
+fn requests_for(stub: &WebhookStub, aggregate_id: &str) -> Vec<ReceivedRequest> {
+    stub.received()
+        .into_iter()
+        .filter(|r| r.json()["aggregate_id"] == aggregate_id)
+        .collect()
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_failed_view_write_when_redriven_then_dead_lettered_events_reach_the_view() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::None).await;
+    let pool = state.pool().clone();
+    let tab_id = state.tab_id;
+    let dead_letters = Arc::new(PostgresQueryDeadLetters::new(pool.clone(), Currency::USD));
+    let broken_query = KitchenTabQuery::new(
+        "kitchen_tab_query",
+        Arc::new(PostgresViewRepository::new("missing_view", pool.clone())),
+        QueryErrorPolicy::new(RetryPolicy::none()).with_dead_letters(dead_letters.clone()),
+    );
+    let events = vec![EventEnvelope {
+        aggregate_id: tab_id.to_string(),
+        sequence: 1,
+        payload: TabEvent::FoodOrderPlaced {
+            id: tab_id,
+            menu_item: MenuItem {
+                menu_number: 1,
+                description: "Steak".into(),
+                price: usd(Decimal::from(20)),
+                quantity: 1,
+                ..MenuItem::default()
+            },
+        },
+        metadata: HashMap::new(),
+    }];
+    broken_query.dispatch(&tab_id.to_string(), &events).await;
+    let letter = dead_letters
+        .load("kitchen_tab_query")
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|l| l.view_id == tab_id.to_string())
+        .unwrap();
+    assert!(letter.error.contains("missing_view"));
+    assert_eq!(letter.events[0].payload, events[0].payload);
+
+    // Act
+    redrive_tab_queries(
+        pool.clone(),
+        Currency::USD,
+        WaiterTabViewRepository::new(pool.clone()),
+        KitchenTabViewRepository::new(pool.clone()),
+        BarTabViewRepository::new(pool.clone()),
+        OpenTabsViewRepository::new(pool.clone()),
+    )
+    .await
+    .unwrap();
+
+    // Assert
+    let actual = state.load_kitchen_todo_list().await;
+    assert_eq!(actual.len(), 1);
+    assert!(!dead_letters
+        .load("kitchen_tab_query")
+        .await
+        .unwrap()
+        .iter()
+        .any(|l| l.view_id == tab_id.to_string()));
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_concurrent_drinks_served_when_dispatched_with_retry_then_every_command_commits() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::Open).await;
+    let TabAggregate::Postgres(cqrs) = &state.tab_aggregate else {
+        unreachable!("postgres state uses the postgres framework")
+    };
+    let dispatcher = Arc::new(TabCommandDispatcher::new(cqrs.clone()).with_max_attempts(20));
+    let menu_numbers = 1..=4;
+    state
+        .execute_command(TabCommand::PlaceOrder {
+            order_items: menu_numbers
+                .clone()
+                .map(|menu_number| OrderItem {
+                    menu_number,
+                    description: format!("Drink {menu_number}"),
+                    is_drink: true,
+                    price: usd(Decimal::from(2)),
+                    notes: None,
+                })
+                .collect(),
+            priority: OrderPriority::Normal,
+        })
+        .await;
+
+    // Act
+    let tasks: Vec<_> = menu_numbers
+        .map(|menu_number| {
+            let dispatcher = dispatcher.clone();
+            let tab_id = state.tab_id;
+            tokio::spawn(async move {
+                dispatcher
+                    .execute(
+                        &tab_id.to_string(),
+                        TabCommand::MarkDrinksServed {
+                            id: tab_id,
+                            items: vec![(menu_number, 1)],
+                        },
+                        &TestState::command_context(),
+                    )
+                    .await
+            })
+        })
+        .collect();
+
+    // Assert
+    for task in tasks {
+        task.await.unwrap().unwrap();
+    }
+    let actual = dispatcher
+        .execute(
+            &state.tab_id.to_string(),
+            TabCommand::MarkDrinksServed {
+                id: state.tab_id,
+                items: vec![(1, 1)],
+            },
+            &TestState::command_context(),
+        )
+        .await;
+    assert!(matches!(
+        actual,
+        Err(AggregateError::UserError(TabError::DrinkNotOutstanding {
+            menu_number: 1
+        }))
+    ));
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_order_sent_twice_with_same_idempotency_key_then_food_is_ordered_once() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::Open).await;
+    let TabAggregate::Postgres(cqrs) = &state.tab_aggregate else {
+        unreachable!("postgres state uses the postgres framework")
+    };
+    let dispatcher = Arc::new(
+        TabCommandDispatcher::new(cqrs.clone()).with_idempotency(Arc::new(
+            PostgresIdempotencyStore::new(state.pool().clone()),
+        )),
+    );
+    let context =
+        TestState::command_context().with_idempotency_key(&format!("order-{}", state.tab_id));
+
+    // Act
+    let tasks: Vec<_> = (0..2)
+        .map(|_| {
+            let dispatcher = dispatcher.clone();
+            let context = context.clone();
+            let tab_id = state.tab_id;
+            tokio::spawn(async move {
+                dispatcher
+                    .execute(&tab_id.to_string(), steak_order(), &context)
+                    .await
+            })
+        })
+        .collect();
+
+    // Assert
+    for task in tasks {
+        task.await.unwrap().unwrap();
+    }
+    let actual = state.load_kitchen_todo_list().await;
+    assert_eq!(actual.len(), 1);
+    assert_eq!(actual[0].food_items().len(), 1);
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_stale_idempotency_claim_then_it_is_taken_over_and_other_tabs_cannot_reuse_the_key() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::Open).await;
+    let store = PostgresIdempotencyStore::new(state.pool().clone());
+    let key = format!("stale-{}", state.tab_id);
+    let tab_id = state.tab_id.to_string();
+    assert_eq!(
+        store.claim(&key, &tab_id, "hash").await.unwrap(),
+        Claim::New
+    );
+    assert_eq!(
+        store.claim(&key, &tab_id, "hash").await.unwrap(),
+        Claim::InFlight
+    );
+    sqlx::query(
+        "UPDATE command_idempotency SET claimed_at = now() - interval '1 hour' WHERE idempotency_key = $1",
+    )
+    .bind(&key)
+    .execute(state.pool())
+    .await
+    .unwrap();
+
+    // Act
+    let taken_over = store.claim(&key, &tab_id, "hash").await.unwrap();
+    let reused = store
+        .claim(&key, &TabId::new().to_string(), "hash")
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(taken_over, Claim::New);
+    assert_eq!(
+        store.claim(&key, &tab_id, "hash").await.unwrap(),
+        Claim::InFlight
+    );
+    assert_eq!(reused, Claim::KeyReused);
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_idempotency_key_claimed_for_another_command_then_it_is_reported_as_reused() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::Open).await;
+    let store = PostgresIdempotencyStore::new(state.pool().clone());
+    let key = format!("payload-{}", state.tab_id);
+    let tab_id = state.tab_id.to_string();
+    let hash = command_hash(&steak_order()).unwrap();
+    store.claim(&key, &tab_id, &hash).await.unwrap();
+
+    // Act
+    let actual = store.claim(&key, &tab_id, "another-command").await.unwrap();
+
+    // Assert
+    assert_eq!(actual, Claim::KeyReused);
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_keyed_command_committed_without_completing_then_claim_returns_its_success() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::Open).await;
+    let TabAggregate::Postgres(cqrs) = &state.tab_aggregate else {
+        unreachable!("postgres state uses the postgres framework")
+    };
+    let store = PostgresIdempotencyStore::new(state.pool().clone()).with_lease(Duration::ZERO);
+    let key = format!("crash-{}", state.tab_id);
+    let tab_id = state.tab_id.to_string();
+    let hash = command_hash(&steak_order()).unwrap();
+    let context = TestState::command_context().with_idempotency_key(&key);
+    store.claim(&key, &tab_id, &hash).await.unwrap();
+
+    // Act
+    cqrs.execute_with_metadata(&tab_id, steak_order(), context.to_metadata())
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(
+        store.claim(&key, &tab_id, &hash).await.unwrap(),
+        Claim::Completed(CommandOutcome::Succeeded)
+    );
+}
+
+#[tokio::test]
+#[cfg_attr(
+    any(feature = "in-memory", feature = "sqlite"),
+    ignore = "requires PostgreSQL"
+)]
+async fn given_recorded_exchange_rates_then_latest_rate_in_effect_is_used() {
+    // Arrange
+    let state = TestState::postgres(AggregateState::Open).await;
+    let rates = PostgresExchangeRates::new(state.pool().clone());
+    let currency = Currency::new(&random_currency_code()).unwrap();
+    let now = Utc::now();
+    for (rate, effective_at) in [
+        ("0.0170", now - ChronoDuration::days(2)),
+        ("0.0175", now - ChronoDuration::hours(1)),
+        ("0.0200", now + ChronoDuration::days(1)),
+    ] {
+        let rate = ExchangeRate::new(currency, Currency::USD, rate.parse().unwrap(), effective_at)
+            .unwrap();
+        rates.record(&rate).await.unwrap();
+    }
+
+    // Act
+    let actual = rates.rate(currency, Currency::USD).await.unwrap();
+
+    // Assert
+    let actual = actual.expect("a rate is in effect");
+    assert_eq!(actual.rate(), "0.0175".parse::<Decimal>().unwrap());
+    assert_eq!(rates.rate(Currency::USD, currency).await.unwrap(), None);
+}
+
+// Each run gets its own currency so rates recorded by earlier runs don't interfere.
+fn random_currency_code() -> String {
+    uuid::Uuid::new_v4()
+        .as_bytes()
+        .iter()
+        .take(3)
+        .map(|b| char::from(b'A' + b % 26))
+        .collect()
+}
@@ -1,7 +1,24 @@
+use std::sync::Arc;
+
+#[cfg(feature = "sqlite")]
+use cafe_tab::infrasctructure::{
+    persistence::context::sqlite::{migrate_sqlite_db, sqlite_pool},
+    respository::sqlite::{
+        cqrs::{sqlite_cqrs_tab, SqliteTabCqrsFramework},
+        view_repository::SqliteViewRepository,
+    },
+};
 use cafe_tab::{
     domain::tab::{
+        aggregate::Tab,
         command::TabCommand,
-        queries::{kitchen::KitchenTodoList, open_tabs::WaiterTodoList},
+        error::TabError,
+        queries::{
+            bar::BarTodoList,
+            kitchen::{KitchenTodoList, TodoListItem},
+            kitchen_ticket::KitchenPrinter,
+            open_tabs::{OpenTabQuery, WaiterTodoList},
+        },
         services::TabServices,
         tab_id::TabId,
         waiter_id::WaiterId,
@@ -11,18 +28,69 @@ use cafe_tab::{
             connection_parameters::ConnectionBuilder,
             postgres::{create_db, migrate_db, postgres_pool},
         },
-        respository::postgresql::cqrs::{cqrs_tab, TabCqrsFramework},
+        printing::sink::MemoryPrinterSink,
+        respository::{
+            memory::{
+                cqrs::{mem_cqrs_tab, MemTabCqrsFramework},
+                dead_letters::MemQueryDeadLetters,
+                view_repository::MemViewRepository,
+            },
+            postgresql::{
+                cqrs::{cqrs_tab, cqrs_tab_with_projectors, TabCqrsFramework},
+                projector::Projector,
+            },
+            query_errors::QueryErrorPolicy,
+        },
+    },
+    shared_kernel::{
+        command_context::CommandContext,
+        money::{Currency, Money},
+        BarTabViewRepository, KitchenTabViewRepository, OpenTabsViewRepository,
+        WaiterTabViewRepository,
     },
-    shared_kernel::{KitchenTabViewRepository, WaiterTabViewRepository},
 };
+use cqrs_es::{persist::ViewRepository, AggregateError};
+use postgres_es::PostgresViewRepository;
+use rust_decimal::Decimal;
 use secrecy::Secret;
+use sqlx::{Pool, Postgres};
 use uuid::Uuid;
 
+const INSERT_EVENT: &str =
+    "INSERT INTO events (aggregate_type, aggregate_id, sequence, event_type, event_version, payload, metadata)
+     VALUES ($1, $2, $3, $4, $5, $6, $7)";
+
 pub struct TestState {
     pub tab_id: TabId,
-    pub tab_aggregate: TabCqrsFramework,
-    pub tab_kitchen_todo_list: KitchenTabViewRepository,
-    pub waiter_todo_list: WaiterTabViewRepository,
+    pub tab_aggregate: TabAggregate,
+    pub tab_kitchen_todo_list: Arc<dyn ViewRepository<KitchenTodoList, Tab>>,
+    pub waiter_todo_list: Arc<dyn ViewRepository<WaiterTodoList, Tab>>,
+    pub open_tabs: Arc<dyn OpenTabQuery + Send + Sync>,
+    pub kitchen_printer: MemoryPrinterSink,
+    backend: Backend,
+}
+
+pub enum TabAggregate {
+    Postgres(TabCqrsFramework),
+    Memory(MemTabCqrsFramework),
+    #[cfg(feature = "sqlite")]
+    Sqlite(SqliteTabCqrsFramework),
+}
+
+enum Backend {
+    Postgres {
+        bar_todo_list: BarTabViewRepository,
+        pool: Pool<Postgres>,
+    },
+    Memory {
+        bar_todo_list: Arc<MemViewRepository<BarTodoList, Tab>>,
+        kitchen_todo_list: Arc<MemViewRepository<KitchenTodoList, Tab>>,
+    },
+    #[cfg(feature = "sqlite")]
+    Sqlite {
+        bar_todo_list: Arc<SqliteViewRepository<BarTodoList, Tab>>,
+        pool: Pool<sqlx::Sqlite>,
+    },
 }
 
 #[derive(Debug)]
@@ -31,8 +99,130 @@ pub enum AggregateState {
     None,
 }
 
+impl TabAggregate {
+    pub async fn execute(
+        &self,
+        aggregate_id: &str,
+        command: TabCommand,
+        context: &CommandContext,
+    ) -> Result<(), AggregateError<TabError>> {
+        let metadata = context.to_metadata();
+        match self {
+            Self::Postgres(cqrs) => {
+                cqrs.execute_with_metadata(aggregate_id, command, metadata)
+                    .await
+            }
+            Self::Memory(cqrs) => {
+                cqrs.execute_with_metadata(aggregate_id, command, metadata)
+                    .await
+            }
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(cqrs) => {
+                cqrs.execute_with_metadata(aggregate_id, command, metadata)
+                    .await
+            }
+        }
+    }
+}
+
 impl TestState {
+    // Runs against PostgreSQL unless the `in-memory` or `sqlite` feature is enabled.
     pub async fn new(aggregate_state: AggregateState) -> Self {
+        if cfg!(feature = "in-memory") {
+            Self::in_memory(aggregate_state).await
+        } else {
+            Self::database(aggregate_state).await
+        }
+    }
+
+    #[cfg(feature = "sqlite")]
+    async fn database(aggregate_state: AggregateState) -> Self {
+        Self::sqlite(aggregate_state).await
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    async fn database(aggregate_state: AggregateState) -> Self {
+        Self::postgres(aggregate_state).await
+    }
+
+    #[cfg(feature = "sqlite")]
+    pub async fn sqlite(aggregate_state: AggregateState) -> Self {
+        let path = std::env::temp_dir().join(format!("cafe-tab-{}.db", Uuid::new_v4()));
+        let pool = sqlite_pool(path.to_str().expect("invalid temp path")).await;
+        migrate_sqlite_db(&pool).await;
+        let waiter_todo_list =
+            Arc::new(SqliteViewRepository::new("waiter_tab_query", pool.clone()));
+        let tab_kitchen_todo_list =
+            Arc::new(SqliteViewRepository::new("kitchen_tab_query", pool.clone()));
+        let bar_todo_list = Arc::new(SqliteViewRepository::new("bar_tab_query", pool.clone()));
+        let open_tabs = Arc::new(SqliteViewRepository::new("tab_query", pool.clone()));
+        let kitchen_printer = MemoryPrinterSink::new();
+        let tab_aggregate = TabAggregate::Sqlite(sqlite_cqrs_tab(
+            pool.clone(),
+            TabServices::default(),
+            waiter_todo_list.clone(),
+            tab_kitchen_todo_list.clone(),
+            bar_todo_list.clone(),
+            open_tabs.clone(),
+            Some(KitchenPrinter::new(Arc::new(kitchen_printer.clone()))),
+            QueryErrorPolicy::default(),
+            None,
+        ));
+
+        Self::initialize(
+            tab_aggregate,
+            tab_kitchen_todo_list,
+            waiter_todo_list,
+            open_tabs,
+            kitchen_printer,
+            Backend::Sqlite {
+                bar_todo_list,
+                pool,
+            },
+            aggregate_state,
+        )
+        .await
+    }
+
+    pub async fn postgres(aggregate_state: AggregateState) -> Self {
+        Self::with_snapshot_size(aggregate_state, None).await
+    }
+
+    pub async fn in_memory(aggregate_state: AggregateState) -> Self {
+        let waiter_todo_list = Arc::new(MemViewRepository::new());
+        let tab_kitchen_todo_list = Arc::new(MemViewRepository::new());
+        let bar_todo_list = Arc::new(MemViewRepository::new());
+        let open_tabs = Arc::new(MemViewRepository::new());
+        let kitchen_printer = MemoryPrinterSink::new();
+        let tab_aggregate = TabAggregate::Memory(mem_cqrs_tab(
+            TabServices::default(),
+            waiter_todo_list.clone(),
+            tab_kitchen_todo_list.clone(),
+            bar_todo_list.clone(),
+            open_tabs.clone(),
+            Some(KitchenPrinter::new(Arc::new(kitchen_printer.clone()))),
+            QueryErrorPolicy::default().with_dead_letters(Arc::new(MemQueryDeadLetters::new())),
+        ));
+
+        Self::initialize(
+            tab_aggregate,
+            tab_kitchen_todo_list.clone(),
+            waiter_todo_list,
+            open_tabs,
+            kitchen_printer,
+            Backend::Memory {
+                bar_todo_list,
+                kitchen_todo_list: tab_kitchen_todo_list,
+            },
+            aggregate_state,
+        )
+        .await
+    }
+
+    pub async fn with_snapshot_size(
+        aggregate_state: AggregateState,
+        snapshot_size: Option<usize>,
+    ) -> Self {
         let db_name = Uuid::new_v4().to_string();
         let params = ConnectionBuilder::new()
             .with_credentials("postgres", Secret::new(String::from("password")))
@@ -41,34 +231,146 @@ impl TestState {
         create_db(&params).await;
         migrate_db(&params).await;
         let pool = postgres_pool(&params).await;
-        let services = TabServices {};
+        let services = TabServices::default();
         let waiter_todo_list = WaiterTabViewRepository::new(pool.clone());
         let tab_kitchen_todo_list = KitchenTabViewRepository::new(pool.clone());
-        let tab_aggregate = cqrs_tab(
-            pool,
+        let bar_todo_list = BarTabViewRepository::new(pool.clone());
+        let open_tabs = OpenTabsViewRepository::new(pool.clone());
+        let kitchen_printer = MemoryPrinterSink::new();
+        let tab_aggregate = TabAggregate::Postgres(cqrs_tab(
+            pool.clone(),
             services,
             waiter_todo_list.clone(),
             tab_kitchen_todo_list.clone(),
-        );
+            bar_todo_list.clone(),
+            open_tabs.clone(),
+            Some(KitchenPrinter::new(Arc::new(kitchen_printer.clone()))),
+            QueryErrorPolicy::default(),
+            snapshot_size,
+        ));
+
+        Self::initialize(
+            tab_aggregate,
+            Arc::<PostgresViewRepository<_, _>>::from(tab_kitchen_todo_list),
+            Arc::<PostgresViewRepository<_, _>>::from(waiter_todo_list),
+            Arc::new(open_tabs),
+            kitchen_printer,
+            Backend::Postgres {
+                bar_todo_list,
+                pool,
+            },
+            aggregate_state,
+        )
+        .await
+    }
+
+    async fn initialize(
+        tab_aggregate: TabAggregate,
+        tab_kitchen_todo_list: Arc<dyn ViewRepository<KitchenTodoList, Tab>>,
+        waiter_todo_list: Arc<dyn ViewRepository<WaiterTodoList, Tab>>,
+        open_tabs: Arc<dyn OpenTabQuery + Send + Sync>,
+        kitchen_printer: MemoryPrinterSink,
+        backend: Backend,
+        aggregate_state: AggregateState,
+    ) -> Self {
         let tab_id = TabId::new();
         let waiter_id = WaiterId::new();
         Self::initialize_aggregate_state(&tab_aggregate, tab_id, waiter_id, aggregate_state).await;
 
         Self {
             tab_id,
+            tab_aggregate,
             tab_kitchen_todo_list,
             waiter_todo_list,
-            tab_aggregate,
+            open_tabs,
+            kitchen_printer,
+            backend,
         }
     }
 
     pub async fn execute_command(&self, command: TabCommand) {
+        self.execute_command_with_context(command, Self::command_context())
+            .await;
+    }
+
+    pub async fn execute_command_with_context(&self, command: TabCommand, context: CommandContext) {
         self.tab_aggregate
-            .execute(&self.tab_id.to_string(), command)
+            .execute(&self.tab_id.to_string(), command, &context)
             .await
             .expect("failed to order execute a command on the aggregate");
     }
 
+    pub async fn load_event_metadata(&self) -> Vec<serde_json::Value> {
+        const SQL: &str = "SELECT metadata FROM events WHERE aggregate_id = $1 ORDER BY sequence";
+        match &self.backend {
+            #[cfg(feature = "sqlite")]
+            Backend::Sqlite { pool, .. } => sqlx::query_scalar::<_, String>(SQL)
+                .bind(self.tab_id.to_string())
+                .fetch_all(pool)
+                .await
+                .expect("failed to load event metadata")
+                .iter()
+                .map(|m| serde_json::from_str(m).expect("invalid event metadata"))
+                .collect(),
+            _ => sqlx::query_scalar(SQL)
+                .bind(self.tab_id.to_string())
+                .fetch_all(self.pool())
+                .await
+                .expect("failed to load event metadata"),
+        }
+    }
+
+    pub async fn load_snapshot_sequence(&self) -> Option<i64> {
+        sqlx::query_scalar(
+            "SELECT last_sequence FROM snapshots WHERE aggregate_id = $1 ORDER BY last_sequence DESC LIMIT 1",
+        )
+        .bind(self.tab_id.to_string())
+        .fetch_optional(self.pool())
+        .await
+        .expect("failed to load snapshot")
+    }
+
+    pub fn command_context() -> CommandContext {
+        CommandContext::new("test-waiter").with_terminal("test-terminal")
+    }
+
+    pub async fn insert_event_fixture(&self, fixture: &str) {
+        let fixture = fixture.replace(
+            "7b4a3c1e-2f1d-4c8e-9a57-3e2b8f0d6a11",
+            &self.tab_id.to_string(),
+        );
+        let rows: Vec<serde_json::Value> =
+            serde_json::from_str(&fixture).expect("invalid event fixture");
+        for row in rows {
+            match &self.backend {
+                #[cfg(feature = "sqlite")]
+                Backend::Sqlite { pool, .. } => sqlx::query(INSERT_EVENT)
+                    .bind(row["aggregate_type"].as_str())
+                    .bind(row["aggregate_id"].as_str())
+                    .bind(row["sequence"].as_i64())
+                    .bind(row["event_type"].as_str())
+                    .bind(row["event_version"].as_str())
+                    .bind(row["payload"].to_string())
+                    .bind(row["metadata"].to_string())
+                    .execute(pool)
+                    .await
+                    .map(|_| ()),
+                _ => sqlx::query(INSERT_EVENT)
+                    .bind(row["aggregate_type"].as_str())
+                    .bind(row["aggregate_id"].as_str())
+                    .bind(row["sequence"].as_i64())
+                    .bind(row["event_type"].as_str())
+                    .bind(row["event_version"].as_str())
+                    .bind(&row["payload"])
+                    .bind(&row["metadata"])
+                    .execute(self.pool())
+                    .await
+                    .map(|_| ()),
+            }
+            .expect("failed to insert event fixture");
+        }
+    }
+
     pub async fn load_kitchen_todo_list(&self) -> KitchenTodoList {
         self.tab_kitchen_todo_list
             .load(&self.tab_id.to_string())
@@ -85,8 +387,71 @@ impl TestState {
             .unwrap()
     }
 
+    pub async fn load_bar_todo_list(&self) -> BarTodoList {
+        match &self.backend {
+            Backend::Postgres { bar_todo_list, .. } => {
+                bar_todo_list.load(&self.tab_id.to_string()).await
+            }
+            Backend::Memory { bar_todo_list, .. } => {
+                bar_todo_list.load(&self.tab_id.to_string()).await
+            }
+            #[cfg(feature = "sqlite")]
+            Backend::Sqlite { bar_todo_list, .. } => {
+                bar_todo_list.load(&self.tab_id.to_string()).await
+            }
+        }
+        .expect("failed to load the bar tab view")
+        .unwrap()
+    }
+
+    pub async fn load_kitchen_queue(&self) -> Vec<(TabId, TodoListItem)> {
+        match &self.backend {
+            Backend::Postgres { pool, .. } => {
+                KitchenTabViewRepository::new(pool.clone())
+                    .load_queue()
+                    .await
+            }
+            Backend::Memory {
+                kitchen_todo_list, ..
+            } => kitchen_todo_list.load_queue().await,
+            #[cfg(feature = "sqlite")]
+            Backend::Sqlite { pool, .. } => {
+                SqliteViewRepository::new("kitchen_tab_query", pool.clone())
+                    .load_queue()
+                    .await
+            }
+        }
+        .expect("failed to load the kitchen tab views")
+    }
+
+    pub async fn load_all_bar_todo_lists(&self) -> Vec<BarTodoList> {
+        match &self.backend {
+            Backend::Postgres { bar_todo_list, .. } => bar_todo_list.load_all().await,
+            Backend::Memory { bar_todo_list, .. } => bar_todo_list.load_outstanding().await,
+            #[cfg(feature = "sqlite")]
+            Backend::Sqlite { bar_todo_list, .. } => bar_todo_list.load_outstanding().await,
+        }
+        .expect("failed to load the bar tab views")
+    }
+
+    pub fn projected_tab_aggregate(&self) -> (TabCqrsFramework, Vec<Projector>) {
+        cqrs_tab_with_projectors(
+            self.pool().clone(),
+            TabServices::default(),
+            Some(KitchenPrinter::new(Arc::new(self.kitchen_printer.clone()))),
+            None,
+        )
+    }
+
+    pub fn pool(&self) -> &Pool<Postgres> {
+        match &self.backend {
+            Backend::Postgres { pool, .. } => pool,
+            _ => panic!("test requires the PostgreSQL backend"),
+        }
+    }
+
     async fn initialize_aggregate_state(
-        tab_aggregate: &TabCqrsFramework,
+        tab_aggregate: &TabAggregate,
         tab_id: TabId,
         waiter_id: WaiterId,
         aggregate_state: AggregateState,
@@ -101,6 +466,7 @@ impl TestState {
                             waiter_id,
                             table: 1,
                         },
+                        &Self::command_context(),
                     )
                     .await
                     .expect("failed to open tab");
@@ -109,3 +475,7 @@ impl TestState {
         }
     }
 }
+
+pub fn usd(amount: impl Into<Decimal>) -> Money {
+    Money::new(amount.into(), Currency::USD).unwrap()
+}
@@ -0,0 +1,97 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpListener,
+    task::JoinHandle,
+};
+
+#[derive(Clone, Debug)]
+pub struct ReceivedRequest {
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+// Minimal HTTP endpoint that records every request and answers 503 to the
+// first `failures` attempts of each delivery, then 200.
+pub struct WebhookStub {
+    url: String,
+    received: Arc<Mutex<Vec<ReceivedRequest>>>,
+    task: JoinHandle<()>,
+}
+
+impl WebhookStub {
+    pub async fn start(failures: usize) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hooks", listener.local_addr().unwrap());
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let mut attempts: HashMap<String, usize> = HashMap::new();
+        let requests = received.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let mut reader = BufReader::new(socket);
+                let mut headers = HashMap::new();
+                let mut line = String::new();
+                reader.read_line(&mut line).await.unwrap();
+                loop {
+                    line.clear();
+                    reader.read_line(&mut line).await.unwrap();
+                    match line.trim_end().split_once(": ") {
+                        Some((name, value)) => {
+                            headers.insert(name.to_lowercase(), value.to_string());
+                        }
+                        None => break,
+                    }
+                }
+                let length = headers
+                    .get("content-length")
+                    .map_or(0, |l| l.parse().unwrap());
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).await.unwrap();
+                let delivery = headers.get("x-cafe-delivery").cloned().unwrap_or_default();
+                let attempt = attempts.entry(delivery).or_default();
+                *attempt += 1;
+                let status = if *attempt <= failures { 503 } else { 200 };
+                requests
+                    .lock()
+                    .unwrap()
+                    .push(ReceivedRequest { headers, body });
+                let response = format!(
+                    "HTTP/1.1 {status} Stub\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                );
+                let _ = reader.get_mut().write_all(response.as_bytes()).await;
+                let _ = reader.get_mut().shutdown().await;
+            }
+        });
+
+        Self {
+            url,
+            received,
+            task,
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn received(&self) -> Vec<ReceivedRequest> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+impl Drop for WebhookStub {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl ReceivedRequest {
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body).unwrap()
+    }
+}